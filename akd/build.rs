@@ -0,0 +1,23 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under both the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree and the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree.
+
+//! Generates the protobuf message types when the protobuf_serialization feature is enabled
+
+fn main() {
+    #[cfg(feature = "protobuf_serialization")]
+    build_protos();
+}
+
+#[cfg(feature = "protobuf_serialization")]
+fn build_protos() {
+    protobuf_codegen::Codegen::new()
+        .pure()
+        .include("src/proto/specs")
+        .input("src/proto/specs/types.proto")
+        .cargo_out_dir("protos")
+        .run_from_script();
+}
@@ -8,27 +8,80 @@
 //! An implementation of an append-only zero knowledge set
 use crate::{
     errors::TreeNodeError,
-    proof_structs::{AppendOnlyProof, MembershipProof, NonMembershipProof, SingleAppendOnlyProof},
-    storage::{Storable, Storage},
+    proof_structs::{
+        AppendOnlyProof, AppendOnlyRangeProof, BatchMembershipProof, CompactNonMembershipProof,
+        MembershipProof, NonMembershipProof, PrefixAbsenceProof, SingleAppendOnlyProof,
+    },
+    storage::{Storable, Storage, StorageUtil},
     tree_node::*,
 };
 
-use crate::serialization::to_digest;
+use crate::serialization::{from_digest, to_digest};
 
-use crate::storage::types::StorageType;
+use crate::storage::types::{
+    DbRecord, EpochCommitMarker, EpochRootHash, EpochTimestamp, LeafMetadata, StorageType,
+};
+#[cfg(feature = "vrf")]
+use crate::{ecvrf::VRFPublicKey, storage::types::AkdLabel};
 use crate::{errors::*, node_label::*, tree_node::TreeNode, ARITY, *};
 use async_recursion::async_recursion;
-use log::{debug, info};
+use futures::{Stream, StreamExt};
+use log::{debug, info, warn};
 use std::marker::{Send, Sync};
-use tokio::time::Instant;
+use std::time::Instant;
 use winter_crypto::Hasher;
 
 use keyed_priority_queue::{Entry, KeyedPriorityQueue};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 /// The default azks key
 pub const DEFAULT_AZKS_KEY: u8 = 1u8;
 
+/// The number of leaves inserted between calls to the progress callback of
+/// [Azks::batch_insert_leaves_helper]
+pub const INSERT_PROGRESS_INTERVAL: usize = 1000;
+
+/// The options of a batch insertion with [Azks::batch_insert_leaves_helper]. The default
+/// inserts the batch as the directory does: in one chunk, updating labels already in the tree
+/// and without reporting progress.
+#[derive(Clone, Copy, Default)]
+pub struct InsertOptions<'a> {
+    /// Whether the hashes of the leaves already have their epochs bound in, as the auditor's
+    /// do, so the epoch isn't merged into them again when hashing their parents
+    pub append_only_exclude_usage: bool,
+    /// How labels which already have a leaf in the tree are treated
+    pub policy: InsertPolicy,
+    /// The maximum number of leaves inserted and hashed up to the root at a time, or None to
+    /// insert the whole batch at once
+    pub max_chunk_size: Option<usize>,
+    /// Called with the number of leaves processed so far and the total number to process,
+    /// every [INSERT_PROGRESS_INTERVAL] leaves and once all of them are in
+    pub progress: Option<&'a (dyn Fn(usize, usize) + Send + Sync)>,
+}
+
+/// How a batch insertion treats a label which already has a leaf in the tree
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InsertPolicy {
+    /// Overwrite the value of the existing leaf
+    #[default]
+    UpdateExisting,
+    /// Fail the whole batch with [TreeNodeError::LabelAlreadyExists]
+    RejectExisting,
+    /// Overwrite the value of the existing leaf, unless the value is unchanged
+    UpdateIfChanged,
+}
+
+/// The state of the tree in storage, as found by [Azks::health_check]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthReport<H: Hasher> {
+    /// Whether the storage layer answered the read of the azks record
+    pub storage_reachable: bool,
+    /// The latest epoch of the stored azks, or None if storage wasn't reachable
+    pub latest_epoch: Option<u64>,
+    /// The root hash of the tree as of the latest epoch, or None if storage wasn't reachable
+    pub root_hash: Option<H::Digest>,
+}
+
 /// An append-only zero knowledge set, the data structure used to efficiently implement
 /// a auditable key directory.
 #[derive(Debug, Eq, PartialEq)]
@@ -131,10 +184,176 @@ impl Azks {
         storage: &S,
         insertion_set: Vec<Node<H>>,
     ) -> Result<(), AkdError> {
-        self.batch_insert_leaves_helper::<_, H>(storage, insertion_set, false)
+        self.batch_insert_leaves_with_policy::<_, H>(
+            storage,
+            insertion_set,
+            InsertPolicy::default(),
+        )
+        .await
+    }
+
+    /// Inserts a single leaf as a new epoch, like [Azks::batch_insert_leaves], once its label has
+    /// been checked to be the one the directory's VRF derives for the username and version, with
+    /// the supplied VRF proof. Otherwise it fails with [AzksError::InvalidLabelDerivation] and
+    /// the tree is left untouched. This is for insertions coming from a component which isn't
+    /// trusted to derive labels itself, at the cost of a VRF verification per leaf.
+    #[cfg(feature = "vrf")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn insert_verified<S: Storage + Sync + Send, H: Hasher>(
+        &mut self,
+        storage: &S,
+        leaf: Node<H>,
+        vrf_public_key: &VRFPublicKey,
+        username: &AkdLabel,
+        stale: bool,
+        version: u64,
+        vrf_proof: &[u8],
+    ) -> Result<(), AkdError> {
+        if let Err(vrf_error) =
+            vrf_public_key.verify_label::<H>(username, stale, version, vrf_proof, leaf.label)
+        {
+            debug!("Label derivation of {} failed: {}", leaf.label, vrf_error);
+            return Err(AkdError::AzksErr(AzksError::InvalidLabelDerivation(
+                leaf.label,
+            )));
+        }
+        self.batch_insert_leaves::<_, H>(storage, vec![leaf]).await
+    }
+
+    /// Insert a batch of leaves, handling labels already in the tree according to `policy`
+    pub async fn batch_insert_leaves_with_policy<S: Storage + Sync + Send, H: Hasher>(
+        &mut self,
+        storage: &S,
+        insertion_set: Vec<Node<H>>,
+        policy: InsertPolicy,
+    ) -> Result<(), AkdError> {
+        let options = InsertOptions {
+            policy,
+            ..InsertOptions::default()
+        };
+        self.batch_insert_leaves_helper::<_, H>(storage, insertion_set, options)
             .await
     }
 
+    /// Insert a batch of new leaves, like [Azks::batch_insert_leaves], storing each leaf's
+    /// metadata alongside it. See [LeafMetadata]: the metadata is NOT committed to by the tree.
+    /// Fails with [AzksError::LeafMetadataTooLong], before anything is written, if any of the
+    /// metadata is too long.
+    ///
+    /// The metadata is written in the same storage transaction as the leaves. If no transaction
+    /// is active, one is started here and committed once both have been written, or rolled back
+    /// if either fails. If the caller already has a transaction active, both are written within
+    /// it, and on failure it's up to the caller to roll it back.
+    pub async fn batch_insert_leaves_with_metadata<S: Storage + Sync + Send, H: Hasher>(
+        &mut self,
+        storage: &S,
+        insertion_set: Vec<(Node<H>, Vec<u8>)>,
+    ) -> Result<(), AkdError> {
+        let mut leaves = Vec::with_capacity(insertion_set.len());
+        let mut metadata = Vec::with_capacity(insertion_set.len());
+        for (leaf, data) in insertion_set {
+            metadata.push(DbRecord::LeafMetadata(LeafMetadata::new(leaf.label, data)?));
+            leaves.push(leaf);
+        }
+
+        let previous_epoch = self.latest_epoch;
+        let previous_num_nodes = self.num_nodes;
+        // begin_transaction is false if the caller's transaction is already active
+        let own_transaction = storage.begin_transaction().await;
+        let mut result = self.batch_insert_leaves::<_, H>(storage, leaves).await;
+        if result.is_ok() {
+            result = storage.batch_set(metadata).await.map_err(AkdError::Storage);
+        }
+        if own_transaction {
+            result = match result {
+                Ok(()) => storage
+                    .commit_transaction()
+                    .await
+                    .map_err(AkdError::Storage),
+                Err(err) => {
+                    // ignore any rollback error(s)
+                    let _ = storage.rollback_transaction().await;
+                    Err(err)
+                }
+            };
+            if result.is_err() {
+                self.latest_epoch = previous_epoch;
+                self.num_nodes = previous_num_nodes;
+            }
+        }
+        result
+    }
+
+    /// Sets (or replaces) the metadata of the leaf with the given label. Since the metadata
+    /// isn't part of the tree, this takes effect immediately, without a new epoch. Fails with
+    /// [AzksError::LeafMetadataTooLong] if the metadata is too long.
+    pub async fn set_leaf_metadata<S: Storage + Sync + Send>(
+        &self,
+        storage: &S,
+        label: NodeLabel,
+        data: Vec<u8>,
+    ) -> Result<(), AkdError> {
+        let metadata = LeafMetadata::new(label, data)?;
+        if self.get_existing_leaf(storage, label).await?.is_none() {
+            return Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                label,
+                self.get_latest_epoch(),
+            )));
+        }
+        storage.set(DbRecord::LeafMetadata(metadata)).await?;
+        Ok(())
+    }
+
+    /// Returns the leaf with the given label as of the latest epoch along with its metadata,
+    /// if any was stored, or None if there's no such leaf. The metadata is NOT covered by any
+    /// proof about the leaf, so it shouldn't be handed to clients as if it were.
+    pub async fn get_leaf_with_metadata<S: Storage + Sync + Send>(
+        &self,
+        storage: &S,
+        label: NodeLabel,
+    ) -> Result<Option<(TreeNode, Option<LeafMetadata>)>, AkdError> {
+        let leaf = match self.get_existing_leaf(storage, label).await? {
+            Some(leaf) => leaf,
+            None => return Ok(None),
+        };
+        let metadata = match storage.get::<LeafMetadata>(&NodeKey(label)).await {
+            Ok(DbRecord::LeafMetadata(metadata)) => Some(metadata),
+            Ok(_) | Err(StorageError::NotFound(_)) => None,
+            Err(other) => return Err(AkdError::Storage(other)),
+        };
+        Ok(Some((leaf, metadata)))
+    }
+
+    /// Inserts (or overwrites) a checkpoint leaf holding `digest` at the reserved `label`,
+    /// in a new epoch of the tree. A checkpoint commits to some data external to the
+    /// directory (e.g. a hash of an operator's log) as of the epoch, and clients check it
+    /// against the root hash with [crate::client::verify_checkpoint].
+    ///
+    /// The label should come from [NodeLabel::checkpoint], so that it can't collide with
+    /// the label of a user's leaf, which the tree has no way to tell apart from a checkpoint.
+    /// Labels shorter than 256 bits are rejected with [AzksError::InvalidCheckpointLabel],
+    /// since they could be prefixes of the labels of other leaves.
+    pub async fn insert_checkpoint<S: Storage + Sync + Send, H: Hasher>(
+        &mut self,
+        storage: &S,
+        label: NodeLabel,
+        digest: H::Digest,
+    ) -> Result<(), AkdError> {
+        if label.get_len() != 256 {
+            return Err(AkdError::AzksErr(AzksError::InvalidCheckpointLabel(label)));
+        }
+        let checkpoint = Node::<H> {
+            label,
+            hash: digest,
+        };
+        self.batch_insert_leaves_with_policy::<_, H>(
+            storage,
+            vec![checkpoint],
+            InsertPolicy::UpdateExisting,
+        )
+        .await
+    }
+
     async fn preload_nodes_for_insertion<S: Storage + Sync + Send, H: Hasher>(
         &self,
         storage: &S,
@@ -189,18 +408,298 @@ impl Azks {
         Ok(load_count)
     }
 
+    /// Loads the nodes which proofs for the given labels will need, so that the storage layer
+    /// has them cached by the time the proofs are generated, e.g. when a proof server is told
+    /// which labels are about to be queried. This is the same preload a batch lookup makes:
+    /// the root-to-leaf path of each label along with the siblings along it, read from storage
+    /// one level of the tree at a time. Returns the number of nodes loaded.
+    ///
+    /// Whether the nodes are kept is up to the storage: one without a cache just reads them.
+    /// This doesn't modify the tree, so it can run in a background task alongside other
+    /// readers, with its own handle on the storage.
+    pub async fn prefetch_paths<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        labels: &[NodeLabel],
+    ) -> Result<u64, AkdError> {
+        let prefixes_set = crate::utils::build_lookup_prefixes_set(labels);
+        self.bfs_preload_nodes::<S, H>(storage, prefixes_set).await
+    }
+
+    /// Returns the labels of the nodes whose stored records may have changed after the given
+    /// epoch, i.e. those which a cache loaded at that epoch can no longer serve. A node's
+    /// last_epoch is the latest epoch of any update in its subtree, so the walk down from the
+    /// root stops at nodes with last_epoch <= since_epoch, and everything below them is left
+    /// out. The children of a changed node are included too, since inserting a node above a
+    /// child rewrites the child's parent without touching anything else about it.
+    ///
+    /// The nodes are read directly from the data layer at the latest epoch of this azks,
+    /// bypassing any cache, since the cache is what's being checked. Each layer of the walk
+    /// is read with a single batched read.
+    pub async fn get_changed_labels_since<S: Storage + Sync + Send>(
+        &self,
+        storage: &S,
+        since_epoch: u64,
+    ) -> Result<Vec<NodeLabel>, AkdError> {
+        let mut changed = Vec::new();
+        let mut current_nodes = vec![NodeKey(NodeLabel::root())];
+        while !current_nodes.is_empty() {
+            let nodes =
+                TreeNode::batch_get_direct_from_storage(storage, &current_nodes, self.latest_epoch)
+                    .await?;
+            if nodes.len() != current_nodes.len() {
+                return Err(AkdError::AzksErr(AzksError::TreeIntegrity(format!(
+                    "Found {} of the {} nodes of a layer below the root",
+                    nodes.len(),
+                    current_nodes.len()
+                ))));
+            }
+            let mut next_nodes = Vec::new();
+            for node in nodes {
+                changed.push(node.label);
+                if node.last_epoch <= since_epoch {
+                    continue;
+                }
+                for dir in 0..ARITY {
+                    if let Some(child_label) = node.get_child_label(Direction::Some(dir)) {
+                        next_nodes.push(NodeKey(child_label));
+                    }
+                }
+            }
+            current_nodes = next_nodes;
+        }
+        Ok(changed)
+    }
+
     /// An azks is built both by the [crate::directory::Directory] and the auditor.
-    /// However, both constructions have very minor differences, and the append_only_usage
-    /// bool keeps track of this.
+    /// However, both constructions have very minor differences, and the
+    /// append_only_exclude_usage option keeps track of this.
+    ///
+    /// The insertion runs in two passes. First every label of the batch is looked up in the
+    /// tree and resolved against the policy, before anything is written, so that a rejected
+    /// batch leaves the tree untouched. Then the resolved leaves are inserted and hashed up
+    /// to the root. With a maximum chunk size, each pass works through the batch that many
+    /// leaves at a time: the nodes preloaded for a lookup and the nodes queued for rehashing
+    /// are bounded by the chunk size. The batch itself isn't, and neither is the result of
+    /// the first pass, which holds the existing record of every label being updated until
+    /// the second pass writes it. Chunking costs more storage round-trips, as nodes near the
+    /// root are rehashed for every chunk. All of the chunks are inserted within the same
+    /// (single) new epoch, and the epoch is only persisted when the caller writes the azks
+    /// after this returns, so a batch which fails part way through is never visible as a
+    /// published epoch.
+    ///
+    /// The progress callback is called inline, so it should return quickly.
     pub async fn batch_insert_leaves_helper<S: Storage + Sync + Send, H: Hasher>(
         &mut self,
         storage: &S,
         insertion_set: Vec<Node<H>>,
-        append_only_exclude_usage: bool,
+        options: InsertOptions<'_>,
+    ) -> Result<(), AkdError> {
+        let InsertOptions {
+            append_only_exclude_usage,
+            policy,
+            max_chunk_size,
+            progress,
+        } = options;
+        let chunk_size = max_chunk_size.unwrap_or(insertion_set.len()).max(1);
+
+        // Resolve labels which are already in the tree before modifying anything,
+        // so that a rejected batch leaves the tree untouched.
+        let mut insertions = Vec::with_capacity(insertion_set.len());
+        for chunk in insertion_set.chunks(chunk_size) {
+            insertions.extend(
+                self.resolve_insertions::<_, H>(storage, chunk, policy)
+                    .await?,
+            );
+        }
+
+        self.increment_epoch();
+
+        let total = insertions.len();
+        let mut processed = 0;
+        let mut remaining = insertions.into_iter();
+        while processed < total {
+            let chunk = remaining.by_ref().take(chunk_size).collect::<Vec<_>>();
+            self.insert_resolved_chunk::<_, H>(storage, chunk, append_only_exclude_usage, || {
+                processed += 1;
+                if let Some(progress) = progress {
+                    if processed % INSERT_PROGRESS_INTERVAL == 0 || processed == total {
+                        progress(processed, total);
+                    }
+                }
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Loads `leaves` into an empty tree as its first epoch, for an initial import of a large
+    /// number of leaves. All of the leaves are inserted with hashing off, and then every node
+    /// of the tree is hashed exactly once, in a single bottom-up pass. Unlike
+    /// [Azks::batch_insert_leaves], this skips looking up each label in the tree beforehand
+    /// (there's nothing to find), and orders the hashing by a walk of the tree rather than by
+    /// queueing the ancestors of every leaf. Since the tree's shape and hashes don't depend on
+    /// the order of insertion, the result is the same as inserting the leaves as a batch.
+    ///
+    /// Fails with [AzksError::TreeNotEmpty] if any leaf was inserted before, and with
+    /// [TreeNodeError::LabelAlreadyExists] if a label appears more than once in `leaves`.
+    pub async fn bulk_load<S: Storage + Sync + Send, H: Hasher>(
+        &mut self,
+        storage: &S,
+        leaves: Vec<Node<H>>,
     ) -> Result<(), AkdError> {
+        if self.num_nodes != 1 {
+            return Err(AkdError::AzksErr(AzksError::TreeNotEmpty(self.num_nodes)));
+        }
+        let mut labels = HashSet::with_capacity(leaves.len());
+        for leaf in &leaves {
+            if !labels.insert(leaf.label) {
+                return Err(AkdError::TreeNode(TreeNodeError::LabelAlreadyExists(
+                    leaf.label,
+                )));
+            }
+        }
+
+        self.increment_epoch();
+
+        let tic = Instant::now();
+        let mut root_node = TreeNode::get_from_storage(
+            storage,
+            &NodeKey(NodeLabel::root()),
+            self.get_latest_epoch(),
+        )
+        .await?;
+        for leaf in leaves {
+            let new_leaf =
+                get_leaf_node::<H>(leaf.label, &leaf.hash, NodeLabel::root(), self.latest_epoch);
+            root_node
+                .insert_leaf::<_, H>(
+                    storage,
+                    new_leaf,
+                    self.latest_epoch,
+                    &mut self.num_nodes,
+                    None,
+                )
+                .await?;
+        }
+        let toc = Instant::now() - tic;
+        info!("Bulk load insertion took {} s", toc.as_secs_f64());
+
+        // Read the tree breadth-first, a layer at a time with a single batched read, then hash
+        // the layers from the deepest up so that each node is hashed after its children.
+        // Hashing a node only rewrites the node itself, so the copies read here stay current
+        let tic = Instant::now();
+        let mut layers: Vec<Vec<TreeNode>> = Vec::new();
+        let mut keys = vec![NodeKey(NodeLabel::root())];
+        while !keys.is_empty() {
+            let layer = TreeNode::batch_get_from_storage(storage, &keys, self.latest_epoch).await?;
+            if layer.len() != keys.len() {
+                return Err(AkdError::AzksErr(AzksError::TreeIntegrity(format!(
+                    "Found {} of the {} nodes of a layer below the root",
+                    layer.len(),
+                    keys.len()
+                ))));
+            }
+            keys = layer
+                .iter()
+                .flat_map(|node| node.left_child.iter().chain(node.right_child.iter()))
+                .map(|label| NodeKey(*label))
+                .collect();
+            layers.push(layer);
+        }
+        for layer in layers.into_iter().rev() {
+            for mut node in layer {
+                node.update_node_hash::<_, H>(storage, self.latest_epoch, None)
+                    .await?;
+            }
+        }
+        let toc = Instant::now() - tic;
+        info!("Bulk load hashing took {} s", toc.as_secs_f64());
+        Ok(())
+    }
+
+    /// Inserts the leaves pulled from `stream` in a single new epoch, `chunk_size` leaves at a
+    /// time, so that a large import (e.g. read from a database cursor) never needs to be
+    /// pulled into memory all at once. Each chunk is inserted and hashed up to the root before
+    /// the next one is pulled from the stream.
+    ///
+    /// Unlike [Azks::batch_insert_leaves_helper], labels already in the tree are resolved
+    /// against `policy` one chunk at a time, so a label rejected by
+    /// [InsertPolicy::RejectExisting] fails the insertion after the earlier chunks have been
+    /// inserted. The insertion therefore runs within a storage transaction. If no transaction
+    /// is active, one is started here: it's committed once the whole stream has been inserted,
+    /// and rolled back if any chunk fails, so that nothing of a failed insertion is left in
+    /// storage. If the caller already has a transaction active, the nodes are written within
+    /// it, and on failure it's up to the caller to roll it back. Either way the transaction
+    /// holds every node written until it's committed, so the storage layer's memory use grows
+    /// with the stream even though the leaves are pulled a chunk at a time.
+    ///
+    /// The latest epoch of the azks only advances once the whole stream has been inserted: if
+    /// any chunk fails, it's left at the previous epoch.
+    pub async fn insert_from_stream<S, H, St>(
+        &mut self,
+        storage: &S,
+        stream: St,
+        chunk_size: usize,
+        policy: InsertPolicy,
+    ) -> Result<(), AkdError>
+    where
+        S: Storage + Sync + Send,
+        H: Hasher,
+        St: Stream<Item = Node<H>> + Unpin,
+    {
+        let previous_epoch = self.latest_epoch;
+        let previous_num_nodes = self.num_nodes;
+        // begin_transaction is false if the caller's transaction is already active
+        let own_transaction = storage.begin_transaction().await;
+        // The nodes are written at the new epoch as they're inserted, so the epoch is advanced
+        // in memory up front and rolled back if the insertion doesn't complete
+        self.increment_epoch();
+
+        let mut chunks = stream.chunks(chunk_size.max(1));
+        while let Some(chunk) = chunks.next().await {
+            let result = match self
+                .resolve_insertions::<_, H>(storage, &chunk, policy)
+                .await
+            {
+                Ok(insertions) => {
+                    self.insert_resolved_chunk::<_, H>(storage, insertions, false, || {})
+                        .await
+                }
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                self.latest_epoch = previous_epoch;
+                self.num_nodes = previous_num_nodes;
+                if own_transaction {
+                    // ignore any rollback error(s)
+                    let _ = storage.rollback_transaction().await;
+                }
+                return Err(err);
+            }
+        }
+        if own_transaction {
+            if let Err(err) = storage.commit_transaction().await {
+                self.latest_epoch = previous_epoch;
+                self.num_nodes = previous_num_nodes;
+                return Err(AkdError::Storage(err));
+            }
+        }
+        Ok(())
+    }
+
+    /// Preloads the nodes needed to insert `chunk`, and pairs each of its leaves with the leaf
+    /// already in the tree under the same label, if any, dropping or rejecting the leaves
+    /// which `policy` says shouldn't be inserted
+    async fn resolve_insertions<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        chunk: &[Node<H>],
+        policy: InsertPolicy,
+    ) -> Result<Vec<(Node<H>, Option<TreeNode>)>, AkdError> {
         let tic = Instant::now();
         let load_count = self
-            .preload_nodes_for_insertion::<S, H>(storage, &insertion_set)
+            .preload_nodes_for_insertion::<S, H>(storage, chunk)
             .await?;
         let toc = Instant::now() - tic;
         info!(
@@ -209,34 +708,90 @@ impl Azks {
             toc.as_secs_f64()
         );
 
-        self.increment_epoch();
+        let mut insertions = Vec::with_capacity(chunk.len());
+        for node in chunk {
+            let existing = self.get_existing_leaf(storage, node.label).await?;
+            match (existing, policy) {
+                (Some(_), InsertPolicy::RejectExisting) => {
+                    return Err(AkdError::TreeNode(TreeNodeError::LabelAlreadyExists(
+                        node.label,
+                    )));
+                }
+                (Some(leaf), InsertPolicy::UpdateIfChanged)
+                    if leaf.hash == from_digest::<H>(node.hash) => {}
+                (existing, _) => insertions.push((*node, existing)),
+            }
+        }
+        Ok(insertions)
+    }
 
+    /// Inserts (or updates) the resolved leaves of a chunk at the latest epoch, and then
+    /// rehashes them and their ancestors up to the root. `on_inserted` is called after each
+    /// leaf is written.
+    async fn insert_resolved_chunk<S: Storage + Sync + Send, H: Hasher>(
+        &mut self,
+        storage: &S,
+        chunk: Vec<(Node<H>, Option<TreeNode>)>,
+        append_only_exclude_usage: bool,
+        mut on_inserted: impl FnMut() + Send,
+    ) -> Result<(), AkdError> {
         let mut hash_q = KeyedPriorityQueue::<NodeLabel, i32>::new();
         let mut priorities: i32 = 0;
+        // The root is rehashed at the end of every chunk, so it's reloaded for each
         let mut root_node = TreeNode::get_from_storage(
             storage,
             &NodeKey(NodeLabel::root()),
             self.get_latest_epoch(),
         )
         .await?;
-        for node in insertion_set {
-            let new_leaf =
-                get_leaf_node::<H>(node.label, &node.hash, NodeLabel::root(), self.latest_epoch);
-            debug!("BEGIN insert leaf");
-            root_node
-                .insert_leaf::<_, H>(
-                    storage,
-                    new_leaf,
+        for (node, existing) in chunk {
+            if let Some(mut leaf) = existing {
+                // The leaf is already in the tree, so just replace its value. Its ancestors
+                // are rehashed below like those of any newly inserted leaf.
+                debug!("BEGIN update leaf");
+                leaf.hash = from_digest::<H>(node.hash);
+                leaf.last_epoch = self.latest_epoch;
+                leaf.write_to_storage(storage).await?;
+                debug!("END update leaf");
+            } else {
+                let new_leaf = get_leaf_node::<H>(
+                    node.label,
+                    &node.hash,
+                    NodeLabel::root(),
                     self.latest_epoch,
-                    &mut self.num_nodes,
-                    Some(append_only_exclude_usage),
-                )
-                .await?;
-            debug!("END insert leaf");
+                );
+                debug!("BEGIN insert leaf");
+                root_node
+                    .insert_leaf::<_, H>(
+                        storage,
+                        new_leaf,
+                        self.latest_epoch,
+                        &mut self.num_nodes,
+                        Some(append_only_exclude_usage),
+                    )
+                    .await?;
+                debug!("END insert leaf");
+            }
 
             hash_q.push(node.label, priorities);
             priorities -= 1;
+            on_inserted();
         }
+        self.hash_up_to_root::<_, H>(storage, hash_q, priorities, append_only_exclude_usage)
+            .await
+    }
+
+    /// Rehashes the nodes in the queue, and then their ancestors up to the root. The nodes
+    /// with the highest priority (i.e. those queued first) are rehashed first, and each parent
+    /// is queued with a lower priority than any node before it, so that it's only rehashed
+    /// after all of its queued descendants.
+    async fn hash_up_to_root<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        mut hash_q: KeyedPriorityQueue<NodeLabel, i32>,
+        mut priorities: i32,
+        append_only_exclude_usage: bool,
+    ) -> Result<(), AkdError> {
         // Now hash up the tree, the highest priority items will be closer to the leaves.
         while let Some((next_node_label, _)) = hash_q.pop() {
             let mut next_node: TreeNode = TreeNode::get_from_storage(
@@ -253,7 +808,7 @@ impl Azks {
                 )
                 .await?;
             if !next_node.is_root() {
-                match hash_q.entry(next_node.parent) {
+                match hash_q.entry(next_node.get_parent_label()?) {
                     Entry::Vacant(entry) => {
                         entry.set_priority(priorities);
                     }
@@ -268,306 +823,1489 @@ impl Azks {
         Ok(())
     }
 
-    /// Returns the Merkle membership proof for the trie as it stood at epoch
-    // Assumes the verifier has access to the root at epoch
-    pub async fn get_membership_proof<S: Storage + Sync + Send, H: Hasher>(
+    /// Returns the same proof as [`Azks::get_non_membership_proof`], but pads the traversal
+    /// in the same way as [`Azks::get_constant_time_membership_proof`].
+    pub async fn get_constant_time_non_membership_proof<S: Storage + Sync + Send, H: Hasher>(
         &self,
         storage: &S,
         label: NodeLabel,
-        _epoch: u64,
-    ) -> Result<MembershipProof<H>, AkdError> {
-        let (pf, _) = self.get_membership_proof_and_node(storage, label).await?;
-        Ok(pf)
+    ) -> Result<NonMembershipProof<H>, AkdError> {
+        let proof = self.get_non_membership_proof(storage, label).await?;
+        // The children of the longest prefix are read as one more layer of the traversal
+        let depth = proof.longest_prefix_membership_proof.layer_proofs.len() + 1;
+        self.pad_proof_traversal(storage, label, depth).await?;
+        Ok(proof)
     }
 
-    // EOZ: There is a needless_range_loop warning by Clippy for `for i in 0..ARITY`
-    // and the suggestion is to use `for (i, <item>) in longest_prefix_children.iter_mut().enumerate().take(ARITY)`
-    // but I think this is inaccurate
-    #[allow(clippy::needless_range_loop)]
-    /// In a compressed trie, the proof consists of the longest prefix
-    /// of the label that is included in the trie, as well as its children, to show that
-    /// none of the children is equal to the given label.
-    pub async fn get_non_membership_proof<S: Storage + Sync + Send, H: Hasher>(
+    /// Reads the nodes on the path to `label` below the `depth` layers which a proof actually
+    /// traversed, down to the label's full length. Most of these don't exist and the results
+    /// are discarded: the reads only serve to make every proof traversal the same shape, not
+    /// to make the reads take the same time.
+    async fn pad_proof_traversal<S: Storage + Sync + Send>(
         &self,
         storage: &S,
         label: NodeLabel,
-    ) -> Result<NonMembershipProof<H>, AkdError> {
-        let (longest_prefix_membership_proof, lcp_node_label) =
-            self.get_membership_proof_and_node(storage, label).await?;
-        let lcp_node: TreeNode =
-            TreeNode::get_from_storage(storage, &NodeKey(lcp_node_label), self.get_latest_epoch())
-                .await?;
-        let longest_prefix = lcp_node.label;
-        // load with placeholder nodes, to be replaced in the loop below
-        let mut longest_prefix_children = [Node {
-            label: EMPTY_LABEL,
-            hash: crate::utils::empty_node_hash::<H>(),
-        }; ARITY];
-        for i in 0..ARITY {
-            let child = lcp_node
-                .get_child_state(storage, Some(i), self.latest_epoch)
-                .await?;
-            match child {
-                None => {
-                    debug!("i = {}, empty", i);
-                    continue;
-                }
-                Some(child) => {
-                    let unwrapped_child: TreeNode = TreeNode::get_from_storage(
-                        storage,
-                        &NodeKey(child.label),
-                        self.get_latest_epoch(),
-                    )
-                    .await?;
-                    debug!("Label of child {} is {:?}", i, unwrapped_child.label);
-                    longest_prefix_children[i] = Node {
-                        label: unwrapped_child.label,
-                        hash: optional_child_state_hash::<H>(&Some(unwrapped_child))?,
-                    };
+        depth: usize,
+    ) -> Result<(), AkdError> {
+        for len in (depth as u32 + 1)..(label.get_len() + 1) {
+            // Mirror the reads of a real layer: the child on the path, its sibling,
+            // and then the child again as the next node to visit
+            let child = label.get_prefix(len);
+            for key in [child, label.get_sibling_prefix(len), child] {
+                match TreeNode::get_from_storage(storage, &NodeKey(key), self.get_latest_epoch())
+                    .await
+                {
+                    Ok(_) | Err(StorageError::NotFound(_)) => {}
+                    Err(other) => return Err(AkdError::Storage(other)),
                 }
             }
         }
-
-        debug!("Lcp label = {:?}", longest_prefix);
-        Ok(NonMembershipProof {
-            label,
-            longest_prefix,
-            longest_prefix_children,
-            longest_prefix_membership_proof,
-        })
+        Ok(())
     }
 
-    // FIXME add an error if the epochs don't exist or end is less than start ep.
-    /// An append-only proof for going from `start_epoch` to `end_epoch` consists of roots of subtrees
-    /// the azks tree that remain unchanged from `start_epoch` to `end_epoch` and the leaves inserted into the
-    /// tree after `start_epoch` and  up until `end_epoch`.
-    /// If there is no errors, this function returns an `Ok` result, containing the
-    ///  append-only proof and otherwise, it returns a [errors::AkdError].
+    /// Recomputes the hash of the node with the given label from its children as of the
+    /// latest epoch. If the stored hash doesn't match (e.g. after a partial write), the
+    /// correct hash is written back and propagated up to the root. Returns whether a repair
+    /// was needed. Leaves store their value rather than a hash of their children, so they
+    /// are never repaired.
     ///
-    /// **RESTRICTIONS**: Note that `start_epoch` and `end_epoch` are valid only when the following are true
-    /// * `start_epoch` <= `end_epoch`
-    /// * `start_epoch` and `end_epoch` are both existing epochs of this AZKS
-    pub async fn get_append_only_proof<S: Storage + Sync + Send, H: Hasher>(
+    /// This writes to storage outside of a publish, so it must only be called by the single
+    /// writer of the directory, with no publish in progress.
+    pub async fn repair_node_hash<S: Storage + Sync + Send, H: Hasher>(
         &self,
         storage: &S,
-        start_epoch: u64,
-        end_epoch: u64,
-    ) -> Result<AppendOnlyProof<H>, AkdError> {
-        let mut proofs = Vec::<SingleAppendOnlyProof<H>>::new();
-        let mut epochs = Vec::<u64>::new();
-        // Suppose the epochs start_epoch and end_epoch exist in the set.
-        // This function should return the proof that nothing was removed/changed from the tree
-        // between these epochs.
-        for ep in start_epoch..end_epoch {
-            let node = TreeNode::get_from_storage(
-                storage,
-                &NodeKey(NodeLabel::root()),
-                self.get_latest_epoch(),
-            )
+        label: NodeLabel,
+    ) -> Result<bool, AkdError> {
+        let epoch = self.get_latest_epoch();
+        let mut node = TreeNode::get_from_storage(storage, &NodeKey(label), epoch).await?;
+        // The hash of an empty root isn't computed from its (missing) children
+        if node.is_leaf() || (node.left_child.is_none() && node.right_child.is_none()) {
+            return Ok(false);
+        }
+
+        let hash = node
+            .compute_children_hash::<_, H>(storage, epoch, false)
             .await?;
-            let (unchanged, leaves) = self
-                .get_append_only_proof_helper::<_, H>(storage, node, ep, ep + 1)
+        if from_digest::<H>(hash) == node.hash {
+            return Ok(false);
+        }
+        // The node keeps its last_epoch, so its stored state is corrected in place
+        node.hash = from_digest::<H>(hash);
+        node.write_to_storage(storage).await?;
+
+        while !node.is_root() {
+            let parent = node.get_parent_label()?;
+            node = TreeNode::get_from_storage(storage, &NodeKey(parent), epoch).await?;
+            let hash = node
+                .compute_children_hash::<_, H>(storage, epoch, false)
                 .await?;
-            proofs.push(SingleAppendOnlyProof {
-                inserted: leaves,
-                unchanged_nodes: unchanged,
-            });
-            epochs.push(ep);
+            node.hash = from_digest::<H>(hash);
+            node.write_to_storage(storage).await?;
         }
-
-        Ok(AppendOnlyProof { proofs, epochs })
+        Ok(true)
     }
 
-    #[async_recursion]
-    async fn get_append_only_proof_helper<S: Storage + Sync + Send, H: Hasher>(
+    /// Returns the length (in edges) of the longest path from the root to a leaf as of the
+    /// latest epoch. Since leaf labels are VRF outputs the tree should stay roughly balanced,
+    /// so a warning is logged if the depth exceeds [`Azks::expected_max_leaf_depth`] for the
+    /// number of leaves. That would point at either a bug in the compression of the tree or
+    /// at adversarially chosen labels, and makes for needlessly large proofs.
+    pub async fn max_leaf_depth<S: Storage + Sync + Send>(
         &self,
         storage: &S,
-        node: TreeNode,
-        start_epoch: u64,
-        end_epoch: u64,
-    ) -> Result<AppendOnlyHelper<H>, AkdError> {
-        let mut unchanged = Vec::<Node<H>>::new();
-        let mut leaves = Vec::<Node<H>>::new();
+    ) -> Result<u32, AkdError> {
+        let mut max_depth = 0u32;
+        let mut num_leaves = 0u64;
+        let mut depth = 0u32;
+        let mut current_nodes = vec![NodeKey(NodeLabel::root())];
 
-        if node.get_latest_epoch() <= start_epoch {
-            if node.is_root() {
-                // this is the case where the root is unchanged since the last epoch
-                return Ok((unchanged, leaves));
+        // Visit the tree one level at a time, so the deepest leaf is in the last level seen
+        while !current_nodes.is_empty() {
+            let nodes =
+                TreeNode::batch_get_from_storage(storage, &current_nodes, self.get_latest_epoch())
+                    .await?;
+            current_nodes = Vec::<NodeKey>::new();
+
+            for node in &nodes {
+                if node.is_leaf() {
+                    num_leaves += 1;
+                    max_depth = depth;
+                }
+                for dir in 0..ARITY {
+                    if let Some(child_label) = node.get_child_label(Direction::Some(dir)) {
+                        current_nodes.push(NodeKey(child_label));
+                    }
+                }
             }
-            unchanged.push(Node::<H> {
-                label: node.label,
-                hash: optional_child_state_hash::<H>(&Some(node))?,
-            });
+            depth += 1;
+        }
 
-            return Ok((unchanged, leaves));
+        let expected = Self::expected_max_leaf_depth(num_leaves);
+        if max_depth > expected {
+            warn!(
+                "The deepest leaf of the tree is at depth {}, above the expected bound of {} for {} leaves",
+                max_depth, expected, num_leaves
+            );
         }
+        Ok(max_depth)
+    }
 
-        if node.least_descendant_ep > end_epoch {
-            return Ok((unchanged, leaves));
+    /// A generous bound on the depth of a tree whose leaves have uniformly random labels. The
+    /// expected depth of the deepest leaf of such a tree is about twice the log of the number
+    /// of leaves.
+    pub fn expected_max_leaf_depth(num_leaves: u64) -> u32 {
+        let log_leaves = 64 - num_leaves.leading_zeros();
+        3 * log_leaves + 8
+    }
+
+    /// The number of nodes, including the root, of a correctly compressed tree with the given
+    /// number of leaves whose root has the given number of children.
+    ///
+    /// In a compressed tree every interior node other than the root has exactly two children,
+    /// and the root is always present, with anywhere from zero to two children. Each non-root
+    /// node is the child of exactly one node, so with `n` leaves and `i` non-root interior
+    /// nodes, `n + i = root_children + 2i`, i.e. `i = n - root_children`. Adding the leaves
+    /// and the root gives `2n - root_children + 1` nodes: `2n - 1` once the leaves diverge at
+    /// the root's first bit, and `2n` while they all share it.
+    ///
+    /// The root has no more children than leaves, and has a child if there are any leaves.
+    /// Counts which no compressed tree can have, e.g. read from a corrupted tree, are returned
+    /// as [AzksError::TreeIntegrity].
+    pub fn expected_node_count(num_leaves: usize, root_children: usize) -> Result<usize, AkdError> {
+        let impossible = || {
+            AkdError::AzksErr(AzksError::TreeIntegrity(format!(
+                "No compressed tree has {} leaves under a root with {} children",
+                num_leaves, root_children
+            )))
+        };
+        if root_children > ARITY
+            || root_children > num_leaves
+            || (num_leaves > 0 && root_children == 0)
+        {
+            return Err(impossible());
         }
+        num_leaves
+            .checked_mul(2)
+            .and_then(|count| count.checked_add(1))
+            .and_then(|count| count.checked_sub(root_children))
+            .ok_or_else(impossible)
+    }
 
-        if node.is_leaf() {
-            leaves.push(Node::<H> {
-                label: node.label,
-                hash: to_digest::<H>(&node.hash)?,
-            });
-        } else {
-            for child_label in [node.left_child, node.right_child] {
-                match child_label {
-                    None => {
-                        continue;
-                    }
-                    Some(label) => {
-                        let child_node = TreeNode::get_from_storage(
-                            storage,
-                            &NodeKey(label),
-                            self.get_latest_epoch(),
-                        )
-                        .await?;
-                        let mut rec_output = self
-                            .get_append_only_proof_helper::<_, H>(
-                                storage,
-                                child_node,
-                                start_epoch,
-                                end_epoch,
-                            )
-                            .await?;
-                        unchanged.append(&mut rec_output.0);
-                        leaves.append(&mut rec_output.1);
-                    }
+    /// Checks the shape of the tree as of the latest epoch against the invariants of a
+    /// compressed tree. Every interior node other than the root must have two children, and
+    /// the number of nodes found must match both [`Azks::expected_node_count`] for the number
+    /// of leaves found and the node count this azks keeps. A mismatch points at either a bug in
+    /// the compression of the tree, or at nodes which have been orphaned, duplicated or lost
+    /// in storage.
+    pub async fn verify_tree_integrity<S: Storage + Sync + Send>(
+        &self,
+        storage: &S,
+    ) -> Result<(), AkdError> {
+        let mut num_nodes = 0usize;
+        let mut num_leaves = 0usize;
+        let mut root_children = 0usize;
+        let mut current_nodes = vec![NodeKey(NodeLabel::root())];
+
+        while !current_nodes.is_empty() {
+            let nodes =
+                TreeNode::batch_get_from_storage(storage, &current_nodes, self.get_latest_epoch())
+                    .await?;
+            if nodes.len() != current_nodes.len() {
+                return Err(AkdError::AzksErr(AzksError::TreeIntegrity(format!(
+                    "{} of the {} nodes referenced by their parents are missing",
+                    current_nodes.len() - nodes.len(),
+                    current_nodes.len()
+                ))));
+            }
+            current_nodes = Vec::<NodeKey>::new();
+
+            for node in &nodes {
+                num_nodes += 1;
+                if node.is_leaf() {
+                    num_leaves += 1;
                 }
+                let children = (0..ARITY)
+                    .filter_map(|dir| node.get_child_label(Direction::Some(dir)))
+                    .collect::<Vec<_>>();
+                if node.is_root() {
+                    root_children = children.len();
+                } else if !node.is_leaf() && children.len() != ARITY {
+                    return Err(AkdError::AzksErr(AzksError::TreeIntegrity(format!(
+                        "Interior node {} has {} children",
+                        node.label,
+                        children.len()
+                    ))));
+                }
+                current_nodes.extend(children.into_iter().map(NodeKey));
             }
         }
-        Ok((unchanged, leaves))
+
+        let expected = Self::expected_node_count(num_leaves, root_children)?;
+        if num_nodes != expected || num_nodes as u64 != self.num_nodes {
+            return Err(AkdError::AzksErr(AzksError::TreeIntegrity(format!(
+                "Found {} nodes for {} leaves, expected {} and the azks counts {}",
+                num_nodes, num_leaves, expected, self.num_nodes
+            ))));
+        }
+        Ok(())
     }
 
-    // FIXME: these functions below should be moved into higher-level API
-    /// Gets the root hash for this azks
-    pub async fn get_root_hash<S: Storage + Sync + Send, H: Hasher>(
+    /// Checks that every tree node in storage can be reached from the root as of the latest
+    /// epoch. A node which no parent refers to points at a bug or a partial write, and takes
+    /// up storage for nothing. Unlike [`Azks::verify_tree_integrity`], which can only tell
+    /// that the count of nodes is off, this scans every node in storage (see [StorageUtil]),
+    /// so it's expensive but fails with [AzksError::OrphanedNodes] listing all of the orphans,
+    /// so that they can be cleaned up in one pass. Nodes which don't exist yet as of the latest
+    /// epoch, i.e. written by a publish in progress, aren't orphans.
+    pub async fn verify_no_orphaned_nodes<S: StorageUtil + Sync + Send>(
         &self,
         storage: &S,
-    ) -> Result<H::Digest, AkdError> {
-        self.get_root_hash_at_epoch::<_, H>(storage, self.get_latest_epoch())
-            .await
+    ) -> Result<(), AkdError> {
+        let mut reachable = HashSet::new();
+        let mut current_nodes = vec![NodeKey(NodeLabel::root())];
+        while !current_nodes.is_empty() {
+            let nodes =
+                TreeNode::batch_get_from_storage(storage, &current_nodes, self.get_latest_epoch())
+                    .await?;
+            current_nodes = Vec::<NodeKey>::new();
+            for node in nodes {
+                reachable.insert(node.label);
+                current_nodes.extend(
+                    (0..ARITY)
+                        .filter_map(|dir| node.get_child_label(Direction::Some(dir)))
+                        .map(NodeKey),
+                );
+            }
+        }
+
+        let mut orphans = storage
+            .batch_get_type_direct::<TreeNodeWithPreviousValue>()
+            .await?
+            .into_iter()
+            .filter_map(|record| match record {
+                DbRecord::TreeNode(node) => Some(node),
+                _ => None,
+            })
+            .filter(|node| {
+                !reachable.contains(&node.label)
+                    && (node.latest_node.last_epoch <= self.get_latest_epoch()
+                        || node.previous_node.is_some())
+            })
+            .map(|node| node.label)
+            .collect::<Vec<_>>();
+        if orphans.is_empty() {
+            return Ok(());
+        }
+        orphans.sort();
+        Err(AkdError::AzksErr(AzksError::OrphanedNodes(orphans)))
     }
 
-    /// Gets the root hash of the tree at a epoch.
-    /// Since this is accessing the root node and the root node exists at all epochs that
-    /// the azks does, this would never be called at an epoch before the birth of the root node.
-    pub async fn get_root_hash_at_epoch<S: Storage + Sync + Send, H: Hasher>(
-        &self,
+    /// A cheap check that the tree in storage can serve proofs, e.g. for a readiness probe
+    /// before a server starts taking requests. It reads the azks record directly from storage
+    /// and the root node as of the azks's latest epoch, and checks that the root is marked as
+    /// the root and wasn't updated after the latest epoch. Unlike
+    /// [`Azks::verify_tree_integrity`] it doesn't walk the tree, so it doesn't catch damage
+    /// below the root.
+    ///
+    /// If the read of the azks record fails with [StorageError::Connection] or
+    /// [StorageError::Timeout], a report with `storage_reachable` unset is returned, without
+    /// the latest epoch or root hash. Other errors from storage (including
+    /// [StorageError::NotFound] for a missing azks or root) are returned as they are, a root
+    /// breaking the invariants checked by
+    /// [`Azks::assert_root_invariants`] as [AzksError::RootInvariantViolated], and a root
+    /// updated after the latest epoch as [AzksError::TreeIntegrity].
+    pub async fn health_check<S: Storage + Sync + Send, H: Hasher>(
         storage: &S,
-        epoch: u64,
-    ) -> Result<H::Digest, AkdError> {
-        if self.latest_epoch < epoch {
-            // cannot retrieve information for future epoch
-            return Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
-                NodeLabel::root(),
-                epoch,
-            )));
+    ) -> Result<HealthReport<H>, AkdError> {
+        let azks = match storage.get_direct::<Azks>(&DEFAULT_AZKS_KEY).await {
+            Ok(DbRecord::Azks(azks)) => azks,
+            Ok(_) => {
+                return Err(AkdError::Storage(StorageError::Other(
+                    "Retrieved a record of the wrong type for the azks".to_string(),
+                )))
+            }
+            Err(err @ StorageError::Connection(_)) | Err(err @ StorageError::Timeout { .. }) => {
+                warn!("Storage is unreachable: {}", err);
+                return Ok(HealthReport {
+                    storage_reachable: false,
+                    latest_epoch: None,
+                    root_hash: None,
+                });
+            }
+            Err(other) => return Err(AkdError::Storage(other)),
+        };
+        let latest_epoch = azks.get_latest_epoch();
+        let root_key = NodeKey(NodeLabel::root());
+        let root_node = TreeNode::get_from_storage(storage, &root_key, latest_epoch).await?;
+        check_root_invariants(&root_node)?;
+        if root_node.last_epoch > latest_epoch {
+            return Err(AkdError::AzksErr(AzksError::TreeIntegrity(format!(
+                "The root was last updated in epoch {}, after the latest epoch {} of the azks",
+                root_node.last_epoch, latest_epoch
+            ))));
         }
-        let root_node: TreeNode = TreeNode::get_from_storage(
+        let root_hash = hash_u8_with_label::<H>(&root_node.hash, root_node.label)?;
+        Ok(HealthReport {
+            storage_reachable: true,
+            latest_epoch: Some(latest_epoch),
+            root_hash: Some(root_hash),
+        })
+    }
+
+    /// Checks that the node stored at the root label as of the latest epoch is a root: it's
+    /// marked as the root, it's labelled with the zero-length label, and it's its own parent.
+    /// Insertion special-cases the root based on these, so a root breaking any of them would
+    /// lead to a misshapen tree and wrong proofs. A violation is returned as
+    /// [AzksError::RootInvariantViolated].
+    pub async fn assert_root_invariants<S: Storage + Sync + Send>(
+        &self,
+        storage: &S,
+    ) -> Result<(), AkdError> {
+        let root_node = TreeNode::get_from_storage(
             storage,
             &NodeKey(NodeLabel::root()),
             self.get_latest_epoch(),
         )
         .await?;
-        hash_u8_with_label::<H>(&root_node.hash, root_node.label)
+        check_root_invariants(&root_node)?;
+        Ok(())
     }
 
-    /// Gets the latest epoch of this azks. If an update aka epoch transition
-    /// is in progress, this should return the most recent completed epoch.
-    pub fn get_latest_epoch(&self) -> u64 {
-        self.latest_epoch
+    /// Returns the leaf with the given label as of the latest epoch, if there is one
+    async fn get_existing_leaf<S: Storage + Sync + Send>(
+        &self,
+        storage: &S,
+        label: NodeLabel,
+    ) -> Result<Option<TreeNode>, AkdError> {
+        match TreeNode::get_from_storage(storage, &NodeKey(label), self.get_latest_epoch()).await {
+            Ok(node) if node.node_type == NodeType::Leaf => Ok(Some(node)),
+            Ok(_) | Err(StorageError::NotFound(_)) => Ok(None),
+            Err(other) => Err(AkdError::Storage(other)),
+        }
     }
 
-    fn increment_epoch(&mut self) {
-        let epoch = self.latest_epoch + 1;
-        self.latest_epoch = epoch;
+    /// Returns the Merkle membership proof for the trie as it stood at epoch
+    // Assumes the verifier has access to the root at epoch
+    pub async fn get_membership_proof<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        label: NodeLabel,
+        _epoch: u64,
+    ) -> Result<MembershipProof<H>, AkdError> {
+        let (pf, _) = self.get_membership_proof_and_node(storage, label).await?;
+        Ok(pf)
     }
 
-    /// This function returns the node label for the node whose label is the longest common
-    /// prefix for the queried label. It also returns a membership proof for said label.
-    /// This is meant to be used in both, getting membership proofs and getting non-membership proofs.
-    pub async fn get_membership_proof_and_node<S: Storage + Sync + Send, H: Hasher>(
+    /// Returns the same proof as [`Azks::get_membership_proof`], but pads the traversal so
+    /// that it reads as many nodes from storage as a path down to the label's full length,
+    /// wherever in the tree the label's leaf sits. Together with
+    /// [`Azks::get_constant_time_non_membership_proof`] this hides whether the label is in
+    /// the tree from the number and shape of the storage reads made for the proof.
+    ///
+    /// The padding costs three reads for each layer below the real path, so for 256-bit
+    /// labels about 750 reads per proof rather than a few dozen. Despite the name, the time
+    /// taken isn't constant: the padding reads are of nodes which mostly don't exist, so they
+    /// can be answered faster or slower than reads of real nodes, and a cache in front of the
+    /// storage hits on recently read nodes and misses on the rest. An observer who can time
+    /// the storage reads may still tell the two kinds of proof apart.
+    pub async fn get_constant_time_membership_proof<S: Storage + Sync + Send, H: Hasher>(
         &self,
         storage: &S,
         label: NodeLabel,
-    ) -> Result<(MembershipProof<H>, NodeLabel), AkdError> {
-        let mut layer_proofs = Vec::new();
-        let mut curr_node: TreeNode = TreeNode::get_from_storage(
-            storage,
-            &NodeKey(NodeLabel::root()),
-            self.get_latest_epoch(),
-        )
-        .await?;
-
-        let mut dir = curr_node.label.get_dir(label);
-        let mut equal = label == curr_node.label;
-        let mut prev_node = NodeLabel::root();
-        while !equal && dir.is_some() {
-            prev_node = curr_node.label;
+        _epoch: u64,
+    ) -> Result<MembershipProof<H>, AkdError> {
+        let (pf, _) = self.get_membership_proof_and_node(storage, label).await?;
+        self.pad_proof_traversal(storage, label, pf.layer_proofs.len())
+            .await?;
+        Ok(pf)
+    }
 
-            let mut nodes = [Node::<H> {
-                label: EMPTY_LABEL,
-                hash: crate::utils::empty_node_hash::<H>(),
-            }; ARITY - 1];
-            let mut count = 0;
-            let direction = dir.ok_or(AkdError::TreeNode(TreeNodeError::NoDirection(
-                curr_node.label,
-                None,
-            )))?;
-            let next_state = curr_node
-                .get_child_state(storage, Some(direction), self.latest_epoch)
-                .await?;
-            if next_state.is_some() {
-                for i in 0..ARITY {
-                    let no_direction_error =
-                        AkdError::TreeNode(TreeNodeError::NoDirection(curr_node.label, None));
+    /// Returns a single proof of membership or non-membership for each of the `labels` in
+    /// the trie as it stood at `epoch`. The internal nodes shared by the paths to the labels
+    /// are only accounted for once, see [`BatchMembershipProof`].
+    pub async fn get_batch_membership_proof<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        labels: &[NodeLabel],
+        epoch: u64,
+    ) -> Result<BatchMembershipProof<H>, AkdError> {
+        if self.latest_epoch < epoch {
+            // cannot retrieve information for future epoch
+            return Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                NodeLabel::root(),
+                epoch,
+            )));
+        }
 
-                    if i != dir.ok_or(no_direction_error)? {
-                        let sibling = curr_node
-                            .get_child_state(storage, Direction::Some(i), self.latest_epoch)
-                            .await?;
-                        nodes[count] = Node::<H> {
-                            label: optional_child_state_to_label(&sibling),
-                            hash: optional_child_state_hash::<H>(&sibling)?,
+        let mut labels = labels.to_vec();
+        labels.sort();
+        labels.dedup();
+
+        let mut membership = Vec::new();
+        let mut non_membership = Vec::new();
+        let mut siblings = Vec::new();
+
+        let root = TreeNode::get_from_storage(storage, &NodeKey(NodeLabel::root()), epoch).await?;
+        // Every node visited is an internal node whose label is a prefix of each of its labels
+        let mut to_visit = vec![(root, labels)];
+        while let Some((node, node_labels)) = to_visit.pop() {
+            // A label equal to an internal node's label can't be a leaf of the tree
+            non_membership.extend(node_labels.iter().filter(|label| **label == node.label));
+
+            for dir in 0..ARITY {
+                let child_labels = node_labels
+                    .iter()
+                    .filter(|label| node.label.get_dir(**label) == Direction::Some(dir));
+                match node.get_child_state(storage, Some(dir), epoch).await? {
+                    None => non_membership.extend(child_labels),
+                    Some(child) => {
+                        let (below, absent): (Vec<NodeLabel>, Vec<NodeLabel>) =
+                            child_labels.partition(|label| child.label.is_prefix_of(label));
+                        non_membership.extend(absent);
+
+                        if !below.is_empty() && !child.is_leaf() {
+                            to_visit.push((child, below));
+                            continue;
+                        }
+
+                        let child_node = Node::<H> {
+                            label: child.label,
+                            hash: optional_child_state_hash::<H>(&Some(child))?,
                         };
-                        count += 1;
+                        if below.contains(&child_node.label) {
+                            membership.push(child_node);
+                        } else {
+                            siblings.push(child_node);
+                        }
+                        non_membership.extend(below.into_iter().filter(|l| *l != child_node.label));
                     }
                 }
-            } else {
-                break;
             }
-            layer_proofs.push(proof_structs::LayerProof {
-                label: curr_node.label,
-                siblings: nodes,
-                direction: dir,
-            });
-            let new_curr_node: TreeNode = TreeNode::get_from_storage(
-                storage,
-                &NodeKey(curr_node.get_child(dir).unwrap().unwrap()),
-                self.get_latest_epoch(),
-            )
-            .await?;
-            curr_node = new_curr_node;
-            dir = curr_node.label.get_dir(label);
-            equal = label == curr_node.label;
         }
 
-        if !equal {
-            let new_curr_node: TreeNode =
-                TreeNode::get_from_storage(storage, &NodeKey(prev_node), self.get_latest_epoch())
-                    .await?;
-            curr_node = new_curr_node;
-
-            layer_proofs.pop();
-        }
-        let hash_val = if curr_node.is_leaf() {
-            H::merge_with_int(to_digest::<H>(&curr_node.hash)?, curr_node.last_epoch)
-        } else {
-            to_digest::<H>(&curr_node.hash)?
-        };
+        Ok(BatchMembershipProof {
+            membership,
+            non_membership,
+            siblings,
+        })
+    }
+
+    // EOZ: There is a needless_range_loop warning by Clippy for `for i in 0..ARITY`
+    // and the suggestion is to use `for (i, <item>) in longest_prefix_children.iter_mut().enumerate().take(ARITY)`
+    // but I think this is inaccurate
+    #[allow(clippy::needless_range_loop)]
+    /// In a compressed trie, the proof consists of the longest prefix
+    /// of the label that is included in the trie, as well as its children, to show that
+    /// none of the children is equal to the given label.
+    pub async fn get_non_membership_proof<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        label: NodeLabel,
+    ) -> Result<NonMembershipProof<H>, AkdError> {
+        let (longest_prefix_membership_proof, lcp_node_label) =
+            self.get_membership_proof_and_node(storage, label).await?;
+        let lcp_node: TreeNode =
+            TreeNode::get_from_storage(storage, &NodeKey(lcp_node_label), self.get_latest_epoch())
+                .await?;
+        let longest_prefix = lcp_node.label;
+        let longest_prefix_children = self
+            .get_child_nodes::<_, H>(storage, &lcp_node, self.latest_epoch)
+            .await?;
+
+        debug!("Lcp label = {:?}", longest_prefix);
+        Ok(NonMembershipProof {
+            label,
+            longest_prefix,
+            longest_prefix_children,
+            longest_prefix_membership_proof,
+        })
+    }
+
+    /// Returns a proof that no label in the tree at `epoch` starts with the given prefix, see
+    /// [`PrefixAbsenceProof`]. Fails with [`TreeNodeError::PrefixNotEmpty`] if one does, or if
+    /// the prefix is the label of a node of the tree.
+    pub async fn get_prefix_absence_proof<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        prefix: NodeLabel,
+        epoch: u64,
+    ) -> Result<PrefixAbsenceProof<H>, AkdError> {
+        if self.latest_epoch < epoch {
+            // cannot retrieve information for future epoch
+            return Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                prefix, epoch,
+            )));
+        }
+        // Walk down the prefix's path to the deepest node whose label is a prefix of it
+        let mut node =
+            TreeNode::get_from_storage(storage, &NodeKey(NodeLabel::root()), epoch).await?;
+        while let Some(dir) = node.label.get_dir(prefix) {
+            match node.get_child_state(storage, Some(dir), epoch).await? {
+                Some(child) if child.label.is_prefix_of(&prefix) => node = child,
+                _ => break,
+            }
+        }
+
+        // The prefix may itself be the label of a leaf or interior node. Only the root is in
+        // the tree even when no label is under it, as it's there in the empty tree.
+        if node.label == prefix && prefix != NodeLabel::root() {
+            return Err(AkdError::TreeNode(TreeNodeError::PrefixNotEmpty(prefix)));
+        }
+        let longest_prefix_children = self.get_child_nodes::<_, H>(storage, &node, epoch).await?;
+        if longest_prefix_children
+            .iter()
+            .any(|child| child.label != EMPTY_LABEL && prefix.is_prefix_of(&child.label))
+        {
+            return Err(AkdError::TreeNode(TreeNodeError::PrefixNotEmpty(prefix)));
+        }
+        let longest_prefix_membership_proof = self
+            .get_membership_proof::<_, H>(storage, node.label, epoch)
+            .await?;
+        Ok(PrefixAbsenceProof {
+            prefix,
+            longest_prefix: node.label,
+            longest_prefix_children,
+            longest_prefix_membership_proof,
+        })
+    }
+
+    /// Returns the label and hash of each of the node's children at `epoch`, or the empty label
+    /// and hash for a missing child, as they are committed to in the node's hash.
+    async fn get_child_nodes<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        node: &TreeNode,
+        epoch: u64,
+    ) -> Result<[Node<H>; ARITY], AkdError> {
+        // load with placeholder nodes, to be replaced in the loop below
+        let mut children = [Node {
+            label: EMPTY_LABEL,
+            hash: crate::utils::dummy_child_hash::<H>(),
+        }; ARITY];
+        for (i, child_node) in children.iter_mut().enumerate() {
+            let child = node.get_child_state(storage, Some(i), epoch).await?;
+            match child {
+                None => {
+                    debug!("i = {}, empty", i);
+                    continue;
+                }
+                Some(child) => {
+                    debug!("Label of child {} is {:?}", i, child.label);
+                    *child_node = Node {
+                        label: child.label,
+                        hash: optional_child_state_hash::<H>(&Some(child))?,
+                    };
+                }
+            }
+        }
+        Ok(children)
+    }
+
+    /// Returns a proof that the label was not in the tree at `epoch`, in the form of membership
+    /// proofs for the leaves either side of it, see [`CompactNonMembershipProof`]. Fails with
+    /// [`TreeNodeError::LabelAlreadyExists`] if the label is in the tree. The neighbors are
+    /// found among the nodes as they stood at `epoch`, and their membership proofs are made
+    /// by [`Azks::get_membership_proof`] for the same epoch.
+    pub async fn get_non_membership_proof_compact<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        label: NodeLabel,
+        epoch: u64,
+    ) -> Result<CompactNonMembershipProof<H>, AkdError> {
+        if self.latest_epoch < epoch {
+            // cannot retrieve information for future epoch
+            return Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                label, epoch,
+            )));
+        }
+        let (predecessor, successor) = self.find_neighbor_leaves(storage, label, epoch).await?;
+        let predecessor = match predecessor {
+            Some(leaf) => Some(
+                self.get_membership_proof::<_, H>(storage, leaf, epoch)
+                    .await?,
+            ),
+            None => None,
+        };
+        let successor = match successor {
+            Some(leaf) => Some(
+                self.get_membership_proof::<_, H>(storage, leaf, epoch)
+                    .await?,
+            ),
+            None => None,
+        };
+        Ok(CompactNonMembershipProof {
+            label,
+            predecessor,
+            successor,
+        })
+    }
+
+    /// Finds the labels of the last leaf before `label` and the first leaf after it. Walking
+    /// down the label's path, each child off the path lies entirely before or after the label,
+    /// so the neighbors are the extreme leaves of the deepest such children on either side.
+    async fn find_neighbor_leaves<S: Storage + Sync + Send>(
+        &self,
+        storage: &S,
+        label: NodeLabel,
+        epoch: u64,
+    ) -> Result<(Option<NodeLabel>, Option<NodeLabel>), AkdError> {
+        let mut before: Option<TreeNode> = None;
+        let mut after: Option<TreeNode> = None;
+        let mut node =
+            TreeNode::get_from_storage(storage, &NodeKey(NodeLabel::root()), epoch).await?;
+        loop {
+            let mut next = None;
+            let mut found_after = false;
+            for dir in 0..ARITY {
+                let child = match node.get_child_state(storage, Some(dir), epoch).await? {
+                    Some(child) => child,
+                    None => continue,
+                };
+                match child.label.cmp(&label.get_prefix(child.label.get_len())) {
+                    std::cmp::Ordering::Less => before = Some(child),
+                    std::cmp::Ordering::Greater if !found_after => {
+                        found_after = true;
+                        after = Some(child);
+                    }
+                    std::cmp::Ordering::Greater => {}
+                    std::cmp::Ordering::Equal if child.is_leaf() => {
+                        return Err(AkdError::TreeNode(TreeNodeError::LabelAlreadyExists(label)));
+                    }
+                    std::cmp::Ordering::Equal => next = Some(child),
+                }
+            }
+            match next {
+                Some(child) => node = child,
+                None => break,
+            }
+        }
+
+        let predecessor = match before {
+            Some(subtree) => Some(self.extreme_leaf(storage, subtree, true, epoch).await?),
+            None => None,
+        };
+        let successor = match after {
+            Some(subtree) => Some(self.extreme_leaf(storage, subtree, false, epoch).await?),
+            None => None,
+        };
+        Ok((predecessor, successor))
+    }
+
+    /// Returns the label of the last leaf below `node` at `epoch` if `last` is set, or the first
+    /// otherwise
+    async fn extreme_leaf<S: Storage + Sync + Send>(
+        &self,
+        storage: &S,
+        mut node: TreeNode,
+        last: bool,
+        epoch: u64,
+    ) -> Result<NodeLabel, AkdError> {
+        while !node.is_leaf() {
+            let mut children = Vec::with_capacity(ARITY);
+            for dir in 0..ARITY {
+                if let Some(child) = node.get_child_state(storage, Some(dir), epoch).await? {
+                    children.push(child);
+                }
+            }
+            let child = if last {
+                children.pop()
+            } else {
+                children.into_iter().next()
+            };
+            node = child.ok_or_else(|| {
+                AkdError::AzksErr(AzksError::TreeIntegrity(format!(
+                    "Interior node {} has no children",
+                    node.label
+                )))
+            })?;
+        }
+        Ok(node.label)
+    }
+
+    // FIXME add an error if the epochs don't exist or end is less than start ep.
+    /// An append-only proof for going from `start_epoch` to `end_epoch` consists of roots of subtrees
+    /// the azks tree that remain unchanged from `start_epoch` to `end_epoch` and the leaves inserted into the
+    /// tree after `start_epoch` and  up until `end_epoch`.
+    /// If there is no errors, this function returns an `Ok` result, containing the
+    ///  append-only proof and otherwise, it returns a [errors::AkdError].
+    ///
+    /// **RESTRICTIONS**: Note that `start_epoch` and `end_epoch` are valid only when the following are true
+    /// * `start_epoch` <= `end_epoch`
+    /// * `start_epoch` and `end_epoch` are both existing epochs of this AZKS
+    pub async fn get_append_only_proof<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        start_epoch: u64,
+        end_epoch: u64,
+    ) -> Result<AppendOnlyProof<H>, AkdError> {
+        let mut proofs = Vec::<SingleAppendOnlyProof<H>>::new();
+        let mut epochs = Vec::<u64>::new();
+        // Suppose the epochs start_epoch and end_epoch exist in the set.
+        // This function should return the proof that nothing was removed/changed from the tree
+        // between these epochs.
+        for ep in start_epoch..end_epoch {
+            let node = TreeNode::get_from_storage(
+                storage,
+                &NodeKey(NodeLabel::root()),
+                self.get_latest_epoch(),
+            )
+            .await?;
+            let (unchanged, leaves) = self
+                .get_append_only_proof_helper::<_, H>(storage, node, ep, ep + 1)
+                .await?;
+            proofs.push(SingleAppendOnlyProof {
+                inserted: leaves,
+                unchanged_nodes: unchanged,
+            });
+            epochs.push(ep);
+        }
+
+        Ok(AppendOnlyProof { proofs, epochs })
+    }
+
+    /// Returns the append-only proof for the single transition from `epoch - 1` to `epoch`,
+    /// without wrapping it in an [`AppendOnlyProof`]. An auditor tailing the directory checks
+    /// the latest transition with this and [`crate::auditor::verify_single_epoch`].
+    pub async fn get_single_epoch_proof<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        epoch: u64,
+    ) -> Result<SingleAppendOnlyProof<H>, AkdError> {
+        if epoch == 0 || self.latest_epoch < epoch {
+            return Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                NodeLabel::root(),
+                epoch,
+            )));
+        }
+        let node = TreeNode::get_from_storage(
+            storage,
+            &NodeKey(NodeLabel::root()),
+            self.get_latest_epoch(),
+        )
+        .await?;
+        let (unchanged, leaves) = self
+            .get_append_only_proof_helper::<_, H>(storage, node, epoch - 1, epoch)
+            .await?;
+        Ok(SingleAppendOnlyProof {
+            inserted: leaves,
+            unchanged_nodes: unchanged,
+        })
+    }
+
+    /// Returns a single append-only proof for the whole transition from `start_epoch` to
+    /// `end_epoch`, for an auditor which only trusts the root hashes of those two epochs.
+    /// It's verified with [`crate::auditor::verify_append_only_range`].
+    ///
+    /// Like the other append-only proofs, it's built from the latest states of the nodes, so
+    /// `end_epoch` should be the latest epoch: a leaf which has been written since `end_epoch`
+    /// no longer has the state it had at `end_epoch`, and the proof can't be built.
+    pub async fn get_append_only_range_proof<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        start_epoch: u64,
+        end_epoch: u64,
+    ) -> Result<AppendOnlyRangeProof<H>, AkdError> {
+        if start_epoch > end_epoch || self.latest_epoch < end_epoch {
+            return Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                NodeLabel::root(),
+                end_epoch,
+            )));
+        }
+        let node = TreeNode::get_from_storage(
+            storage,
+            &NodeKey(NodeLabel::root()),
+            self.get_latest_epoch(),
+        )
+        .await?;
+        let (unchanged, leaves) = self
+            .get_append_only_proof_helper::<_, H>(storage, node, start_epoch, end_epoch)
+            .await?;
+
+        // The helper only gives the leaves' values, so look up the epochs they were written in
+        let leaf_keys = leaves
+            .iter()
+            .map(|leaf| NodeKey(leaf.label))
+            .collect::<Vec<_>>();
+        let leaf_epochs =
+            TreeNode::batch_get_from_storage(storage, &leaf_keys, self.get_latest_epoch())
+                .await?
+                .into_iter()
+                .map(|leaf| (leaf.label, leaf.last_epoch))
+                .collect::<HashMap<_, _>>();
+        let mut inserted_epochs = Vec::with_capacity(leaves.len());
+        for leaf in &leaves {
+            match leaf_epochs.get(&leaf.label) {
+                Some(&epoch) if epoch <= end_epoch => inserted_epochs.push(epoch),
+                _ => {
+                    return Err(AkdError::TreeNode(TreeNodeError::NoStateAtEpoch(
+                        leaf.label, end_epoch,
+                    )))
+                }
+            }
+        }
+
+        Ok(AppendOnlyRangeProof {
+            inserted: leaves,
+            inserted_epochs,
+            unchanged_nodes: unchanged,
+        })
+    }
+
+    #[async_recursion]
+    async fn get_append_only_proof_helper<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        node: TreeNode,
+        start_epoch: u64,
+        end_epoch: u64,
+    ) -> Result<AppendOnlyHelper<H>, AkdError> {
+        let mut unchanged = Vec::<Node<H>>::new();
+        let mut leaves = Vec::<Node<H>>::new();
+
+        if node.get_latest_epoch() <= start_epoch {
+            if node.is_root() {
+                // this is the case where the root is unchanged since the last epoch
+                return Ok((unchanged, leaves));
+            }
+            unchanged.push(Node::<H> {
+                label: node.label,
+                hash: optional_child_state_hash::<H>(&Some(node))?,
+            });
+
+            return Ok((unchanged, leaves));
+        }
+
+        if node.least_descendant_ep > end_epoch {
+            return Ok((unchanged, leaves));
+        }
+
+        if node.is_leaf() {
+            leaves.push(Node::<H> {
+                label: node.label,
+                hash: to_digest::<H>(&node.hash)?,
+            });
+        } else {
+            for child_label in [node.left_child, node.right_child] {
+                match child_label {
+                    None => {
+                        continue;
+                    }
+                    Some(label) => {
+                        let child_node = TreeNode::get_from_storage(
+                            storage,
+                            &NodeKey(label),
+                            self.get_latest_epoch(),
+                        )
+                        .await?;
+                        let mut rec_output = self
+                            .get_append_only_proof_helper::<_, H>(
+                                storage,
+                                child_node,
+                                start_epoch,
+                                end_epoch,
+                            )
+                            .await?;
+                        unchanged.append(&mut rec_output.0);
+                        leaves.append(&mut rec_output.1);
+                    }
+                }
+            }
+        }
+        Ok((unchanged, leaves))
+    }
+
+    /// Returns a digest over the set of leaves inserted at `epoch`, so that two replicas of a
+    /// directory can check that they inserted the same leaves in an epoch by comparing a
+    /// single digest. A leaf counts as inserted at the epoch of its latest write, so a leaf
+    /// whose value was replaced in a later epoch counts towards that later epoch instead.
+    ///
+    /// The leaves are sorted by label (see the [Ord] implementation of [NodeLabel]) and the
+    /// digest is the hash of the concatenation of
+    /// * the epoch, as 8 big-endian bytes
+    /// * the number of leaves, as 8 big-endian bytes
+    /// * for each leaf, the length of its label as 4 big-endian bytes, the 32 bytes of the
+    ///   label's value, and the bytes of the leaf's value digest
+    ///
+    /// On a mismatch, the inserted leaves themselves can be compared using
+    /// [`Azks::get_append_only_proof`] from `epoch - 1` to `epoch`.
+    pub async fn epoch_insertion_digest<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        epoch: u64,
+    ) -> Result<H::Digest, AkdError> {
+        if self.latest_epoch < epoch {
+            return Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                NodeLabel::root(),
+                epoch,
+            )));
+        }
+
+        let mut leaves = Vec::<TreeNode>::new();
+        let mut current_nodes = vec![NodeKey(NodeLabel::root())];
+        while !current_nodes.is_empty() {
+            let nodes =
+                TreeNode::batch_get_from_storage(storage, &current_nodes, self.get_latest_epoch())
+                    .await?;
+            current_nodes = Vec::<NodeKey>::new();
+
+            for node in nodes {
+                // Writing a leaf rewrites all of its ancestors, so nothing below a node last
+                // written before the epoch, or whose every leaf is newer, was written at it
+                if node.last_epoch < epoch || node.least_descendant_ep > epoch {
+                    continue;
+                }
+                if node.is_leaf() {
+                    if node.last_epoch == epoch {
+                        leaves.push(node);
+                    }
+                    continue;
+                }
+                for dir in 0..ARITY {
+                    if let Some(child_label) = node.get_child_label(Direction::Some(dir)) {
+                        current_nodes.push(NodeKey(child_label));
+                    }
+                }
+            }
+        }
+        leaves.sort_by_key(|leaf| leaf.label);
+
+        let mut hash_input = Vec::with_capacity(16 + leaves.len() * 68);
+        hash_input.extend_from_slice(&epoch.to_be_bytes());
+        hash_input.extend_from_slice(&(leaves.len() as u64).to_be_bytes());
+        for leaf in &leaves {
+            hash_input.extend_from_slice(&leaf.label.get_len().to_be_bytes());
+            hash_input.extend_from_slice(&leaf.label.get_val());
+            hash_input.extend_from_slice(&leaf.hash);
+        }
+        Ok(H::hash(&hash_input))
+    }
+
+    /// Returns the roots of the largest subtrees which haven't changed since `t_init`, ordered
+    /// by label, together with their hashes as they contribute to their parents. These are the
+    /// unchanged nodes of an append-only proof starting at `t_init`.
+    ///
+    /// Writing a leaf rewrites all of its ancestors, so the latest epoch of a node is the
+    /// latest epoch of any node in its subtree. The traversal stops at the first node on each
+    /// path which was last written at or before `t_init`, returning it rather than any of its
+    /// descendants, and skips subtrees all of whose leaves were inserted after `t_init`. If
+    /// nothing changed since `t_init`, this is just the root.
+    pub async fn collect_unchanged_roots<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        t_init: u64,
+    ) -> Result<Vec<Node<H>>, AkdError> {
+        let mut unchanged = Vec::<Node<H>>::new();
+        let mut current_nodes = vec![NodeKey(NodeLabel::root())];
+        while !current_nodes.is_empty() {
+            let nodes =
+                TreeNode::batch_get_from_storage(storage, &current_nodes, self.get_latest_epoch())
+                    .await?;
+            current_nodes = Vec::<NodeKey>::new();
+
+            for node in nodes {
+                if node.get_latest_epoch() <= t_init {
+                    unchanged.push(Node::<H> {
+                        label: node.label,
+                        hash: optional_child_state_hash::<H>(&Some(node))?,
+                    });
+                    continue;
+                }
+                if node.least_descendant_ep > t_init {
+                    continue;
+                }
+                for dir in 0..ARITY {
+                    if let Some(child_label) = node.get_child_label(Direction::Some(dir)) {
+                        current_nodes.push(NodeKey(child_label));
+                    }
+                }
+            }
+        }
+        unchanged.sort_by_key(|node| node.label);
+        Ok(unchanged)
+    }
+
+    // FIXME: these functions below should be moved into higher-level API
+    /// Gets the root hash for this azks
+    pub async fn get_root_hash<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+    ) -> Result<H::Digest, AkdError> {
+        self.get_root_hash_at_epoch::<_, H>(storage, self.get_latest_epoch())
+            .await
+    }
+
+    /// Gets the root hash of the tree at a epoch.
+    /// Since this is accessing the root node and the root node exists at all epochs that
+    /// the azks does, this would never be called at an epoch before the birth of the root node.
+    ///
+    /// For an epoch before the latest one, the [EpochRootHash] recorded when the epoch was
+    /// published is returned if there is one. The latest epoch may not have been committed yet
+    /// (e.g. while it's being published), so a record for it could be one left behind by a
+    /// partial commit: its hash is always computed from the root node, see
+    /// [Azks::get_root_hash_from_tree]. The same goes for an earlier epoch without a record
+    /// (e.g. one published before the records were written, or a tree built outside of a
+    /// directory).
+    pub async fn get_root_hash_at_epoch<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        epoch: u64,
+    ) -> Result<H::Digest, AkdError> {
+        if self.latest_epoch < epoch {
+            // cannot retrieve information for future epoch
+            return Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                NodeLabel::root(),
+                epoch,
+            )));
+        }
+        if epoch < self.latest_epoch {
+            match storage.get::<EpochRootHash>(&epoch).await {
+                Ok(DbRecord::EpochRootHash(root)) => return to_digest::<H>(&root.root_hash),
+                Ok(_) | Err(StorageError::NotFound(_)) => {}
+                Err(other) => return Err(AkdError::Storage(other)),
+            }
+        }
+        self.get_root_hash_from_tree::<_, H>(storage, epoch).await
+    }
+
+    /// Computes the root hash of the tree at an epoch from the state of the root node as of
+    /// the epoch, without reading any [EpochRootHash] record. Only the latest and the previous
+    /// state of each node are kept, so this can only read back to the epoch of the root's
+    /// previous state: for an older epoch a [StorageError::NotFound] is returned rather than
+    /// the hash of a later root.
+    pub async fn get_root_hash_from_tree<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        epoch: u64,
+    ) -> Result<H::Digest, AkdError> {
+        let root_node: TreeNode =
+            TreeNode::get_from_storage(storage, &NodeKey(NodeLabel::root()), epoch).await?;
+        if root_node.last_epoch > epoch {
+            // the state of the root at the epoch has since been overwritten
+            return Err(AkdError::Storage(StorageError::NotFound(format!(
+                "Root hash at epoch {}",
+                epoch
+            ))));
+        }
+        hash_u8_with_label::<H>(&root_node.hash, root_node.label)
+    }
+
+    /// Streams the root hash recorded for each epoch from `start_epoch` to `end_epoch`
+    /// inclusive, in order, as `(epoch, root_hash)` pairs. An auditor coming online can seed
+    /// the hashes for [crate::auditor::audit_verify] from this, and verify each epoch
+    /// transition as soon as both of its hashes have arrived rather than after reading the
+    /// whole history.
+    ///
+    /// The hashes are read from the [EpochRootHash] records written as each epoch is published.
+    /// Unlike [Azks::get_root_hash_at_epoch] there's no fallback to the state of the root node,
+    /// so an epoch without a record (e.g. one published before the records were written)
+    /// yields [AzksError::RootHashNotRecorded], and an epoch after the latest one yields
+    /// [TreeNodeError::NonexistentAtEpoch]. The stream ends after its first error.
+    pub fn stream_root_hashes<'a, S: Storage + Sync + Send, H: Hasher + 'a>(
+        &self,
+        storage: &'a S,
+        start_epoch: u64,
+        end_epoch: u64,
+    ) -> impl Stream<Item = Result<(u64, H::Digest), AkdError>> + 'a {
+        let latest_epoch = self.latest_epoch;
+        futures::stream::unfold(Some(start_epoch), move |next_epoch| async move {
+            let epoch = next_epoch.filter(|epoch| *epoch <= end_epoch)?;
+            let result = Self::get_recorded_root_hash::<_, H>(storage, epoch, latest_epoch).await;
+            let next_epoch = match result {
+                Ok(_) => epoch.checked_add(1),
+                Err(_) => None,
+            };
+            Some((result.map(|root_hash| (epoch, root_hash)), next_epoch))
+        })
+    }
+
+    async fn get_recorded_root_hash<S: Storage + Sync + Send, H: Hasher>(
+        storage: &S,
+        epoch: u64,
+        latest_epoch: u64,
+    ) -> Result<H::Digest, AkdError> {
+        if latest_epoch < epoch {
+            return Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                NodeLabel::root(),
+                epoch,
+            )));
+        }
+        match storage.get::<EpochRootHash>(&epoch).await {
+            Ok(DbRecord::EpochRootHash(root)) => to_digest::<H>(&root.root_hash),
+            Ok(_) | Err(StorageError::NotFound(_)) => {
+                Err(AkdError::AzksErr(AzksError::RootHashNotRecorded(epoch)))
+            }
+            Err(other) => Err(AkdError::Storage(other)),
+        }
+    }
+
+    /// Records the root hash of each epoch which doesn't have an [EpochRootHash] yet, e.g. for
+    /// a directory created before root hashes were recorded, so that its history can be
+    /// streamed with [Azks::stream_root_hashes]. A missing root hash is recomputed from the
+    /// state of the root node as of its epoch with [Azks::get_root_hash_from_tree], so this
+    /// can only recover the root hashes of the epochs since the root's previous state. Returns
+    /// the epochs whose root hashes are still missing because they can no longer be recomputed.
+    pub async fn backfill_root_hashes<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+    ) -> Result<Vec<u64>, AkdError> {
+        let epochs = (0..=self.latest_epoch).collect::<Vec<_>>();
+        let recorded = storage
+            .batch_get::<EpochRootHash>(&epochs)
+            .await?
+            .into_iter()
+            .filter_map(|record| match record {
+                DbRecord::EpochRootHash(root) => Some(root.epoch),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+
+        let mut backfilled = vec![];
+        let mut unrecoverable = vec![];
+        for epoch in epochs.into_iter().filter(|epoch| !recorded.contains(epoch)) {
+            match self.get_root_hash_from_tree::<_, H>(storage, epoch).await {
+                Ok(root_hash) => backfilled.push(DbRecord::EpochRootHash(EpochRootHash {
+                    epoch,
+                    root_hash: from_digest::<H>(root_hash),
+                })),
+                Err(AkdError::Storage(StorageError::NotFound(_))) => unrecoverable.push(epoch),
+                Err(other) => return Err(other),
+            }
+        }
+        if !backfilled.is_empty() {
+            info!("Backfilling the root hashes of {} epochs", backfilled.len());
+            storage.batch_set(backfilled).await?;
+        }
+        Ok(unrecoverable)
+    }
+
+    /// Returns whether the nodes with the two labels were siblings in the tree at epoch, i.e.
+    /// whether the node at their longest common prefix was their parent, with one of them in
+    /// each of its child slots. Fails with [TreeNodeError::NonexistentAtEpoch] if either label
+    /// isn't in the tree at the epoch (or its state at the epoch has since been overwritten),
+    /// rather than answering false.
+    pub async fn are_siblings<S: Storage + Sync + Send>(
+        &self,
+        storage: &S,
+        label_a: NodeLabel,
+        label_b: NodeLabel,
+        epoch: u64,
+    ) -> Result<bool, AkdError> {
+        for label in [label_a, label_b] {
+            let exists = self.latest_epoch >= epoch
+                && match TreeNode::get_from_storage(storage, &NodeKey(label), epoch).await {
+                    Ok(node) => node.last_epoch <= epoch,
+                    Err(StorageError::NotFound(_)) => false,
+                    Err(other) => return Err(AkdError::Storage(other)),
+                };
+            if !exists {
+                return Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                    label, epoch,
+                )));
+            }
+        }
+
+        let (lcp_label, dir_b, dir_a) = label_a.get_longest_common_prefix_and_dirs(label_b);
+        if dir_a.is_none() || dir_b.is_none() {
+            // The labels are equal, or one is a prefix of the other
+            return Ok(false);
+        }
+        let parent = match TreeNode::get_from_storage(storage, &NodeKey(lcp_label), epoch).await {
+            Ok(node) if node.last_epoch <= epoch => node,
+            Ok(_) => {
+                // the state of the node at the epoch has since been overwritten
+                return Err(AkdError::Storage(StorageError::NotFound(format!(
+                    "TreeNode {:?} at epoch {}",
+                    NodeKey(lcp_label),
+                    epoch
+                ))));
+            }
+            Err(StorageError::NotFound(_)) => return Ok(false),
+            Err(other) => return Err(AkdError::Storage(other)),
+        };
+        Ok(parent.get_child_label(dir_a) == Some(label_a)
+            && parent.get_child_label(dir_b) == Some(label_b))
+    }
+
+    /// Returns a fingerprint of the whole state of the directory at an epoch, for a quick check
+    /// of whether two copies of it are identical before comparing them node by node. This is
+    /// the root hash at the epoch, which commits to every node in the tree, so two directories
+    /// with the same fingerprint have the same state unless a collision has been found in `H`.
+    /// An epoch can be read back as with [Azks::get_root_hash_at_epoch]; see
+    /// [crate::auditor::fingerprint_range] to compare a whole history of root hashes.
+    pub async fn state_fingerprint<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        epoch: u64,
+    ) -> Result<H::Digest, AkdError> {
+        self.get_root_hash_at_epoch::<_, H>(storage, epoch).await
+    }
+
+    /// Returns the root hash of the tree as it is, and the root hash it would have if `leaf`
+    /// were inserted in the next epoch, as `(before, after)`. Without the insertion the next
+    /// epoch would have the same root hash as the current one, so the pair gives the effect
+    /// of the leaf alone.
+    ///
+    /// The insertion is made inside a storage transaction which is rolled back afterwards, so
+    /// neither the stored tree nor this azks is modified. This fails if a transaction is
+    /// already active on the storage.
+    pub async fn root_hash_delta<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        leaf: Node<H>,
+    ) -> Result<(H::Digest, H::Digest), AkdError> {
+        let before = self.get_root_hash::<_, H>(storage).await?;
+        if !storage.begin_transaction().await {
+            return Err(AkdError::Storage(StorageError::Transaction(
+                "Transaction is already active".to_string(),
+            )));
+        }
+        let mut overlay = self.clone();
+        let after = match overlay
+            .batch_insert_leaves::<_, H>(storage, vec![leaf])
+            .await
+        {
+            Ok(()) => overlay.get_root_hash::<_, H>(storage).await,
+            Err(err) => Err(err),
+        };
+        storage.rollback_transaction().await?;
+        Ok((before, after?))
+    }
+
+    /// Prunes the historical node states which are no longer needed to serve reads at
+    /// or after `cutoff_epoch`. Every stored tree node keeps its latest state along with the
+    /// state it replaced. The replaced state is dropped only when the latest state was already
+    /// in place at `cutoff_epoch`, so reading any node at `cutoff_epoch` gives the same result
+    /// before and after pruning. Returns the number of pruned node states.
+    ///
+    /// **NOTE**: After pruning, append-only proofs and key history proofs which need the
+    /// state of the tree at an epoch below `cutoff_epoch` can no longer be generated.
+    pub async fn prune_before_epoch<S: Storage + Sync + Send>(
+        &self,
+        storage: &S,
+        cutoff_epoch: u64,
+    ) -> Result<u64, AkdError> {
+        if self.latest_epoch < cutoff_epoch {
+            // pruning past the latest epoch could drop states an in-flight publish relies on
+            return Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                NodeLabel::root(),
+                cutoff_epoch,
+            )));
+        }
+
+        let mut pruned_records = Vec::<DbRecord>::new();
+        let mut current_nodes = vec![NodeKey(NodeLabel::root())];
+        while !current_nodes.is_empty() {
+            let records = storage
+                .batch_get::<TreeNodeWithPreviousValue>(&current_nodes)
+                .await?;
+            current_nodes = Vec::<NodeKey>::new();
+
+            for record in records {
+                if let DbRecord::TreeNode(mut node) = record {
+                    for child_label in [node.latest_node.left_child, node.latest_node.right_child]
+                        .iter()
+                        .flatten()
+                    {
+                        current_nodes.push(NodeKey(*child_label));
+                    }
+                    // A read at cutoff_epoch only falls back on the previous state when the
+                    // latest state was written after cutoff_epoch
+                    if node.previous_node.is_some() && node.latest_node.last_epoch <= cutoff_epoch {
+                        node.previous_node = None;
+                        pruned_records.push(DbRecord::TreeNode(node));
+                    }
+                }
+            }
+        }
+
+        let num_pruned = pruned_records.len() as u64;
+        if !pruned_records.is_empty() {
+            storage.batch_set(pruned_records).await?;
+        }
+        info!(
+            "Pruned {} historical node states below epoch {}",
+            num_pruned, cutoff_epoch
+        );
+        Ok(num_pruned)
+    }
+
+    /// Finds the latest epoch which was finalized at or before `timestamp_millis` (milliseconds
+    /// since the unix epoch), by binary searching over the [`EpochTimestamp`] records.
+    /// Returns `None` if the timestamp precedes the first published epoch.
+    ///
+    /// **NOTE**: This requires that a timestamp was recorded for every published epoch, see
+    /// [`crate::directory::Directory::with_epoch_timestamps`].
+    pub async fn epoch_at_or_before<S: Storage + Sync + Send>(
+        &self,
+        storage: &S,
+        timestamp_millis: u64,
+    ) -> Result<Option<u64>, AkdError> {
+        let latest_epoch = self.get_latest_epoch();
+        if latest_epoch == 0 || Self::get_epoch_timestamp(storage, 1).await? > timestamp_millis {
+            return Ok(None);
+        }
+
+        // Invariant: the epoch `low` was finalized at or before the timestamp
+        let (mut low, mut high) = (1, latest_epoch);
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            if Self::get_epoch_timestamp(storage, mid).await? <= timestamp_millis {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        Ok(Some(low))
+    }
+
+    async fn get_epoch_timestamp<S: Storage + Sync + Send>(
+        storage: &S,
+        epoch: u64,
+    ) -> Result<u64, AkdError> {
+        match storage.get::<EpochTimestamp>(&epoch).await? {
+            DbRecord::EpochTimestamp(timestamp) => Ok(timestamp.timestamp_millis),
+            _ => Err(AkdError::Storage(StorageError::NotFound(format!(
+                "EpochTimestamp {}",
+                epoch
+            )))),
+        }
+    }
+
+    /// Returns whether every write of the given epoch was committed to storage, i.e. whether
+    /// its [`EpochCommitMarker`] is present. The marker is committed in the same storage
+    /// transaction as the other records of the epoch, including the azks which advances to
+    /// it, so a writer recovering from a failed publish can tell whether the epoch's writes
+    /// completed: if the latest epoch's node writes are present without its marker, they must
+    /// be rolled forward or cleaned up.
+    ///
+    /// The genesis epoch is written when the azks is created rather than published, so it
+    /// has no marker and is always considered committed.
+    pub async fn is_epoch_fully_committed<S: Storage + Sync + Send>(
+        storage: &S,
+        epoch: u64,
+    ) -> Result<bool, AkdError> {
+        if epoch == 0 {
+            return Ok(true);
+        }
+        match storage.get_direct::<EpochCommitMarker>(&epoch).await {
+            Ok(DbRecord::EpochCommitMarker(_)) => Ok(true),
+            Ok(_) | Err(StorageError::NotFound(_)) => Ok(false),
+            Err(other) => Err(AkdError::Storage(other)),
+        }
+    }
+
+    /// Gets the latest epoch of this azks. If an update aka epoch transition
+    /// is in progress, this should return the most recent completed epoch.
+    pub fn get_latest_epoch(&self) -> u64 {
+        self.latest_epoch
+    }
+
+    /// Gets the latest epoch of this azks, the same as [`Azks::get_latest_epoch`]. For an
+    /// azks read from storage (e.g. via
+    /// [`crate::directory::Directory::retrieve_current_azks`]) this only accounts for epochs
+    /// whose publish has been committed.
+    pub fn latest_epoch(&self) -> u64 {
+        self.get_latest_epoch()
+    }
+
+    /// Gets the number of epochs of this azks, including the initial (empty) epoch 0.
+    pub fn num_epochs(&self) -> u64 {
+        self.latest_epoch + 1
+    }
+
+    /// Returns true if `epoch` has been reached by this azks, i.e. the state of the
+    /// tree at `epoch` can be queried.
+    pub fn epoch_exists(&self, epoch: u64) -> bool {
+        epoch <= self.latest_epoch
+    }
+
+    fn increment_epoch(&mut self) {
+        let epoch = self.latest_epoch + 1;
+        self.latest_epoch = epoch;
+    }
+
+    /// This function returns the node label for the node whose label is the longest common
+    /// prefix for the queried label. It also returns a membership proof for said label.
+    /// This is meant to be used in both, getting membership proofs and getting non-membership proofs.
+    pub async fn get_membership_proof_and_node<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        label: NodeLabel,
+    ) -> Result<(MembershipProof<H>, NodeLabel), AkdError> {
+        let mut layer_proofs = Vec::new();
+        let mut curr_node: TreeNode = TreeNode::get_from_storage(
+            storage,
+            &NodeKey(NodeLabel::root()),
+            self.get_latest_epoch(),
+        )
+        .await?;
+
+        let mut dir = curr_node.label.get_dir(label);
+        let mut equal = label == curr_node.label;
+        let mut prev_node = NodeLabel::root();
+        while !equal && dir.is_some() {
+            prev_node = curr_node.label;
+
+            let mut nodes = [Node::<H> {
+                label: EMPTY_LABEL,
+                hash: crate::utils::dummy_child_hash::<H>(),
+            }; ARITY - 1];
+            let mut count = 0;
+            let direction = dir.ok_or(AkdError::TreeNode(TreeNodeError::NoDirection(
+                curr_node.label,
+                None,
+            )))?;
+            let next_state = curr_node
+                .get_child_state(storage, Some(direction), self.latest_epoch)
+                .await?;
+            if next_state.is_some() {
+                for i in 0..ARITY {
+                    let no_direction_error =
+                        AkdError::TreeNode(TreeNodeError::NoDirection(curr_node.label, None));
+
+                    if i != dir.ok_or(no_direction_error)? {
+                        let sibling = curr_node
+                            .get_child_state(storage, Direction::Some(i), self.latest_epoch)
+                            .await?;
+                        nodes[count] = Node::<H> {
+                            label: optional_child_state_to_label(&sibling),
+                            hash: optional_child_state_hash::<H>(&sibling)?,
+                        };
+                        count += 1;
+                    }
+                }
+            } else {
+                break;
+            }
+            layer_proofs.push(proof_structs::LayerProof {
+                label: curr_node.label,
+                siblings: nodes,
+                direction: dir,
+            });
+            let new_curr_node: TreeNode = TreeNode::get_from_storage(
+                storage,
+                &NodeKey(curr_node.get_child(dir).unwrap().unwrap()),
+                self.get_latest_epoch(),
+            )
+            .await?;
+            curr_node = new_curr_node;
+            dir = curr_node.label.get_dir(label);
+            equal = label == curr_node.label;
+        }
+
+        if !equal {
+            let new_curr_node: TreeNode =
+                TreeNode::get_from_storage(storage, &NodeKey(prev_node), self.get_latest_epoch())
+                    .await?;
+            curr_node = new_curr_node;
+
+            layer_proofs.pop();
+        }
+        let hash_val = if curr_node.is_leaf() {
+            merge_epoch::<H>(to_digest::<H>(&curr_node.hash)?, curr_node.last_epoch)
+        } else {
+            to_digest::<H>(&curr_node.hash)?
+        };
 
         Ok((
             MembershipProof::<H> {
@@ -578,128 +2316,1475 @@ impl Azks {
             prev_node,
         ))
     }
-}
+}
+
+type AppendOnlyHelper<H> = (Vec<Node<H>>, Vec<Node<H>>);
+
+fn check_root_invariants(root_node: &TreeNode) -> Result<(), AzksError> {
+    if !root_node.is_root() {
+        return Err(AzksError::RootInvariantViolated(format!(
+            "The node at the root label is a {:?} node",
+            root_node.node_type
+        )));
+    }
+    if root_node.label != NodeLabel::root() {
+        return Err(AzksError::RootInvariantViolated(format!(
+            "The root is labelled {} rather than with the empty label",
+            root_node.label
+        )));
+    }
+    if root_node.parent != NodeLabel::root() {
+        return Err(AzksError::RootInvariantViolated(format!(
+            "The root's parent is {} rather than itself",
+            root_node.parent
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        auditor::{audit_verify, fingerprint_range, verify_append_only_range, verify_single_epoch},
+        client::{
+            verify_batch_membership, verify_checkpoint, verify_membership,
+            verify_membership_verbose, verify_membership_with_max_depth, verify_merkle_path,
+            verify_nonmembership, verify_nonmembership_compact, verify_prefix_absence,
+        },
+        node_label::hash_label,
+        proof_structs::LayerProof,
+        storage::{memory::AsyncInMemoryDatabase, timed_cache::TimedCache, StorageUtil},
+    };
+    use proptest::prelude::{any, prop, proptest, ProptestConfig};
+    use rand::{rngs::OsRng, seq::SliceRandom, RngCore};
+    use std::collections::BTreeMap;
+    use winter_crypto::hashers::Blake3_256;
+    use winter_math::fields::f128::BaseElement;
+
+    type Blake3 = Blake3_256<BaseElement>;
+    type Blake3Digest = <Blake3_256<winter_math::fields::f128::BaseElement> as Hasher>::Digest;
+
+    #[tokio::test]
+    async fn test_batch_insert_basic() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let num_nodes = 10;
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks1 = Azks::new::<_, Blake3>(&db).await?;
+        azks1.increment_epoch();
+
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+
+        for _ in 0..num_nodes {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3::hash(&input);
+            let node = Node::<Blake3> { label, hash };
+            insertion_set.push(node);
+            azks1.insert_leaf::<_, Blake3>(&db, node, 1).await?;
+        }
+
+        let db2 = AsyncInMemoryDatabase::new();
+        let mut azks2 = Azks::new::<_, Blake3>(&db2).await?;
+
+        azks2
+            .batch_insert_leaves::<_, Blake3>(&db2, insertion_set)
+            .await?;
+
+        assert_eq!(
+            azks1.get_root_hash::<_, Blake3>(&db).await?,
+            azks2.get_root_hash::<_, Blake3>(&db2).await?,
+            "Batch insert doesn't match individual insert"
+        );
+
+        Ok(())
+    }
+
+    /// The insertion order of the first permutation whose root hash differed
+    #[derive(Debug)]
+    struct OrderMismatch {
+        order: Vec<NodeLabel>,
+    }
+
+    /// Batch inserts the leaves in several orders, each into a fresh tree, and checks that
+    /// every tree ends up with the same root hash as inserting them in the given order.
+    async fn assert_order_independent(
+        leaves: &[Node<Blake3>],
+    ) -> Result<Result<(), OrderMismatch>, AkdError> {
+        let mut rng = OsRng;
+
+        let mut orders = vec![leaves.to_vec()];
+        orders.push(leaves.iter().rev().cloned().collect());
+        let mut sorted = leaves.to_vec();
+        sorted.sort_by_key(|a| a.label);
+        orders.push(sorted.iter().rev().cloned().collect());
+        orders.push(sorted);
+        for _ in 0..10 {
+            let mut shuffled = leaves.to_vec();
+            shuffled.shuffle(&mut rng);
+            orders.push(shuffled);
+        }
+
+        let mut expected_root_hash = None;
+        for order in orders {
+            let db = AsyncInMemoryDatabase::new();
+            let mut azks = Azks::new::<_, Blake3>(&db).await?;
+            azks.batch_insert_leaves::<_, Blake3>(&db, order.clone())
+                .await?;
+            let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+
+            match expected_root_hash {
+                None => expected_root_hash = Some(root_hash),
+                Some(expected) if expected != root_hash => {
+                    return Ok(Err(OrderMismatch {
+                        order: order.iter().map(|node| node.label).collect(),
+                    }));
+                }
+                _ => {}
+            }
+        }
+        Ok(Ok(()))
+    }
+
+    #[tokio::test]
+    async fn test_insert_order_independent() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut random_leaf = |label: NodeLabel| {
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            Node::<Blake3> {
+                label,
+                hash: Blake3Digest::new(input),
+            }
+        };
+
+        // Random labels, which mostly branch close to the root
+        for num_nodes in [1, 2, 3, 10, 50] {
+            let leaves: Vec<Node<Blake3>> = (0..num_nodes)
+                .map(|_| random_leaf(NodeLabel::random(&mut OsRng)))
+                .collect();
+            if let Err(mismatch) = assert_order_independent(&leaves).await? {
+                panic!("Root hash depends on insertion order: {:?}", mismatch.order);
+            }
+        }
+
+        // Labels sharing long prefixes, so that insertions push nodes down and compress
+        // paths deep in the tree
+        let leaves: Vec<Node<Blake3>> = (0..16u64)
+            .map(|i| random_leaf(NodeLabel::new(byte_arr_from_u64(i << 40 | i), 256)))
+            .collect();
+        if let Err(mismatch) = assert_order_independent(&leaves).await? {
+            panic!("Root hash depends on insertion order: {:?}", mismatch.order);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_insert_progress() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let num_nodes = 2 * INSERT_PROGRESS_INTERVAL + 5;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..num_nodes {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        let calls = std::sync::Mutex::new(vec![]);
+        let progress =
+            |processed: usize, total: usize| calls.lock().unwrap().push((processed, total));
+        let options = InsertOptions {
+            progress: Some(&progress),
+            ..InsertOptions::default()
+        };
+        azks.batch_insert_leaves_helper::<_, Blake3>(&db, insertion_set, options)
+            .await?;
+
+        assert_eq!(
+            vec![
+                (INSERT_PROGRESS_INTERVAL, num_nodes),
+                (2 * INSERT_PROGRESS_INTERVAL, num_nodes),
+                (num_nodes, num_nodes),
+            ],
+            *calls.lock().unwrap()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_insert_chunked() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..50 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+
+        // A batch larger than the chunk size is inserted in several chunks, within one epoch
+        let chunked_db = AsyncInMemoryDatabase::new();
+        let mut chunked_azks = Azks::new::<_, Blake3>(&chunked_db).await?;
+        chunked_azks
+            .batch_insert_leaves_helper::<_, Blake3>(
+                &chunked_db,
+                insertion_set,
+                InsertOptions {
+                    max_chunk_size: Some(7),
+                    ..InsertOptions::default()
+                },
+            )
+            .await?;
+
+        assert_eq!(1, chunked_azks.get_latest_epoch());
+        assert_eq!(azks.num_nodes, chunked_azks.num_nodes);
+        assert_eq!(
+            azks.get_root_hash::<_, Blake3>(&db).await?,
+            chunked_azks.get_root_hash::<_, Blake3>(&chunked_db).await?
+        );
+        chunked_azks.verify_tree_integrity(&chunked_db).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_from_stream() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..50 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+
+        // Streaming the same leaves in chunks builds the same tree, within one epoch
+        let streamed_db = AsyncInMemoryDatabase::new();
+        let mut streamed_azks = Azks::new::<_, Blake3>(&streamed_db).await?;
+        streamed_azks
+            .insert_from_stream::<_, Blake3, _>(
+                &streamed_db,
+                futures::stream::iter(insertion_set.clone()),
+                7,
+                InsertPolicy::default(),
+            )
+            .await?;
+
+        assert_eq!(1, streamed_azks.get_latest_epoch());
+        assert_eq!(azks.num_nodes, streamed_azks.num_nodes);
+        assert_eq!(
+            azks.get_root_hash::<_, Blake3>(&db).await?,
+            streamed_azks
+                .get_root_hash::<_, Blake3>(&streamed_db)
+                .await?
+        );
+        streamed_azks.verify_tree_integrity(&streamed_db).await?;
+
+        // A stream which fails part way through leaves the azks at the previous epoch
+        let mut new_leaves = vec![];
+        for _ in 0..10 {
+            let label = NodeLabel::random(&mut rng);
+            let hash = Blake3Digest::new([1u8; 32]);
+            new_leaves.push(Node::<Blake3> { label, hash });
+        }
+        new_leaves.push(insertion_set[0]);
+        let result = streamed_azks
+            .insert_from_stream::<_, Blake3, _>(
+                &streamed_db,
+                futures::stream::iter(new_leaves.clone()),
+                7,
+                InsertPolicy::RejectExisting,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(AkdError::TreeNode(TreeNodeError::LabelAlreadyExists(_)))
+        ));
+        assert_eq!(1, streamed_azks.get_latest_epoch());
+        assert_eq!(azks.num_nodes, streamed_azks.num_nodes);
+        // and nothing of the chunks inserted before the failure is left in storage
+        assert!(!streamed_db.is_transaction_active().await);
+        assert_eq!(
+            azks.get_root_hash::<_, Blake3>(&db).await?,
+            streamed_azks
+                .get_root_hash::<_, Blake3>(&streamed_db)
+                .await?
+        );
+        assert!(streamed_azks
+            .get_existing_leaf(&streamed_db, new_leaves[0].label)
+            .await?
+            .is_none());
+        streamed_azks.verify_tree_integrity(&streamed_db).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_paths() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..50 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+
+        // Only the root is loaded without any labels
+        assert_eq!(1, azks.prefetch_paths::<_, Blake3>(&db, &[]).await?);
+
+        // A label's path takes at least one node per layer of its membership proof, plus the
+        // leaf itself, and fewer than the whole tree
+        let proof = azks
+            .get_membership_proof::<_, Blake3>(&db, insertion_set[0].label, 1)
+            .await?;
+        let load_count = azks
+            .prefetch_paths::<_, Blake3>(&db, &[insertion_set[0].label])
+            .await?;
+        assert!(load_count > proof.layer_proofs.len() as u64);
+        assert!(load_count < azks.num_nodes);
+        Ok(())
+    }
+
+    #[cfg(feature = "vrf")]
+    #[tokio::test]
+    async fn test_insert_verified() -> Result<(), AkdError> {
+        use crate::ecvrf::{HardCodedAkdVRF, VRFKeyStorage};
+
+        let vrf = HardCodedAkdVRF {};
+        let vrf_public_key = vrf.get_vrf_public_key().await?;
+        let username = AkdLabel::from_utf8_str("hello");
+        let proof = vrf
+            .get_label_proof::<Blake3>(&username, false, 1)
+            .await?
+            .to_bytes();
+        let label = vrf.get_node_label::<Blake3>(&username, false, 1).await?;
+        let hash = Blake3Digest::new([1u8; 32]);
+
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+
+        // A label which isn't the VRF's for the username and version is rejected
+        let other_label = vrf.get_node_label::<Blake3>(&username, false, 2).await?;
+        assert_eq!(
+            Err(AkdError::AzksErr(AzksError::InvalidLabelDerivation(
+                other_label
+            ))),
+            azks.insert_verified::<_, Blake3>(
+                &db,
+                Node::<Blake3> {
+                    label: other_label,
+                    hash,
+                },
+                &vrf_public_key,
+                &username,
+                false,
+                1,
+                &proof,
+            )
+            .await
+        );
+        assert_eq!(0, azks.get_latest_epoch());
+
+        azks.insert_verified::<_, Blake3>(
+            &db,
+            Node::<Blake3> { label, hash },
+            &vrf_public_key,
+            &username,
+            false,
+            1,
+            &proof,
+        )
+        .await?;
+        assert_eq!(1, azks.get_latest_epoch());
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+        let membership_proof = azks
+            .get_membership_proof::<_, Blake3>(&db, label, 1)
+            .await?;
+        verify_membership::<Blake3>(root_hash, &membership_proof)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_state_fingerprint() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..12 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+        let (first, second) = insertion_set.split_at(10);
+
+        // Two copies of the directory which agree at epoch 1 but not at epoch 2
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        let other_db = AsyncInMemoryDatabase::new();
+        let mut other_azks = Azks::new::<_, Blake3>(&other_db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, first.to_vec())
+            .await?;
+        other_azks
+            .batch_insert_leaves::<_, Blake3>(&other_db, first.to_vec())
+            .await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, vec![second[0]])
+            .await?;
+        other_azks
+            .batch_insert_leaves::<_, Blake3>(&other_db, vec![second[1]])
+            .await?;
+
+        let fingerprints = [
+            azks.state_fingerprint::<_, Blake3>(&db, 1).await?,
+            azks.state_fingerprint::<_, Blake3>(&db, 2).await?,
+        ];
+        let other_fingerprints = [
+            other_azks
+                .state_fingerprint::<_, Blake3>(&other_db, 1)
+                .await?,
+            other_azks
+                .state_fingerprint::<_, Blake3>(&other_db, 2)
+                .await?,
+        ];
+        assert_eq!(azks.get_root_hash::<_, Blake3>(&db).await?, fingerprints[1]);
+        assert_eq!(fingerprints[0], other_fingerprints[0]);
+        assert_ne!(fingerprints[1], other_fingerprints[1]);
+
+        // Ranges are fingerprinted from the root hashes recorded in storage, which the trees
+        // built here only have once they're backfilled
+        for (db, azks) in [(&db, &azks), (&other_db, &other_azks)] {
+            db.set(DbRecord::Azks(azks.clone())).await?;
+            assert_eq!(
+                Err(AkdError::AzksErr(AzksError::RootHashNotRecorded(1))),
+                fingerprint_range::<_, Blake3>(db, 1, 2).await
+            );
+            assert_eq!(vec![0], azks.backfill_root_hashes::<_, Blake3>(db).await?);
+        }
+
+        // A range matches only the same hashes from the same start epoch
+        let range = fingerprint_range::<_, Blake3>(&db, 1, 2).await?;
+        assert_eq!(range, fingerprint_range::<_, Blake3>(&db, 1, 2).await?);
+        assert_ne!(
+            range,
+            fingerprint_range::<_, Blake3>(&other_db, 1, 2).await?
+        );
+        assert_ne!(range, fingerprint_range::<_, Blake3>(&db, 2, 2).await?);
+        assert_ne!(range, fingerprint_range::<_, Blake3>(&db, 1, 1).await?);
+        assert_eq!(
+            fingerprint_range::<_, Blake3>(&db, 1, 1).await?,
+            fingerprint_range::<_, Blake3>(&other_db, 1, 1).await?
+        );
+
+        // Every epoch in the range must have a recorded root hash
+        assert_eq!(
+            Err(AkdError::AzksErr(AzksError::RootHashNotRecorded(0))),
+            fingerprint_range::<_, Blake3>(&db, 0, 2).await
+        );
+        assert!(matches!(
+            fingerprint_range::<_, Blake3>(&db, 1, 3).await,
+            Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(_, 3)))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_orphaned_nodes() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..20 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+        azks.verify_no_orphaned_nodes(&db).await?;
+
+        // Leaves written without being linked into the tree are all reported
+        let mut orphans = vec![NodeLabel::random(&mut rng), NodeLabel::random(&mut rng)];
+        for label in &orphans {
+            get_leaf_node::<Blake3>(*label, &insertion_set[0].hash, NodeLabel::root(), 1)
+                .write_to_storage(&db)
+                .await?;
+        }
+        // A node written ahead of the latest epoch isn't an orphan yet
+        get_leaf_node::<Blake3>(
+            NodeLabel::random(&mut rng),
+            &insertion_set[0].hash,
+            NodeLabel::root(),
+            2,
+        )
+        .write_to_storage(&db)
+        .await?;
+
+        orphans.sort();
+        match azks.verify_no_orphaned_nodes(&db).await {
+            Err(AkdError::AzksErr(AzksError::OrphanedNodes(found))) => assert_eq!(orphans, found),
+            other => panic!("Expected orphaned nodes, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_are_siblings() -> Result<(), AkdError> {
+        let label = |first_byte: u8| {
+            let mut val = [0u8; 32];
+            val[0] = first_byte;
+            NodeLabel::new(val, 256)
+        };
+        let leaf = |label: NodeLabel| Node::<Blake3> {
+            label,
+            hash: Blake3::hash(&label.label_val),
+        };
+        let a = label(0b0000_0000);
+        let b = label(0b0100_0000);
+        let c = label(0b1000_0000);
+        let d = label(0b0010_0000);
+
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, vec![leaf(a), leaf(b)])
+            .await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, vec![leaf(c)])
+            .await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, vec![leaf(d)])
+            .await?;
+
+        assert!(azks.are_siblings(&db, a, b, 1).await?);
+        assert!(azks.are_siblings(&db, b, a, 2).await?);
+        assert!(!azks.are_siblings(&db, a, c, 2).await?);
+        assert!(!azks.are_siblings(&db, a, a, 2).await?);
+        // Interior nodes have siblings too
+        let prefix = a.get_longest_common_prefix(b);
+        assert!(azks.are_siblings(&db, prefix, c, 2).await?);
+        // Inserting d moves a below a new node
+        assert!(!azks.are_siblings(&db, a, b, 3).await?);
+        assert!(azks.are_siblings(&db, a, d, 3).await?);
+
+        // A label which isn't in the tree at the epoch is an error
+        assert!(matches!(
+            azks.are_siblings(&db, a, c, 1).await,
+            Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(label, 1))) if label == c
+        ));
+        assert!(matches!(
+            azks.are_siblings(&db, label(0b1100_0000), a, 3).await,
+            Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(_, 3)))
+        ));
+        assert!(azks.are_siblings(&db, a, d, 4).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bulk_load() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..200 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.bulk_load::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+
+        insertion_set.reverse();
+        let batch_db = AsyncInMemoryDatabase::new();
+        let mut batch_azks = Azks::new::<_, Blake3>(&batch_db).await?;
+        batch_azks
+            .batch_insert_leaves::<_, Blake3>(&batch_db, insertion_set.clone())
+            .await?;
+
+        assert_eq!(azks.get_latest_epoch(), batch_azks.get_latest_epoch());
+        assert_eq!(azks.num_nodes, batch_azks.num_nodes);
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+        assert_eq!(
+            root_hash,
+            batch_azks.get_root_hash::<_, Blake3>(&batch_db).await?
+        );
+        azks.verify_tree_integrity(&db).await?;
+        let proof = azks
+            .get_membership_proof::<_, Blake3>(&db, insertion_set[0].label, 1)
+            .await?;
+        verify_membership::<Blake3>(root_hash, &proof)?;
+
+        // Only an empty tree can be bulk loaded, and only with distinct labels
+        assert!(matches!(
+            azks.bulk_load::<_, Blake3>(&db, vec![]).await,
+            Err(AkdError::AzksErr(AzksError::TreeNotEmpty(_)))
+        ));
+        let duplicate_db = AsyncInMemoryDatabase::new();
+        let mut duplicate_azks = Azks::new::<_, Blake3>(&duplicate_db).await?;
+        assert!(matches!(
+            duplicate_azks
+                .bulk_load::<_, Blake3>(
+                    &duplicate_db,
+                    vec![insertion_set[0], insertion_set[1], insertion_set[0]]
+                )
+                .await,
+            Err(AkdError::TreeNode(TreeNodeError::LabelAlreadyExists(_)))
+        ));
+        assert_eq!(duplicate_azks.get_latest_epoch(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_leaf_metadata() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<(Node<Blake3>, Vec<u8>)> = vec![];
+        for i in 0..5u8 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push((Node::<Blake3> { label, hash }, vec![i; 3]));
+        }
+        let leaves = insertion_set
+            .iter()
+            .map(|(leaf, _)| *leaf)
+            .collect::<Vec<_>>();
+
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves_with_metadata::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+
+        // The metadata doesn't change the tree
+        let plain_db = AsyncInMemoryDatabase::new();
+        let mut plain_azks = Azks::new::<_, Blake3>(&plain_db).await?;
+        plain_azks
+            .batch_insert_leaves::<_, Blake3>(&plain_db, leaves.clone())
+            .await?;
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+        assert_eq!(
+            root_hash,
+            plain_azks.get_root_hash::<_, Blake3>(&plain_db).await?
+        );
+
+        for (leaf, data) in &insertion_set {
+            let (node, metadata) = azks
+                .get_leaf_with_metadata(&db, leaf.label)
+                .await?
+                .expect("Leaf should exist");
+            assert_eq!(node.label, leaf.label);
+            assert_eq!(
+                metadata.as_ref().map(LeafMetadata::data),
+                Some(data.as_slice())
+            );
+        }
+        let (_, metadata) = plain_azks
+            .get_leaf_with_metadata(&plain_db, leaves[0].label)
+            .await?
+            .expect("Leaf should exist");
+        assert_eq!(metadata, None);
+        assert!(azks
+            .get_leaf_with_metadata(&db, NodeLabel::random(&mut rng))
+            .await?
+            .is_none());
+
+        // Metadata can be replaced without a new epoch, and only for leaves in the tree
+        azks.set_leaf_metadata(&db, leaves[0].label, vec![42])
+            .await?;
+        let (_, metadata) = azks
+            .get_leaf_with_metadata(&db, leaves[0].label)
+            .await?
+            .expect("Leaf should exist");
+        assert_eq!(metadata.as_ref().map(LeafMetadata::data), Some(&[42u8][..]));
+        assert_eq!(azks.get_latest_epoch(), 1);
+        assert_eq!(azks.get_root_hash::<_, Blake3>(&db).await?, root_hash);
+        assert!(azks
+            .set_leaf_metadata(&db, NodeLabel::random(&mut rng), vec![42])
+            .await
+            .is_err());
+
+        // Metadata too long for storage is rejected, along with the leaves inserted with it
+        let too_long = vec![0u8; LeafMetadata::MAX_DATA_LEN + 1];
+        assert!(matches!(
+            azks.set_leaf_metadata(&db, leaves[0].label, too_long.clone())
+                .await,
+            Err(AkdError::AzksErr(AzksError::LeafMetadataTooLong { len, .. }))
+                if len == LeafMetadata::MAX_DATA_LEN + 1
+        ));
+        let label = NodeLabel::random(&mut rng);
+        let leaf = Node::<Blake3> {
+            label,
+            hash: Blake3::hash(&EMPTY_VALUE),
+        };
+        assert!(matches!(
+            azks.batch_insert_leaves_with_metadata::<_, Blake3>(&db, vec![(leaf, too_long)])
+                .await,
+            Err(AkdError::AzksErr(AzksError::LeafMetadataTooLong { .. }))
+        ));
+        assert_eq!(azks.get_latest_epoch(), 1);
+        assert!(azks.get_existing_leaf(&db, label).await?.is_none());
+        assert!(!db.is_transaction_active().await);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_changed_labels_since() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..51 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+        let new_leaf = insertion_set.pop().unwrap();
+
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+
+        // Everything has changed since the tree was empty
+        let changed = azks.get_changed_labels_since(&db, 0).await?;
+        assert_eq!(azks.num_nodes, changed.len() as u64);
+
+        // Cache the whole tree as of epoch 1
+        let cache = TimedCache::new(None);
+        for record in StorageUtil::batch_get_all_direct(&db).await? {
+            if let DbRecord::TreeNode(_) = record {
+                cache.put(&record).await;
+            }
+        }
+
+        azks.batch_insert_leaves::<_, Blake3>(&db, vec![new_leaf])
+            .await?;
+        let changed = azks.get_changed_labels_since(&db, 1).await?;
+        assert!(changed.contains(&NodeLabel::root()));
+        assert!(changed.contains(&new_leaf.label));
+        assert!((changed.len() as u64) < azks.num_nodes);
+
+        // Only the changed nodes miss once the cache moves on
+        cache.advance_epoch(2, &changed).await;
+        for leaf in insertion_set.iter() {
+            let hit = cache
+                .hit_test::<TreeNodeWithPreviousValue>(&NodeKey(leaf.label))
+                .await;
+            assert_eq!(!changed.contains(&leaf.label), hit.is_some());
+        }
+        assert!(cache
+            .hit_test::<TreeNodeWithPreviousValue>(&NodeKey(NodeLabel::root()))
+            .await
+            .is_none());
+
+        // Nothing but the root needs checking when nothing has changed
+        let changed = azks.get_changed_labels_since(&db, 2).await?;
+        assert_eq!(vec![NodeLabel::root()], changed);
+        Ok(())
+    }
 
-type AppendOnlyHelper<H> = (Vec<Node<H>>, Vec<Node<H>>);
+    #[tokio::test]
+    async fn test_insert_policy() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..10 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{
-        auditor::audit_verify,
-        client::{verify_membership, verify_nonmembership},
-        storage::memory::AsyncInMemoryDatabase,
-    };
-    use rand::{rngs::OsRng, seq::SliceRandom, RngCore};
-    use winter_crypto::hashers::Blake3_256;
-    use winter_math::fields::f128::BaseElement;
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+        let num_nodes = azks.num_nodes;
+
+        // Re-inserting an existing label is an error under RejectExisting,
+        // and leaves the tree as it was
+        let result = azks
+            .batch_insert_leaves_with_policy::<_, Blake3>(
+                &db,
+                vec![insertion_set[0]],
+                InsertPolicy::RejectExisting,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(AkdError::TreeNode(TreeNodeError::LabelAlreadyExists(label)))
+                if label == insertion_set[0].label
+        ));
+        assert_eq!(1, azks.get_latest_epoch());
+        assert_eq!(root_hash, azks.get_root_hash::<_, Blake3>(&db).await?);
+
+        // Re-inserting unchanged values is skipped under UpdateIfChanged
+        azks.batch_insert_leaves_with_policy::<_, Blake3>(
+            &db,
+            insertion_set.clone(),
+            InsertPolicy::UpdateIfChanged,
+        )
+        .await?;
+        assert_eq!(root_hash, azks.get_root_hash::<_, Blake3>(&db).await?);
 
-    type Blake3 = Blake3_256<BaseElement>;
-    type Blake3Digest = <Blake3_256<winter_math::fields::f128::BaseElement> as Hasher>::Digest;
+        // A changed value replaces the existing leaf without adding a node
+        let mut updated = insertion_set[0];
+        updated.hash = Blake3::hash(&[1u8]);
+        azks.batch_insert_leaves::<_, Blake3>(&db, vec![updated])
+            .await?;
+        let epoch = azks.get_latest_epoch();
+        assert_eq!(num_nodes, azks.num_nodes);
+        assert_ne!(root_hash, azks.get_root_hash::<_, Blake3>(&db).await?);
+
+        let proof = azks
+            .get_membership_proof::<_, Blake3>(&db, updated.label, epoch)
+            .await?;
+        assert_eq!(merge_epoch::<Blake3>(updated.hash, epoch), proof.hash_val);
+        verify_membership::<Blake3>(azks.get_root_hash::<_, Blake3>(&db).await?, &proof)?;
+
+        Ok(())
+    }
 
     #[tokio::test]
-    async fn test_batch_insert_basic() -> Result<(), AkdError> {
+    async fn test_repair_node_hash() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..10 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+        let epoch = azks.get_latest_epoch();
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+
+        // A consistent tree needs no repairs
+        let leaf = TreeNode::get_from_storage(&db, &NodeKey(insertion_set[0].label), epoch).await?;
+        assert!(!azks.repair_node_hash::<_, Blake3>(&db, leaf.label).await?);
+        assert!(!azks.repair_node_hash::<_, Blake3>(&db, leaf.parent).await?);
+
+        // Corrupt the hashes of the leaf's parent and of the root, as a partial write of the
+        // path from the leaf up to the root could
+        for label in [leaf.parent, NodeLabel::root()] {
+            let mut node = TreeNode::get_from_storage(&db, &NodeKey(label), epoch).await?;
+            node.hash = [0u8; 32];
+            node.write_to_storage(&db).await?;
+        }
+        assert_ne!(root_hash, azks.get_root_hash::<_, Blake3>(&db).await?);
+
+        assert!(azks.repair_node_hash::<_, Blake3>(&db, leaf.parent).await?);
+        assert_eq!(root_hash, azks.get_root_hash::<_, Blake3>(&db).await?);
+        assert!(!azks.repair_node_hash::<_, Blake3>(&db, leaf.parent).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_leaf_depth() -> Result<(), AkdError> {
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        assert_eq!(0, azks.max_leaf_depth(&db).await?);
+
+        // Random labels keep the tree balanced
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..100 {
+            let label = NodeLabel::random(&mut rng);
+            let hash = Blake3::hash(&label.get_val());
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set)
+            .await?;
+        let depth = azks.max_leaf_depth(&db).await?;
+        assert!(depth >= 7);
+        assert!(depth <= Azks::expected_max_leaf_depth(100));
+
+        // Labels which each differ from the all-zero label in a single bit form a chain,
+        // with one more level for every label
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for i in 0..31 {
+            let mut label_val = [0u8; 32];
+            if i > 0 {
+                label_val[(i - 1) / 8] = 0b1000_0000u8 >> ((i - 1) % 8);
+            }
+            let label = NodeLabel::new(label_val, 256u32);
+            let hash = Blake3::hash(&label_val);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set)
+            .await?;
+        let depth = azks.max_leaf_depth(&db).await?;
+        assert_eq!(30, depth);
+        assert!(depth > Azks::expected_max_leaf_depth(31));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_tree_integrity() -> Result<(), AkdError> {
+        assert_eq!(1, Azks::expected_node_count(0, 0)?);
+        assert_eq!(2, Azks::expected_node_count(1, 1)?);
+        assert_eq!(19, Azks::expected_node_count(10, 2)?);
+        assert_eq!(20, Azks::expected_node_count(10, 1)?);
+        // Counts which no compressed tree has are errors rather than overflowing
+        for (num_leaves, root_children) in [(0, 2), (1, 2), (10, 0), (10, 3), (usize::MAX, 2)] {
+            assert!(matches!(
+                Azks::expected_node_count(num_leaves, root_children),
+                Err(AkdError::AzksErr(AzksError::TreeIntegrity(_)))
+            ));
+        }
+
         let mut rng = OsRng;
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.verify_tree_integrity(&db).await?;
+
+        for _ in 0..3 {
+            let mut insertion_set: Vec<Node<Blake3>> = vec![];
+            for _ in 0..10 {
+                let label = NodeLabel::random(&mut rng);
+                let mut input = [0u8; 32];
+                rng.fill_bytes(&mut input);
+                let hash = Blake3Digest::new(input);
+                insertion_set.push(Node::<Blake3> { label, hash });
+            }
+            azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set)
+                .await?;
+            azks.verify_tree_integrity(&db).await?;
+        }
+
+        // A node count which disagrees with the tree is flagged
+        azks.num_nodes += 1;
+        let result = azks.verify_tree_integrity(&db).await;
+        assert!(matches!(
+            result,
+            Err(AkdError::AzksErr(AzksError::TreeIntegrity(_)))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_permuted() -> Result<(), AkdError> {
         let num_nodes = 10;
+        let mut rng = OsRng;
         let db = AsyncInMemoryDatabase::new();
         let mut azks1 = Azks::new::<_, Blake3>(&db).await?;
         azks1.increment_epoch();
-
         let mut insertion_set: Vec<Node<Blake3>> = vec![];
 
         for _ in 0..num_nodes {
             let label = NodeLabel::random(&mut rng);
             let mut input = [0u8; 32];
             rng.fill_bytes(&mut input);
-            let hash = Blake3::hash(&input);
+            let hash = Blake3Digest::new(input);
             let node = Node::<Blake3> { label, hash };
             insertion_set.push(node);
             azks1.insert_leaf::<_, Blake3>(&db, node, 1).await?;
         }
 
+        // Try randomly permuting
+        insertion_set.shuffle(&mut rng);
+
         let db2 = AsyncInMemoryDatabase::new();
         let mut azks2 = Azks::new::<_, Blake3>(&db2).await?;
 
-        azks2
-            .batch_insert_leaves::<_, Blake3>(&db2, insertion_set)
-            .await?;
+        azks2
+            .batch_insert_leaves::<_, Blake3>(&db2, insertion_set)
+            .await?;
+
+        assert_eq!(
+            azks1.get_root_hash::<_, Blake3>(&db).await?,
+            azks2.get_root_hash::<_, Blake3>(&db2).await?,
+            "Batch insert doesn't match individual insert"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_membership_proof_permuted() -> Result<(), AkdError> {
+        let num_nodes = 10;
+        let mut rng = OsRng;
+
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+
+        for _ in 0..num_nodes {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            let node = Node::<Blake3> { label, hash };
+            insertion_set.push(node);
+        }
+
+        // Try randomly permuting
+        insertion_set.shuffle(&mut rng);
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+
+        let proof = azks
+            .get_membership_proof(&db, insertion_set[0].label, 1)
+            .await?;
+
+        verify_membership::<Blake3>(azks.get_root_hash::<_, Blake3>(&db).await?, &proof)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_merkle_path() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..10 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+
+        let proof = azks
+            .get_membership_proof::<_, Blake3>(&db, insertion_set[0].label, 1)
+            .await?;
+        let leaf_hash = Blake3::merge(&[proof.hash_val, hash_label::<Blake3>(proof.label)]);
+        let mut path = proof
+            .layer_proofs
+            .iter()
+            .rev()
+            .map(|layer| {
+                let sibling = &layer.siblings[0];
+                let sibling_hash =
+                    Blake3::merge(&[sibling.hash, hash_label::<Blake3>(sibling.label)]);
+                (layer.direction, sibling_hash, layer.label)
+            })
+            .collect::<Vec<_>>();
+        assert!(verify_merkle_path::<Blake3>(leaf_hash, &path, root_hash));
+
+        // Swapping the merge order of a layer leads to a different root
+        path[0].0 = path[0].0.map(|dir| 1 - dir);
+        assert!(!verify_merkle_path::<Blake3>(leaf_hash, &path, root_hash));
+        // and a step without a valid direction is rejected
+        path[0].0 = Some(ARITY);
+        assert!(!verify_merkle_path::<Blake3>(leaf_hash, &path, root_hash));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_membership_proof_max_depth() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..10 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+
+        let mut proof = azks
+            .get_membership_proof::<_, Blake3>(&db, insertion_set[0].label, 1)
+            .await?;
+        let depth = proof.layer_proofs.len();
+        verify_membership::<Blake3>(root_hash, &proof)?;
+        verify_membership_with_max_depth::<Blake3>(root_hash, &proof, depth)?;
+        assert_eq!(
+            Err(AkdError::AzksErr(AzksError::ProofTooDeep {
+                depth,
+                max: depth - 1
+            })),
+            verify_membership_with_max_depth::<Blake3>(root_hash, &proof, depth - 1)
+        );
+
+        // A proof with more layers than a label has bits is rejected by default
+        let layer = &proof.layer_proofs[0];
+        let padding = (depth..=LEAF_LEN as usize)
+            .map(|_| LayerProof {
+                label: layer.label,
+                siblings: layer.siblings,
+                direction: layer.direction,
+            })
+            .collect::<Vec<_>>();
+        proof.layer_proofs.extend(padding);
+        assert_eq!(
+            Err(AkdError::AzksErr(AzksError::ProofTooDeep {
+                depth: LEAF_LEN as usize + 1,
+                max: LEAF_LEN as usize
+            })),
+            verify_membership::<Blake3>(root_hash, &proof)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..10 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+
+        let label = NodeLabel::checkpoint::<Blake3>(b"operator log");
+        assert_ne!(label, NodeLabel::checkpoint::<Blake3>(b"other log"));
+        let first = Blake3::hash(b"log head 1");
+        azks.insert_checkpoint::<_, Blake3>(&db, label, first)
+            .await?;
+        let second = Blake3::hash(b"log head 2");
+        azks.insert_checkpoint::<_, Blake3>(&db, label, second)
+            .await?;
+        assert_eq!(3, azks.get_latest_epoch());
+
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+        let proof = azks.get_membership_proof(&db, label, 3).await?;
+        verify_checkpoint::<Blake3>(root_hash, label, second, 3, &proof)?;
+        // The overwritten digest, or the right digest as of another epoch, is rejected
+        assert!(verify_checkpoint::<Blake3>(root_hash, label, first, 3, &proof).is_err());
+        assert!(verify_checkpoint::<Blake3>(root_hash, label, second, 2, &proof).is_err());
+
+        // A proof for a user's leaf can't stand in for the checkpoint
+        let user_proof = azks
+            .get_membership_proof(&db, insertion_set[0].label, 3)
+            .await?;
+        let user_digest = insertion_set[0].hash;
+        assert!(
+            verify_checkpoint::<Blake3>(root_hash, label, user_digest, 1, &user_proof).is_err()
+        );
+
+        // A label which could be a prefix of other labels is refused
+        let short_label = NodeLabel::new([0u8; 32], 8);
+        assert!(matches!(
+            azks.insert_checkpoint::<_, Blake3>(&db, short_label, first)
+                .await,
+            Err(AkdError::AzksErr(AzksError::InvalidCheckpointLabel(label))) if label == short_label
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_root_hash_delta() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..11 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+        let leaf = insertion_set.pop().unwrap();
+
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set)
+            .await?;
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+        let num_nodes = azks.num_nodes;
+
+        let (before, after) = azks.root_hash_delta::<_, Blake3>(&db, leaf).await?;
+        assert_eq!(root_hash, before);
+        assert_ne!(before, after);
+
+        // Neither the azks nor the stored tree were changed
+        assert!(!db.is_transaction_active().await);
+        assert_eq!(1, azks.get_latest_epoch());
+        assert_eq!(num_nodes, azks.num_nodes);
+        assert_eq!(root_hash, azks.get_root_hash::<_, Blake3>(&db).await?);
+        assert!(azks.get_existing_leaf(&db, leaf.label).await?.is_none());
+
+        // and actually inserting the leaf leads to the predicted root hash
+        azks.batch_insert_leaves::<_, Blake3>(&db, vec![leaf])
+            .await?;
+        assert_eq!(after, azks.get_root_hash::<_, Blake3>(&db).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_epoch_insertion_digest() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let mut batches: Vec<Vec<Node<Blake3>>> = vec![];
+        for _ in 0..2 {
+            let mut insertion_set: Vec<Node<Blake3>> = vec![];
+            for _ in 0..10 {
+                let label = NodeLabel::random(&mut rng);
+                let mut input = [0u8; 32];
+                rng.fill_bytes(&mut input);
+                let hash = Blake3Digest::new(input);
+                insertion_set.push(Node::<Blake3> { label, hash });
+            }
+            batches.push(insertion_set);
+        }
+
+        // Two replicas inserting the same leaves in a different order agree on each epoch
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        let other_db = AsyncInMemoryDatabase::new();
+        let mut other_azks = Azks::new::<_, Blake3>(&other_db).await?;
+        for batch in &batches {
+            azks.batch_insert_leaves::<_, Blake3>(&db, batch.clone())
+                .await?;
+            let mut reversed = batch.clone();
+            reversed.reverse();
+            other_azks
+                .batch_insert_leaves::<_, Blake3>(&other_db, reversed)
+                .await?;
+        }
+        for epoch in 1..=2 {
+            assert_eq!(
+                azks.epoch_insertion_digest::<_, Blake3>(&db, epoch).await?,
+                other_azks
+                    .epoch_insertion_digest::<_, Blake3>(&other_db, epoch)
+                    .await?
+            );
+        }
+
+        // The digest is of exactly the leaves inserted at the epoch, in order of their labels
+        let mut epoch_two = batches[1].clone();
+        epoch_two.sort_by_key(|node| node.label);
+        let mut hash_input = vec![];
+        hash_input.extend_from_slice(&2u64.to_be_bytes());
+        hash_input.extend_from_slice(&10u64.to_be_bytes());
+        for node in &epoch_two {
+            hash_input.extend_from_slice(&node.label.get_len().to_be_bytes());
+            hash_input.extend_from_slice(&node.label.get_val());
+            hash_input.extend_from_slice(&from_digest::<Blake3>(node.hash));
+        }
+        assert_eq!(
+            Blake3::hash(&hash_input),
+            azks.epoch_insertion_digest::<_, Blake3>(&db, 2).await?
+        );
+        assert_ne!(
+            azks.epoch_insertion_digest::<_, Blake3>(&db, 1).await?,
+            azks.epoch_insertion_digest::<_, Blake3>(&db, 2).await?
+        );
 
+        // A replica which inserted a different leaf disagrees
+        let mut changed_batch = batches[1].clone();
+        changed_batch[0].hash = Blake3::hash(&[0u8]);
+        let third_db = AsyncInMemoryDatabase::new();
+        let mut third_azks = Azks::new::<_, Blake3>(&third_db).await?;
+        third_azks
+            .batch_insert_leaves::<_, Blake3>(&third_db, batches[0].clone())
+            .await?;
+        third_azks
+            .batch_insert_leaves::<_, Blake3>(&third_db, changed_batch)
+            .await?;
         assert_eq!(
-            azks1.get_root_hash::<_, Blake3>(&db).await?,
-            azks2.get_root_hash::<_, Blake3>(&db2).await?,
-            "Batch insert doesn't match individual insert"
+            azks.epoch_insertion_digest::<_, Blake3>(&db, 1).await?,
+            third_azks
+                .epoch_insertion_digest::<_, Blake3>(&third_db, 1)
+                .await?
+        );
+        assert_ne!(
+            azks.epoch_insertion_digest::<_, Blake3>(&db, 2).await?,
+            third_azks
+                .epoch_insertion_digest::<_, Blake3>(&third_db, 2)
+                .await?
         );
 
+        assert!(azks
+            .epoch_insertion_digest::<_, Blake3>(&db, 3)
+            .await
+            .is_err());
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_insert_permuted() -> Result<(), AkdError> {
-        let num_nodes = 10;
+    async fn test_collect_unchanged_roots() -> Result<(), AkdError> {
         let mut rng = OsRng;
         let db = AsyncInMemoryDatabase::new();
-        let mut azks1 = Azks::new::<_, Blake3>(&db).await?;
-        azks1.increment_epoch();
-        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        let mut first_batch: Vec<Node<Blake3>> = vec![];
+        for batch_size in [20, 5] {
+            let mut insertion_set: Vec<Node<Blake3>> = vec![];
+            for _ in 0..batch_size {
+                let label = NodeLabel::random(&mut rng);
+                let mut input = [0u8; 32];
+                rng.fill_bytes(&mut input);
+                let hash = Blake3Digest::new(input);
+                insertion_set.push(Node::<Blake3> { label, hash });
+            }
+            if first_batch.is_empty() {
+                first_batch = insertion_set.clone();
+            }
+            azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set)
+                .await?;
+        }
 
-        for _ in 0..num_nodes {
-            let label = NodeLabel::random(&mut rng);
-            let mut input = [0u8; 32];
-            rng.fill_bytes(&mut input);
-            let hash = Blake3Digest::new(input);
-            let node = Node::<Blake3> { label, hash };
-            insertion_set.push(node);
-            azks1.insert_leaf::<_, Blake3>(&db, node, 1).await?;
+        let roots = azks.collect_unchanged_roots::<_, Blake3>(&db, 1).await?;
+        // They're the unchanged nodes of the append-only proof from the same epoch
+        let proof = azks.get_append_only_proof::<_, Blake3>(&db, 1, 2).await?;
+        let mut expected = proof.proofs[0].unchanged_nodes.clone();
+        expected.sort_by_key(|node| node.label);
+        assert_eq!(expected, roots);
+
+        for root in &roots {
+            let node = TreeNode::get_from_storage(&db, &NodeKey(root.label), 2).await?;
+            assert!(node.last_epoch <= 1);
+            // and none of them is inside the subtree of another, so the set is minimal
+            assert!(!roots
+                .iter()
+                .any(|other| other.label != root.label && other.label.is_prefix_of(&root.label)));
+        }
+        // Every leaf which hasn't changed is covered by one of the roots
+        for leaf in &first_batch {
+            assert!(roots
+                .iter()
+                .any(|root| root.label.is_prefix_of(&leaf.label)));
         }
 
-        // Try randomly permuting
-        insertion_set.shuffle(&mut rng);
+        // Nothing has changed since the latest epoch, so only the root is returned
+        let roots = azks.collect_unchanged_roots::<_, Blake3>(&db, 2).await?;
+        assert_eq!(1, roots.len());
+        assert_eq!(NodeLabel::root(), roots[0].label);
 
-        let db2 = AsyncInMemoryDatabase::new();
-        let mut azks2 = Azks::new::<_, Blake3>(&db2).await?;
+        // while every node was written after the empty tree of epoch 0
+        assert!(azks
+            .collect_unchanged_roots::<_, Blake3>(&db, 0)
+            .await?
+            .is_empty());
+        Ok(())
+    }
 
-        azks2
-            .batch_insert_leaves::<_, Blake3>(&db2, insertion_set)
-            .await?;
+    #[tokio::test]
+    async fn test_health_check() -> Result<(), AkdError> {
+        let db = AsyncInMemoryDatabase::new();
+        // Nothing has been written yet
+        assert!(matches!(
+            Azks::health_check::<_, Blake3>(&db).await,
+            Err(AkdError::Storage(StorageError::NotFound(_)))
+        ));
 
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        db.set(DbRecord::Azks(azks.clone())).await?;
+        let report = Azks::health_check::<_, Blake3>(&db).await?;
+        assert!(report.storage_reachable);
+        assert_eq!(Some(0), report.latest_epoch);
         assert_eq!(
-            azks1.get_root_hash::<_, Blake3>(&db).await?,
-            azks2.get_root_hash::<_, Blake3>(&db2).await?,
-            "Batch insert doesn't match individual insert"
+            Some(azks.get_root_hash::<_, Blake3>(&db).await?),
+            report.root_hash
         );
 
+        let mut rng = OsRng;
+        let insertion_set = (0..10)
+            .map(|_| Node::<Blake3> {
+                label: NodeLabel::random(&mut rng),
+                hash: Blake3::hash(&rng.next_u64().to_be_bytes()),
+            })
+            .collect::<Vec<_>>();
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set)
+            .await?;
+        db.set(DbRecord::Azks(azks.clone())).await?;
+        let report = Azks::health_check::<_, Blake3>(&db).await?;
+        assert!(report.storage_reachable);
+        assert_eq!(Some(1), report.latest_epoch);
+        assert_eq!(
+            Some(azks.get_root_hash::<_, Blake3>(&db).await?),
+            report.root_hash
+        );
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_membership_proof_permuted() -> Result<(), AkdError> {
-        let num_nodes = 10;
-        let mut rng = OsRng;
-
-        let mut insertion_set: Vec<Node<Blake3>> = vec![];
-
-        for _ in 0..num_nodes {
-            let label = NodeLabel::random(&mut rng);
-            let mut input = [0u8; 32];
-            rng.fill_bytes(&mut input);
-            let hash = Blake3Digest::new(input);
-            let node = Node::<Blake3> { label, hash };
-            insertion_set.push(node);
-        }
-
-        // Try randomly permuting
-        insertion_set.shuffle(&mut rng);
+    async fn test_root_invariants() -> Result<(), AkdError> {
         let db = AsyncInMemoryDatabase::new();
         let mut azks = Azks::new::<_, Blake3>(&db).await?;
-        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
+        let mut rng = OsRng;
+        let insertion_set = (0..10)
+            .map(|_| Node::<Blake3> {
+                label: NodeLabel::random(&mut rng),
+                hash: Blake3::hash(&rng.next_u64().to_be_bytes()),
+            })
+            .collect::<Vec<_>>();
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set)
             .await?;
-
-        let proof = azks
-            .get_membership_proof(&db, insertion_set[0].label, 1)
+        db.set(DbRecord::Azks(azks.clone())).await?;
+        azks.assert_root_invariants(&db).await?;
+
+        let root_key = NodeKey(NodeLabel::root());
+        let root = TreeNode::get_from_storage(&db, &root_key, 1).await?;
+        let mut not_root = root.clone();
+        not_root.node_type = NodeType::Interior;
+        let mut mislabelled = root.clone();
+        mislabelled.label = NodeLabel::new(byte_arr_from_u64(1), 64);
+        let mut own_child = root.clone();
+        own_child.parent = root.left_child.unwrap();
+        for corrupted in [not_root, mislabelled, own_child] {
+            // Store the corrupted root at the root label, whatever its own label says
+            db.set(DbRecord::TreeNode(TreeNodeWithPreviousValue {
+                label: NodeLabel::root(),
+                latest_node: corrupted,
+                previous_node: None,
+            }))
             .await?;
+            assert!(matches!(
+                azks.assert_root_invariants(&db).await,
+                Err(AkdError::AzksErr(AzksError::RootInvariantViolated(_)))
+            ));
+            // which is also caught by the health check
+            assert!(matches!(
+                Azks::health_check::<_, Blake3>(&db).await,
+                Err(AkdError::AzksErr(AzksError::RootInvariantViolated(_)))
+            ));
+        }
 
-        verify_membership::<Blake3>(azks.get_root_hash::<_, Blake3>(&db).await?, &proof)?;
-
+        // Restoring the root restores the invariants
+        root.write_to_storage(&db).await?;
+        azks.assert_root_invariants(&db).await?;
         Ok(())
     }
 
@@ -800,135 +3885,625 @@ mod tests {
             hash: Blake3::hash(&EMPTY_VALUE),
         });
         let mut azks = Azks::new::<_, Blake3>(&db).await?;
-        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set)
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set)
+            .await?;
+        let search_label = NodeLabel::new(byte_arr_from_u64(0b1111 << 60), 64);
+        let proof = azks.get_non_membership_proof(&db, search_label).await?;
+        assert!(
+            verify_nonmembership::<Blake3>(azks.get_root_hash::<_, Blake3>(&db).await?, &proof)?,
+            "Nonmembership proof does not verify"
+        );
+        Ok(())
+    }
+
+    // This test checks that a non-membership proof in a tree with 1 leaf verifies.
+    #[tokio::test]
+    async fn test_nonmembership_proof_very_small() -> Result<(), AkdError> {
+        let num_nodes = 2;
+
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+
+        for i in 0..num_nodes {
+            let mut label_arr = [0u8; 32];
+            label_arr[31] = u8::from(i);
+            let label = NodeLabel::new(label_arr, 256u32);
+            let mut input = [0u8; 32];
+            input[31] = u8::from(i);
+            let hash = Blake3Digest::new(input);
+            let node = Node::<Blake3> { label, hash };
+            insertion_set.push(node);
+        }
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        let search_label = insertion_set[0].label;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone()[1..2].to_vec())
+            .await?;
+        let proof = azks.get_non_membership_proof(&db, search_label).await?;
+
+        verify_nonmembership::<Blake3>(azks.get_root_hash::<_, Blake3>(&db).await?, &proof)?;
+
+        Ok(())
+    }
+
+    // This test verifies if a non-membership proof in a small tree of 2 leaves
+    // verifies.
+    #[tokio::test]
+    async fn test_nonmembership_proof_small() -> Result<(), AkdError> {
+        let num_nodes = 3;
+        let mut rng = OsRng;
+
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+
+        for _ in 0..num_nodes {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            let node = Node::<Blake3> { label, hash };
+            insertion_set.push(node);
+        }
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        let search_label = insertion_set[num_nodes - 1].label;
+        azks.batch_insert_leaves::<_, Blake3>(
+            &db,
+            insertion_set.clone()[0..num_nodes - 1].to_vec(),
+        )
+        .await?;
+        let proof = azks.get_non_membership_proof(&db, search_label).await?;
+
+        verify_nonmembership::<Blake3>(azks.get_root_hash::<_, Blake3>(&db).await?, &proof)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_nonmembership_proof() -> Result<(), AkdError> {
+        let num_nodes = 10;
+        let mut rng = OsRng;
+
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+
+        for _ in 0..num_nodes {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            let node = Node::<Blake3> { label, hash };
+            insertion_set.push(node);
+        }
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        let search_label = insertion_set[num_nodes - 1].label;
+        azks.batch_insert_leaves::<_, Blake3>(
+            &db,
+            insertion_set.clone()[0..num_nodes - 1].to_vec(),
+        )
+        .await?;
+        let proof = azks.get_non_membership_proof(&db, search_label).await?;
+
+        verify_nonmembership::<Blake3>(azks.get_root_hash::<_, Blake3>(&db).await?, &proof)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_membership_proof() -> Result<(), AkdError> {
+        let num_nodes = 100;
+        let mut rng = OsRng;
+
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..num_nodes {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+        let epoch = azks.get_latest_epoch();
+
+        // A mix of labels which are members and labels which are not
+        let members: Vec<NodeLabel> = insertion_set[..20].iter().map(|node| node.label).collect();
+        let non_members: Vec<NodeLabel> = (0..5).map(|_| NodeLabel::random(&mut rng)).collect();
+        let labels = [members.clone(), non_members.clone()].concat();
+
+        let proof = azks
+            .get_batch_membership_proof::<_, Blake3>(&db, &labels, epoch)
+            .await?;
+        verify_batch_membership::<Blake3>(root_hash, &labels, &proof)?;
+
+        // Every member should have the same hash as in its individual membership proof
+        assert_eq!(members.len(), proof.membership.len());
+        for node in proof.membership.iter() {
+            let single_proof = azks
+                .get_membership_proof::<_, Blake3>(&db, node.label, epoch)
+                .await?;
+            assert_eq!(single_proof.hash_val, node.hash);
+        }
+        let mut proven_non_members = proof.non_membership.clone();
+        proven_non_members.sort();
+        let mut expected_non_members = non_members.clone();
+        expected_non_members.sort();
+        assert_eq!(expected_non_members, proven_non_members);
+
+        // The shared paths should make the batch proof smaller than the individual proofs
+        let mut individual_nodes = 0;
+        for label in members.iter() {
+            let single_proof = azks
+                .get_membership_proof::<_, Blake3>(&db, *label, epoch)
+                .await?;
+            individual_nodes += 1 + single_proof.layer_proofs.len() * (ARITY - 1);
+        }
+        assert!(proof.membership.len() + proof.siblings.len() < individual_nodes);
+
+        // Tampering with a member's hash should fail verification
+        let mut tampered = proof.clone();
+        tampered.membership[0].hash = Blake3::hash(&[0u8]);
+        assert!(verify_batch_membership::<Blake3>(root_hash, &labels, &tampered).is_err());
+
+        // Claiming a member is absent should fail verification
+        let mut tampered = proof.clone();
+        let member = tampered.membership.remove(0);
+        tampered.siblings.push(member);
+        tampered.non_membership.push(member.label);
+        assert!(verify_batch_membership::<Blake3>(root_hash, &labels, &tampered).is_err());
+
+        // A proof for other labels than the ones asked about should fail verification
+        assert!(verify_batch_membership::<Blake3>(root_hash, &members, &proof).is_err());
+        let mut tampered = proof.clone();
+        tampered.non_membership.pop();
+        assert!(verify_batch_membership::<Blake3>(root_hash, &labels, &tampered).is_err());
+
+        // Proofs for an empty tree only contain non-members
+        let empty_db = AsyncInMemoryDatabase::new();
+        let empty_azks = Azks::new::<_, Blake3>(&empty_db).await?;
+        let proof = empty_azks
+            .get_batch_membership_proof::<_, Blake3>(&empty_db, &labels, 0)
+            .await?;
+        assert_eq!(labels.len(), proof.non_membership.len());
+        verify_batch_membership::<Blake3>(
+            empty_azks.get_root_hash::<_, Blake3>(&empty_db).await?,
+            &labels,
+            &proof,
+        )?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_append_only_proof_very_tiny() -> Result<(), AkdError> {
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+
+        let mut insertion_set_1: Vec<Node<Blake3>> = vec![];
+        insertion_set_1.push(Node {
+            label: NodeLabel::new(byte_arr_from_u64(0b0), 64),
+            hash: Blake3::hash(&EMPTY_VALUE),
+        });
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set_1)
+            .await?;
+        let start_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+
+        let mut insertion_set_2: Vec<Node<Blake3>> = vec![];
+        insertion_set_2.push(Node {
+            label: NodeLabel::new(byte_arr_from_u64(0b01 << 62), 64),
+            hash: Blake3::hash(&EMPTY_VALUE),
+        });
+
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set_2)
+            .await?;
+        let end_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+
+        let proof = azks.get_append_only_proof(&db, 1, 2).await?;
+        audit_verify::<Blake3>(vec![start_hash, end_hash], proof).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_single_epoch_proof() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        let mut root_hashes = vec![azks.get_root_hash::<_, Blake3>(&db).await?];
+        for _ in 0..3 {
+            let mut insertion_set: Vec<Node<Blake3>> = vec![];
+            for _ in 0..10 {
+                let label = NodeLabel::random(&mut rng);
+                let mut input = [0u8; 32];
+                rng.fill_bytes(&mut input);
+                let hash = Blake3Digest::new(input);
+                insertion_set.push(Node::<Blake3> { label, hash });
+            }
+            azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set)
+                .await?;
+            root_hashes.push(azks.get_root_hash::<_, Blake3>(&db).await?);
+        }
+
+        // The latest transition is the same as the last segment of a ranged proof
+        let proof = azks.get_single_epoch_proof::<_, Blake3>(&db, 3).await?;
+        let ranged = azks.get_append_only_proof::<_, Blake3>(&db, 2, 3).await?;
+        assert_eq!(ranged.proofs[0], proof);
+        verify_single_epoch::<Blake3>(&proof, root_hashes[2], root_hashes[3], 3).await?;
+
+        // and doesn't verify against the wrong root hashes
+        assert!(matches!(
+            verify_single_epoch::<Blake3>(&proof, root_hashes[2], root_hashes[2], 3).await,
+            Err(AkdError::AuditErr(AuditorError::EndHashMismatch(3, _, _)))
+        ));
+        assert!(
+            verify_single_epoch::<Blake3>(&proof, root_hashes[1], root_hashes[3], 3)
+                .await
+                .is_err()
+        );
+
+        // There's no transition into the first epoch or past the latest one
+        for epoch in [0, 4] {
+            assert_eq!(
+                Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                    NodeLabel::root(),
+                    epoch
+                ))),
+                azks.get_single_epoch_proof::<_, Blake3>(&db, epoch).await
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_append_only_range_proof() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        let mut root_hashes = vec![azks.get_root_hash::<_, Blake3>(&db).await?];
+        for _ in 0..3 {
+            let mut insertion_set: Vec<Node<Blake3>> = vec![];
+            for _ in 0..10 {
+                let label = NodeLabel::random(&mut rng);
+                let mut input = [0u8; 32];
+                rng.fill_bytes(&mut input);
+                let hash = Blake3Digest::new(input);
+                insertion_set.push(Node::<Blake3> { label, hash });
+            }
+            azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set)
+                .await?;
+            root_hashes.push(azks.get_root_hash::<_, Blake3>(&db).await?);
+        }
+
+        // The transition across several epochs verifies against just its end root hashes
+        for start_epoch in 0..3 {
+            let proof = azks
+                .get_append_only_range_proof::<_, Blake3>(&db, start_epoch, 3)
+                .await?;
+            verify_append_only_range::<Blake3>(
+                &proof,
+                root_hashes[start_epoch as usize],
+                root_hashes[3],
+                start_epoch,
+                3,
+            )
             .await?;
-        let search_label = NodeLabel::new(byte_arr_from_u64(0b1111 << 60), 64);
-        let proof = azks.get_non_membership_proof(&db, search_label).await?;
-        assert!(
-            verify_nonmembership::<Blake3>(azks.get_root_hash::<_, Blake3>(&db).await?, &proof)?,
-            "Nonmembership proof does not verify"
+        }
+
+        // It doesn't verify against the wrong root hashes
+        let proof = azks
+            .get_append_only_range_proof::<_, Blake3>(&db, 1, 3)
+            .await?;
+        assert!(matches!(
+            verify_append_only_range::<Blake3>(&proof, root_hashes[1], root_hashes[2], 1, 3).await,
+            Err(AkdError::AuditErr(AuditorError::EndHashMismatch(3, _, _)))
+        ));
+        assert!(matches!(
+            verify_append_only_range::<Blake3>(&proof, root_hashes[0], root_hashes[3], 1, 3).await,
+            Err(AkdError::AuditErr(AuditorError::StartHashMismatch(3, _, _)))
+        ));
+
+        // or if a leaf is bound to another epoch, whether within the range or outside of it
+        let mut wrong_epoch = proof.clone();
+        wrong_epoch.inserted_epochs[0] = if proof.inserted_epochs[0] == 2 { 3 } else { 2 };
+        assert!(matches!(
+            verify_append_only_range::<Blake3>(&wrong_epoch, root_hashes[1], root_hashes[3], 1, 3)
+                .await,
+            Err(AkdError::AuditErr(AuditorError::EndHashMismatch(3, _, _)))
+        ));
+        wrong_epoch.inserted_epochs[0] = 1;
+        assert_eq!(
+            Err(AkdError::AuditErr(AuditorError::LeafEpochMismatch(
+                wrong_epoch.inserted[0].label,
+                1,
+                1,
+                3
+            ))),
+            verify_append_only_range::<Blake3>(&wrong_epoch, root_hashes[1], root_hashes[3], 1, 3)
+                .await
+        );
+
+        // There's no range past the latest epoch
+        assert_eq!(
+            Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                NodeLabel::root(),
+                4
+            ))),
+            azks.get_append_only_range_proof::<_, Blake3>(&db, 1, 4)
+                .await
         );
         Ok(())
     }
 
-    // This test checks that a non-membership proof in a tree with 1 leaf verifies.
     #[tokio::test]
-    async fn test_nonmembership_proof_very_small() -> Result<(), AkdError> {
-        let num_nodes = 2;
-
+    async fn test_membership_proof_verbose() -> Result<(), AkdError> {
+        let mut rng = OsRng;
         let mut insertion_set: Vec<Node<Blake3>> = vec![];
-
-        for i in 0..num_nodes {
-            let mut label_arr = [0u8; 32];
-            label_arr[31] = u8::from(i);
-            let label = NodeLabel::new(label_arr, 256u32);
+        for _ in 0..20 {
+            let label = NodeLabel::random(&mut rng);
             let mut input = [0u8; 32];
-            input[31] = u8::from(i);
+            rng.fill_bytes(&mut input);
             let hash = Blake3Digest::new(input);
-            let node = Node::<Blake3> { label, hash };
-            insertion_set.push(node);
+            insertion_set.push(Node::<Blake3> { label, hash });
         }
         let db = AsyncInMemoryDatabase::new();
         let mut azks = Azks::new::<_, Blake3>(&db).await?;
-        let search_label = insertion_set[0].label;
-        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone()[1..2].to_vec())
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
             .await?;
-        let proof = azks.get_non_membership_proof(&db, search_label).await?;
-
-        verify_nonmembership::<Blake3>(azks.get_root_hash::<_, Blake3>(&db).await?, &proof)?;
-
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+        let proof = azks
+            .get_membership_proof::<_, Blake3>(&db, insertion_set[0].label, 1)
+            .await?;
+        assert!(verify_membership_verbose::<Blake3>(root_hash, &proof).is_ok());
+
+        // Against another root, every layer is folded and the last hash is the real root
+        let trace = verify_membership_verbose::<Blake3>(Blake3::hash(&[1u8]), &proof)
+            .expect_err("verified against the wrong root");
+        let layers = proof.layer_proofs.len();
+        assert_eq!(layers, trace.steps.len());
+        assert_eq!(root_hash, trace.steps[layers - 1].running_hash);
+
+        // A tampered sibling changes the hashes from its layer up, and none below it
+        let mut tampered = proof.clone();
+        tampered.layer_proofs[0].siblings[0].hash = Blake3::hash(&[2u8]);
+        let tampered_trace = verify_membership_verbose::<Blake3>(root_hash, &tampered)
+            .expect_err("verified with a tampered sibling");
+        assert_eq!(layers, tampered_trace.steps.len());
+        assert_eq!(
+            trace.steps[..layers - 1],
+            tampered_trace.steps[..layers - 1]
+        );
+        assert_ne!(
+            trace.steps[layers - 1].running_hash,
+            tampered_trace.steps[layers - 1].running_hash
+        );
+        assert_eq!(layers + 3, tampered_trace.to_string().lines().count());
         Ok(())
     }
 
-    // This test verifies if a non-membership proof in a small tree of 2 leaves
-    // verifies.
     #[tokio::test]
-    async fn test_nonmembership_proof_small() -> Result<(), AkdError> {
-        let num_nodes = 3;
+    async fn test_compact_membership_proof() -> Result<(), AkdError> {
         let mut rng = OsRng;
-
         let mut insertion_set: Vec<Node<Blake3>> = vec![];
-
-        for _ in 0..num_nodes {
+        for _ in 0..20 {
             let label = NodeLabel::random(&mut rng);
             let mut input = [0u8; 32];
             rng.fill_bytes(&mut input);
             let hash = Blake3Digest::new(input);
-            let node = Node::<Blake3> { label, hash };
-            insertion_set.push(node);
+            insertion_set.push(Node::<Blake3> { label, hash });
         }
         let db = AsyncInMemoryDatabase::new();
         let mut azks = Azks::new::<_, Blake3>(&db).await?;
-        let search_label = insertion_set[num_nodes - 1].label;
-        azks.batch_insert_leaves::<_, Blake3>(
-            &db,
-            insertion_set.clone()[0..num_nodes - 1].to_vec(),
-        )
-        .await?;
-        let proof = azks.get_non_membership_proof(&db, search_label).await?;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
+            .await?;
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
 
-        verify_nonmembership::<Blake3>(azks.get_root_hash::<_, Blake3>(&db).await?, &proof)?;
+        // Every layer's direction is implied by the label
+        for leaf in &insertion_set {
+            let proof = azks
+                .get_membership_proof::<_, Blake3>(&db, leaf.label, 1)
+                .await?;
+            let expanded = proof.compact().expand();
+            assert_eq!(proof, expanded);
+            verify_membership::<Blake3>(root_hash, &expanded)?;
+        }
 
+        // A proof moved to another label gets the other label's directions
+        let mut compact = azks
+            .get_membership_proof::<_, Blake3>(&db, insertion_set[0].label, 1)
+            .await?
+            .compact();
+        compact.label = insertion_set[1].label;
+        assert!(verify_membership::<Blake3>(root_hash, &compact.expand()).is_err());
+
+        // and a layer at least as long as the label has no direction to take
+        let mut compact = azks
+            .get_membership_proof::<_, Blake3>(&db, insertion_set[0].label, 1)
+            .await?
+            .compact();
+        compact.layer_proofs[0].prefix_len = 256;
+        let expanded = compact.expand();
+        assert_eq!(None, expanded.layer_proofs[0].direction);
+        assert!(verify_membership::<Blake3>(root_hash, &expanded).is_err());
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_nonmembership_proof() -> Result<(), AkdError> {
-        let num_nodes = 10;
-        let mut rng = OsRng;
+    async fn test_prefix_absence_proof() -> Result<(), AkdError> {
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        let prefix = |first_byte: u8, len: u32| {
+            let mut val = [0u8; 32];
+            val[0] = first_byte;
+            NodeLabel::new(val, len)
+        };
 
-        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        // Nothing is under any prefix of the empty tree, the empty one included
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+        let proof = azks
+            .get_prefix_absence_proof::<_, Blake3>(&db, NodeLabel::root(), 0)
+            .await?;
+        verify_prefix_absence::<Blake3>(root_hash, &proof)?;
 
-        for _ in 0..num_nodes {
-            let label = NodeLabel::random(&mut rng);
+        let mut rng = OsRng;
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for first_byte in [0x00u8, 0x40, 0x50, 0xc0] {
+            let mut val = [0u8; 32];
+            rng.fill_bytes(&mut val);
+            val[0] = first_byte;
             let mut input = [0u8; 32];
             rng.fill_bytes(&mut input);
             let hash = Blake3Digest::new(input);
-            let node = Node::<Blake3> { label, hash };
-            insertion_set.push(node);
+            insertion_set.push(Node::<Blake3> {
+                label: NodeLabel::new(val, 256),
+                hash,
+            });
         }
-        let db = AsyncInMemoryDatabase::new();
-        let mut azks = Azks::new::<_, Blake3>(&db).await?;
-        let search_label = insertion_set[num_nodes - 1].label;
-        azks.batch_insert_leaves::<_, Blake3>(
-            &db,
-            insertion_set.clone()[0..num_nodes - 1].to_vec(),
-        )
-        .await?;
-        let proof = azks.get_non_membership_proof(&db, search_label).await?;
+        let leaf_label = insertion_set[0].label;
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set)
+            .await?;
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+
+        // 0b10 branches away at the root, while 0b011 shares 0b01 with two of the leaves
+        for (absent, longest_prefix) in [
+            (prefix(0x80, 2), NodeLabel::root()),
+            (prefix(0x60, 3), prefix(0x00, 1)),
+        ] {
+            let proof = azks
+                .get_prefix_absence_proof::<_, Blake3>(&db, absent, 1)
+                .await?;
+            assert_eq!(longest_prefix, proof.longest_prefix);
+            verify_prefix_absence::<Blake3>(root_hash, &proof)?;
 
-        verify_nonmembership::<Blake3>(azks.get_root_hash::<_, Blake3>(&db).await?, &proof)?;
+            // The proof doesn't carry over to a prefix of the leaves
+            let mut tampered = proof.clone();
+            tampered.prefix = prefix(0x40, 3);
+            assert!(verify_prefix_absence::<Blake3>(root_hash, &tampered).is_err());
+        }
+
+        // Prefixes with leaves under them have no such proof, nor do the labels of the leaves
+        // and interior nodes themselves
+        for present in [
+            NodeLabel::root(),
+            prefix(0x40, 2),
+            prefix(0x50, 4),
+            prefix(0x40, 3),
+            leaf_label,
+        ] {
+            assert_eq!(
+                Err(AkdError::TreeNode(TreeNodeError::PrefixNotEmpty(present))),
+                azks.get_prefix_absence_proof::<_, Blake3>(&db, present, 1)
+                    .await
+            );
+        }
 
+        // and there's no proof for an epoch which hasn't been published yet
+        let absent = prefix(0x80, 2);
+        assert_eq!(
+            Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                absent, 2
+            ))),
+            azks.get_prefix_absence_proof::<_, Blake3>(&db, absent, 2)
+                .await
+        );
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_append_only_proof_very_tiny() -> Result<(), AkdError> {
+    async fn test_nonmembership_proof_compact() -> Result<(), AkdError> {
         let db = AsyncInMemoryDatabase::new();
         let mut azks = Azks::new::<_, Blake3>(&db).await?;
 
-        let mut insertion_set_1: Vec<Node<Blake3>> = vec![];
-        insertion_set_1.push(Node {
-            label: NodeLabel::new(byte_arr_from_u64(0b0), 64),
-            hash: Blake3::hash(&EMPTY_VALUE),
-        });
-        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set_1)
+        // Nothing is a member of the empty tree
+        let mut rng = OsRng;
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+        let label = NodeLabel::random(&mut rng);
+        let proof = azks
+            .get_non_membership_proof_compact::<_, Blake3>(&db, label, 0)
             .await?;
-        let start_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
-
-        let mut insertion_set_2: Vec<Node<Blake3>> = vec![];
-        insertion_set_2.push(Node {
-            label: NodeLabel::new(byte_arr_from_u64(0b01 << 62), 64),
-            hash: Blake3::hash(&EMPTY_VALUE),
-        });
+        assert!(proof.predecessor.is_none() && proof.successor.is_none());
+        verify_nonmembership_compact::<Blake3>(root_hash, &proof)?;
 
-        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set_2)
+        let mut insertion_set: Vec<Node<Blake3>> = vec![];
+        for _ in 0..30 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            insertion_set.push(Node::<Blake3> { label, hash });
+        }
+        azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone())
             .await?;
-        let end_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+        let mut sorted = insertion_set
+            .iter()
+            .map(|node| node.label)
+            .collect::<Vec<_>>();
+        sorted.sort();
+
+        // The neighbors are the adjacent leaves in label order, or the first or last leaf
+        let labels = [
+            NodeLabel::new([0u8; 32], 256),
+            NodeLabel::new([0xffu8; 32], 256),
+            NodeLabel::random(&mut rng),
+            NodeLabel::random(&mut rng),
+        ];
+        for label in labels {
+            let proof = azks
+                .get_non_membership_proof_compact::<_, Blake3>(&db, label, 1)
+                .await?;
+            let position = sorted.partition_point(|leaf| *leaf < label);
+            assert_eq!(
+                position.checked_sub(1).map(|i| sorted[i]),
+                proof.predecessor.as_ref().map(|proof| proof.label)
+            );
+            assert_eq!(
+                sorted.get(position).copied(),
+                proof.successor.as_ref().map(|proof| proof.label)
+            );
+            verify_nonmembership_compact::<Blake3>(root_hash, &proof)?;
+        }
 
-        let proof = azks.get_append_only_proof(&db, 1, 2).await?;
-        audit_verify::<Blake3>(vec![start_hash, end_hash], proof).await?;
+        // A member of the tree has no such proof
+        assert_eq!(
+            Err(AkdError::TreeNode(TreeNodeError::LabelAlreadyExists(
+                sorted[3]
+            ))),
+            azks.get_non_membership_proof_compact::<_, Blake3>(&db, sorted[3], 1)
+                .await
+        );
+
+        // nor is there one for an epoch which hasn't been published yet
+        let label = NodeLabel::random(&mut rng);
+        assert_eq!(
+            Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+                label, 2
+            ))),
+            azks.get_non_membership_proof_compact::<_, Blake3>(&db, label, 2)
+                .await
+        );
+
+        // Neighbors which aren't adjacent don't verify, even though both are members
+        let proof = CompactNonMembershipProof {
+            label: sorted[10].get_sibling_prefix(256),
+            predecessor: Some(
+                azks.get_membership_proof::<_, Blake3>(&db, sorted[0], 1)
+                    .await?,
+            ),
+            successor: Some(
+                azks.get_membership_proof::<_, Blake3>(&db, sorted[29], 1)
+                    .await?,
+            ),
+        };
+        assert!(verify_nonmembership_compact::<Blake3>(root_hash, &proof).is_err());
 
+        // and neither do neighbors on the wrong side of the label
+        let label = NodeLabel::random(&mut rng);
+        let mut proof = azks
+            .get_non_membership_proof_compact::<_, Blake3>(&db, label, 1)
+            .await?;
+        std::mem::swap(&mut proof.predecessor, &mut proof.successor);
+        assert!(verify_nonmembership_compact::<Blake3>(root_hash, &proof).is_err());
         Ok(())
     }
 
@@ -1032,6 +4607,190 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_prune_before_epoch() -> Result<(), AkdError> {
+        use crate::storage::StorageUtil;
+
+        let num_nodes = 10;
+        let mut rng = OsRng;
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+
+        let mut all_nodes: Vec<Node<Blake3>> = vec![];
+        for _ in 0..3 {
+            let mut insertion_set: Vec<Node<Blake3>> = vec![];
+            for _ in 0..num_nodes {
+                let label = NodeLabel::random(&mut rng);
+                let mut input = [0u8; 32];
+                rng.fill_bytes(&mut input);
+                let hash = Blake3Digest::new(input);
+                insertion_set.push(Node::<Blake3> { label, hash });
+            }
+            all_nodes.extend(insertion_set.iter().cloned());
+            azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set)
+                .await?;
+        }
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+
+        // Record how every node reads at the cutoff epoch prior to pruning
+        let cutoff_epoch = 2;
+        let mut states_at_cutoff = vec![];
+        for record in db
+            .batch_get_type_direct::<TreeNodeWithPreviousValue>()
+            .await?
+        {
+            if let DbRecord::TreeNode(node) = record {
+                let key = NodeKey(node.label);
+                let state = TreeNode::get_from_storage(&db, &key, cutoff_epoch).await;
+                states_at_cutoff.push((key, state));
+            }
+        }
+
+        azks.prune_before_epoch(&db, cutoff_epoch).await?;
+
+        // Nothing observable at the cutoff epoch should have changed
+        for (key, state) in states_at_cutoff {
+            assert_eq!(
+                state,
+                TreeNode::get_from_storage(&db, &key, cutoff_epoch).await
+            );
+        }
+        // The root was updated in epoch 3, so its state at epoch 2 must be retained
+        let root_key = NodeKey(NodeLabel::root());
+        match db.get::<TreeNodeWithPreviousValue>(&root_key).await? {
+            DbRecord::TreeNode(node) => assert!(node.previous_node.is_some()),
+            _ => panic!("Root not found in storage."),
+        }
+
+        // Pruning at the latest epoch drops every previous state, including the root's
+        let num_pruned = azks.prune_before_epoch(&db, 3).await?;
+        assert!(
+            num_pruned > 0,
+            "Expected some historical states to be pruned"
+        );
+        match db.get::<TreeNodeWithPreviousValue>(&root_key).await? {
+            DbRecord::TreeNode(node) => assert!(node.previous_node.is_none()),
+            _ => panic!("Root not found in storage."),
+        }
+        assert_eq!(root_hash, azks.get_root_hash::<_, Blake3>(&db).await?);
+        let proof = azks
+            .get_membership_proof(&db, all_nodes[0].label, azks.get_latest_epoch())
+            .await?;
+        verify_membership::<Blake3>(root_hash, &proof)?;
+
+        // Pruning is idempotent
+        assert_eq!(0, azks.prune_before_epoch(&db, 3).await?);
+
+        // Pruning beyond the latest epoch is not allowed
+        let out = azks.prune_before_epoch(&db, 4).await;
+        let expected = Err::<u64, AkdError>(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+            NodeLabel::root(),
+            4,
+        )));
+        assert_eq!(expected, out);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_epoch_at_or_before() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+
+        // Nothing has been published yet
+        assert_eq!(None, azks.epoch_at_or_before(&db, u64::MAX).await?);
+
+        for epoch in 1..=5 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            azks.batch_insert_leaves::<_, Blake3>(&db, vec![Node::<Blake3> { label, hash }])
+                .await?;
+            db.set(DbRecord::EpochTimestamp(DbRecord::build_epoch_timestamp(
+                epoch,
+                epoch * 100,
+            )))
+            .await?;
+        }
+
+        // Before the first epoch was published
+        assert_eq!(None, azks.epoch_at_or_before(&db, 0).await?);
+        assert_eq!(None, azks.epoch_at_or_before(&db, 99).await?);
+        // Exactly at and in between epoch finalizations
+        assert_eq!(Some(1), azks.epoch_at_or_before(&db, 100).await?);
+        assert_eq!(Some(2), azks.epoch_at_or_before(&db, 299).await?);
+        assert_eq!(Some(3), azks.epoch_at_or_before(&db, 300).await?);
+        // After the latest epoch
+        assert_eq!(Some(5), azks.epoch_at_or_before(&db, 500).await?);
+        assert_eq!(Some(5), azks.epoch_at_or_before(&db, u64::MAX).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_epoch_accessors() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+
+        assert_eq!(0, azks.latest_epoch());
+        assert_eq!(1, azks.num_epochs());
+        assert!(azks.epoch_exists(0));
+        assert!(!azks.epoch_exists(1));
+
+        for _ in 0..3 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            azks.batch_insert_leaves::<_, Blake3>(&db, vec![Node::<Blake3> { label, hash }])
+                .await?;
+        }
+
+        assert_eq!(3, azks.latest_epoch());
+        assert_eq!(azks.get_latest_epoch(), azks.latest_epoch());
+        assert_eq!(4, azks.num_epochs());
+        assert!(azks.epoch_exists(3));
+        assert!(!azks.epoch_exists(4));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_root_hash_at_epoch() -> Result<(), AkdError> {
+        let mut rng = OsRng;
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        let mut root_hashes = vec![];
+        for _ in 0..3 {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3Digest::new(input);
+            azks.batch_insert_leaves::<_, Blake3>(&db, vec![Node::<Blake3> { label, hash }])
+                .await?;
+            root_hashes.push(azks.get_root_hash::<_, Blake3>(&db).await?);
+        }
+
+        // The latest and the previous root hashes are still stored
+        assert_eq!(
+            root_hashes[2],
+            azks.get_root_hash_at_epoch::<_, Blake3>(&db, 3).await?
+        );
+        assert_eq!(
+            root_hashes[1],
+            azks.get_root_hash_at_epoch::<_, Blake3>(&db, 2).await?
+        );
+        // but an older one has been overwritten
+        assert!(matches!(
+            azks.get_root_hash_at_epoch::<_, Blake3>(&db, 1).await,
+            Err(AkdError::Storage(StorageError::NotFound(_)))
+        ));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn future_epoch_throws_error() -> Result<(), AkdError> {
         let db = AsyncInMemoryDatabase::new();
@@ -1046,4 +4805,83 @@ mod tests {
         assert_eq!(expected, out);
         Ok(())
     }
+
+    // Inserts each batch at its own epoch, then checks that every inserted label has a verifying
+    // membership proof, that every other label has a verifying non-membership proof, and that
+    // flipping any single byte of a serialized membership proof stops it from verifying
+    async fn check_proofs_round_trip(
+        batches: Vec<BTreeMap<[u8; 32], [u8; 32]>>,
+        absent: Vec<[u8; 32]>,
+    ) -> Result<(), AkdError> {
+        let db = AsyncInMemoryDatabase::new();
+        let mut azks = Azks::new::<_, Blake3>(&db).await?;
+        let mut inserted: Vec<NodeLabel> = vec![];
+        for batch in batches {
+            let mut insertion_set: Vec<Node<Blake3>> = vec![];
+            for (label_val, value) in batch {
+                let label = NodeLabel::new(label_val, 256u32);
+                if !inserted.contains(&label) {
+                    inserted.push(label);
+                }
+                let hash = Blake3::hash(&value);
+                insertion_set.push(Node::<Blake3> { label, hash });
+            }
+            azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set)
+                .await?;
+        }
+        let root_hash = azks.get_root_hash::<_, Blake3>(&db).await?;
+        let epoch = azks.get_latest_epoch();
+
+        for label in inserted.iter() {
+            let proof = azks
+                .get_membership_proof::<_, Blake3>(&db, *label, epoch)
+                .await?;
+            verify_membership::<Blake3>(root_hash, &proof)?;
+        }
+
+        for label_val in absent {
+            let label = NodeLabel::new(label_val, 256u32);
+            if inserted.contains(&label) {
+                continue;
+            }
+            let proof = azks
+                .get_non_membership_proof::<_, Blake3>(&db, label)
+                .await?;
+            assert!(verify_nonmembership::<Blake3>(root_hash, &proof)?);
+        }
+
+        let proof = azks
+            .get_membership_proof::<_, Blake3>(&db, inserted[0], epoch)
+            .await?;
+        let bytes = bincode::serialize(&proof).unwrap();
+        for i in 0..bytes.len() {
+            let mut tampered = bytes.clone();
+            tampered[i] ^= 0xff;
+            // a flip which doesn't even deserialize is rejected too
+            if let Ok(tampered) = bincode::deserialize::<MembershipProof<Blake3>>(&tampered) {
+                assert!(
+                    verify_membership::<Blake3>(root_hash, &tampered).is_err(),
+                    "Membership proof verified with byte {} flipped",
+                    i
+                );
+            }
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn test_proofs_round_trip(
+            batches in prop::collection::vec(
+                prop::collection::btree_map(any::<[u8; 32]>(), any::<[u8; 32]>(), 1..16),
+                1..4,
+            ),
+            absent in prop::collection::vec(any::<[u8; 32]>(), 1..8),
+        ) {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(check_proofs_round_trip(batches, absent)).unwrap();
+        }
+    }
 }
@@ -7,24 +7,281 @@
 
 //! Code for an auditor of a authenticated key directory
 
+use std::collections::HashSet;
 use std::marker::{Send, Sync};
+use std::task::Poll;
 
-use winter_crypto::Hasher;
+use log::debug;
+
+use winter_crypto::{
+    hashers::{Blake3_256, Sha3_256},
+    Hasher,
+};
+use winter_math::fields::f128::BaseElement;
+
+use futures::TryStreamExt;
 
 use crate::{
-    errors::{AkdError, AuditorError, AzksError},
-    proof_structs::{AppendOnlyProof, SingleAppendOnlyProof},
-    storage::memory::AsyncInMemoryDatabase,
-    Azks,
+    append_only_zks::{InsertOptions, InsertPolicy, DEFAULT_AZKS_KEY},
+    errors::{AkdError, AuditorError, DirectoryError, StorageError},
+    proof_structs::{
+        AppendOnlyProof, AppendOnlyRangeProof, CompactAppendOnlyProof, SingleAppendOnlyProof,
+    },
+    serialization::{from_digest, to_digest, HexDigest},
+    storage::{memory::AsyncInMemoryDatabase, types::DbRecord, Storage},
+    tree_node::merge_epoch,
+    Azks, Node, NodeLabel,
 };
 
+pub use crate::serialization::AuditHasher;
+// Kept here, where it's produced and verified, for existing users of the path
+pub use crate::proof_structs::EpochInclusionProof;
+
+/// The version of the byte layout written by [`AuditBundle::to_bytes`]
+const AUDIT_BUNDLE_VERSION: u8 = 1;
+
+/// A self-describing audit proof: the append-only proof together with the root hashes
+/// of the epochs it spans and the hash function needed to check it. Its byte encoding
+/// can be handed to an auditor and checked with [`verify_bundle`].
+#[derive(Debug, PartialEq)]
+pub struct AuditBundle<H: Hasher> {
+    /// The hash function the proof was generated with
+    pub hasher: AuditHasher,
+    /// The root hashes at each of the epochs covered by the proof
+    pub hashes: Vec<H::Digest>,
+    /// The append-only proof between consecutive epochs
+    pub proof: AppendOnlyProof<H>,
+}
+
+impl<H: Hasher> AuditBundle<H> {
+    /// Encodes the bundle as bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![AUDIT_BUNDLE_VERSION, self.hasher as u8];
+        bytes.extend_from_slice(&(self.hashes.len() as u64).to_le_bytes());
+        for hash in &self.hashes {
+            bytes.extend_from_slice(&from_digest::<H>(*hash));
+        }
+        bytes.extend_from_slice(&(self.proof.epochs.len() as u64).to_le_bytes());
+        for epoch in &self.proof.epochs {
+            bytes.extend_from_slice(&epoch.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.proof.proofs.len() as u64).to_le_bytes());
+        for single_proof in &self.proof.proofs {
+            write_nodes::<H>(&mut bytes, &single_proof.inserted);
+            write_nodes::<H>(&mut bytes, &single_proof.unchanged_nodes);
+        }
+        bytes
+    }
+
+    /// Decodes a bundle previously encoded with [`AuditBundle::to_bytes`]. The hash function
+    /// recorded in the bundle must be `H`, otherwise [`AuditorError::AlgorithmMismatch`] is
+    /// returned rather than decoding the hashes under the wrong hash function.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AkdError>
+    where
+        H: 'static,
+    {
+        let mut reader = BundleReader { bytes, offset: 0 };
+        let version = reader.read_u8()?;
+        if version != AUDIT_BUNDLE_VERSION {
+            return Err(AkdError::AuditErr(AuditorError::MalformedProof(format!(
+                "Unsupported audit bundle version {}",
+                version
+            ))));
+        }
+        let hasher = AuditHasher::from_byte(reader.read_u8()?)?;
+        match AuditHasher::of::<H>() {
+            Some(expected) if expected == hasher => {}
+            Some(expected) => {
+                return Err(AkdError::AuditErr(AuditorError::AlgorithmMismatch {
+                    expected,
+                    got: hasher,
+                }))
+            }
+            None => {
+                return Err(AkdError::AuditErr(AuditorError::MalformedProof(
+                    "Audit bundles can only be decoded with Blake3 or Sha3 hashing".to_string(),
+                )))
+            }
+        }
+        let mut hashes = Vec::new();
+        for _ in 0..reader.read_u64()? {
+            hashes.push(to_digest::<H>(&reader.read_array()?)?);
+        }
+        let mut epochs = Vec::new();
+        for _ in 0..reader.read_u64()? {
+            epochs.push(reader.read_u64()?);
+        }
+        let mut proofs = Vec::new();
+        for _ in 0..reader.read_u64()? {
+            let inserted = read_nodes::<H>(&mut reader)?;
+            let unchanged_nodes = read_nodes::<H>(&mut reader)?;
+            proofs.push(SingleAppendOnlyProof {
+                inserted,
+                unchanged_nodes,
+            });
+        }
+        if reader.offset != bytes.len() {
+            return Err(AkdError::AuditErr(AuditorError::MalformedProof(format!(
+                "Audit bundle has {} trailing bytes",
+                bytes.len() - reader.offset
+            ))));
+        }
+        Ok(AuditBundle {
+            hasher,
+            hashes,
+            proof: AppendOnlyProof { proofs, epochs },
+        })
+    }
+}
+
+fn write_nodes<H: Hasher>(bytes: &mut Vec<u8>, nodes: &[Node<H>]) {
+    bytes.extend_from_slice(&(nodes.len() as u64).to_le_bytes());
+    for node in nodes {
+        bytes.extend_from_slice(&node.label.get_len().to_le_bytes());
+        bytes.extend_from_slice(&node.label.get_val());
+        bytes.extend_from_slice(&from_digest::<H>(node.hash));
+    }
+}
+
+fn read_nodes<H: Hasher>(reader: &mut BundleReader<'_>) -> Result<Vec<Node<H>>, AkdError> {
+    let mut nodes = Vec::new();
+    for _ in 0..reader.read_u64()? {
+        let label_len = reader.read_u32()?;
+        let label_val = reader.read_array()?;
+        let hash = to_digest::<H>(&reader.read_array()?)?;
+        nodes.push(Node {
+            label: NodeLabel::new(label_val, label_len),
+            hash,
+        });
+    }
+    Ok(nodes)
+}
+
+struct BundleReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> BundleReader<'a> {
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], AkdError> {
+        let end = self.offset + len;
+        if end > self.bytes.len() {
+            return Err(AkdError::AuditErr(AuditorError::MalformedProof(
+                "Audit bundle ended unexpectedly".to_string(),
+            )));
+        }
+        let slice = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(slice)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, AkdError> {
+        Ok(self.read_slice(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, AkdError> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.read_slice(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, AkdError> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.read_slice(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn read_array(&mut self) -> Result<[u8; 32], AkdError> {
+        let mut buf = [0u8; 32];
+        buf.copy_from_slice(self.read_slice(32)?);
+        Ok(buf)
+    }
+}
+
+/// Verifies an encoded [`AuditBundle`] using the hash function recorded in it
+pub async fn verify_bundle(bundle_bytes: &[u8]) -> Result<(), AkdError> {
+    match bundle_hasher(bundle_bytes)? {
+        AuditHasher::Blake3 => verify_bundle_with::<Blake3_256<BaseElement>>(bundle_bytes).await,
+        AuditHasher::Sha3 => verify_bundle_with::<Sha3_256<BaseElement>>(bundle_bytes).await,
+    }
+}
+
+/// Verifies an encoded [`AuditBundle`], first checking that it was generated with the
+/// `expected` hash function. Since a bundle names its own hash function, a verifier which
+/// only trusts one of them should use this rather than [`verify_bundle`], so that a proof
+/// made under another (possibly weaker) hash function is rejected with
+/// [`AuditorError::AlgorithmMismatch`] instead of being verified under it.
+pub async fn verify_bundle_with_algorithm(
+    bundle_bytes: &[u8],
+    expected: AuditHasher,
+) -> Result<(), AkdError> {
+    let got = bundle_hasher(bundle_bytes)?;
+    if got != expected {
+        return Err(AkdError::AuditErr(AuditorError::AlgorithmMismatch {
+            expected,
+            got,
+        }));
+    }
+    verify_bundle(bundle_bytes).await
+}
+
+fn bundle_hasher(bundle_bytes: &[u8]) -> Result<AuditHasher, AkdError> {
+    if bundle_bytes.len() < 2 {
+        return Err(AkdError::AuditErr(AuditorError::MalformedProof(
+            "Audit bundle ended unexpectedly".to_string(),
+        )));
+    }
+    AuditHasher::from_byte(bundle_bytes[1])
+}
+
+async fn verify_bundle_with<H: Hasher + Send + Sync + 'static>(
+    bundle_bytes: &[u8],
+) -> Result<(), AkdError> {
+    let bundle = AuditBundle::<H>::from_bytes(bundle_bytes)?;
+    audit_verify::<H>(bundle.hashes, bundle.proof).await
+}
+
+/// Verifies an audit proof in its compact representation, given start and end hashes
+/// for a merkle patricia tree. See [`AppendOnlyProof::compact`].
+pub async fn verify_compact<H: Hasher + Send + Sync>(
+    hashes: Vec<H::Digest>,
+    proof: CompactAppendOnlyProof<H>,
+) -> Result<(), AkdError> {
+    audit_verify::<H>(hashes, proof.expand()?).await
+}
+
 /// Verifies an audit proof, given start and end hashes for a merkle patricia tree.
 pub async fn audit_verify<H: Hasher + Send + Sync>(
     hashes: Vec<H::Digest>,
     proof: AppendOnlyProof<H>,
+) -> Result<(), AkdError> {
+    check_audit_proof_shape::<H>(&hashes, &proof)?;
+    // The epochs are verified one after another, so they can all build their trees in the
+    // same database rather than each allocating its own
+    let db = AsyncInMemoryDatabase::new();
+    for i in 0..hashes.len() - 1 {
+        let start_hash = hashes[i];
+        let end_hash = hashes[i + 1];
+        verify_consecutive_append_only_in::<H>(
+            &db,
+            &proof.proofs[i],
+            start_hash,
+            end_hash,
+            proof.epochs[i] + 1,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Checks that the proof has a segment for each pair of consecutive hashes, over consecutive
+/// epochs
+fn check_audit_proof_shape<H: Hasher>(
+    hashes: &[H::Digest],
+    proof: &AppendOnlyProof<H>,
 ) -> Result<(), AkdError> {
     if proof.epochs.len() + 1 != hashes.len() {
-        return Err(AkdError::AuditErr(AuditorError::VerifyAuditProof(format!(
+        return Err(AkdError::AuditErr(AuditorError::MalformedProof(format!(
             "The proof has a different number of epochs than needed for hashes. 
             The number of hashes you provide should be one more than the number of epochs! 
             Number of epochs = {}, number of hashes = {}",
@@ -33,22 +290,365 @@ pub async fn audit_verify<H: Hasher + Send + Sync>(
         ))));
     }
     if proof.epochs.len() != proof.proofs.len() {
-        return Err(AkdError::AuditErr(AuditorError::VerifyAuditProof(format!(
+        return Err(AkdError::AuditErr(AuditorError::MalformedProof(format!(
             "The proof has {} epochs and {} proofs. These should be equal!",
             proof.epochs.len(),
             proof.proofs.len()
         ))));
     }
-    for i in 0..hashes.len() - 1 {
-        let start_hash = hashes[i];
-        let end_hash = hashes[i + 1];
-        verify_consecutive_append_only::<H>(
-            &proof.proofs[i],
-            start_hash,
-            end_hash,
-            proof.epochs[i] + 1,
+    // Each segment ends at the epoch after its own, which is where the next one starts
+    for (i, epoch) in proof.epochs.iter().enumerate() {
+        let next_epoch = epoch.checked_add(1).ok_or_else(|| {
+            AkdError::AuditErr(AuditorError::MalformedProof(format!(
+                "The proof has a segment starting at epoch {}, which has no next epoch",
+                epoch
+            )))
+        })?;
+        if let Some(following) = proof.epochs.get(i + 1) {
+            if *following != next_epoch {
+                return Err(AkdError::AuditErr(AuditorError::EpochGap(
+                    *epoch, *following,
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// An audit which is verified a bounded number of epoch transitions at a time, for auditors
+/// which can't block until the whole of a long history has been verified, e.g. one driven by
+/// a UI which wants to show its progress. Each call to [`AuditSession::step`] verifies at most
+/// the given number of segments, and the session can be picked up again after a restart with
+/// [`AuditSession::resume`], given the number of segments which had already been verified.
+pub struct AuditSession<H: Hasher> {
+    hashes: Vec<H::Digest>,
+    proof: AppendOnlyProof<H>,
+    verified_segments: usize,
+    db: AsyncInMemoryDatabase,
+}
+
+impl<H: Hasher + Send + Sync> AuditSession<H> {
+    /// Starts an audit of the proof, given the root hashes of its epochs as for
+    /// [`audit_verify`]. Fails if the proof and the hashes don't line up.
+    pub fn new(hashes: Vec<H::Digest>, proof: AppendOnlyProof<H>) -> Result<Self, AkdError> {
+        Self::resume(hashes, proof, 0)
+    }
+
+    /// Picks up an audit of the proof after its first `verified_segments` segments were
+    /// verified, e.g. by an earlier session whose progress was persisted.
+    pub fn resume(
+        hashes: Vec<H::Digest>,
+        proof: AppendOnlyProof<H>,
+        verified_segments: usize,
+    ) -> Result<Self, AkdError> {
+        check_audit_proof_shape::<H>(&hashes, &proof)?;
+        if verified_segments > proof.proofs.len() {
+            return Err(AkdError::AuditErr(AuditorError::MalformedProof(format!(
+                "Can't resume after {} segments of a proof with {} segments",
+                verified_segments,
+                proof.proofs.len()
+            ))));
+        }
+        Ok(Self {
+            hashes,
+            proof,
+            verified_segments,
+            db: AsyncInMemoryDatabase::new(),
+        })
+    }
+
+    /// The number of segments (epoch transitions) verified so far, and the total number
+    pub fn progress(&self) -> (usize, usize) {
+        (self.verified_segments, self.proof.proofs.len())
+    }
+
+    /// Verifies up to `max_segments` more segments. Returns [`Poll::Pending`] while there are
+    /// segments left to verify, and [`Poll::Ready`] once all of them have verified or one has
+    /// failed to. A failed segment isn't counted as verified, so stepping again retries it.
+    pub async fn step(&mut self, max_segments: usize) -> Poll<Result<(), AkdError>> {
+        let end = self
+            .proof
+            .proofs
+            .len()
+            .min(self.verified_segments.saturating_add(max_segments));
+        while self.verified_segments < end {
+            let i = self.verified_segments;
+            if let Err(error) = verify_consecutive_append_only_in::<H>(
+                &self.db,
+                &self.proof.proofs[i],
+                self.hashes[i],
+                self.hashes[i + 1],
+                self.proof.epochs[i] + 1,
+            )
+            .await
+            {
+                return Poll::Ready(Err(error));
+            }
+            self.verified_segments += 1;
+        }
+        if self.verified_segments == self.proof.proofs.len() {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Hashes the root hashes recorded for the epochs from `start_epoch` to `end_epoch` inclusive
+/// into a single digest, so that two replicas can confirm that their whole histories over the
+/// range match with one comparison. The digest chains the hashes in order, so it commits to the
+/// start epoch, the number of epochs and each epoch's root hash: two ranges with the same
+/// fingerprint are identical unless a collision has been found in `H`.
+///
+/// The root hashes are read from storage with [`Azks::stream_root_hashes`], so an epoch in the
+/// range without a recorded root hash fails with [`crate::errors::AzksError::RootHashNotRecorded`].
+pub async fn fingerprint_range<S: Storage + Sync + Send, H: Hasher>(
+    storage: &S,
+    start_epoch: u64,
+    end_epoch: u64,
+) -> Result<H::Digest, AkdError> {
+    if start_epoch > end_epoch {
+        return Err(AkdError::Directory(DirectoryError::InvalidEpoch(format!(
+            "Start epoch {} is greater than the end epoch {}",
+            start_epoch, end_epoch
+        ))));
+    }
+    get_stored_azks(storage)
+        .await?
+        .stream_root_hashes::<_, H>(storage, start_epoch, end_epoch)
+        .try_fold(
+            merge_epoch::<H>(H::hash(&[]), start_epoch),
+            |fingerprint, (_, root_hash)| async move { Ok(H::merge(&[fingerprint, root_hash])) },
         )
-        .await?;
+        .await
+}
+
+async fn get_stored_azks<S: Storage + Sync + Send>(storage: &S) -> Result<Azks, AkdError> {
+    match storage.get::<Azks>(&DEFAULT_AZKS_KEY).await? {
+        DbRecord::Azks(azks) => Ok(azks),
+        _ => Err(AkdError::Storage(StorageError::NotFound(
+            "AZKS not found".to_string(),
+        ))),
+    }
+}
+
+/// A Merkle mountain range (MMR) over the root hashes of a run of consecutive epochs, kept by
+/// whoever attests to the directory's history. Its root commits to every root hash appended so
+/// far, so a client which holds only the (e.g. signed) MMR root can check that the root hash
+/// of any one epoch belongs to the attested history with an [`EpochInclusionProof`] of
+/// O(log epochs) hashes, see [`verify_epoch_inclusion`].
+///
+/// The MMR is built from the root hashes recorded in storage as each epoch is published, with
+/// [`EpochRootMmr::from_storage`], and caught up with the epochs published since with
+/// [`EpochRootMmr::extend_from_storage`].
+#[derive(Debug, PartialEq)]
+pub struct EpochRootMmr<H: Hasher> {
+    start_epoch: u64,
+    // The nodes at each height, leaves first. A height with an odd number of nodes has a peak
+    // as its last node.
+    levels: Vec<Vec<H::Digest>>,
+}
+
+impl<H: Hasher> Default for EpochRootMmr<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: Hasher> EpochRootMmr<H> {
+    /// Creates an empty MMR, which starts at the epoch of the first root hash appended to it
+    pub fn new() -> Self {
+        Self {
+            start_epoch: 0,
+            levels: vec![],
+        }
+    }
+
+    /// Builds the MMR over the root hashes recorded for the epochs from `start_epoch` to
+    /// `end_epoch` inclusive. As with [`fingerprint_range`], an epoch in the range without a
+    /// recorded root hash fails with [`crate::errors::AzksError::RootHashNotRecorded`].
+    pub async fn from_storage<S: Storage + Sync + Send>(
+        storage: &S,
+        start_epoch: u64,
+        end_epoch: u64,
+    ) -> Result<Self, AkdError> {
+        let mut mmr = Self::new();
+        mmr.append_recorded(storage, start_epoch, end_epoch).await?;
+        Ok(mmr)
+    }
+
+    /// Appends the root hashes recorded for the epochs after the last one appended, up to and
+    /// including `end_epoch`, starting at epoch 0 if none have been appended yet
+    pub async fn extend_from_storage<S: Storage + Sync + Send>(
+        &mut self,
+        storage: &S,
+        end_epoch: u64,
+    ) -> Result<(), AkdError> {
+        let next_epoch = self.start_epoch + self.len();
+        self.append_recorded(storage, next_epoch, end_epoch).await
+    }
+
+    async fn append_recorded<S: Storage + Sync + Send>(
+        &mut self,
+        storage: &S,
+        start_epoch: u64,
+        end_epoch: u64,
+    ) -> Result<(), AkdError> {
+        let azks = get_stored_azks(storage).await?;
+        let mut root_hashes =
+            Box::pin(azks.stream_root_hashes::<_, H>(storage, start_epoch, end_epoch));
+        while let Some((epoch, root_hash)) = root_hashes.try_next().await? {
+            self.append(epoch, root_hash)?;
+        }
+        Ok(())
+    }
+
+    /// The number of epochs appended
+    pub fn len(&self) -> u64 {
+        self.levels.first().map_or(0, |leaves| leaves.len() as u64)
+    }
+
+    /// Whether no epochs have been appended
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends the root hash of `epoch`, which must follow the last epoch appended
+    pub fn append(&mut self, epoch: u64, root_hash: H::Digest) -> Result<(), AkdError> {
+        if self.is_empty() {
+            self.start_epoch = epoch;
+            self.levels.push(vec![]);
+        } else if epoch != self.start_epoch + self.len() {
+            return Err(AkdError::AuditErr(AuditorError::EpochGap(
+                self.start_epoch + self.len() - 1,
+                epoch,
+            )));
+        }
+        self.levels[0].push(merge_epoch::<H>(root_hash, epoch));
+
+        // Merge the two last nodes of each height which now has a pair of them, which is one
+        // height for each trailing zero bit of the new number of epochs
+        for height in 0..self.len().trailing_zeros() as usize {
+            let nodes = &self.levels[height];
+            let parent = H::merge(&[nodes[nodes.len() - 2], nodes[nodes.len() - 1]]);
+            if self.levels.len() == height + 1 {
+                self.levels.push(vec![]);
+            }
+            self.levels[height + 1].push(parent);
+        }
+        Ok(())
+    }
+
+    /// The peaks of the MMR, from the tallest (covering the earliest epochs) to the shortest
+    pub fn peaks(&self) -> Vec<H::Digest> {
+        self.levels
+            .iter()
+            .rev()
+            .filter(|nodes| nodes.len() % 2 == 1)
+            .map(|nodes| nodes[nodes.len() - 1])
+            .collect()
+    }
+
+    /// The root of the MMR, which commits to its start epoch, its number of epochs and each of
+    /// their root hashes
+    pub fn root(&self) -> H::Digest {
+        bag_peaks::<H>(self.start_epoch, self.len(), &self.peaks())
+    }
+
+    /// Gets a proof that the root hash appended for `epoch` is included in the MMR
+    pub fn get_epoch_inclusion_proof(
+        &self,
+        epoch: u64,
+    ) -> Result<EpochInclusionProof<H>, AkdError> {
+        if epoch < self.start_epoch || epoch - self.start_epoch >= self.len() {
+            return Err(AkdError::AuditErr(AuditorError::EpochNotInMmr(epoch)));
+        }
+        let index = (epoch - self.start_epoch) as usize;
+        let mut siblings = vec![];
+        for (height, nodes) in self.levels.iter().enumerate() {
+            match nodes.get((index >> height) ^ 1) {
+                Some(sibling) => siblings.push(*sibling),
+                // The node is the last one of an odd height, so it's a peak
+                None => break,
+            }
+        }
+        Ok(EpochInclusionProof {
+            start_epoch: self.start_epoch,
+            num_epochs: self.len(),
+            epoch,
+            siblings,
+            peaks: self.peaks(),
+        })
+    }
+}
+
+/// Bags the peaks of an MMR into its root, starting from a commitment to the range of epochs
+/// it covers: `H(le_bytes_u64(start_epoch) || le_bytes_u64(num_epochs))`. This is part of the
+/// MMR format, kept apart from the binding of an epoch into a root hash by [merge_epoch]
+/// since the number of epochs isn't an epoch.
+fn bag_peaks<H: Hasher>(start_epoch: u64, num_epochs: u64, peaks: &[H::Digest]) -> H::Digest {
+    let size = H::hash(&[start_epoch.to_le_bytes(), num_epochs.to_le_bytes()].concat());
+    peaks
+        .iter()
+        .fold(size, |bagged, peak| H::merge(&[bagged, *peak]))
+}
+
+/// Verifies that `root_hash` is the root hash of the proof's epoch in the MMR with root
+/// `mmr_root`, as returned by [`EpochRootMmr::root`]
+pub fn verify_epoch_inclusion<H: Hasher>(
+    mmr_root: H::Digest,
+    root_hash: H::Digest,
+    proof: &EpochInclusionProof<H>,
+) -> Result<(), AkdError> {
+    let fail = |reason: &str| {
+        Err(AkdError::AuditErr(AuditorError::VerifyEpochInclusion(
+            reason.to_string(),
+        )))
+    };
+    if proof.epoch < proof.start_epoch || proof.epoch - proof.start_epoch >= proof.num_epochs {
+        return fail("the epoch is outside of the range of the MMR");
+    }
+    if proof.peaks.len() != proof.num_epochs.count_ones() as usize {
+        return fail("the number of peaks doesn't match the number of epochs");
+    }
+
+    // The peaks cover runs of epochs of decreasing powers of two, one for each bit set in the
+    // number of epochs, so find the run holding the epoch
+    let index = proof.epoch - proof.start_epoch;
+    let mut offset = 0;
+    let mut peak = None;
+    for (i, height) in (0..u64::BITS)
+        .rev()
+        .filter(|height| proof.num_epochs >> height & 1 == 1)
+        .enumerate()
+    {
+        if index < offset + (1 << height) {
+            peak = Some((i, height as usize));
+            break;
+        }
+        offset += 1 << height;
+    }
+    let (peak_index, height) = match peak {
+        Some(peak) => peak,
+        None => return fail("the epoch is outside of the range of the MMR"),
+    };
+    if proof.siblings.len() != height {
+        return fail("the number of siblings doesn't match the height of the epoch's peak");
+    }
+
+    let mut hash = merge_epoch::<H>(root_hash, proof.epoch);
+    for (level, sibling) in proof.siblings.iter().enumerate() {
+        hash = if index >> level & 1 == 0 {
+            H::merge(&[hash, *sibling])
+        } else {
+            H::merge(&[*sibling, hash])
+        };
+    }
+    if hash != proof.peaks[peak_index] {
+        return fail("the path from the epoch's root hash doesn't lead to its peak");
+    }
+    if bag_peaks::<H>(proof.start_epoch, proof.num_epochs, &proof.peaks) != mmr_root {
+        return fail("the peaks don't match the MMR root");
     }
     Ok(())
 }
@@ -60,31 +660,187 @@ pub async fn verify_consecutive_append_only<H: Hasher + Send + Sync>(
     end_hash: H::Digest,
     epoch: u64,
 ) -> Result<(), AkdError> {
-    // FIXME: Need to get rid of the clone here. Will need modifications to the functions called here.
-    let unchanged_nodes = proof.unchanged_nodes.clone();
-    let inserted = proof.inserted.clone();
+    let db = AsyncInMemoryDatabase::new();
+    verify_consecutive_append_only_in::<H>(&db, proof, start_hash, end_hash, epoch).await
+}
 
+/// Verifies the proof of a single epoch transition, from the root hash `start_hash` at
+/// `epoch - 1` to `end_hash` at `epoch`, as returned by [`Azks::get_single_epoch_proof`]
+pub async fn verify_single_epoch<H: Hasher + Send + Sync>(
+    proof: &SingleAppendOnlyProof<H>,
+    start_hash: H::Digest,
+    end_hash: H::Digest,
+    epoch: u64,
+) -> Result<(), AkdError> {
+    verify_consecutive_append_only::<H>(proof, start_hash, end_hash, epoch).await
+}
+
+async fn verify_consecutive_append_only_in<H: Hasher + Send + Sync>(
+    db: &AsyncInMemoryDatabase,
+    proof: &SingleAppendOnlyProof<H>,
+    start_hash: H::Digest,
+    end_hash: H::Digest,
+    epoch: u64,
+) -> Result<(), AkdError> {
+    let computed_end_root_hash =
+        verify_append_only_segment_in::<H>(db, proof, start_hash, epoch).await?;
+    if computed_end_root_hash != end_hash {
+        return Err(AkdError::AuditErr(AuditorError::EndHashMismatch(
+            epoch,
+            HexDigest::<H>(end_hash).to_string(),
+            HexDigest::<H>(computed_end_root_hash).to_string(),
+        )));
+    }
+    Ok(())
+}
+
+/// Verifies the append-only proof for a single epoch against the given start hash, and returns
+/// the end hash the proof computes. This lets a streaming auditor chain segments, passing each
+/// verified end hash in as the next segment's start hash, without holding more than one
+/// segment's tree in memory at a time. The start hash is authoritative: a proof whose
+/// unchanged nodes compute a different start hash is rejected.
+pub async fn verify_append_only_segment<H: Hasher + Send + Sync>(
+    proof: &SingleAppendOnlyProof<H>,
+    start_hash: H::Digest,
+    epoch: u64,
+) -> Result<H::Digest, AkdError> {
     let db = AsyncInMemoryDatabase::new();
-    let mut azks = Azks::new::<_, H>(&db).await?;
-    azks.batch_insert_leaves_helper::<_, H>(&db, unchanged_nodes, true)
-        .await?;
-    let computed_start_root_hash: H::Digest = azks.get_root_hash::<_, H>(&db).await?;
-    let mut verified = computed_start_root_hash == start_hash;
-    azks.latest_epoch = epoch - 1;
-    let updated_inserted = inserted
+    verify_append_only_segment_in::<H>(&db, proof, start_hash, epoch).await
+}
+
+/// Does the same as [`verify_append_only_segment`], but builds the segment's tree in the given
+/// database, which is cleared first. An auditor verifying segments one after another can pass
+/// the same database for each of them, so that its storage is allocated once and reused rather
+/// than allocated and freed for every segment. Segments verified concurrently each need their
+/// own database. The saving is modest: in the `azks` bench, an audit of 50 epochs of 20 leaves
+/// each takes about 5% less time than with [`verify_append_only_segment`].
+pub async fn verify_append_only_segment_in<H: Hasher + Send + Sync>(
+    db: &AsyncInMemoryDatabase,
+    proof: &SingleAppendOnlyProof<H>,
+    start_hash: H::Digest,
+    epoch: u64,
+) -> Result<H::Digest, AkdError> {
+    // FIXME: Need to get rid of the clone here. Will need modifications to the functions called here.
+    let unchanged_nodes = proof.unchanged_nodes.clone();
+    let inserted = proof
+        .inserted
         .iter()
         .map(|x| {
             let mut y = *x;
-            y.hash = H::merge_with_int(x.hash, epoch);
+            y.hash = merge_epoch::<H>(x.hash, epoch);
             y
         })
         .collect();
-    azks.batch_insert_leaves_helper::<_, H>(&db, updated_inserted, true)
-        .await?;
-    let computed_end_root_hash: H::Digest = azks.get_root_hash::<_, H>(&db).await?;
-    verified = verified && (computed_end_root_hash == end_hash);
-    if !verified {
-        return Err(AkdError::AzksErr(AzksError::VerifyAppendOnlyProof));
+    verify_insertions_in::<H>(db, unchanged_nodes, inserted, start_hash, epoch).await
+}
+
+/// Verifies an append-only proof for the transition from `start_epoch` to `end_epoch` as a
+/// whole, as returned by [`Azks::get_append_only_range_proof`], given only the root hashes
+/// at those two epochs. Each inserted leaf is bound to the epoch it was last written in,
+/// which must be after `start_epoch` and no later than `end_epoch`.
+pub async fn verify_append_only_range<H: Hasher + Send + Sync>(
+    proof: &AppendOnlyRangeProof<H>,
+    start_hash: H::Digest,
+    end_hash: H::Digest,
+    start_epoch: u64,
+    end_epoch: u64,
+) -> Result<(), AkdError> {
+    if proof.inserted.len() != proof.inserted_epochs.len() {
+        return Err(AkdError::AuditErr(AuditorError::MalformedProof(format!(
+            "The proof has {} inserted leaves and {} epochs for them. These should be equal!",
+            proof.inserted.len(),
+            proof.inserted_epochs.len()
+        ))));
+    }
+    let mut inserted = Vec::with_capacity(proof.inserted.len());
+    for (node, &epoch) in proof.inserted.iter().zip(proof.inserted_epochs.iter()) {
+        if epoch <= start_epoch || epoch > end_epoch {
+            return Err(AkdError::AuditErr(AuditorError::LeafEpochMismatch(
+                node.label,
+                epoch,
+                start_epoch,
+                end_epoch,
+            )));
+        }
+        inserted.push(Node::<H> {
+            label: node.label,
+            hash: merge_epoch::<H>(node.hash, epoch),
+        });
+    }
+
+    let db = AsyncInMemoryDatabase::new();
+    let computed_end_root_hash = verify_insertions_in::<H>(
+        &db,
+        proof.unchanged_nodes.clone(),
+        inserted,
+        start_hash,
+        end_epoch,
+    )
+    .await?;
+    if computed_end_root_hash != end_hash {
+        return Err(AkdError::AuditErr(AuditorError::EndHashMismatch(
+            end_epoch,
+            HexDigest::<H>(end_hash).to_string(),
+            HexDigest::<H>(computed_end_root_hash).to_string(),
+        )));
     }
     Ok(())
 }
+
+/// Builds the tree of the unchanged nodes in the given database, which is cleared first, and
+/// checks it against the start hash. Then inserts the leaves, whose hashes must already have
+/// their epochs bound in, and returns the root hash of the resulting tree. Errors are reported
+/// against `epoch`, the epoch at the end of the insertions.
+async fn verify_insertions_in<H: Hasher + Send + Sync>(
+    db: &AsyncInMemoryDatabase,
+    unchanged_nodes: Vec<Node<H>>,
+    inserted: Vec<Node<H>>,
+    start_hash: H::Digest,
+    epoch: u64,
+) -> Result<H::Digest, AkdError> {
+    // Insertion order doesn't affect the root hash, but inserting a label twice would let the
+    // prover change which value ends up at that leaf, so reject duplicates outright. For the
+    // same reason an inserted label must not already be in the tree, which the insertions
+    // below reject.
+    let mut inserted_labels = HashSet::new();
+    for node in inserted.iter() {
+        if !inserted_labels.insert(node.label) {
+            return Err(AkdError::AuditErr(AuditorError::DuplicateInsertedLeaf(
+                node.label,
+            )));
+        }
+    }
+
+    let options = InsertOptions {
+        append_only_exclude_usage: true,
+        policy: InsertPolicy::RejectExisting,
+        ..InsertOptions::default()
+    };
+    db.clear().await;
+    let mut azks = Azks::new::<_, H>(db).await?;
+    azks.batch_insert_leaves_helper::<_, H>(db, unchanged_nodes, options)
+        .await?;
+    let computed_start_root_hash: H::Digest = azks.get_root_hash::<_, H>(db).await?;
+    debug!(
+        "Epoch {}: computed start root hash {}",
+        epoch,
+        HexDigest::<H>(computed_start_root_hash)
+    );
+    if computed_start_root_hash != start_hash {
+        return Err(AkdError::AuditErr(AuditorError::StartHashMismatch(
+            epoch,
+            HexDigest::<H>(start_hash).to_string(),
+            HexDigest::<H>(computed_start_root_hash).to_string(),
+        )));
+    }
+    azks.latest_epoch = epoch.saturating_sub(1);
+    azks.batch_insert_leaves_helper::<_, H>(db, inserted, options)
+        .await?;
+    let computed_end_root_hash: H::Digest = azks.get_root_hash::<_, H>(db).await?;
+    debug!(
+        "Epoch {}: computed end root hash {}",
+        epoch,
+        HexDigest::<H>(computed_end_root_hash)
+    );
+    Ok(computed_end_root_hash)
+}
@@ -7,6 +7,7 @@
 
 //! Code for a client of a auditable key directory
 
+use std::fmt;
 use winter_crypto::Hasher;
 
 use crate::{
@@ -15,16 +16,39 @@ use crate::{
     errors::TreeNodeError,
     errors::{AkdError, AzksError, DirectoryError},
     node_label::{hash_label, NodeLabel},
-    proof_structs::{HistoryProof, LookupProof, MembershipProof, NonMembershipProof, UpdateProof},
+    proof_structs::{
+        BatchMembershipProof, CompactNonMembershipProof, HistoryProof, LookupProof,
+        MembershipProof, NonMembershipProof, PrefixAbsenceProof, UpdateProof,
+    },
+    serialization::HexDigest,
     storage::types::AkdLabel,
-    Direction, ARITY, EMPTY_LABEL,
+    tree_node::merge_epoch,
+    Direction, Node, ARITY, EMPTY_LABEL, LEAF_LEN,
 };
 
-/// Verifies membership, with respect to the root_hash
+/// Verifies membership, with respect to the root_hash. A proof with more layers than
+/// [LEAF_LEN], the deepest a leaf of the tree can be, is rejected before any of it is hashed.
 pub fn verify_membership<H: Hasher>(
     root_hash: H::Digest,
     proof: &MembershipProof<H>,
 ) -> Result<(), AkdError> {
+    verify_membership_with_max_depth::<H>(root_hash, proof, LEAF_LEN as usize)
+}
+
+/// Verifies membership like [verify_membership], but rejects a proof with more than
+/// `max_depth` layers with [AzksError::ProofTooDeep] before hashing any of it. This bounds the
+/// hashing an oversized proof from a malicious server can make a client do.
+pub fn verify_membership_with_max_depth<H: Hasher>(
+    root_hash: H::Digest,
+    proof: &MembershipProof<H>,
+    max_depth: usize,
+) -> Result<(), AkdError> {
+    if proof.layer_proofs.len() > max_depth {
+        return Err(AkdError::AzksErr(AzksError::ProofTooDeep {
+            depth: proof.layer_proofs.len(),
+            max: max_depth,
+        }));
+    }
     if proof.label.label_len == 0 {
         let final_hash = H::merge(&[proof.hash_val, hash_label::<H>(proof.label)]);
         if final_hash == root_hash {
@@ -36,25 +60,198 @@ pub fn verify_membership<H: Hasher>(
         }
     }
 
-    let mut final_hash = H::merge(&[proof.hash_val, hash_label::<H>(proof.label)]);
+    let path = proof
+        .layer_proofs
+        .iter()
+        .rev()
+        .map(|parent| {
+            let sibling = &parent.siblings[0];
+            let sibling_hash = H::merge(&[sibling.hash, hash_label::<H>(sibling.label)]);
+            (parent.direction, sibling_hash, parent.label)
+        })
+        .collect::<Vec<_>>();
+    let leaf_hash = H::merge(&[proof.hash_val, hash_label::<H>(proof.label)]);
+    let final_hash = fold_merkle_path::<H>(leaf_hash, &path)?;
+    if final_hash == root_hash {
+        Ok(())
+    } else {
+        return Err(AkdError::AzksErr(AzksError::VerifyMembershipProof(
+            format!("Membership proof for label {} did not verify", proof.label),
+        )));
+    }
+}
+
+/// One layer of a membership proof as [verify_membership_verbose] folds it up towards the root
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationStep<H: Hasher> {
+    /// The label of the layer's parent
+    pub parent_label: NodeLabel,
+    /// The direction of the path from the parent
+    pub direction: Direction,
+    /// The sibling's hash, merged with its label
+    pub sibling_hash: H::Digest,
+    /// The parent's hash, merged with its label, computed from the path so far
+    pub running_hash: H::Digest,
+}
+
+/// Every hash computed by [verify_membership_verbose] for a proof which failed to verify, so
+/// that the computation can be compared layer by layer with the tree's own hashes to find the
+/// layer at which they diverge.
+#[derive(Debug)]
+pub struct VerificationTrace<H: Hasher> {
+    /// The root hash the proof was verified against
+    pub root_hash: H::Digest,
+    /// The proven node's hash, merged with its label
+    pub leaf_hash: H::Digest,
+    /// The layers of the proof from the bottom up, as far as they were folded
+    pub steps: Vec<VerificationStep<H>>,
+    /// Why the proof failed to verify
+    pub error: AkdError,
+}
+
+impl<H: Hasher> fmt::Display for VerificationTrace<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the error's own display ends the line
+        write!(f, "{}", self.error)?;
+        writeln!(f, "leaf hash: {}", HexDigest::<H>(self.leaf_hash))?;
+        for (i, step) in self.steps.iter().enumerate() {
+            writeln!(
+                f,
+                "layer {}: parent {}, direction {:?}, sibling hash {}, hash {}",
+                i,
+                step.parent_label,
+                step.direction,
+                HexDigest::<H>(step.sibling_hash),
+                HexDigest::<H>(step.running_hash)
+            )?;
+        }
+        write!(f, "expected root hash: {}", HexDigest::<H>(self.root_hash))
+    }
+}
+
+/// Verifies membership like [verify_membership], but on failure returns every hash computed
+/// along the way rather than just the error. This is for debugging proofs which fail to
+/// verify: it keeps all of the intermediate hashes, which [verify_membership] doesn't.
+pub fn verify_membership_verbose<H: Hasher>(
+    root_hash: H::Digest,
+    proof: &MembershipProof<H>,
+) -> Result<(), VerificationTrace<H>> {
+    let leaf_hash = H::merge(&[proof.hash_val, hash_label::<H>(proof.label)]);
+    let mut trace = VerificationTrace {
+        root_hash,
+        leaf_hash,
+        steps: Vec::new(),
+        error: AkdError::AzksErr(AzksError::VerifyMembershipProof(format!(
+            "Membership proof for label {} did not verify",
+            proof.label
+        ))),
+    };
+    if proof.layer_proofs.len() > LEAF_LEN as usize {
+        trace.error = AkdError::AzksErr(AzksError::ProofTooDeep {
+            depth: proof.layer_proofs.len(),
+            max: LEAF_LEN as usize,
+        });
+        return Err(trace);
+    }
+
+    let mut hash = leaf_hash;
     for parent in proof.layer_proofs.iter().rev() {
-        let hashes = parent
-            .siblings
-            .iter()
-            .map(|n| H::merge(&[n.hash, hash_label::<H>(n.label)]))
-            .collect();
-        final_hash = build_and_hash_layer::<H>(hashes, parent.direction, final_hash, parent.label)?;
+        let sibling = &parent.siblings[0];
+        let sibling_hash = H::merge(&[sibling.hash, hash_label::<H>(sibling.label)]);
+        hash = match build_and_hash_layer::<H>(
+            vec![sibling_hash],
+            parent.direction,
+            hash,
+            parent.label,
+        ) {
+            Ok(hash) => hash,
+            Err(error) => {
+                trace.error = error;
+                return Err(trace);
+            }
+        };
+        trace.steps.push(VerificationStep {
+            parent_label: parent.label,
+            direction: parent.direction,
+            sibling_hash,
+            running_hash: hash,
+        });
     }
-    if final_hash == root_hash {
+    if hash == root_hash {
         Ok(())
     } else {
+        Err(trace)
+    }
+}
+
+/// Verifies that the checkpoint leaf at `label` held `digest` as of `epoch`, the epoch in
+/// which it was last inserted with [crate::append_only_zks::Azks::insert_checkpoint], with
+/// respect to the root_hash. The label is given by the client rather than taken from the
+/// proof, usually from [crate::node_label::NodeLabel::checkpoint], so that a proof for some
+/// other leaf is rejected.
+pub fn verify_checkpoint<H: Hasher>(
+    root_hash: H::Digest,
+    label: NodeLabel,
+    digest: H::Digest,
+    epoch: u64,
+    proof: &MembershipProof<H>,
+) -> Result<(), AkdError> {
+    if proof.label != label {
+        return Err(AkdError::AzksErr(AzksError::VerifyMembershipProof(
+            format!(
+                "Checkpoint proof is for label {} rather than {}",
+                proof.label, label
+            ),
+        )));
+    }
+    if proof.hash_val != merge_epoch::<H>(digest, epoch) {
         return Err(AkdError::AzksErr(AzksError::VerifyMembershipProof(
             format!(
-                "Membership proof for label {:?} did not verify",
-                proof.label
+                "Checkpoint at label {} does not hold the expected digest as of epoch {}",
+                label, epoch
             ),
         )));
     }
+    verify_membership::<H>(root_hash, proof)
+}
+
+/// Verifies that a path of siblings leads from `leaf_hash` up to `root_hash`, using the
+/// hashing of the tree of this crate but independently of its nodes and storage, e.g. for
+/// light clients or to cross-check other Merkle structures hashed the same way.
+///
+/// Each hash is the hash a node contributes to its parent, i.e.
+/// `H::merge(&[node_hash, hash_label::<H>(node_label)])`, which for the root is the root hash.
+/// The path is ordered from the starting node up to the root, and each step holds the
+/// direction of the node reached so far among its parent's children, the hash contributed by
+/// its sibling and the label of the parent. With an [ARITY] of 2, direction `Some(0)` merges
+/// the node before its sibling (`H::merge(&[node, sibling])`) and `Some(1)` after it
+/// (`H::merge(&[sibling, node])`); the result is then merged with the hash of the parent's
+/// label to give the parent's hash for the next step.
+///
+/// Returns false if the path leads to a different root hash, if a step has no direction
+/// or a direction past the last child, or if the path is longer than [LEAF_LEN] steps (in which
+/// case none of it is hashed).
+pub fn verify_merkle_path<H: Hasher>(
+    leaf_hash: H::Digest,
+    path: &[(Direction, H::Digest, NodeLabel)],
+    root_hash: H::Digest,
+) -> bool {
+    if path.len() > LEAF_LEN as usize {
+        return false;
+    }
+    matches!(fold_merkle_path::<H>(leaf_hash, path), Ok(hash) if hash == root_hash)
+}
+
+/// Folds a path of siblings into the hash it leads to, as described for [verify_merkle_path]
+fn fold_merkle_path<H: Hasher>(
+    leaf_hash: H::Digest,
+    path: &[(Direction, H::Digest, NodeLabel)],
+) -> Result<H::Digest, AkdError> {
+    let mut hash = leaf_hash;
+    for (direction, sibling_hash, parent_label) in path {
+        hash = build_and_hash_layer::<H>(vec![*sibling_hash], *direction, hash, *parent_label)?;
+    }
+    Ok(hash)
 }
 
 /// Verifies the non-membership proof with respect to the root hash
@@ -108,6 +305,274 @@ pub fn verify_nonmembership<H: Hasher>(
     Ok(verified)
 }
 
+/// Verifies the proof that no label starts with the prefix, with respect to the root hash. The
+/// children must hash to the longest prefix's committed hash, so that they're the children
+/// the tree actually has, and each of them must branch away from the prefix in question, since
+/// every leaf below a child starts with the child's label.
+pub fn verify_prefix_absence<H: Hasher>(
+    root_hash: H::Digest,
+    proof: &PrefixAbsenceProof<H>,
+) -> Result<(), AkdError> {
+    let verification_error = |message: String| {
+        Err(AkdError::Directory(DirectoryError::VerifyLookupProof(
+            message,
+        )))
+    };
+    let membership_proof = &proof.longest_prefix_membership_proof;
+    if membership_proof.label != proof.longest_prefix {
+        return verification_error(format!(
+            "The membership proof is for {} rather than the longest prefix {}",
+            membership_proof.label, proof.longest_prefix
+        ));
+    }
+    if !proof.longest_prefix.is_prefix_of(&proof.prefix) {
+        return verification_error(format!(
+            "{} is not a prefix of {}",
+            proof.longest_prefix, proof.prefix
+        ));
+    }
+    let children = &proof.longest_prefix_children;
+    // Only the root of the empty tree has no children, and its hash isn't made from them
+    let lcp_hash = if children.iter().all(|child| child.label == EMPTY_LABEL) {
+        crate::utils::empty_node_hash::<H>()
+    } else {
+        let children_hashes = children
+            .iter()
+            .map(|child| H::merge(&[child.hash, hash_label::<H>(child.label)]))
+            .collect::<Vec<_>>();
+        H::merge(&[children_hashes[0], children_hashes[1]])
+    };
+    if lcp_hash != membership_proof.hash_val {
+        return verification_error("lcp_hash != longest_prefix_hash".to_string());
+    }
+    verify_membership(root_hash, membership_proof)?;
+
+    // A child on the prefix's path would make it a longer prefix, and one under the prefix
+    // has leaves under it too
+    for child in proof.longest_prefix_children.iter() {
+        if child.label != EMPTY_LABEL
+            && (child.label.is_prefix_of(&proof.prefix) || proof.prefix.is_prefix_of(&child.label))
+        {
+            return verification_error(format!(
+                "The child {} of {} does not branch away from the prefix {}",
+                child.label, proof.longest_prefix, proof.prefix
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Verifies the non-membership proof in the form of the label's neighbors, with respect to the
+/// root hash. Both neighbors must be members of the tree, with the label strictly between
+/// them, and they must be adjacent: walking down from the node at which their paths branch,
+/// the predecessor's path must only ever turn right and the successor's only ever left, or
+/// there'd be leaves between them. A missing neighbor means the other one is the first or
+/// last leaf of the whole tree, and with neither the tree must be empty.
+pub fn verify_nonmembership_compact<H: Hasher>(
+    root_hash: H::Digest,
+    proof: &CompactNonMembershipProof<H>,
+) -> Result<(), AkdError> {
+    let verification_error = |message: String| {
+        Err(AkdError::Directory(DirectoryError::VerifyLookupProof(
+            message,
+        )))
+    };
+    let label = proof.label;
+    if let Some(predecessor) = &proof.predecessor {
+        verify_membership::<H>(root_hash, predecessor)?;
+        if predecessor.label.get_len() != label.get_len() || predecessor.label >= label {
+            return verification_error(format!(
+                "The predecessor {} does not come before {}",
+                predecessor.label, label
+            ));
+        }
+    }
+    if let Some(successor) = &proof.successor {
+        verify_membership::<H>(root_hash, successor)?;
+        if successor.label.get_len() != label.get_len() || successor.label <= label {
+            return verification_error(format!(
+                "The successor {} does not come after {}",
+                successor.label, label
+            ));
+        }
+    }
+
+    // Only the layers below the node at which the neighbors' paths branch are constrained,
+    // which is the whole path if there's a single neighbor
+    let branch_len = match (&proof.predecessor, &proof.successor) {
+        (Some(predecessor), Some(successor)) => Some(
+            predecessor
+                .label
+                .get_longest_common_prefix(successor.label)
+                .get_len(),
+        ),
+        (None, None) => {
+            let empty_root_hash = H::merge(&[
+                crate::utils::empty_node_hash::<H>(),
+                hash_label::<H>(NodeLabel::root()),
+            ]);
+            if root_hash != empty_root_hash {
+                return verification_error(format!(
+                    "No neighbors are given for {}, but the tree is not empty",
+                    label
+                ));
+            }
+            return Ok(());
+        }
+        _ => None,
+    };
+    // A path keeps to one side of the tree if at every layer it turns that way, or the other
+    // child is missing (which only the root's children can be)
+    let keeps_to = |proof: &MembershipProof<H>, direction: usize| {
+        proof
+            .layer_proofs
+            .iter()
+            .filter(|layer| match branch_len {
+                Some(len) => layer.label.get_len() > len,
+                None => true,
+            })
+            .all(|layer| {
+                layer.direction == Some(direction) || layer.siblings[0].label == EMPTY_LABEL
+            })
+    };
+    if let Some(predecessor) = &proof.predecessor {
+        if !keeps_to(predecessor, 1) {
+            return verification_error(format!(
+                "The predecessor {} is not the last leaf before {}",
+                predecessor.label, label
+            ));
+        }
+    }
+    if let Some(successor) = &proof.successor {
+        if !keeps_to(successor, 0) {
+            return verification_error(format!(
+                "The successor {} is not the first leaf after {}",
+                successor.label, label
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Verifies a batch of membership and non-membership proofs with respect to the root hash.
+/// The proof must account for exactly the `labels` asked about, each as either a member or a
+/// non-member, so that a proof for a different set of labels doesn't verify.
+pub fn verify_batch_membership<H: Hasher>(
+    root_hash: H::Digest,
+    labels: &[NodeLabel],
+    proof: &BatchMembershipProof<H>,
+) -> Result<(), AkdError> {
+    let mut expected = labels.to_vec();
+    expected.sort();
+    expected.dedup();
+    let mut proven: Vec<NodeLabel> = proof
+        .membership
+        .iter()
+        .map(|node| node.label)
+        .chain(proof.non_membership.iter().copied())
+        .collect();
+    proven.sort();
+    if proven != expected {
+        return Err(AkdError::AzksErr(AzksError::VerifyMembershipProof(
+            "Batch membership proof is not for the requested labels".to_string(),
+        )));
+    }
+
+    let nodes: Vec<Node<H>> = proof
+        .membership
+        .iter()
+        .chain(proof.siblings.iter())
+        .copied()
+        .collect();
+    if nodes.iter().any(|node| node.label.get_len() == 0) {
+        return Err(AkdError::AzksErr(AzksError::VerifyMembershipProof(
+            "Batch membership proof cannot contain the root or an empty node".to_string(),
+        )));
+    }
+
+    let root = NodeLabel::root();
+    let computed_root_hash = if nodes.is_empty() {
+        H::merge(&[crate::utils::empty_node_hash::<H>(), hash_label::<H>(root)])
+    } else {
+        let mut hashes = Vec::with_capacity(ARITY);
+        for dir in 0..ARITY {
+            let subtree: Vec<Node<H>> = nodes
+                .iter()
+                .filter(|node| root.get_dir(node.label) == Direction::Some(dir))
+                .copied()
+                .collect();
+            // The root is the only node which may be missing a child
+            if subtree.is_empty() {
+                hashes.push(H::merge(&[
+                    crate::utils::dummy_child_hash::<H>(),
+                    hash_label::<H>(EMPTY_LABEL),
+                ]));
+            } else {
+                hashes.push(hash_batch_subtree::<H>(&subtree)?);
+            }
+        }
+        hash_layer::<H>(hashes, root)
+    };
+    if computed_root_hash != root_hash {
+        return Err(AkdError::AzksErr(AzksError::VerifyMembershipProof(
+            "Batch membership proof did not verify".to_string(),
+        )));
+    }
+
+    // Each of the nodes is the root of a subtree whose contents are unknown, so a label which
+    // is not a member cannot be in any of them. Its deepest prefix in the tree must then be an
+    // internal node, whose child towards the label is either empty or not a prefix of it.
+    for label in proof.non_membership.iter() {
+        if nodes.iter().any(|node| node.label.is_prefix_of(label)) {
+            return Err(AkdError::AzksErr(AzksError::VerifyMembershipProof(
+                format!(
+                    "Non-membership of label {} in batch membership proof did not verify",
+                    label
+                ),
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Helper for verify_batch_membership, rebuilds the subtree spanned by the given nodes and
+/// returns the hash of its root with its label
+fn hash_batch_subtree<H: Hasher>(nodes: &[Node<H>]) -> Result<H::Digest, AkdError> {
+    let (first, rest) = match nodes {
+        [] => {
+            return Err(AkdError::AzksErr(AzksError::VerifyMembershipProof(
+                "Batch membership proof is missing a child of an internal node".to_string(),
+            )))
+        }
+        [node] => return Ok(H::merge(&[node.hash, hash_label::<H>(node.label)])),
+        [first, rest @ ..] => (first, rest),
+    };
+
+    // The label of an internal node is the longest common prefix of its descendants
+    let label = rest.iter().fold(first.label, |lcp, node| {
+        lcp.get_longest_common_prefix(node.label)
+    });
+    if nodes.iter().any(|node| node.label == label) {
+        return Err(AkdError::AzksErr(AzksError::VerifyMembershipProof(
+            format!(
+                "Node with label {} in batch membership proof is not a leaf of the subtree",
+                label
+            ),
+        )));
+    }
+
+    let mut hashes = Vec::with_capacity(ARITY);
+    for dir in 0..ARITY {
+        let subtree: Vec<Node<H>> = nodes
+            .iter()
+            .filter(|node| label.get_dir(node.label) == Direction::Some(dir))
+            .copied()
+            .collect();
+        hashes.push(hash_batch_subtree::<H>(&subtree)?);
+    }
+    Ok(hash_layer::<H>(hashes, label))
+}
+
 /// Verifies a lookup with respect to the root_hash
 pub fn lookup_verify<H: Hasher>(
     vrf_pk: &VRFPublicKey,
@@ -384,6 +849,12 @@ fn build_and_hash_layer<H: Hasher>(
         parent_label,
         None,
     )))?;
+    // a direction past the last child would otherwise panic on insertion below
+    if direction >= ARITY {
+        return Err(AkdError::TreeNode(TreeNodeError::InvalidDirection(
+            direction,
+        )));
+    }
     let mut hashes_mut = hashes.to_vec();
     hashes_mut.insert(direction, ancestor_hash);
     Ok(hash_layer::<H>(hashes_mut, parent_label))
@@ -397,7 +868,7 @@ fn hash_layer<H: Hasher>(hashes: Vec<H::Digest>, parent_label: NodeLabel) -> H::
 
 fn hash_leaf_with_value<H: Hasher>(value: &crate::AkdValue, epoch: u64, proof: &[u8]) -> H::Digest {
     let single_hash = crate::utils::bind_commitment::<H>(value, proof);
-    H::merge_with_int(single_hash, epoch)
+    merge_epoch::<H>(single_hash, epoch)
 }
 
 #[allow(unused)]
@@ -11,14 +11,18 @@ use crate::append_only_zks::Azks;
 
 use crate::ecvrf::{VRFKeyStorage, VRFPublicKey};
 use crate::proof_structs::*;
-use crate::{helper_structs::LookupInfo, EpochHash, Node};
+use crate::{helper_structs::LookupInfo, EpochHash, Node, NodeLabel};
 
 use crate::errors::{AkdError, DirectoryError, StorageError};
 
-use crate::storage::types::{AkdLabel, AkdValue, DbRecord, ValueState, ValueStateRetrievalFlag};
-use crate::storage::Storage;
+use crate::storage::types::{
+    AkdLabel, AkdValue, DbRecord, EpochRootHash, EpochTimestamp, ValueState,
+    ValueStateRetrievalFlag,
+};
+use crate::storage::{ReadAfterWritePolicy, Storage};
+use crate::tree_node::{NodeKey, TreeNode};
 
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 
 #[cfg(feature = "rand")]
 use rand::{distributions::Alphanumeric, CryptoRng, Rng};
@@ -26,6 +30,7 @@ use rand::{distributions::Alphanumeric, CryptoRng, Rng};
 use std::collections::HashMap;
 use std::marker::{Send, Sync};
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use winter_crypto::{Digest, Hasher};
 
 #[cfg(feature = "rand")]
@@ -50,6 +55,12 @@ pub struct Directory<S, V> {
     storage: S,
     vrf: V,
     read_only: bool,
+    /// Whether an [`EpochTimestamp`] is recorded for each published epoch
+    epoch_timestamps: bool,
+    /// Whether proofs are generated with a traversal whose shape doesn't depend on the tree
+    constant_time_proofs: bool,
+    /// How a publish checks that the epoch it committed can be read back from storage
+    read_after_write: ReadAfterWritePolicy,
     /// The cache lock guarantees that the cache is not
     /// flushed mid-proof generation. We allow multiple proof generations
     /// to occur (RwLock.read() operations can have multiple) but we want
@@ -66,25 +77,91 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
     pub async fn new<H: Hasher>(storage: &S, vrf: &V, read_only: bool) -> Result<Self, AkdError> {
         let azks = Directory::<S, V>::get_azks_from_storage(storage, false).await;
 
-        if read_only && azks.is_err() {
-            return Err(AkdError::Directory(DirectoryError::ReadOnlyDirectory(
-                "Cannot start directory in read-only mode when AZKS is missing".to_string(),
-            )));
-        } else if azks.is_err() {
-            // generate a new azks if one is not found
-            let azks = Azks::new::<_, H>(storage).await?;
-            // store it
-            storage.set(DbRecord::Azks(azks.clone())).await?;
+        match azks {
+            Err(_) if read_only => {
+                return Err(AkdError::Directory(DirectoryError::ReadOnlyDirectory(
+                    "Cannot start directory in read-only mode when AZKS is missing".to_string(),
+                )));
+            }
+            Err(_) => {
+                // generate a new azks if one is not found
+                let azks = Azks::new::<_, H>(storage).await?;
+                let root_hash = azks.get_root_hash::<_, H>(storage).await?;
+                // store it, along with the root hash of the empty tree at epoch 0
+                storage
+                    .batch_set(vec![
+                        DbRecord::Azks(azks),
+                        DbRecord::EpochRootHash(EpochRootHash {
+                            epoch: 0,
+                            root_hash: crate::serialization::from_digest::<H>(root_hash),
+                        }),
+                    ])
+                    .await?;
+            }
+            Ok(azks) => {
+                // a directory created before root hashes were recorded can't serve audits from
+                // storage until they're backfilled
+                let latest_epoch = azks.get_latest_epoch();
+                match storage.get::<EpochRootHash>(&latest_epoch).await {
+                    Ok(_) => {}
+                    Err(StorageError::NotFound(_)) => warn!(
+                        "No root hash is recorded for the latest epoch {}, see \
+                        Azks::backfill_root_hashes",
+                        latest_epoch
+                    ),
+                    Err(other) => return Err(AkdError::Storage(other)),
+                }
+            }
         }
 
         Ok(Directory {
             storage: storage.clone(),
             read_only,
+            epoch_timestamps: false,
+            constant_time_proofs: false,
+            read_after_write: ReadAfterWritePolicy::default(),
             cache_lock: Arc::new(tokio::sync::RwLock::new(())),
             vrf: vrf.clone(),
         })
     }
 
+    /// Sets whether the time at which each epoch is finalized should be recorded on publish,
+    /// which allows for finding the epoch as of a given time with [`Azks::epoch_at_or_before`].
+    /// This is disabled by default, since not all deployments advance epochs on a clock. When
+    /// enabled, a publish fails with [`DirectoryError::ClockBeforeUnixEpoch`] rather than
+    /// record a meaningless timestamp if the system clock reads before the UNIX epoch.
+    pub fn with_epoch_timestamps(mut self, epoch_timestamps: bool) -> Self {
+        self.epoch_timestamps = epoch_timestamps;
+        self
+    }
+
+    /// Sets whether membership and non-membership proofs should be generated with a
+    /// traversal of the same shape. Ordinarily a membership proof walks down to a leaf while
+    /// a non-membership proof stops at the longest common prefix of the label, so the time
+    /// taken to serve a lookup can reveal to an observer of the network (or of the storage
+    /// backend) whether the queried label exists. With this enabled, every proof traversal is
+    /// padded with dummy storage reads down to the full length of the label, see
+    /// [`Azks::get_constant_time_membership_proof`]. This costs about 750 storage reads per
+    /// proof rather than a few dozen, so it is disabled by default and only worth enabling
+    /// for directories where the existence of a label is itself sensitive. Only the number
+    /// of reads is made uniform: reads of missing nodes and cache hits versus misses still
+    /// take different times, so proof timing can still leak whether a label exists.
+    pub fn with_constant_time_proofs(mut self, constant_time_proofs: bool) -> Self {
+        self.constant_time_proofs = constant_time_proofs;
+        self
+    }
+
+    /// Sets how a publish checks that the epoch it committed can be read back from storage,
+    /// see [`Storage::verify_epoch_visible`]. This is [`ReadAfterWritePolicy::Skip`] by
+    /// default, which suits strongly consistent storage. On eventually consistent storage,
+    /// [`ReadAfterWritePolicy::RetryWithBackoff`] makes a publish only return once the new
+    /// epoch is visible to readers. Otherwise the publish fails with
+    /// [`DirectoryError::CommittedNotReadable`], although the epoch has been committed.
+    pub fn with_read_after_write_policy(mut self, read_after_write: ReadAfterWritePolicy) -> Self {
+        self.read_after_write = read_after_write;
+        self
+    }
+
     /// Updates the directory to include the updated key-value pairs.
     pub async fn publish<H: Hasher>(
         &self,
@@ -191,6 +268,19 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
             return Ok(EpochHash(current_epoch, root_hash));
         }
 
+        // read the clock before anything is written, so a clock which can't be read fails the
+        // publish without leaving a transaction behind
+        let timestamp_millis = if self.epoch_timestamps {
+            let since_epoch = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|err| {
+                    AkdError::Directory(DirectoryError::ClockBeforeUnixEpoch(err.to_string()))
+                })?;
+            Some(since_epoch.as_millis() as u64)
+        } else {
+            None
+        };
+
         if let false = self.storage.begin_transaction().await {
             error!("Transaction is already active");
             return Err(AkdError::Storage(StorageError::Transaction(
@@ -202,17 +292,34 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
         current_azks
             .batch_insert_leaves::<_, H>(&self.storage, insertion_set)
             .await?;
+        // the epoch isn't committed yet, so any record of its root hash is a stale one
+        let root_hash = current_azks
+            .get_root_hash_from_tree::<_, H>(&self.storage, next_epoch)
+            .await?;
 
         // batch all the inserts into a single transactional write to storage
-        let mut updates = vec![DbRecord::Azks(current_azks.clone())];
+        let mut updates = vec![
+            DbRecord::Azks(current_azks.clone()),
+            DbRecord::EpochRootHash(EpochRootHash {
+                epoch: next_epoch,
+                root_hash: crate::serialization::from_digest::<H>(root_hash),
+            }),
+        ];
         for update in user_data_update_set.into_iter() {
             updates.push(DbRecord::ValueState(update));
         }
+        if let Some(timestamp_millis) = timestamp_millis {
+            updates.push(DbRecord::EpochTimestamp(EpochTimestamp {
+                epoch: next_epoch,
+                timestamp_millis,
+            }));
+        }
         self.storage.batch_set(updates).await?;
 
-        // now commit the transaction
+        // now commit the transaction, provided no other writer has published an epoch since
+        // we read the current one
         debug!("Committing transaction");
-        if let Err(err) = self.storage.commit_transaction().await {
+        if let Err(err) = self.storage.commit_epoch(current_epoch).await {
             // ignore any rollback error(s)
             let _ = self.storage.rollback_transaction().await;
             return Err(AkdError::Storage(err));
@@ -220,9 +327,18 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
             debug!("Transaction committed");
         }
 
-        let root_hash = current_azks
-            .get_root_hash_at_epoch::<_, H>(&self.storage, next_epoch)
-            .await?;
+        // The epoch is committed from here on, so there's nothing left to roll back if it
+        // can't be read back
+        if let Err(err) = self
+            .storage
+            .verify_epoch_visible(next_epoch, self.read_after_write)
+            .await
+        {
+            return Err(AkdError::Directory(DirectoryError::CommittedNotReadable {
+                epoch: next_epoch,
+                error: err,
+            }));
+        }
 
         Ok(EpochHash(next_epoch, root_hash))
         // At the moment the tree root is not being written anywhere. Eventually we
@@ -268,8 +384,8 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
             plaintext_value: plaintext_value.clone(),
             version: lookup_info.value_state.version,
             existence_vrf_proof: existence_vrf.to_bytes().to_vec(),
-            existence_proof: current_azks
-                .get_membership_proof(&self.storage, lookup_info.existent_label, current_epoch)
+            existence_proof: self
+                .membership_proof::<H>(current_azks, lookup_info.existent_label, current_epoch)
                 .await?,
             marker_vrf_proof: self
                 .vrf
@@ -277,8 +393,8 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
                 .await?
                 .to_bytes()
                 .to_vec(),
-            marker_proof: current_azks
-                .get_membership_proof(&self.storage, lookup_info.marker_label, current_epoch)
+            marker_proof: self
+                .membership_proof::<H>(current_azks, lookup_info.marker_label, current_epoch)
                 .await?,
             freshness_vrf_proof: self
                 .vrf
@@ -286,8 +402,8 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
                 .await?
                 .to_bytes()
                 .to_vec(),
-            freshness_proof: current_azks
-                .get_non_membership_proof(&self.storage, lookup_info.non_existent_label)
+            freshness_proof: self
+                .non_membership_proof::<H>(current_azks, lookup_info.non_existent_label)
                 .await?,
             commitment_proof: crate::utils::get_commitment_proof::<H>(
                 &commitment_key.as_bytes(),
@@ -444,8 +560,8 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
 
             for ver in last_version + 1..(1 << next_marker) {
                 let label_for_ver = self.vrf.get_node_label::<H>(uname, false, ver).await?;
-                let non_existence_of_ver = current_azks
-                    .get_non_membership_proof(&self.storage, label_for_ver)
+                let non_existence_of_ver = self
+                    .non_membership_proof::<H>(&current_azks, label_for_ver)
                     .await?;
                 non_existence_of_next_few.push(non_existence_of_ver);
                 next_few_vrf_proofs.push(
@@ -463,8 +579,8 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
             for marker_power in next_marker..final_marker + 1 {
                 let ver = 1 << marker_power;
                 let label_for_ver = self.vrf.get_node_label::<H>(uname, false, ver).await?;
-                let non_existence_of_ver = current_azks
-                    .get_non_membership_proof(&self.storage, label_for_ver)
+                let non_existence_of_ver = self
+                    .non_membership_proof::<H>(&current_azks, label_for_ver)
                     .await?;
                 non_existence_of_future_markers.push(non_existence_of_ver);
                 future_marker_vrf_proofs.push(
@@ -555,8 +671,8 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
 
             for ver in last_version + 1..(1 << next_marker) {
                 let label_for_ver = self.vrf.get_node_label::<H>(uname, false, ver).await?;
-                let non_existence_of_ver = current_azks
-                    .get_non_membership_proof(&self.storage, label_for_ver)
+                let non_existence_of_ver = self
+                    .non_membership_proof::<H>(&current_azks, label_for_ver)
                     .await?;
                 non_existence_of_next_few.push(non_existence_of_ver);
                 next_few_vrf_proofs.push(
@@ -574,8 +690,8 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
             for marker_power in next_marker..final_marker + 1 {
                 let ver = 1 << marker_power;
                 let label_for_ver = self.vrf.get_node_label::<H>(uname, false, ver).await?;
-                let non_existence_of_ver = current_azks
-                    .get_non_membership_proof(&self.storage, label_for_ver)
+                let non_existence_of_ver = self
+                    .non_membership_proof::<H>(&current_azks, label_for_ver)
                     .await?;
                 non_existence_of_future_markers.push(non_existence_of_ver);
                 future_marker_vrf_proofs.push(
@@ -600,10 +716,12 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
 
     /// Poll for changes in the epoch number of the AZKS struct
     /// stored in the storage layer. If an epoch change is detected,
-    /// the object cache (if present) is flushed immediately so
-    /// that new objects are retrieved from the storage layer against
-    /// the "latest" epoch. There is a "special" flow in the storage layer
-    /// to do a storage-layer retrieval which ignores the cache
+    /// the cached objects (if any) which may have changed are invalidated
+    /// immediately so that they're retrieved from the storage layer against
+    /// the "latest" epoch, while unchanged tree nodes are kept. There is a "special" flow in the storage layer
+    /// to do a storage-layer retrieval which ignores the cache.
+    /// Only available with the _tokio_runtime_ feature, since it sleeps between polls
+    #[cfg(feature = "tokio_runtime")]
     pub async fn poll_for_azks_changes(
         &self,
         period: tokio::time::Duration,
@@ -619,12 +737,20 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
 
             let latest = Directory::<S, V>::get_azks_from_storage(&self.storage, true).await?;
             if latest.latest_epoch > last.latest_epoch {
+                // find the nodes which changed since the last epoch before taking the lock,
+                // as they're read directly from the data layer and so don't touch the cache
+                let changed = latest
+                    .get_changed_labels_since(&self.storage, last.latest_epoch)
+                    .await?;
                 {
                     // acquire a singleton lock prior to flushing the cache to assert that no
                     // cache accesses are underway (i.e. publish/proof generations/etc)
                     let _guard = self.cache_lock.write().await;
-                    // flush the cache in its entirety
-                    self.storage.flush_cache().await;
+                    // drop the cached nodes which changed since the last epoch, keeping the
+                    // rest of the tree (a storage whose cache can't do this flushes it all)
+                    self.storage
+                        .advance_cache_epoch(latest.latest_epoch, &changed)
+                        .await;
                     // re-fetch the azks to load it into cache so when we release the cache lock
                     // others will see the new AZKS loaded up and ready
                     last = Directory::<S, V>::get_azks_from_storage(&self.storage, false).await?;
@@ -682,6 +808,33 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
         Directory::<S, V>::get_azks_from_storage(&self.storage, false).await
     }
 
+    async fn membership_proof<H: Hasher>(
+        &self,
+        azks: &Azks,
+        label: NodeLabel,
+        epoch: u64,
+    ) -> Result<MembershipProof<H>, AkdError> {
+        if self.constant_time_proofs {
+            azks.get_constant_time_membership_proof(&self.storage, label, epoch)
+                .await
+        } else {
+            azks.get_membership_proof(&self.storage, label, epoch).await
+        }
+    }
+
+    async fn non_membership_proof<H: Hasher>(
+        &self,
+        azks: &Azks,
+        label: NodeLabel,
+    ) -> Result<NonMembershipProof<H>, AkdError> {
+        if self.constant_time_proofs {
+            azks.get_constant_time_non_membership_proof(&self.storage, label)
+                .await
+        } else {
+            azks.get_non_membership_proof(&self.storage, label).await
+        }
+    }
+
     async fn get_azks_from_storage(
         storage: &S,
         ignore_cache: bool,
@@ -731,8 +884,8 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
             .vrf
             .get_node_label_from_vrf_pf::<H>(existence_vrf)
             .await?;
-        let existence_at_ep = current_azks
-            .get_membership_proof(&self.storage, label_at_ep, epoch)
+        let existence_at_ep = self
+            .membership_proof::<H>(&current_azks, label_at_ep, epoch)
             .await?;
         let mut previous_val_stale_at_ep = Option::None;
         let mut previous_val_vrf_proof = Option::None;
@@ -742,8 +895,7 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
                 .get_node_label::<H>(uname, true, version - 1)
                 .await?;
             previous_val_stale_at_ep = Option::Some(
-                current_azks
-                    .get_membership_proof(&self.storage, prev_label_at_ep, epoch)
+                self.membership_proof::<H>(&current_azks, prev_label_at_ep, epoch)
                     .await?,
             );
             previous_val_vrf_proof = Option::Some(
@@ -800,6 +952,66 @@ impl<S: Storage + Sync + Send, V: VRFKeyStorage> Directory<S, V> {
             .await
     }
 
+    /// Checks that the value state a user published at `epoch` agrees with the leaf committed
+    /// to the tree for it, i.e. that committing to the stored plaintext value reproduces the
+    /// value of the leaf at the value state's label, written in the same epoch. The value
+    /// states and the tree are separate records, so a partial write can leave them diverged
+    /// even when the tree itself is intact. A divergence is reported as
+    /// [`DirectoryError::InconsistentValueState`].
+    pub async fn verify_value_state_consistency<H: Hasher>(
+        &self,
+        uname: &AkdLabel,
+        epoch: u64,
+    ) -> Result<(), AkdError> {
+        // The guard will be dropped at the end of the check
+        let _guard = self.cache_lock.read().await;
+
+        let current_azks = self.retrieve_current_azks().await?;
+        let value_state = self
+            .storage
+            .get_user_state(uname, ValueStateRetrievalFlag::SpecificEpoch(epoch))
+            .await?;
+        let leaf = match TreeNode::get_from_storage(
+            &self.storage,
+            &NodeKey(value_state.label),
+            current_azks.get_latest_epoch(),
+        )
+        .await
+        {
+            Ok(node) if node.is_leaf() => node,
+            Ok(_) | Err(StorageError::NotFound(_)) => {
+                return Err(AkdError::Directory(DirectoryError::InconsistentValueState(
+                    format!("no leaf is labelled {}", value_state.label),
+                )));
+            }
+            Err(other) => return Err(AkdError::Storage(other)),
+        };
+
+        let commitment_key = self.derive_commitment_key::<H>().await?;
+        let expected = crate::utils::commit_value::<H>(
+            &commitment_key.as_bytes(),
+            &value_state.label,
+            &value_state.plaintext_val,
+        );
+        if leaf.hash != crate::serialization::from_digest::<H>(expected) {
+            return Err(AkdError::Directory(DirectoryError::InconsistentValueState(
+                format!(
+                    "the leaf labelled {} doesn't commit to the value published at epoch {}",
+                    value_state.label, epoch
+                ),
+            )));
+        }
+        if leaf.last_epoch != value_state.epoch {
+            return Err(AkdError::Directory(DirectoryError::InconsistentValueState(
+                format!(
+                    "the leaf labelled {} was written at epoch {}, not at epoch {}",
+                    value_state.label, leaf.last_epoch, value_state.epoch
+                ),
+            )));
+        }
+        Ok(())
+    }
+
     // FIXME (Issue #184): This should be derived properly. Instead of hashing the VRF private
     // key, we should derive this properly from a server secret.
     async fn derive_commitment_key<H: Hasher>(&self) -> Result<H::Digest, AkdError> {
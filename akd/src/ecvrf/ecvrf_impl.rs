@@ -43,6 +43,22 @@ pub const OUTPUT_LENGTH: usize = 64;
 /// The number of bytes of [`Proof`]
 pub const PROOF_LENGTH: usize = 80;
 
+/// Returns the VRF input (alpha) for a version of a username. The alpha is the digest
+/// `H::merge(H::hash(uname), H::merge_with_int(H::hash(stale_byte), version))`, serialized
+/// to its bytes, where the stale byte is `0` for the label of a version being retired and
+/// `1` for the label of a fresh version. The server proves, and clients verify, over exactly
+/// these bytes, so a label is bound to the username, the version and its freshness.
+pub fn get_label_alpha<H: Hasher>(uname: &AkdLabel, stale: bool, version: u64) -> Vec<u8> {
+    let name_hash_bytes = H::hash(uname);
+    let stale_bytes = if stale { &[0u8] } else { &[1u8] };
+
+    let hashed_label = H::merge(&[
+        name_hash_bytes,
+        H::merge_with_int(H::hash(stale_bytes), version),
+    ]);
+    crate::serialization::from_digest::<H>(hashed_label).to_vec()
+}
+
 /// An ECVRF private key
 #[derive(Debug)]
 #[cfg_attr(
@@ -211,6 +227,10 @@ impl VRFPublicKey {
     /// This function is called to verify that a given NodeLabel is indeed
     /// the VRF for a given version (fresh or stale) for a username.
     /// Hence, it also takes as input the server's public key.
+    ///
+    /// The proof is verified over the alpha of [get_label_alpha], and the label must be the
+    /// first 32 bytes of the proof's output, so a server can't place a username at any
+    /// position other than the one the VRF mandates.
     pub fn verify_label<H: Hasher>(
         &self,
         uname: &AkdLabel,
@@ -219,16 +239,7 @@ impl VRFPublicKey {
         proof: &[u8],
         label: NodeLabel,
     ) -> Result<(), VrfError> {
-        // Initialization of VRF context by providing a curve
-
-        let name_hash_bytes = H::hash(uname);
-        let stale_bytes = if stale { &[0u8] } else { &[1u8] };
-
-        let hashed_label = H::merge(&[
-            name_hash_bytes,
-            H::merge_with_int(H::hash(stale_bytes), version),
-        ]);
-        let message_vec = crate::serialization::from_digest::<H>(hashed_label);
+        let message_vec = get_label_alpha::<H>(uname, stale, version);
         let message: &[u8] = message_vec.as_slice();
 
         // VRF proof verification (returns VRF hash output)
@@ -28,7 +28,7 @@ mod ecvrf_impl;
 mod traits;
 // export the functionality we want visible
 #[cfg(feature = "vrf")]
-pub use crate::ecvrf::ecvrf_impl::{Proof, VRFPrivateKey, VRFPublicKey};
+pub use crate::ecvrf::ecvrf_impl::{get_label_alpha, Proof, VRFPrivateKey, VRFPublicKey};
 #[cfg(feature = "vrf")]
 pub use crate::ecvrf::traits::VRFKeyStorage;
 
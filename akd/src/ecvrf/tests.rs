@@ -24,6 +24,11 @@ use rand::{CryptoRng, RngCore};
 use ed25519_dalek::{self, SecretKey as ed25519_PrivateKey};
 
 use crate::ecvrf::ecvrf_impl::*;
+use crate::ecvrf::{HardCodedAkdVRF, VRFKeyStorage};
+use crate::errors::VrfError;
+use crate::storage::types::AkdLabel;
+use winter_crypto::hashers::Blake3_256;
+use winter_math::fields::f128::BaseElement;
 
 /// A type family for schemes which know how to generate key material from
 /// a cryptographically-secure [`CryptoRng`][::rand::CryptoRng].
@@ -267,6 +272,38 @@ fn test_output_from_proof() {
     }
 }
 
+#[tokio::test]
+async fn test_verify_label() -> Result<(), VrfError> {
+    type Blake3 = Blake3_256<BaseElement>;
+    let vrf = HardCodedAkdVRF {};
+    let pk = vrf.get_vrf_public_key().await?;
+    let uname = AkdLabel::from_utf8_str("hello");
+
+    let proof = vrf.get_label_proof::<Blake3>(&uname, false, 1).await?;
+    let label = vrf.get_node_label::<Blake3>(&uname, false, 1).await?;
+    let bytes = proof.to_bytes();
+    pk.verify_label::<Blake3>(&uname, false, 1, &bytes, label)?;
+
+    // The proof doesn't verify for another username, version or freshness
+    let other = AkdLabel::from_utf8_str("world");
+    assert!(pk
+        .verify_label::<Blake3>(&other, false, 1, &bytes, label)
+        .is_err());
+    assert!(pk
+        .verify_label::<Blake3>(&uname, false, 2, &bytes, label)
+        .is_err());
+    assert!(pk
+        .verify_label::<Blake3>(&uname, true, 1, &bytes, label)
+        .is_err());
+
+    // and a label other than the one the VRF mandates is rejected
+    let substituted = vrf.get_node_label::<Blake3>(&uname, false, 2).await?;
+    assert!(pk
+        .verify_label::<Blake3>(&uname, false, 1, &bytes, substituted)
+        .is_err());
+    Ok(())
+}
+
 #[test]
 fn test_publickey_clone() {
     // PublicKey has its own implementation of Clone
@@ -7,8 +7,8 @@
 
 //! This module implements traits for managing ECVRF, mainly pertaining to storage
 //! of public and private keys
+use super::ecvrf_impl::get_label_alpha;
 use super::{Proof, VRFPrivateKey, VRFPublicKey};
-use crate::serialization::from_digest;
 use crate::{errors::VrfError, node_label::NodeLabel, storage::types::AkdLabel};
 
 use async_trait::async_trait;
@@ -78,14 +78,7 @@ pub trait VRFKeyStorage: Clone + Sync + Send {
         version: u64,
     ) -> Result<Proof, VrfError> {
         let key = self.get_vrf_private_key().await?;
-        let name_hash_bytes = H::hash(uname);
-        let stale_bytes = if stale { &[0u8] } else { &[1u8] };
-
-        let hashed_label = H::merge(&[
-            name_hash_bytes,
-            H::merge_with_int(H::hash(stale_bytes), version),
-        ]);
-        let message_vec = from_digest::<H>(hashed_label);
+        let message_vec = get_label_alpha::<H>(uname, stale, version);
         let message: &[u8] = message_vec.as_slice();
 
         // VRF proof and hash output
@@ -9,6 +9,7 @@
 use core::fmt;
 
 use crate::node_label::NodeLabel;
+use crate::serialization::AuditHasher;
 
 /// Symbolizes a AkdError, thrown by the akd.
 #[cfg_attr(test, derive(PartialEq))]
@@ -26,6 +27,8 @@ pub enum AkdError {
     Storage(StorageError),
     /// Audit verification error thrown
     AuditErr(AuditorError),
+    /// A structure failed to serialize or deserialize
+    Serialization(String),
     /// Test error
     TestErr(String),
 }
@@ -89,6 +92,9 @@ impl std::fmt::Display for AkdError {
             AkdError::AuditErr(err) => {
                 writeln!(f, "AKD Auditor Error {}", err)
             }
+            AkdError::Serialization(err) => {
+                writeln!(f, "AKD Serialization Error: {}", err)
+            }
             AkdError::TestErr(err) => {
                 writeln!(f, "{}", err)
             }
@@ -117,6 +123,26 @@ pub enum TreeNodeError {
     NoStateAtEpoch(NodeLabel, u64),
     /// Failed to deserialize a digest
     DigestDeserializationFailed(String),
+    /// A leaf with this label is already in the tree
+    LabelAlreadyExists(NodeLabel),
+    /// The node with this label is its own parent without being the root, or is labelled as
+    /// the root without being the root
+    SelfParentCycle(NodeLabel),
+    /// The label of a leaf being inserted equals, or is a prefix of, the label of an
+    /// interior node already in the tree, so the leaf has no valid position
+    LabelCollidesWithInterior(NodeLabel),
+    /// The index of a bit is past the end of the label
+    BitIndexOutOfRange(NodeLabel, u32),
+    /// A node refers to a child whose record is missing from storage altogether, rather than
+    /// the child not having existed yet at the epoch, so data has been lost
+    StateRecordMissing {
+        /// The label of the missing child
+        label: NodeLabel,
+        /// The epoch at which the child was read
+        epoch: u64,
+    },
+    /// There are leaves whose labels start with this prefix
+    PrefixNotEmpty(NodeLabel),
 }
 
 impl std::error::Error for TreeNodeError {}
@@ -132,10 +158,10 @@ impl fmt::Display for TreeNodeError {
                 )
             }
             Self::NoDirection(node_label, child_label) => {
-                let mut to_print = format!("no direction provided for the node {:?}", node_label);
+                let mut to_print = format!("no direction provided for the node {}", node_label);
                 // Add child info if given.
                 if let Some(child_label) = child_label {
-                    let child_str = format!(" and child {:?}", child_label);
+                    let child_str = format!(" and child {}", child_label);
                     to_print.push_str(&child_str);
                 }
                 write!(f, "{}", to_print)
@@ -155,20 +181,50 @@ impl fmt::Display for TreeNodeError {
             Self::NonexistentAtEpoch(label, epoch) => {
                 write!(
                     f,
-                    "This node, labelled {:?}, did not exist at epoch {:?}.",
+                    "This node, labelled {}, did not exist at epoch {}.",
                     label, epoch
                 )
             }
             Self::NoStateAtEpoch(label, epoch) => {
                 write!(
                     f,
-                    "This node, labelled {:?}, did not exist at epoch {:?}.",
+                    "This node, labelled {}, did not exist at epoch {}.",
                     label, epoch
                 )
             }
             Self::DigestDeserializationFailed(inner_error) => {
                 write!(f, "Encountered a serialization error {}", inner_error)
             }
+            Self::LabelAlreadyExists(label) => {
+                write!(f, "A leaf labelled {} is already in the tree", label)
+            }
+            Self::SelfParentCycle(label) => {
+                write!(
+                    f,
+                    "The node labelled {} would make a cycle walking up to the root",
+                    label
+                )
+            }
+            Self::LabelCollidesWithInterior(label) => {
+                write!(
+                    f,
+                    "The leaf labelled {} collides with an interior node",
+                    label
+                )
+            }
+            Self::BitIndexOutOfRange(label, index) => {
+                write!(f, "The label {} has no bit at index {}", label, index)
+            }
+            Self::StateRecordMissing { label, epoch } => {
+                write!(
+                    f,
+                    "The record of the node labelled {}, read at epoch {}, is missing from storage",
+                    label, epoch
+                )
+            }
+            Self::PrefixNotEmpty(prefix) => {
+                write!(f, "The tree has leaves under the prefix {}", prefix)
+            }
         }
     }
 }
@@ -179,10 +235,36 @@ impl fmt::Display for TreeNodeError {
 pub enum AzksError {
     /// Membership proof did not verify
     VerifyMembershipProof(String),
-    /// Append-only proof did not verify
-    VerifyAppendOnlyProof,
     /// Thrown when a place where an epoch is needed wasn't provided one.
     NoEpochGiven,
+    /// The shape of the tree breaks the invariants of a compressed tree
+    TreeIntegrity(String),
+    /// The node at the root label isn't a well-formed root
+    RootInvariantViolated(String),
+    /// A membership proof has more layers than the verifier accepts
+    ProofTooDeep {
+        /// The number of layers in the proof
+        depth: usize,
+        /// The largest number of layers accepted
+        max: usize,
+    },
+    /// The label of a leaf to insert isn't the one the VRF derives for the supplied proof
+    InvalidLabelDerivation(NodeLabel),
+    /// An operation which needs an empty tree found one with this many nodes
+    TreeNotEmpty(u64),
+    /// Nodes in storage which can't be reached from the root
+    OrphanedNodes(Vec<NodeLabel>),
+    /// A checkpoint label shorter than a full label, which could be a prefix of other labels
+    InvalidCheckpointLabel(NodeLabel),
+    /// The metadata of a leaf is longer than [crate::storage::types::LeafMetadata::MAX_DATA_LEN]
+    LeafMetadataTooLong {
+        /// The label of the leaf
+        label: NodeLabel,
+        /// The length of the metadata, in bytes
+        len: usize,
+    },
+    /// No root hash was recorded when the epoch was published
+    RootHashNotRecorded(u64),
 }
 
 impl std::error::Error for AzksError {}
@@ -193,12 +275,63 @@ impl fmt::Display for AzksError {
             Self::VerifyMembershipProof(error_string) => {
                 write!(f, "{}", error_string)
             }
-            Self::VerifyAppendOnlyProof => {
-                write!(f, "Append only proof did not verify!")
-            }
             Self::NoEpochGiven => {
                 write!(f, "An epoch was required but not supplied")
             }
+            Self::TreeIntegrity(error_string) => {
+                write!(f, "Tree integrity check failed: {}", error_string)
+            }
+            Self::RootInvariantViolated(error_string) => {
+                write!(f, "Root invariant violated: {}", error_string)
+            }
+            Self::ProofTooDeep { depth, max } => {
+                write!(
+                    f,
+                    "Proof has {} layers, more than the maximum of {}",
+                    depth, max
+                )
+            }
+            Self::InvalidLabelDerivation(label) => {
+                write!(
+                    f,
+                    "The label {} is not derived by the VRF for the supplied proof",
+                    label
+                )
+            }
+            Self::TreeNotEmpty(num_nodes) => {
+                write!(f, "Expected an empty tree, but it has {} nodes", num_nodes)
+            }
+            Self::OrphanedNodes(labels) => {
+                let labels = labels
+                    .iter()
+                    .map(|label| label.to_string())
+                    .collect::<Vec<_>>();
+                write!(
+                    f,
+                    "Found {} nodes unreachable from the root: {}",
+                    labels.len(),
+                    labels.join(", ")
+                )
+            }
+            Self::InvalidCheckpointLabel(label) => {
+                write!(
+                    f,
+                    "Checkpoint label {} could be a prefix of the labels of other leaves",
+                    label
+                )
+            }
+            Self::LeafMetadataTooLong { label, len } => {
+                write!(
+                    f,
+                    "Metadata of leaf {} is {} bytes, more than the maximum of {}",
+                    label,
+                    len,
+                    crate::storage::types::LeafMetadata::MAX_DATA_LEN
+                )
+            }
+            Self::RootHashNotRecorded(epoch) => {
+                write!(f, "No root hash was recorded for epoch {}", epoch)
+            }
         }
     }
 }
@@ -215,6 +348,18 @@ pub enum DirectoryError {
     InvalidEpoch(String),
     /// AZKS not found in read-only directory mode
     ReadOnlyDirectory(String),
+    /// A stored value state disagrees with the leaf committed to the tree for it
+    InconsistentValueState(String),
+    /// A publish committed its epoch, but the epoch couldn't be read back afterwards. The
+    /// epoch stays committed: it isn't rolled back, and mustn't be published again.
+    CommittedNotReadable {
+        /// The epoch which was committed
+        epoch: u64,
+        /// Why the epoch couldn't be read back
+        error: StorageError,
+    },
+    /// The system clock reads earlier than the UNIX epoch, so the publish can't be timestamped
+    ClockBeforeUnixEpoch(String),
 }
 
 impl std::error::Error for DirectoryError {}
@@ -234,6 +379,23 @@ impl fmt::Display for DirectoryError {
             Self::ReadOnlyDirectory(inner_message) => {
                 write!(f, "Directory in read-only mode: {}", inner_message)
             }
+            Self::InconsistentValueState(err_string) => {
+                write!(
+                    f,
+                    "Value state is inconsistent with the tree: {}",
+                    err_string
+                )
+            }
+            Self::CommittedNotReadable { epoch, error } => {
+                write!(
+                    f,
+                    "Epoch {} was committed but can't be read back: {}",
+                    epoch, error
+                )
+            }
+            Self::ClockBeforeUnixEpoch(err_string) => {
+                write!(f, "System clock is before the UNIX epoch: {}", err_string)
+            }
         }
     }
 }
@@ -250,6 +412,30 @@ pub enum StorageError {
     Connection(String),
     /// Some other storage-layer error occurred
     Other(String),
+    /// A storage operation didn't complete within its deadline
+    Timeout {
+        /// The storage operation which timed out
+        operation: String,
+        /// How long the operation ran for before it was abandoned
+        elapsed: std::time::Duration,
+    },
+    /// Another writer advanced the epoch between when a publish read it and when it committed
+    EpochRaceLost {
+        /// The epoch the committing writer expected to find in storage
+        expected: u64,
+        /// The epoch which was actually found in storage
+        actual: u64,
+    },
+    /// A record which was written couldn't be read back within the attempts allowed by the
+    /// read-after-write policy of the commit
+    WriteNotVisible {
+        /// A description of the record which couldn't be read back
+        key: String,
+        /// The number of times the record was read
+        attempts: u32,
+    },
+    /// The transaction committing the given epoch doesn't record the epoch's root hash
+    RootHashNotRecorded(u64),
 }
 
 impl std::error::Error for StorageError {}
@@ -269,6 +455,26 @@ impl fmt::Display for StorageError {
             StorageError::Other(inner) => {
                 write!(f, "Other storage error: {}", inner)
             }
+            StorageError::Timeout { operation, elapsed } => {
+                write!(
+                    f,
+                    "Storage operation {} timed out after {:?}",
+                    operation, elapsed
+                )
+            }
+            StorageError::EpochRaceLost { expected, actual } => {
+                write!(
+                    f,
+                    "Epoch race lost: expected current epoch {}, found {}",
+                    expected, actual
+                )
+            }
+            StorageError::WriteNotVisible { key, attempts } => {
+                write!(f, "Written {} not visible after {} reads", key, attempts)
+            }
+            StorageError::RootHashNotRecorded(epoch) => {
+                write!(f, "Commit of epoch {} doesn't record its root hash", epoch)
+            }
         }
     }
 }
@@ -304,12 +510,36 @@ impl fmt::Display for VrfError {
     }
 }
 
-/// The errors thrown by various algorithms in [crate::directory::Directory]
+/// The errors thrown by the auditor when an append-only proof does not verify
 #[cfg_attr(test, derive(PartialEq))]
 #[derive(Debug)]
 pub enum AuditorError {
-    /// A general auditor error
-    VerifyAuditProof(String),
+    /// The unchanged nodes of the proof for the given epoch do not hash to the start root hash.
+    /// Holds the epoch, then the expected and computed hashes in hex.
+    StartHashMismatch(u64, String, String),
+    /// The inserted and unchanged nodes of the proof for the given epoch do not hash to the
+    /// end root hash. Holds the epoch, then the expected and computed hashes in hex.
+    EndHashMismatch(u64, String, String),
+    /// The proof skips over epochs, going from the first epoch to the second
+    EpochGap(u64, u64),
+    /// The proof is not structured correctly
+    MalformedProof(String),
+    /// A leaf of the proof is bound to an epoch outside of the range it covers. Holds the
+    /// leaf's label and epoch, then the start and end epochs of the range.
+    LeafEpochMismatch(NodeLabel, u64, u64, u64),
+    /// The inserted nodes of an append-only proof contain the given label more than once
+    DuplicateInsertedLeaf(NodeLabel),
+    /// The audit bundle was generated with a different hash function than the verifier expects
+    AlgorithmMismatch {
+        /// The hash function the verifier expects
+        expected: AuditHasher,
+        /// The hash function recorded in the bundle
+        got: AuditHasher,
+    },
+    /// The epoch isn't in the range of epochs appended to an [`crate::auditor::EpochRootMmr`]
+    EpochNotInMmr(u64),
+    /// An epoch inclusion proof did not verify against the MMR root
+    VerifyEpochInclusion(String),
 }
 
 impl std::error::Error for AuditorError {}
@@ -317,8 +547,58 @@ impl std::error::Error for AuditorError {}
 impl fmt::Display for AuditorError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::VerifyAuditProof(err_string) => {
-                write!(f, "Failed to verify audit {}", err_string)
+            Self::StartHashMismatch(epoch, expected, computed) => {
+                write!(
+                    f,
+                    "Append-only proof for epoch {} does not match the start root hash \
+                    (expected {}, computed {})",
+                    epoch, expected, computed
+                )
+            }
+            Self::EndHashMismatch(epoch, expected, computed) => {
+                write!(
+                    f,
+                    "Append-only proof for epoch {} does not match the end root hash \
+                    (expected {}, computed {})",
+                    epoch, expected, computed
+                )
+            }
+            Self::EpochGap(epoch, next_epoch) => {
+                write!(
+                    f,
+                    "Audit proof epochs are not consecutive, found {} followed by {}",
+                    epoch, next_epoch
+                )
+            }
+            Self::MalformedProof(err_string) => {
+                write!(f, "Malformed audit proof: {}", err_string)
+            }
+            Self::LeafEpochMismatch(label, epoch, start_epoch, end_epoch) => {
+                write!(
+                    f,
+                    "The leaf {} was inserted at epoch {}, outside of the range ({}, {}]",
+                    label, epoch, start_epoch, end_epoch
+                )
+            }
+            Self::DuplicateInsertedLeaf(label) => {
+                write!(
+                    f,
+                    "Append-only proof inserts the label {} more than once",
+                    label
+                )
+            }
+            Self::AlgorithmMismatch { expected, got } => {
+                write!(
+                    f,
+                    "Audit bundle was generated with {:?} hashing, expected {:?}",
+                    got, expected
+                )
+            }
+            Self::EpochNotInMmr(epoch) => {
+                write!(f, "Epoch {} has not been appended to the MMR", epoch)
+            }
+            Self::VerifyEpochInclusion(err_string) => {
+                write!(f, "Failed to verify epoch inclusion: {}", err_string)
             }
         }
     }
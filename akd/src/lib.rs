@@ -307,6 +307,12 @@
 //! akd = { version = "0.5", features = ["vrf", "public-tests"] }
 //! ```
 //!
+//! 4. _tokio_runtime_ (on by-default): Will enable the functionality which relies on timers, and therefore needs to be run
+//!    under a [`tokio`] runtime. This is the [`storage::timeout`] storage wrapper, [`Directory::poll_for_azks_changes`], and
+//!    retrying with a backoff in [`storage::ReadAfterWritePolicy`]. Everything else (publishing, proof generation, and the
+//!    in-memory storage layers) only uses the runtime-independent synchronization primitives of [`tokio`], so with the
+//!    no-default-features flags the directory can be driven by any executor (e.g. `async-std`, `smol` or a custom one).
+//!
 
 #![warn(missing_docs)]
 #![allow(clippy::multiple_crate_versions)]
@@ -329,6 +335,8 @@ pub mod errors;
 pub mod helper_structs;
 pub mod node_label;
 pub mod proof_structs;
+#[cfg(feature = "protobuf_serialization")]
+pub mod proto;
 pub mod serialization;
 pub mod storage;
 pub mod tree_node;
@@ -336,11 +344,12 @@ pub mod tree_node;
 mod utils;
 
 // ========== Type re-exports which are commonly used ========== //
-pub use append_only_zks::Azks;
+pub use append_only_zks::{Azks, InsertPolicy};
 pub use directory::Directory;
 pub use helper_structs::{EpochHash, Node};
 pub use node_label::NodeLabel;
 pub use storage::types::{AkdLabel, AkdValue};
+pub use utils::{dummy_child_hash, empty_node_hash};
 
 // ========== Constants and type aliases ========== //
 #[cfg(any(test, feature = "public-tests"))]
@@ -7,6 +7,7 @@
 
 //! The representation for the label of a tree node.
 
+use crate::errors::TreeNodeError;
 #[cfg(feature = "serde_serialization")]
 use crate::serialization::{bytes_deserialize_hex, bytes_serialize_hex};
 use crate::{Direction, EMPTY_LABEL};
@@ -20,6 +21,10 @@ use std::{
 };
 use winter_crypto::Hasher;
 
+/// The domain separator prefixed to the name of a checkpoint when deriving its label, see
+/// [NodeLabel::checkpoint]
+pub const CHECKPOINT_LABEL_DOMAIN: &[u8] = b"akd_checkpoint_label";
+
 /// The NodeLabel struct represents the label for a TreeNode.
 /// Since the label itself may have any number of zeros pre-pended,
 /// just using a native type, unless it is a bit-vector, wouldn't work.
@@ -63,14 +68,39 @@ impl Ord for NodeLabel {
     }
 }
 
+/// Labels longer than this are displayed with the bits in the middle elided
+const DISPLAY_MAX_BITS: u32 = 32;
+/// The number of bits displayed at either end of a label whose middle is elided
+const DISPLAY_EDGE_BITS: u32 = 16;
+
+/// Displays the path of the label through the tree as a bit string, followed by its length,
+/// e.g. `0b1011 (len=4)`. Only the first and last few bits of long labels are shown, e.g.
+/// `0b0110...1001 (len=256)`.
 impl fmt::Display for NodeLabel {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "(0x{}, {})",
-            hex::encode(&self.label_val),
-            self.label_len
-        )
+        let bits = |range: std::ops::Range<u32>| -> String {
+            range
+                .map(|index| {
+                    if self.get_bit_at(index) == 1 {
+                        '1'
+                    } else {
+                        '0'
+                    }
+                })
+                .collect()
+        };
+        let len = self.label_len;
+        if len > DISPLAY_MAX_BITS {
+            write!(
+                f,
+                "0b{}...{} (len={})",
+                bits(0..DISPLAY_EDGE_BITS),
+                bits(len - DISPLAY_EDGE_BITS..len),
+                len
+            )
+        } else {
+            write!(f, "0b{} (len={})", bits(0..len), len)
+        }
     }
 }
 
@@ -108,6 +138,26 @@ impl NodeLabel {
         }
     }
 
+    /// Creates a full-length (256-bit) label from the bytes of a digest, for placing a leaf in
+    /// the tree. Bits are read MSB-first, i.e. bit 0 of the label (the first branch taken from
+    /// the root) is the most significant bit of the digest's first byte.
+    pub fn from_digest<H: Hasher>(digest: H::Digest) -> Self {
+        Self::new(crate::serialization::from_digest::<H>(digest), 256)
+    }
+
+    /// Returns the reserved label of the checkpoint leaf named `name`, see
+    /// [crate::append_only_zks::Azks::insert_checkpoint].
+    ///
+    /// Every 256-bit label is a possible VRF output, and a shorter label can't hold a leaf
+    /// since it would be a prefix of the labels of other leaves, so no label lies strictly
+    /// outside the VRF label space. Instead the label is the hash of `name` behind the
+    /// [CHECKPOINT_LABEL_DOMAIN] separator: the hash of a domain-separated input is
+    /// independent of the VRF outputs, so a user's label collides with it with negligible
+    /// probability, as long as each checkpoint has a distinct name.
+    pub fn checkpoint<H: Hasher>(name: &[u8]) -> Self {
+        Self::from_digest::<H>(H::hash(&[CHECKPOINT_LABEL_DOMAIN, name].concat()))
+    }
+
     /// Returns the bit at a specified index, and a 0 on an out of range index
     /// Note that this is calculated from the right, for example:
     /// let mut label = [0u8; 32];
@@ -122,7 +172,7 @@ impl NodeLabel {
     /// * label.get_bit_at(6) = 0
     /// * label.get_bit_at(7) = 0
     fn get_bit_at(&self, index: u32) -> u8 {
-        if index >= self.label_len {
+        if index >= self.label_len || index >= 256 {
             return 0;
         }
 
@@ -132,6 +182,35 @@ impl NodeLabel {
         (self.label_val[index_full_blocks] >> (7 - index_remainder)) & 1
     }
 
+    /// Returns the bit at the given index, counting from the most significant bit of the label
+    /// at index 0, as a node's path through the tree does: a set bit at the index of a node's
+    /// length means the label is to the right of that node. Unlike the tree's own bit access,
+    /// which reads bits past the end of a label as 0, an index outside of the label is an
+    /// error, e.g. index 3 of `0b101 (len=3)`.
+    pub fn bit_at(&self, index: u32) -> Result<bool, TreeNodeError> {
+        if index >= self.label_len {
+            return Err(TreeNodeError::BitIndexOutOfRange(*self, index));
+        }
+        Ok(self.get_bit_at(index) == 1)
+    }
+
+    /// Returns the bits of the label packed into as few bytes as hold them, most significant
+    /// bit first, along with the number of bits. In the (binary) tree a label *is* the sequence
+    /// of directions from the root: the bit at index `i` is the direction (0 for left, 1 for
+    /// right) taken by the path to the label from its ancestor of length `i`. So the directions
+    /// along a path needn't be sent with the label it leads to, see
+    /// [crate::proof_structs::CompactMembershipProof]. Any bits past the end of the label in
+    /// the last byte are cleared.
+    pub fn direction_bits(&self) -> (Vec<u8>, u32) {
+        let len = self.get_len().min(256);
+        let num_bytes = len.div_ceil(8) as usize;
+        let mut bits = self.label_val[..num_bytes].to_vec();
+        if let Some(last) = bits.last_mut() {
+            *last &= 0xffu8 << ((8 - len % 8) % 8);
+        }
+        (bits, len)
+    }
+
     /// Returns the prefix of a specified length, and the entire value on an out of range length
     pub(crate) fn get_prefix(&self, len: u32) -> Self {
         if len >= self.get_len() {
@@ -152,6 +231,12 @@ impl NodeLabel {
         Self::new(out_val, len)
     }
 
+    /// Returns whether this label is a prefix of `other`. Every label is a prefix of itself,
+    /// and the root label is a prefix of every label.
+    pub fn is_prefix_of(&self, other: &Self) -> bool {
+        self.get_len() <= other.get_len() && other.get_prefix(self.get_len()) == *self
+    }
+
     // The sibling of a node in a binary tree has the same label as its sibling
     // except its last bit is flipped (e.g., 000 and 001 are siblings).
     // This function returns the sibling prefix of a specified length.
@@ -183,6 +268,27 @@ impl NodeLabel {
         Self::new(out_val, len)
     }
 
+    /// Returns the label of the sibling position of this label, i.e. the label with its last
+    /// bit flipped, or None for the root label, which has no sibling. In a compressed tree a
+    /// node may not be at the sibling position itself, but the sibling node (if any) has the
+    /// returned label as a prefix.
+    pub fn sibling(&self) -> Option<Self> {
+        if self.get_len() == 0 {
+            return None;
+        }
+        Some(self.get_sibling_prefix(self.get_len()))
+    }
+
+    /// Returns the label of the parent position of this label, i.e. the label without its
+    /// last bit, or None for the root label. In a compressed tree the parent node of a node
+    /// with this label is at the returned label or at one of its prefixes.
+    pub fn parent(&self) -> Option<Self> {
+        if self.get_len() == 0 {
+            return None;
+        }
+        Some(self.get_prefix(self.get_len() - 1))
+    }
+
     /// Takes as input a pointer to the caller and another NodeLabel,
     /// returns a NodeLabel that is the longest common prefix of the two.
     #[must_use]
@@ -238,6 +344,17 @@ pub fn hash_label<H: Hasher>(label: NodeLabel) -> H::Digest {
     H::hash(&hash_input)
 }
 
+/// Returns every prefix of the label, from the root (length 0) up to the label itself,
+/// ordered by increasing length. These are all the positions a node on the path from the
+/// root to the label could occupy, regardless of which of them exist in a given tree.
+pub fn ancestor_prefixes(label: NodeLabel) -> Vec<NodeLabel> {
+    let mut prefixes = Vec::with_capacity(label.get_len() as usize + 1);
+    for len in 0..(label.get_len() + 1) {
+        prefixes.push(label.get_prefix(len));
+    }
+    prefixes
+}
+
 // Creates a byte array of 32 bytes from a u64
 // Note that this representation is big-endian, and
 // places the bits to the front of the output byte_array.
@@ -306,6 +423,27 @@ mod tests {
         }
     }
 
+    #[test]
+    pub fn test_bit_at() {
+        let label = NodeLabel::new(byte_arr_from_u64(0b1011u64 << 60), 4);
+        assert_eq!(Ok(true), label.bit_at(0));
+        assert_eq!(Ok(false), label.bit_at(1));
+        assert_eq!(Ok(true), label.bit_at(3));
+        assert_eq!(
+            Err(TreeNodeError::BitIndexOutOfRange(label, 4)),
+            label.bit_at(4)
+        );
+
+        // the root label has no bits at all, while a full label has 256 of them
+        assert_eq!(
+            Err(TreeNodeError::BitIndexOutOfRange(NodeLabel::root(), 0)),
+            NodeLabel::root().bit_at(0)
+        );
+        let label = NodeLabel::new([0xffu8; 32], 256);
+        assert_eq!(Ok(true), label.bit_at(255));
+        assert!(label.bit_at(256).is_err());
+    }
+
     /// In this test, we have a label of length 256, logically equal to
     /// 1 followed by 255 0s. We want to make sure its 0th bit is read out as 1.
     #[test]
@@ -659,6 +797,109 @@ mod tests {
         )
     }
 
+    /// Test for is_prefix_of with prefixes of a label, itself, and the root label.
+    #[test]
+    pub fn test_is_prefix_of() {
+        let label_1 = NodeLabel::new(byte_arr_from_u64(0b11010000u64 << 56), 8u32);
+        let label_2 = NodeLabel::new(byte_arr_from_u64(0b1101u64 << 60), 4u32);
+        let label_3 = NodeLabel::new(byte_arr_from_u64(0b1100u64 << 60), 4u32);
+
+        assert!(label_2.is_prefix_of(&label_1));
+        assert!(!label_1.is_prefix_of(&label_2));
+        assert!(!label_3.is_prefix_of(&label_1));
+
+        // A label is a prefix of itself
+        assert!(label_1.is_prefix_of(&label_1));
+
+        // The root label is a prefix of everything, but only the root is a prefix of it
+        let root = NodeLabel::root();
+        assert!(root.is_prefix_of(&label_1));
+        assert!(root.is_prefix_of(&root));
+        assert!(!label_1.is_prefix_of(&root));
+    }
+
+    /// Test for is_prefix_of against get_prefix on random labels.
+    #[test]
+    pub fn test_is_prefix_of_random() {
+        let mut rng = OsRng;
+        for len in 0..257 {
+            let label = NodeLabel::random(&mut rng);
+            let prefix = label.get_prefix(len);
+            assert!(prefix.is_prefix_of(&label));
+            if len > 0 {
+                assert!(!label.get_sibling_prefix(len).is_prefix_of(&label));
+            }
+        }
+    }
+
+    /// The root label is its own only ancestor prefix.
+    #[test]
+    pub fn test_ancestor_prefixes_root() {
+        assert_eq!(
+            vec![NodeLabel::root()],
+            ancestor_prefixes(NodeLabel::root())
+        );
+    }
+
+    /// A full-length label has a prefix of every length, each a prefix of the next.
+    #[test]
+    pub fn test_ancestor_prefixes_full_length() {
+        let mut rng = OsRng;
+        let label = NodeLabel::random(&mut rng);
+        let prefixes = ancestor_prefixes(label);
+        assert_eq!(257, prefixes.len());
+        assert_eq!(NodeLabel::root(), prefixes[0]);
+        assert_eq!(label, prefixes[256]);
+        for (len, prefix) in prefixes.iter().enumerate() {
+            assert_eq!(len as u32, prefix.get_len());
+            assert!(prefix.is_prefix_of(&label));
+        }
+    }
+
+    /// Labels are displayed as bit strings, eliding the middle of long ones.
+    #[test]
+    pub fn test_display() {
+        let label = NodeLabel::new(byte_arr_from_u64(0b1011u64 << 60), 4);
+        assert_eq!("0b1011 (len=4)", label.to_string());
+        assert_eq!("0b (len=0)", NodeLabel::root().to_string());
+
+        let mut val = [0u8; 32];
+        val[0] = 0b1000_0001u8;
+        val[31] = 0b0000_0011u8;
+        let label = NodeLabel::new(val, 256);
+        assert_eq!(
+            "0b1000000100000000...0000000000000011 (len=256)",
+            label.to_string()
+        );
+    }
+
+    /// A label built from a digest is full-length, and its bits are those of the digest
+    /// read MSB-first.
+    #[test]
+    pub fn test_from_digest() {
+        use winter_crypto::hashers::Blake3_256;
+        use winter_math::fields::f128::BaseElement;
+
+        type Blake3 = Blake3_256<BaseElement>;
+
+        let digest = Blake3::hash(b"hello, world!");
+        let bytes = crate::serialization::from_digest::<Blake3>(digest);
+        let label = NodeLabel::from_digest::<Blake3>(digest);
+        assert_eq!(256, label.get_len());
+        assert_eq!(bytes, label.get_val());
+        for index in 0..256u32 {
+            let expected = (bytes[index as usize / 8] >> (7 - index % 8)) & 1;
+            assert_eq!(expected, label.get_bit_at(index));
+        }
+
+        // only the top bit of the first byte is set, so the leaf sits to the right of the root
+        let mut val = [0u8; 32];
+        val[0] = 0b1000_0000u8;
+        let digest = crate::serialization::to_digest::<Blake3>(&val).unwrap();
+        let label = NodeLabel::from_digest::<Blake3>(digest);
+        assert_eq!(Direction::Some(1), NodeLabel::root().get_dir(label));
+    }
+
     /// This test tests get_dir by manually computing the prefix and the bit
     /// immediately following the prefix of that length.
     #[test]
@@ -774,6 +1015,67 @@ mod tests {
         );
     }
 
+    /// Tests that sibling and parent labels flip and drop the last bit of a label, for labels
+    /// of every length of a random label, down to the root which has neither.
+    #[test]
+    pub fn test_sibling_and_parent() {
+        let mut rng = OsRng;
+        let full_label = NodeLabel::random(&mut rng);
+        for len in 1..=256u32 {
+            let label = full_label.get_prefix(len);
+            let sibling = label.sibling().unwrap();
+            let parent = label.parent().unwrap();
+            assert_eq!(Some(label), sibling.sibling());
+            assert_ne!(label, sibling);
+            assert_eq!(len, sibling.get_len());
+            assert_eq!(len - 1, parent.get_len());
+            assert_eq!(Some(parent), sibling.parent());
+            assert_eq!(parent, label.get_longest_common_prefix(sibling));
+        }
+
+        let label = NodeLabel::new(byte_arr_from_u64(0b1010u64 << 60), 4);
+        assert_eq!(
+            Some(NodeLabel::new(byte_arr_from_u64(0b1011u64 << 60), 4)),
+            label.sibling()
+        );
+        assert_eq!(
+            Some(NodeLabel::new(byte_arr_from_u64(0b101u64 << 61), 3)),
+            label.parent()
+        );
+
+        assert_eq!(None, NodeLabel::root().sibling());
+        assert_eq!(None, NodeLabel::root().parent());
+    }
+
+    /// Tests that the packed direction bits of a label are the directions taken by walking
+    /// down from the root to the label, one bit at a time.
+    #[test]
+    pub fn test_direction_bits() {
+        let mut rng = OsRng;
+        let full_label = NodeLabel::random(&mut rng);
+        for len in [0u32, 1, 7, 8, 9, 100, 255, 256] {
+            let label = full_label.get_prefix(len);
+            let (bits, count) = label.direction_bits();
+            assert_eq!(len, count);
+            assert!(bits.len() * 8 >= len as usize && bits.len() * 8 < len as usize + 8);
+
+            let mut node = NodeLabel::root();
+            for index in 0..len {
+                let direction = node.get_dir(label).unwrap();
+                assert_eq!(
+                    direction as u8,
+                    (bits[index as usize / 8] >> (7 - index % 8)) & 1
+                );
+                node = label.get_prefix(index + 1);
+            }
+            assert_eq!(node, label);
+        }
+
+        // Bits past the end of the label are cleared
+        let label = NodeLabel::new([0xffu8; 32], 11);
+        assert_eq!((vec![0xffu8, 0b1110_0000], 11), label.direction_bits());
+    }
+
     // Test for serialization / deserialization
     #[test]
     pub fn serialize_deserialize() {
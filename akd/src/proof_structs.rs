@@ -9,9 +9,26 @@
 //! while the proofs [`HistoryProof`] and [`LookupProof`] are AKD proofs.
 
 #[cfg(feature = "serde_serialization")]
-use crate::serialization::{digest_deserialize, digest_serialize};
-use crate::{node_label::NodeLabel, storage::types::AkdValue, Direction, Node, ARITY};
-use winter_crypto::Hasher;
+use crate::serialization::{
+    digest_deserialize, digest_serialize, digests_deserialize, digests_serialize,
+};
+use crate::{
+    errors::{AkdError, AuditorError},
+    node_label::NodeLabel,
+    storage::types::AkdValue,
+    Direction, Node, ARITY,
+};
+use std::collections::{BTreeMap, HashMap};
+use winter_crypto::{Digest, Hasher};
+
+#[cfg(feature = "protobuf_serialization")]
+use crate::proto::ConversionError;
+#[cfg(any(feature = "serde_serialization", feature = "protobuf_serialization"))]
+use crate::serialization::SerializationFormat;
+#[cfg(feature = "protobuf_serialization")]
+use protobuf::Message;
+#[cfg(feature = "protobuf_serialization")]
+use std::convert::TryFrom;
 
 /// Proof value at a single layer of the tree
 /// Note that this is really a helper struct to
@@ -75,6 +92,115 @@ pub struct MembershipProof<H: Hasher> {
     pub layer_proofs: Vec<LayerProof<H>>,
 }
 
+#[cfg(any(feature = "serde_serialization", feature = "protobuf_serialization"))]
+impl<H: Hasher> MembershipProof<H> {
+    /// Serializes the proof in the given format
+    pub fn to_bytes(&self, format: SerializationFormat) -> Result<Vec<u8>, AkdError> {
+        match format {
+            #[cfg(feature = "serde_serialization")]
+            SerializationFormat::Bincode => {
+                bincode::serialize(self).map_err(|err| AkdError::Serialization(err.to_string()))
+            }
+            #[cfg(feature = "protobuf_serialization")]
+            SerializationFormat::Protobuf => {
+                let message = crate::proto::specs::types::MembershipProof::from(self);
+                Ok(message.write_to_bytes().map_err(ConversionError::from)?)
+            }
+        }
+    }
+
+    /// Deserializes a proof written by [`MembershipProof::to_bytes`] in the given format
+    pub fn from_bytes(format: SerializationFormat, bytes: &[u8]) -> Result<Self, AkdError> {
+        match format {
+            #[cfg(feature = "serde_serialization")]
+            SerializationFormat::Bincode => {
+                bincode::deserialize(bytes).map_err(|err| AkdError::Serialization(err.to_string()))
+            }
+            #[cfg(feature = "protobuf_serialization")]
+            SerializationFormat::Protobuf => {
+                let message = crate::proto::specs::types::MembershipProof::parse_from_bytes(bytes)
+                    .map_err(ConversionError::from)?;
+                Ok(Self::try_from(&message)?)
+            }
+        }
+    }
+}
+
+impl<H: Hasher> MembershipProof<H> {
+    /// Renders the proof as a JSON object, for verifiers written in other languages (e.g.
+    /// browser clients) which can't read the binary formats. The object is compact (no
+    /// whitespace), all digests and label values are lowercase hex, and it has the layout
+    ///
+    /// ```text
+    /// MembershipProof = {
+    ///   "label": Label,            // the label of the leaf
+    ///   "hash_val": Hex,           // the hash of the leaf, including its epoch
+    ///   "layer_proofs": [Layer]    // ordered from the root down to the leaf
+    /// }
+    /// Layer = {
+    ///   "label": Label,            // the label of the parent at this layer
+    ///   "siblings": [Sibling],     // the other children of the parent
+    ///   "direction": "left" | "right" | null
+    /// }
+    /// Sibling = { "label": Label, "hash": Hex }
+    /// Label = { "value": Hex, "length": Number }  // value is 32 bytes, length in bits
+    /// Hex = String                 // 64 lowercase hex characters
+    /// ```
+    ///
+    /// The direction is the side of the parent on which the path to the leaf continues, i.e.
+    /// the node on the path is hashed before its sibling for `"left"` and after it for
+    /// `"right"`, see [crate::client::verify_merkle_path]. It's null for a layer without a
+    /// valid direction, which never verifies.
+    pub fn to_json(&self) -> String {
+        let layer_proofs = self
+            .layer_proofs
+            .iter()
+            .map(|layer| {
+                let siblings = layer
+                    .siblings
+                    .iter()
+                    .map(|sibling| {
+                        format!(
+                            "{{\"label\":{},\"hash\":\"{}\"}}",
+                            label_to_json(&sibling.label),
+                            digest_to_hex::<H>(sibling.hash)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                let direction = match layer.direction {
+                    Some(0) => "\"left\"",
+                    Some(1) => "\"right\"",
+                    _ => "null",
+                };
+                format!(
+                    "{{\"label\":{},\"siblings\":[{}],\"direction\":{}}}",
+                    label_to_json(&layer.label),
+                    siblings.join(","),
+                    direction
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"label\":{},\"hash_val\":\"{}\",\"layer_proofs\":[{}]}}",
+            label_to_json(&self.label),
+            digest_to_hex::<H>(self.hash_val),
+            layer_proofs.join(",")
+        )
+    }
+}
+
+fn label_to_json(label: &NodeLabel) -> String {
+    format!(
+        "{{\"value\":\"{}\",\"length\":{}}}",
+        hex::encode(label.get_val()),
+        label.get_len()
+    )
+}
+
+fn digest_to_hex<H: Hasher>(digest: H::Digest) -> String {
+    hex::encode(crate::serialization::from_digest::<H>(digest))
+}
+
 // Manual implementation of Clone, see: https://github.com/rust-lang/rust/issues/41481
 impl<H: Hasher> Clone for MembershipProof<H> {
     fn clone(&self) -> Self {
@@ -86,6 +212,117 @@ impl<H: Hasher> Clone for MembershipProof<H> {
     }
 }
 
+impl<H: Hasher> MembershipProof<H> {
+    /// Builds the [`CompactMembershipProof`] representation of this proof, which keeps only
+    /// the length of each layer's label and leaves out its direction.
+    pub fn compact(&self) -> CompactMembershipProof<H> {
+        CompactMembershipProof {
+            label: self.label,
+            hash_val: self.hash_val,
+            layer_proofs: self
+                .layer_proofs
+                .iter()
+                .map(|layer| CompactLayerProof {
+                    prefix_len: layer.label.get_len(),
+                    siblings: layer.siblings,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A compacted [`MembershipProof`]. The label of each layer is a prefix of the proven label,
+/// so only its length is sent, and the direction of the path at the layer is the bit of the
+/// proven label at the index of that length (see [`NodeLabel::direction_bits`]), so it's
+/// implied by the label rather than sent with every layer.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "serde_serialization", serde(bound = ""))]
+pub struct CompactMembershipProof<H: Hasher> {
+    /// The node label
+    pub label: NodeLabel,
+    /// The hash of the value
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "digest_serialize")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "digest_deserialize")
+    )]
+    pub hash_val: H::Digest,
+    /// The proofs at the layers up the tree, without their directions
+    pub layer_proofs: Vec<CompactLayerProof<H>>,
+}
+
+// Manual implementation of Clone, see: https://github.com/rust-lang/rust/issues/41481
+impl<H: Hasher> Clone for CompactMembershipProof<H> {
+    fn clone(&self) -> Self {
+        Self {
+            label: self.label,
+            hash_val: self.hash_val,
+            layer_proofs: self.layer_proofs.clone(),
+        }
+    }
+}
+
+impl<H: Hasher> CompactMembershipProof<H> {
+    /// Expands the proof back into the full [`MembershipProof`], taking the label and the
+    /// direction of each layer from the proven label. A layer whose prefix is at least as
+    /// long as the proven label gets no direction, so the expanded proof doesn't verify.
+    pub fn expand(&self) -> MembershipProof<H> {
+        let (bits, len) = self.label.direction_bits();
+        MembershipProof {
+            label: self.label,
+            hash_val: self.hash_val,
+            layer_proofs: self
+                .layer_proofs
+                .iter()
+                .map(|layer| {
+                    let index = layer.prefix_len;
+                    let direction = if index < len {
+                        Some(((bits[index as usize / 8] >> (7 - index % 8)) & 1) as usize)
+                    } else {
+                        None
+                    };
+                    LayerProof {
+                        label: self.label.get_prefix(index),
+                        siblings: layer.siblings,
+                        direction,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A [`LayerProof`] without its direction, as part of a [`CompactMembershipProof`]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "serde_serialization", serde(bound = ""))]
+pub struct CompactLayerProof<H: Hasher> {
+    /// The length of the parent's label, which is a prefix of the proven label
+    pub prefix_len: u32,
+    /// Siblings of the parent
+    pub siblings: [Node<H>; ARITY - 1],
+}
+
+// Manual implementation of Clone, see: https://github.com/rust-lang/rust/issues/41481
+impl<H: Hasher> Clone for CompactLayerProof<H> {
+    fn clone(&self) -> Self {
+        Self {
+            prefix_len: self.prefix_len,
+            siblings: self.siblings,
+        }
+    }
+}
+
 /// Merkle Patricia proof of non-membership for a [`NodeLabel`] in the tree
 /// at a given epoch.
 #[derive(Debug, PartialEq)]
@@ -117,6 +354,107 @@ impl<H: Hasher> Clone for NonMembershipProof<H> {
     }
 }
 
+/// Proof that a label is not in the tree, made of membership proofs for the leaves either side
+/// of where it would be: its predecessor and successor in label order, which must be adjacent
+/// leaves of the tree. A label before the first leaf or after the last one only has the one
+/// neighbor, and a label in an empty tree has neither.
+///
+/// This is the form some sparse Merkle tree designs use, so it suits verifiers built around
+/// them or clients which want the neighbors themselves. Otherwise [`NonMembershipProof`] is
+/// preferable: one of the neighbors always lies below the node the label's path ends at,
+/// which that proof stops at, so the two full paths of this form are longer. They share
+/// their nodes down to where they branch, but those are sent once for each neighbor.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "serde_serialization", serde(bound = ""))]
+pub struct CompactNonMembershipProof<H: Hasher> {
+    /// The label in question
+    pub label: NodeLabel,
+    /// The membership proof of the last leaf before the label, if any
+    pub predecessor: Option<MembershipProof<H>>,
+    /// The membership proof of the first leaf after the label, if any
+    pub successor: Option<MembershipProof<H>>,
+}
+
+// Manual implementation of Clone, see: https://github.com/rust-lang/rust/issues/41481
+impl<H: Hasher> Clone for CompactNonMembershipProof<H> {
+    fn clone(&self) -> Self {
+        Self {
+            label: self.label,
+            predecessor: self.predecessor.clone(),
+            successor: self.successor.clone(),
+        }
+    }
+}
+
+/// Proof that no label in the tree starts with a given prefix, e.g. that a namespace of
+/// labels is unused. Like a [`NonMembershipProof`], it's made of the longest prefix in the
+/// tree of the label in question along with that node's children: all the leaves below a
+/// child share its label, so a prefix the children branch away from (or which only reaches
+/// an empty child) has no leaves under it, even if some labels share part of it.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "serde_serialization", serde(bound = ""))]
+pub struct PrefixAbsenceProof<H: Hasher> {
+    /// The prefix in question
+    pub prefix: NodeLabel,
+    /// The longest prefix of the prefix in question in the tree
+    pub longest_prefix: NodeLabel,
+    /// The children of the longest prefix
+    pub longest_prefix_children: [Node<H>; ARITY],
+    /// The membership proof of the longest prefix
+    pub longest_prefix_membership_proof: MembershipProof<H>,
+}
+
+// Manual implementation of Clone, see: https://github.com/rust-lang/rust/issues/41481
+impl<H: Hasher> Clone for PrefixAbsenceProof<H> {
+    fn clone(&self) -> Self {
+        Self {
+            prefix: self.prefix,
+            longest_prefix: self.longest_prefix,
+            longest_prefix_children: self.longest_prefix_children,
+            longest_prefix_membership_proof: self.longest_prefix_membership_proof.clone(),
+        }
+    }
+}
+
+/// Merkle Patricia proof of membership and non-membership for a batch of [`NodeLabel`]s
+/// in the tree at a given epoch. Rather than including a separate sibling path for each
+/// label, the paths from the root share their common internal nodes: the verifier rebuilds
+/// the part of the tree spanned by the `membership` and `siblings` nodes, deriving every
+/// internal node's label as the longest common prefix of its children.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "serde_serialization", serde(bound = ""))]
+pub struct BatchMembershipProof<H: Hasher> {
+    /// The leaves for the labels which are members of the tree
+    pub membership: Vec<Node<H>>,
+    /// The labels which are not members of the tree
+    pub non_membership: Vec<NodeLabel>,
+    /// The roots of the subtrees which branch off of the paths to the labels
+    pub siblings: Vec<Node<H>>,
+}
+
+// Manual implementation of Clone, see: https://github.com/rust-lang/rust/issues/41481
+impl<H: Hasher> Clone for BatchMembershipProof<H> {
+    fn clone(&self) -> Self {
+        Self {
+            membership: self.membership.clone(),
+            non_membership: self.non_membership.clone(),
+            siblings: self.siblings.clone(),
+        }
+    }
+}
+
 /// Proof that no leaves were deleted from the initial epoch.
 /// This is done using a list of SingleAppendOnly proofs, one proof
 /// for each epoch between the initial epoch and final epochs which are
@@ -134,6 +472,280 @@ pub struct AppendOnlyProof<H: Hasher> {
     pub epochs: Vec<u64>,
 }
 
+impl<H: Hasher> AppendOnlyProof<H> {
+    /// Serializes the proof in the given format
+    #[cfg(any(feature = "serde_serialization", feature = "protobuf_serialization"))]
+    pub fn to_bytes(&self, format: SerializationFormat) -> Result<Vec<u8>, AkdError> {
+        match format {
+            #[cfg(feature = "serde_serialization")]
+            SerializationFormat::Bincode => {
+                bincode::serialize(self).map_err(|err| AkdError::Serialization(err.to_string()))
+            }
+            #[cfg(feature = "protobuf_serialization")]
+            SerializationFormat::Protobuf => {
+                let message = crate::proto::specs::types::AppendOnlyProof::from(self);
+                Ok(message.write_to_bytes().map_err(ConversionError::from)?)
+            }
+        }
+    }
+
+    /// Deserializes a proof written by [`AppendOnlyProof::to_bytes`] in the given format
+    #[cfg(any(feature = "serde_serialization", feature = "protobuf_serialization"))]
+    pub fn from_bytes(format: SerializationFormat, bytes: &[u8]) -> Result<Self, AkdError> {
+        match format {
+            #[cfg(feature = "serde_serialization")]
+            SerializationFormat::Bincode => {
+                bincode::deserialize(bytes).map_err(|err| AkdError::Serialization(err.to_string()))
+            }
+            #[cfg(feature = "protobuf_serialization")]
+            SerializationFormat::Protobuf => {
+                let message = crate::proto::specs::types::AppendOnlyProof::parse_from_bytes(bytes)
+                    .map_err(ConversionError::from)?;
+                Ok(Self::try_from(&message)?)
+            }
+        }
+    }
+
+    /// Builds the [`CompactAppendOnlyProof`] representation of this proof, where every
+    /// distinct node across all of the epochs is stored only once.
+    pub fn compact(&self) -> CompactAppendOnlyProof<H> {
+        let mut nodes = Vec::<Node<H>>::new();
+        let mut node_indices = HashMap::<(NodeLabel, [u8; 32]), u64>::new();
+        let mut index_of = |node: &Node<H>| -> u64 {
+            *node_indices
+                .entry((node.label, node.hash.as_bytes()))
+                .or_insert_with(|| {
+                    nodes.push(*node);
+                    (nodes.len() - 1) as u64
+                })
+        };
+
+        let proofs = self
+            .proofs
+            .iter()
+            .map(|proof| CompactSingleAppendOnlyProof {
+                inserted: proof.inserted.iter().map(&mut index_of).collect(),
+                unchanged_nodes: proof.unchanged_nodes.iter().map(&mut index_of).collect(),
+            })
+            .collect();
+
+        CompactAppendOnlyProof {
+            nodes,
+            proofs,
+            epochs: self.epochs.clone(),
+        }
+    }
+
+    /// Lists the differences between this proof and `other`, segment by segment, e.g. to find
+    /// where two servers which should hold the same tree diverge. Proofs generated from the
+    /// same tree over the same epochs are identical, so any difference points at the segment
+    /// (and the node) where the trees differ. Within a segment, nodes are matched up by label
+    /// and the differences are ordered by label. A label repeated within a segment is reported
+    /// once for each of its occurrences, even if the other proof repeats it too. An empty list
+    /// means the proofs are the same up to the order of the nodes within each segment.
+    pub fn diff(&self, other: &Self) -> Vec<ProofDiff> {
+        let num_segments = self
+            .epochs
+            .len()
+            .max(self.proofs.len())
+            .max(other.epochs.len())
+            .max(other.proofs.len());
+        let mut diffs = Vec::new();
+        for segment in 0..num_segments {
+            let ours = self.epochs.get(segment).copied();
+            let theirs = other.epochs.get(segment).copied();
+            if ours != theirs {
+                diffs.push(ProofDiff::Epoch {
+                    segment,
+                    ours,
+                    theirs,
+                });
+            }
+
+            let our_proof = self.proofs.get(segment);
+            let their_proof = other.proofs.get(segment);
+            let unchanged = diff_nodes::<H>(
+                our_proof.map_or(&[], |proof| &proof.unchanged_nodes),
+                their_proof.map_or(&[], |proof| &proof.unchanged_nodes),
+            );
+            for (label, ours, theirs) in unchanged {
+                diffs.push(ProofDiff::UnchangedNode {
+                    segment,
+                    label,
+                    ours,
+                    theirs,
+                });
+            }
+            let inserted = diff_nodes::<H>(
+                our_proof.map_or(&[], |proof| &proof.inserted),
+                their_proof.map_or(&[], |proof| &proof.inserted),
+            );
+            for (label, ours, theirs) in inserted {
+                diffs.push(ProofDiff::Inserted {
+                    segment,
+                    label,
+                    ours,
+                    theirs,
+                });
+            }
+        }
+        diffs
+    }
+}
+
+/// A difference between two [`AppendOnlyProof`]s, as found by [`AppendOnlyProof::diff`].
+/// Segments are indexed from the start of the proofs, and each side holds the value from
+/// the proof `diff` was called on (`ours`) and from the other proof (`theirs`), or `None`
+/// if that proof has no such segment or node. Node hashes are hex encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofDiff {
+    /// The proofs cover different epochs at a segment
+    Epoch {
+        /// The index of the segment
+        segment: usize,
+        /// The epoch of our segment
+        ours: Option<u64>,
+        /// The epoch of their segment
+        theirs: Option<u64>,
+    },
+    /// An unchanged node of a segment is missing from one of the proofs, or has a different
+    /// hash in each
+    UnchangedNode {
+        /// The index of the segment
+        segment: usize,
+        /// The label of the node
+        label: NodeLabel,
+        /// The hash of our node
+        ours: Option<String>,
+        /// The hash of their node
+        theirs: Option<String>,
+    },
+    /// An inserted leaf of a segment is missing from one of the proofs, or has a different
+    /// value in each
+    Inserted {
+        /// The index of the segment
+        segment: usize,
+        /// The label of the leaf
+        label: NodeLabel,
+        /// The value of our leaf
+        ours: Option<String>,
+        /// The value of their leaf
+        theirs: Option<String>,
+    },
+}
+
+type NodeDiff = (NodeLabel, Option<String>, Option<String>);
+
+/// Matches up two sets of nodes by label, returning the labels whose hashes differ along with
+/// each side's hash in hex. A well-formed proof never repeats a label, so a label which
+/// appears more than once in either set is always returned, once for each of its occurrences,
+/// with the occurrences on each side paired up in order.
+fn diff_nodes<H: Hasher>(ours: &[Node<H>], theirs: &[Node<H>]) -> Vec<NodeDiff> {
+    let mut by_label = BTreeMap::<NodeLabel, (Vec<String>, Vec<String>)>::new();
+    for node in ours {
+        by_label
+            .entry(node.label)
+            .or_default()
+            .0
+            .push(digest_to_hex::<H>(node.hash));
+    }
+    for node in theirs {
+        by_label
+            .entry(node.label)
+            .or_default()
+            .1
+            .push(digest_to_hex::<H>(node.hash));
+    }
+    let mut diffs = Vec::new();
+    for (label, (ours, theirs)) in by_label {
+        let occurrences = ours.len().max(theirs.len());
+        for i in 0..occurrences {
+            let (ours, theirs) = (ours.get(i).cloned(), theirs.get(i).cloned());
+            if occurrences > 1 || ours != theirs {
+                diffs.push((label, ours, theirs));
+            }
+        }
+    }
+    diffs
+}
+
+/// A compacted [`AppendOnlyProof`]. Consecutive epochs of a mostly-stable tree share
+/// many of the same unchanged subtree roots, so here each distinct node is stored once
+/// in `nodes` and the proof for each epoch refers to its nodes by their index.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "serde_serialization", serde(bound = ""))]
+pub struct CompactAppendOnlyProof<H: Hasher> {
+    /// The distinct nodes & digests referenced by the proofs
+    pub nodes: Vec<Node<H>>,
+    /// Proof for a single epoch being append-only, referencing `nodes` by index
+    pub proofs: Vec<CompactSingleAppendOnlyProof>,
+    /// Epochs over which this audit is being performed
+    pub epochs: Vec<u64>,
+}
+
+// Manual implementation of Clone, see: https://github.com/rust-lang/rust/issues/41481
+impl<H: Hasher> Clone for CompactAppendOnlyProof<H> {
+    fn clone(&self) -> Self {
+        Self {
+            nodes: self.nodes.clone(),
+            proofs: self.proofs.clone(),
+            epochs: self.epochs.clone(),
+        }
+    }
+}
+
+impl<H: Hasher> CompactAppendOnlyProof<H> {
+    /// Expands the node references back into the full [`AppendOnlyProof`]. Fails if any
+    /// of the proofs references a node index which is out of range.
+    pub fn expand(&self) -> Result<AppendOnlyProof<H>, AkdError> {
+        let resolve = |indices: &[u64]| -> Result<Vec<Node<H>>, AkdError> {
+            indices
+                .iter()
+                .map(|index| {
+                    self.nodes.get(*index as usize).copied().ok_or_else(|| {
+                        AkdError::AuditErr(AuditorError::MalformedProof(format!(
+                            "Node index {} is out of range for a compact proof with {} nodes",
+                            index,
+                            self.nodes.len()
+                        )))
+                    })
+                })
+                .collect()
+        };
+
+        let mut proofs = Vec::<SingleAppendOnlyProof<H>>::new();
+        for proof in self.proofs.iter() {
+            proofs.push(SingleAppendOnlyProof {
+                inserted: resolve(&proof.inserted)?,
+                unchanged_nodes: resolve(&proof.unchanged_nodes)?,
+            });
+        }
+
+        Ok(AppendOnlyProof {
+            proofs,
+            epochs: self.epochs.clone(),
+        })
+    }
+}
+
+/// The proof for a single epoch of a [`CompactAppendOnlyProof`], holding
+/// indices into [`CompactAppendOnlyProof::nodes`] rather than the nodes themselves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct CompactSingleAppendOnlyProof {
+    /// Indices of the inserted nodes & digests
+    pub inserted: Vec<u64>,
+    /// Indices of the unchanged nodes & digests
+    pub unchanged_nodes: Vec<u64>,
+}
+
 /// Proof that no leaves were deleted from the initial epoch.
 /// This means that unchanged_nodes should hash to the initial root hash
 /// and the vec of inserted is the set of leaves inserted between these epochs.
@@ -163,6 +775,89 @@ impl<H: Hasher> Clone for SingleAppendOnlyProof<H> {
     }
 }
 
+/// Proof that no leaves were deleted between two epochs which need not be adjacent, without
+/// the root hashes of the epochs in between. As for a [SingleAppendOnlyProof], the
+/// unchanged_nodes should hash to the initial root hash, and the tree built from them and the
+/// inserted leaves should hash to the final root hash. Since the leaves were inserted over
+/// several epochs, each one is bound to the epoch it was last written in, which is given at
+/// the same index of inserted_epochs.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "serde_serialization", serde(bound = ""))]
+pub struct AppendOnlyRangeProof<H: Hasher> {
+    /// The inserted nodes & digests
+    pub inserted: Vec<Node<H>>,
+    /// The epoch each inserted node was last written in
+    pub inserted_epochs: Vec<u64>,
+    /// The unchanged nodes & digests
+    pub unchanged_nodes: Vec<Node<H>>,
+}
+
+// Manual implementation of Clone, see: https://github.com/rust-lang/rust/issues/41481
+impl<H: Hasher> Clone for AppendOnlyRangeProof<H> {
+    fn clone(&self) -> Self {
+        Self {
+            inserted: self.inserted.clone(),
+            inserted_epochs: self.inserted_epochs.clone(),
+            unchanged_nodes: self.unchanged_nodes.clone(),
+        }
+    }
+}
+
+/// A proof that the root hash of an epoch is included in an
+/// [`EpochRootMmr`](crate::auditor::EpochRootMmr), see
+/// [`verify_epoch_inclusion`](crate::auditor::verify_epoch_inclusion)
+#[derive(Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+#[cfg_attr(feature = "serde_serialization", serde(bound = ""))]
+pub struct EpochInclusionProof<H: Hasher> {
+    /// The first epoch of the MMR
+    pub start_epoch: u64,
+    /// The number of epochs in the MMR
+    pub num_epochs: u64,
+    /// The epoch whose root hash is proven
+    pub epoch: u64,
+    /// The siblings on the path from the epoch up to its peak, lowest first
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "digests_serialize")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "digests_deserialize")
+    )]
+    pub siblings: Vec<H::Digest>,
+    /// All of the peaks of the MMR, tallest first
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "digests_serialize")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "digests_deserialize")
+    )]
+    pub peaks: Vec<H::Digest>,
+}
+
+// Manual implementation of Clone, see: https://github.com/rust-lang/rust/issues/41481
+impl<H: Hasher> Clone for EpochInclusionProof<H> {
+    fn clone(&self) -> Self {
+        Self {
+            start_epoch: self.start_epoch,
+            num_epochs: self.num_epochs,
+            epoch: self.epoch,
+            siblings: self.siblings.clone(),
+            peaks: self.peaks.clone(),
+        }
+    }
+}
+
 /// Proof that a given label was at a particular state at the given epoch.
 /// This means we need to show that the state and version we are claiming for this node must have been:
 /// * committed in the tree,
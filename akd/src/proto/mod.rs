@@ -0,0 +1,243 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under both the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree and the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree.
+
+//! Conversions between the tree proofs of this crate and the protobuf messages defined in
+//! `src/proto/specs/types.proto`, which services written in other languages can parse
+
+pub mod specs;
+
+use crate::errors::AkdError;
+use crate::proof_structs::{AppendOnlyProof, LayerProof, MembershipProof, SingleAppendOnlyProof};
+use crate::serialization::{from_digest, to_digest};
+use crate::{Node, NodeLabel, ARITY};
+use protobuf::MessageField;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use winter_crypto::Hasher;
+
+/// An error converting a protobuf message into one of the types of this crate
+#[derive(Debug)]
+pub enum ConversionError {
+    /// A field of the message is missing or holds an invalid value
+    Deserialization(String),
+    /// The bytes couldn't be parsed as a protobuf message
+    Protobuf(String),
+}
+
+impl std::error::Error for ConversionError {}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Deserialization(inner) => write!(f, "Invalid protobuf message: {}", inner),
+            Self::Protobuf(inner) => write!(f, "Failed to parse protobuf message: {}", inner),
+        }
+    }
+}
+
+impl From<protobuf::Error> for ConversionError {
+    fn from(error: protobuf::Error) -> Self {
+        Self::Protobuf(error.to_string())
+    }
+}
+
+impl From<ConversionError> for AkdError {
+    fn from(error: ConversionError) -> Self {
+        Self::Serialization(error.to_string())
+    }
+}
+
+fn require<'a, T>(field: &'a MessageField<T>, name: &str) -> Result<&'a T, ConversionError> {
+    field
+        .as_ref()
+        .ok_or_else(|| ConversionError::Deserialization(format!("Missing field {}", name)))
+}
+
+fn decode_digest<H: Hasher>(bytes: &[u8]) -> Result<H::Digest, ConversionError> {
+    if bytes.len() != 32 {
+        return Err(ConversionError::Deserialization(format!(
+            "Digest has {} bytes, expected 32",
+            bytes.len()
+        )));
+    }
+    to_digest::<H>(bytes).map_err(|err| ConversionError::Deserialization(err.to_string()))
+}
+
+// ==== NodeLabel ==== //
+
+impl From<&NodeLabel> for specs::types::NodeLabel {
+    fn from(input: &NodeLabel) -> Self {
+        let mut result = Self::new();
+        result.label_val = input.label_val.to_vec();
+        result.label_len = input.label_len;
+        result
+    }
+}
+
+impl TryFrom<&specs::types::NodeLabel> for NodeLabel {
+    type Error = ConversionError;
+
+    fn try_from(input: &specs::types::NodeLabel) -> Result<Self, Self::Error> {
+        let label_val: [u8; 32] = input.label_val.as_slice().try_into().map_err(|_| {
+            ConversionError::Deserialization(format!(
+                "Label value has {} bytes, expected 32",
+                input.label_val.len()
+            ))
+        })?;
+        Ok(NodeLabel::new(label_val, input.label_len))
+    }
+}
+
+// ==== Node ==== //
+
+impl<H: Hasher> From<&Node<H>> for specs::types::Node {
+    fn from(input: &Node<H>) -> Self {
+        let mut result = Self::new();
+        result.label = MessageField::some((&input.label).into());
+        result.hash = from_digest::<H>(input.hash).to_vec();
+        result
+    }
+}
+
+impl<H: Hasher> TryFrom<&specs::types::Node> for Node<H> {
+    type Error = ConversionError;
+
+    fn try_from(input: &specs::types::Node) -> Result<Self, Self::Error> {
+        Ok(Node {
+            label: require(&input.label, "label")?.try_into()?,
+            hash: decode_digest::<H>(&input.hash)?,
+        })
+    }
+}
+
+// ==== LayerProof ==== //
+
+impl<H: Hasher> From<&LayerProof<H>> for specs::types::LayerProof {
+    fn from(input: &LayerProof<H>) -> Self {
+        let mut result = Self::new();
+        result.label = MessageField::some((&input.label).into());
+        result.siblings = input.siblings.iter().map(|node| node.into()).collect();
+        result.direction = input.direction.map(|direction| direction as u32);
+        result
+    }
+}
+
+impl<H: Hasher> TryFrom<&specs::types::LayerProof> for LayerProof<H> {
+    type Error = ConversionError;
+
+    fn try_from(input: &specs::types::LayerProof) -> Result<Self, Self::Error> {
+        let siblings = input
+            .siblings
+            .iter()
+            .map(|node| node.try_into())
+            .collect::<Result<Vec<Node<H>>, _>>()?;
+        let num_siblings = siblings.len();
+        Ok(LayerProof {
+            label: require(&input.label, "label")?.try_into()?,
+            siblings: siblings.try_into().map_err(|_| {
+                ConversionError::Deserialization(format!(
+                    "Layer proof has {} siblings, expected {}",
+                    num_siblings,
+                    ARITY - 1
+                ))
+            })?,
+            direction: input.direction.map(|direction| direction as usize),
+        })
+    }
+}
+
+// ==== MembershipProof ==== //
+
+impl<H: Hasher> From<&MembershipProof<H>> for specs::types::MembershipProof {
+    fn from(input: &MembershipProof<H>) -> Self {
+        let mut result = Self::new();
+        result.label = MessageField::some((&input.label).into());
+        result.hash_val = from_digest::<H>(input.hash_val).to_vec();
+        result.layer_proofs = input
+            .layer_proofs
+            .iter()
+            .map(|proof| proof.into())
+            .collect();
+        result
+    }
+}
+
+impl<H: Hasher> TryFrom<&specs::types::MembershipProof> for MembershipProof<H> {
+    type Error = ConversionError;
+
+    fn try_from(input: &specs::types::MembershipProof) -> Result<Self, Self::Error> {
+        Ok(MembershipProof {
+            label: require(&input.label, "label")?.try_into()?,
+            hash_val: decode_digest::<H>(&input.hash_val)?,
+            layer_proofs: input
+                .layer_proofs
+                .iter()
+                .map(|proof| proof.try_into())
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+// ==== SingleAppendOnlyProof ==== //
+
+impl<H: Hasher> From<&SingleAppendOnlyProof<H>> for specs::types::SingleAppendOnlyProof {
+    fn from(input: &SingleAppendOnlyProof<H>) -> Self {
+        let mut result = Self::new();
+        result.inserted = input.inserted.iter().map(|node| node.into()).collect();
+        result.unchanged_nodes = input
+            .unchanged_nodes
+            .iter()
+            .map(|node| node.into())
+            .collect();
+        result
+    }
+}
+
+impl<H: Hasher> TryFrom<&specs::types::SingleAppendOnlyProof> for SingleAppendOnlyProof<H> {
+    type Error = ConversionError;
+
+    fn try_from(input: &specs::types::SingleAppendOnlyProof) -> Result<Self, Self::Error> {
+        Ok(SingleAppendOnlyProof {
+            inserted: input
+                .inserted
+                .iter()
+                .map(|node| node.try_into())
+                .collect::<Result<Vec<_>, _>>()?,
+            unchanged_nodes: input
+                .unchanged_nodes
+                .iter()
+                .map(|node| node.try_into())
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+// ==== AppendOnlyProof ==== //
+
+impl<H: Hasher> From<&AppendOnlyProof<H>> for specs::types::AppendOnlyProof {
+    fn from(input: &AppendOnlyProof<H>) -> Self {
+        let mut result = Self::new();
+        result.proofs = input.proofs.iter().map(|proof| proof.into()).collect();
+        result.epochs = input.epochs.clone();
+        result
+    }
+}
+
+impl<H: Hasher> TryFrom<&specs::types::AppendOnlyProof> for AppendOnlyProof<H> {
+    type Error = ConversionError;
+
+    fn try_from(input: &specs::types::AppendOnlyProof) -> Result<Self, Self::Error> {
+        Ok(AppendOnlyProof {
+            proofs: input
+                .proofs
+                .iter()
+                .map(|proof| proof.try_into())
+                .collect::<Result<Vec<_>, _>>()?,
+            epochs: input.epochs.clone(),
+        })
+    }
+}
@@ -0,0 +1,11 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under both the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree and the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree.
+
+//! The message types generated from `types.proto` by the build script
+#![allow(missing_docs)]
+
+include!(concat!(env!("OUT_DIR"), "/protos/mod.rs"));
@@ -7,15 +7,68 @@
 
 //! This module contains serialization calls for helping serialize/deserialize digests
 
-use crate::errors::{AkdError, TreeNodeError};
+use crate::errors::{AkdError, AuditorError, TreeNodeError};
 
 #[cfg(feature = "serde_serialization")]
 use hex::{FromHex, ToHex};
 #[cfg(feature = "serde_serialization")]
 use serde::{Deserialize, Serialize};
-use winter_crypto::{Digest, Hasher};
+use std::any::TypeId;
+use winter_crypto::{
+    hashers::{Blake3_256, Sha3_256},
+    Digest, Hasher,
+};
+use winter_math::fields::f128::BaseElement;
 use winter_utils::{Deserializable, SliceReader};
 
+/// The formats proofs can be serialized in with their `to_bytes` and `from_bytes`. Which
+/// variants exist depends on the enabled features, so matches on it need a wildcard arm.
+#[cfg(any(feature = "serde_serialization", feature = "protobuf_serialization"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SerializationFormat {
+    /// bincode over the serde representation, compact but only readable from Rust
+    #[cfg(feature = "serde_serialization")]
+    Bincode,
+    /// The protobuf messages of `src/proto/specs/types.proto`, readable from other languages
+    #[cfg(feature = "protobuf_serialization")]
+    Protobuf,
+}
+
+/// Identifies the hash function a [`crate::auditor::AuditBundle`] was generated with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditHasher {
+    /// Blake3 256-bit hashing over the f128 base field
+    Blake3 = 1,
+    /// Sha3 256-bit hashing over the f128 base field
+    Sha3 = 2,
+}
+
+impl AuditHasher {
+    /// The identifier of the hash function `H`, if it's one which bundles can be made with
+    pub(crate) fn of<H: Hasher + 'static>() -> Option<Self> {
+        let id = TypeId::of::<H>();
+        if id == TypeId::of::<Blake3_256<BaseElement>>() {
+            Some(AuditHasher::Blake3)
+        } else if id == TypeId::of::<Sha3_256<BaseElement>>() {
+            Some(AuditHasher::Sha3)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn from_byte(byte: u8) -> Result<Self, AkdError> {
+        match byte {
+            1 => Ok(AuditHasher::Blake3),
+            2 => Ok(AuditHasher::Sha3),
+            _ => Err(AkdError::AuditErr(AuditorError::MalformedProof(format!(
+                "Unknown audit bundle hasher id {}",
+                byte
+            )))),
+        }
+    }
+}
+
 /// Converts from &[u8] to H::Digest
 pub fn to_digest<H: Hasher>(input: &[u8]) -> Result<H::Digest, AkdError> {
     Ok(H::Digest::read_from(&mut SliceReader::new(input))
@@ -27,6 +80,22 @@ pub fn from_digest<H: Hasher>(input: H::Digest) -> [u8; 32] {
     input.as_bytes()
 }
 
+/// Wraps a digest so that it renders as a lowercase hex string with both `{}` and `{:?}`,
+/// e.g. when logging digests or including them in error messages
+pub struct HexDigest<H: Hasher>(pub H::Digest);
+
+impl<H: Hasher> std::fmt::Display for HexDigest<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(from_digest::<H>(self.0)))
+    }
+}
+
+impl<H: Hasher> std::fmt::Debug for HexDigest<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HexDigest({})", self)
+    }
+}
+
 /// A serde serializer for the type `winter_crypto::Digest`
 #[cfg(feature = "serde_serialization")]
 pub fn digest_serialize<S, T>(x: &T, s: S) -> Result<S::Ok, S::Error>
@@ -48,6 +117,32 @@ where
     T::read_from(&mut SliceReader::new(&buf)).map_err(serde::de::Error::custom)
 }
 
+/// A serde serializer for a list of the type `winter_crypto::Digest`
+#[cfg(feature = "serde_serialization")]
+pub fn digests_serialize<S, T>(x: &[T], s: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    T: Digest,
+{
+    x.iter()
+        .map(|digest| digest.as_bytes())
+        .collect::<Vec<_>>()
+        .serialize(s)
+}
+
+/// A serde deserializer for a list of the type `winter_crypto::Digest`
+#[cfg(feature = "serde_serialization")]
+pub fn digests_deserialize<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Digest,
+{
+    Vec::<[u8; 32]>::deserialize(deserializer)?
+        .iter()
+        .map(|buf| T::read_from(&mut SliceReader::new(buf)).map_err(serde::de::Error::custom))
+        .collect()
+}
+
 /// A serde hex serializer for bytes
 #[cfg(feature = "serde_serialization")]
 pub fn bytes_serialize_hex<S, T>(x: &T, s: S) -> Result<S::Ok, S::Error>
@@ -78,9 +173,12 @@ mod tests {
     use crate::directory::Directory;
     use crate::ecvrf::HardCodedAkdVRF;
     use crate::errors::AkdError;
-    use crate::proof_structs::{AppendOnlyProof, HistoryProof, LookupProof};
+    use crate::proof_structs::{
+        AppendOnlyProof, HistoryProof, LayerProof, LookupProof, MembershipProof,
+    };
     use crate::storage::memory::AsyncInMemoryDatabase;
     use crate::storage::types::{AkdLabel, AkdValue};
+    use crate::{Node, NodeLabel};
     use winter_crypto::hashers::Blake3_256;
     use winter_math::fields::f128::BaseElement;
     type Blake3 = Blake3_256<BaseElement>;
@@ -92,6 +190,50 @@ mod tests {
         digest: H::Digest,
     }
 
+    #[test]
+    pub fn hex_digest_display() {
+        let digest = Blake3::hash(b"hello, world!");
+        let hex_string = hex::encode(from_digest::<Blake3>(digest));
+        assert_eq!(hex_string, format!("{}", HexDigest::<Blake3>(digest)));
+        assert_eq!(
+            format!("HexDigest({})", hex_string),
+            format!("{:?}", HexDigest::<Blake3>(digest))
+        );
+    }
+
+    #[test]
+    pub fn membership_proof_to_json() {
+        let leaf_label = NodeLabel::new([0b1100_0000u8; 32], 2);
+        let sibling_label = NodeLabel::new([0u8; 32], 1);
+        let proof = MembershipProof::<Blake3> {
+            label: leaf_label,
+            hash_val: Blake3::hash(b"leaf"),
+            layer_proofs: vec![LayerProof {
+                label: NodeLabel::root(),
+                siblings: [Node {
+                    label: sibling_label,
+                    hash: Blake3::hash(b"sibling"),
+                }],
+                direction: Some(1),
+            }],
+        };
+        let hex_of = |digest| hex::encode(from_digest::<Blake3>(digest));
+        let expected = format!(
+            concat!(
+                "{{\"label\":{{\"value\":\"{}\",\"length\":2}},\"hash_val\":\"{}\",",
+                "\"layer_proofs\":[{{\"label\":{{\"value\":\"{}\",\"length\":0}},",
+                "\"siblings\":[{{\"label\":{{\"value\":\"{}\",\"length\":1}},",
+                "\"hash\":\"{}\"}}],\"direction\":\"right\"}}]}}"
+            ),
+            "c0".repeat(32),
+            hex_of(Blake3::hash(b"leaf")),
+            "00".repeat(32),
+            "00".repeat(32),
+            hex_of(Blake3::hash(b"sibling")),
+        );
+        assert_eq!(expected, proof.to_json());
+    }
+
     #[test]
     pub fn serialize_deserialize() {
         use winter_crypto::hashers::Blake3_256;
@@ -109,6 +251,23 @@ mod tests {
 
     // Serialization tests for proof structs
 
+    #[test]
+    pub fn epoch_inclusion_proof_roundtrip() -> Result<(), AkdError> {
+        use crate::auditor::EpochRootMmr;
+        use crate::proof_structs::EpochInclusionProof;
+
+        let mut mmr = EpochRootMmr::<Blake3>::new();
+        for epoch in 1..=6u64 {
+            mmr.append(epoch, Blake3::hash(&epoch.to_le_bytes()))?;
+        }
+        let proof = mmr.get_epoch_inclusion_proof(3)?;
+
+        let serialized = bincode::serialize(&proof).unwrap();
+        let deserialized: EpochInclusionProof<Blake3> = bincode::deserialize(&serialized).unwrap();
+        assert_eq!(proof, deserialized);
+        Ok(())
+    }
+
     #[tokio::test]
     pub async fn lookup_proof_roundtrip() -> Result<(), AkdError> {
         let db = AsyncInMemoryDatabase::new();
@@ -222,4 +381,40 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    pub async fn proof_formats_roundtrip() -> Result<(), AkdError> {
+        let db = AsyncInMemoryDatabase::new();
+        let vrf = HardCodedAkdVRF {};
+        let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+        for i in 0..2 {
+            akd.publish::<Blake3>(vec![(
+                AkdLabel::from_utf8_str(&format!("hello{}", i)),
+                AkdValue::from_utf8_str(&format!("world{}", i)),
+            )])
+            .await?;
+        }
+        let membership_proof = akd
+            .lookup::<Blake3>(AkdLabel::from_utf8_str("hello0"))
+            .await?
+            .existence_proof;
+        let audit_proof = akd.audit::<Blake3>(1, 2).await?;
+
+        let formats = vec![
+            SerializationFormat::Bincode,
+            #[cfg(feature = "protobuf_serialization")]
+            SerializationFormat::Protobuf,
+        ];
+        for format in formats {
+            let bytes = membership_proof.to_bytes(format)?;
+            let deserialized = MembershipProof::<Blake3>::from_bytes(format, &bytes)?;
+            assert_eq!(membership_proof, deserialized);
+
+            let bytes = audit_proof.to_bytes(format)?;
+            let deserialized = AppendOnlyProof::<Blake3>::from_bytes(format, &bytes)?;
+            assert_eq!(audit_proof, deserialized);
+        }
+
+        Ok(())
+    }
 }
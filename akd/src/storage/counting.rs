@@ -0,0 +1,230 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under both the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree and the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree.
+
+//! A storage wrapper which counts the operations performed on the storage it wraps
+
+use crate::errors::StorageError;
+use crate::node_label::NodeLabel;
+use crate::storage::types::{
+    AkdLabel, AkdValue, DbRecord, KeyData, ValueState, ValueStateKey, ValueStateRetrievalFlag,
+};
+use crate::storage::{Storable, Storage};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// The number of storage operations counted by a [CountingStorage]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageStats {
+    /// Single record and single user reads
+    pub reads: u64,
+    /// Single record writes, including tombstoning value states
+    pub writes: u64,
+    /// Batched reads and writes, each counted once however many records it holds
+    pub batch_ops: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    reads: AtomicU64,
+    writes: AtomicU64,
+    batch_ops: AtomicU64,
+}
+
+/// Wraps a [Storage] implementation, counting the reads, writes and batched operations made
+/// on it, e.g. to attribute the storage cost of an operation or to check that an optimization
+/// makes fewer round-trips. Reset the counts with [CountingStorage::reset] before the operation
+/// to measure, and read them with [CountingStorage::stats] after it.
+///
+/// Only the data operations passed on to the wrapped storage are counted: transactions, cache
+/// management and the operations the wrapped storage makes on itself (e.g. when committing an
+/// epoch) aren't. Clones of the wrapper share their counts.
+#[derive(Clone)]
+pub struct CountingStorage<S: Storage> {
+    storage: S,
+    counters: Arc<Counters>,
+}
+
+impl<S: Storage + Sync + Send> CountingStorage<S> {
+    /// Wraps the given storage, with all of the counts at zero
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    /// The operations counted since the wrapper was created or last reset
+    pub fn stats(&self) -> StorageStats {
+        StorageStats {
+            reads: self.counters.reads.load(Ordering::Relaxed),
+            writes: self.counters.writes.load(Ordering::Relaxed),
+            batch_ops: self.counters.batch_ops.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Sets all of the counts back to zero
+    pub fn reset(&self) {
+        self.counters.reads.store(0, Ordering::Relaxed);
+        self.counters.writes.store(0, Ordering::Relaxed);
+        self.counters.batch_ops.store(0, Ordering::Relaxed);
+    }
+
+    fn count(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[async_trait]
+impl<S: Storage + Sync + Send> Storage for CountingStorage<S> {
+    async fn log_metrics(&self, level: log::Level) {
+        self.storage.log_metrics(level).await
+    }
+
+    async fn begin_transaction(&self) -> bool {
+        self.storage.begin_transaction().await
+    }
+
+    async fn commit_transaction(&self) -> Result<(), StorageError> {
+        self.storage.commit_transaction().await
+    }
+
+    async fn rollback_transaction(&self) -> Result<(), StorageError> {
+        self.storage.rollback_transaction().await
+    }
+
+    async fn is_transaction_active(&self) -> bool {
+        self.storage.is_transaction_active().await
+    }
+
+    async fn commit_epoch(&self, expected_epoch: u64) -> Result<(), StorageError> {
+        self.storage.commit_epoch(expected_epoch).await
+    }
+
+    async fn set(&self, record: DbRecord) -> Result<(), StorageError> {
+        Self::count(&self.counters.writes);
+        self.storage.set(record).await
+    }
+
+    async fn batch_set(&self, records: Vec<DbRecord>) -> Result<(), StorageError> {
+        Self::count(&self.counters.batch_ops);
+        self.storage.batch_set(records).await
+    }
+
+    async fn get<St: Storable>(&self, id: &St::StorageKey) -> Result<DbRecord, StorageError> {
+        Self::count(&self.counters.reads);
+        self.storage.get::<St>(id).await
+    }
+
+    async fn get_direct<St: Storable>(
+        &self,
+        id: &St::StorageKey,
+    ) -> Result<DbRecord, StorageError> {
+        Self::count(&self.counters.reads);
+        self.storage.get_direct::<St>(id).await
+    }
+
+    async fn flush_cache(&self) {
+        self.storage.flush_cache().await
+    }
+
+    async fn advance_cache_epoch(&self, epoch: u64, changed: &[NodeLabel]) {
+        self.storage.advance_cache_epoch(epoch, changed).await
+    }
+
+    async fn tombstone_value_states(&self, keys: &[ValueStateKey]) -> Result<(), StorageError> {
+        Self::count(&self.counters.writes);
+        self.storage.tombstone_value_states(keys).await
+    }
+
+    async fn batch_get<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> Result<Vec<DbRecord>, StorageError> {
+        Self::count(&self.counters.batch_ops);
+        self.storage.batch_get::<St>(ids).await
+    }
+
+    async fn batch_get_direct<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> Result<Vec<DbRecord>, StorageError> {
+        Self::count(&self.counters.batch_ops);
+        self.storage.batch_get_direct::<St>(ids).await
+    }
+
+    async fn get_user_data(&self, username: &AkdLabel) -> Result<KeyData, StorageError> {
+        Self::count(&self.counters.reads);
+        self.storage.get_user_data(username).await
+    }
+
+    async fn get_user_state(
+        &self,
+        username: &AkdLabel,
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<ValueState, StorageError> {
+        Self::count(&self.counters.reads);
+        self.storage.get_user_state(username, flag).await
+    }
+
+    async fn get_user_state_versions(
+        &self,
+        usernames: &[AkdLabel],
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<HashMap<AkdLabel, (u64, AkdValue)>, StorageError> {
+        Self::count(&self.counters.batch_ops);
+        self.storage.get_user_state_versions(usernames, flag).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::AsyncInMemoryDatabase;
+    use crate::storage::types::EpochTimestamp;
+    use serial_test::serial;
+
+    #[tokio::test]
+    async fn test_operations_are_counted() -> Result<(), StorageError> {
+        let storage = CountingStorage::new(AsyncInMemoryDatabase::new());
+        let timestamp = |epoch| {
+            DbRecord::EpochTimestamp(EpochTimestamp {
+                epoch,
+                timestamp_millis: 0,
+            })
+        };
+        storage.set(timestamp(1)).await?;
+        storage.batch_set(vec![timestamp(2), timestamp(3)]).await?;
+        storage.get::<EpochTimestamp>(&1).await?;
+        storage.get_direct::<EpochTimestamp>(&2).await?;
+        storage.batch_get::<EpochTimestamp>(&[1, 2, 3]).await?;
+        assert_eq!(
+            StorageStats {
+                reads: 2,
+                writes: 1,
+                batch_ops: 2,
+            },
+            storage.stats()
+        );
+
+        // Clones share their counts, and a reset clears them for all of them
+        let clone = storage.clone();
+        clone.get::<EpochTimestamp>(&3).await?;
+        assert_eq!(3, storage.stats().reads);
+        storage.reset();
+        assert_eq!(StorageStats::default(), clone.stats());
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_counting_passes_through() {
+        let storage = CountingStorage::new(AsyncInMemoryDatabase::new());
+        crate::storage::tests::run_test_cases_for_storage_impl(&storage).await;
+    }
+}
@@ -0,0 +1,341 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under both the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree and the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree.
+
+//! This module contains a minimal byte-level key-value store interface, and an adaptor
+//! which implements [Storage] on top of any such store. A new backend (e.g. Redis or
+//! FoundationDB) only needs to implement the four methods of [AsyncKeyValueStore].
+
+use crate::errors::StorageError;
+use crate::storage::transaction::Transaction;
+use crate::storage::types::{
+    AkdLabel, AkdValue, DbRecord, KeyData, StorageType, ValueState, ValueStateKey,
+    ValueStateRetrievalFlag,
+};
+use crate::storage::{Storable, Storage};
+use async_trait::async_trait;
+use log::{debug, error, info, trace, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// A byte-level asynchronous key-value store
+#[async_trait]
+pub trait AsyncKeyValueStore: Clone + Send + Sync {
+    /// Retrieve the value stored under a key, if there is one
+    async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError>;
+
+    /// Store a value under a key, replacing any previous value
+    async fn set(&self, key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError>;
+
+    /// Store multiple key-value pairs, ideally atomically
+    async fn batch_set(&self, items: Vec<(Vec<u8>, Vec<u8>)>) -> Result<(), StorageError>;
+
+    /// Retrieve all the key-value pairs whose key starts with the given prefix
+    async fn scan_prefix(&self, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, StorageError>;
+}
+
+// ===== Reference in-memory key-value store ==== //
+
+/// A reference implementation of [AsyncKeyValueStore] over an in-memory ordered map
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryKeyValueStore {
+    map: Arc<tokio::sync::RwLock<BTreeMap<Vec<u8>, Vec<u8>>>>,
+}
+
+impl InMemoryKeyValueStore {
+    /// Creates a new, empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl AsyncKeyValueStore for InMemoryKeyValueStore {
+    async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(self.map.read().await.get(key).cloned())
+    }
+
+    async fn set(&self, key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError> {
+        self.map.write().await.insert(key, value);
+        Ok(())
+    }
+
+    async fn batch_set(&self, items: Vec<(Vec<u8>, Vec<u8>)>) -> Result<(), StorageError> {
+        let mut guard = self.map.write().await;
+        for (key, value) in items {
+            guard.insert(key, value);
+        }
+        Ok(())
+    }
+
+    async fn scan_prefix(&self, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, StorageError> {
+        let guard = self.map.read().await;
+        Ok(guard
+            .range(prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+}
+
+// ===== Storage adaptor ==== //
+
+/// Implements [Storage] on top of any [AsyncKeyValueStore]. Records are encoded with
+/// bincode and stored under their full binary id, except for value states which are
+/// stored under a per-user prefix so that all the states of a user can be found with
+/// a single [AsyncKeyValueStore::scan_prefix].
+#[derive(Debug)]
+pub struct KeyValueStorage<K: AsyncKeyValueStore> {
+    store: K,
+    trans: Transaction,
+}
+
+impl<K: AsyncKeyValueStore> KeyValueStorage<K> {
+    /// Creates a new storage layer over the given key-value store
+    pub fn new(store: K) -> Self {
+        Self {
+            store,
+            trans: Transaction::new(),
+        }
+    }
+
+    /// The prefix of the keys of all the value states of a user. The length of the
+    /// username is included, so that no user's prefix is a prefix of another's.
+    fn user_prefix(username: &[u8]) -> Vec<u8> {
+        let mut prefix = vec![StorageType::ValueState as u8];
+        prefix.extend_from_slice(&(username.len() as u64).to_be_bytes());
+        prefix.extend_from_slice(username);
+        prefix
+    }
+
+    fn value_state_key(username: &[u8], epoch: u64) -> Vec<u8> {
+        let mut key = Self::user_prefix(username);
+        key.extend_from_slice(&epoch.to_be_bytes());
+        key
+    }
+
+    fn record_key(record: &DbRecord) -> Vec<u8> {
+        match record {
+            DbRecord::ValueState(state) => Self::value_state_key(&state.username, state.epoch),
+            other => other.get_full_binary_id(),
+        }
+    }
+
+    fn encode(record: &DbRecord) -> Result<(Vec<u8>, Vec<u8>), StorageError> {
+        let value = bincode::serialize(record)
+            .map_err(|err| StorageError::Other(format!("Failed to encode record: {}", err)))?;
+        Ok((Self::record_key(record), value))
+    }
+
+    fn decode(value: &[u8]) -> Result<DbRecord, StorageError> {
+        bincode::deserialize(value)
+            .map_err(|err| StorageError::Other(format!("Failed to decode record: {}", err)))
+    }
+}
+
+impl<K: AsyncKeyValueStore> Clone for KeyValueStorage<K> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            trans: Transaction::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl<K: AsyncKeyValueStore> Storage for KeyValueStorage<K> {
+    async fn log_metrics(&self, level: log::Level) {
+        let msg = "KeyValueStorage keeps no metrics of its own".to_string();
+
+        match level {
+            log::Level::Trace => trace!("{}", msg),
+            log::Level::Debug => debug!("{}", msg),
+            log::Level::Info => info!("{}", msg),
+            log::Level::Warn => warn!("{}", msg),
+            _ => error!("{}", msg),
+        }
+        self.trans.log_metrics(level).await;
+    }
+
+    async fn begin_transaction(&self) -> bool {
+        self.trans.begin_transaction().await
+    }
+
+    async fn commit_transaction(&self) -> Result<(), StorageError> {
+        // this retrieves all the trans operations, and "de-activates" the transaction flag
+        let ops = self.trans.commit_transaction().await?;
+        self.batch_set(ops).await
+    }
+
+    async fn rollback_transaction(&self) -> Result<(), StorageError> {
+        self.trans.rollback_transaction().await
+    }
+
+    async fn is_transaction_active(&self) -> bool {
+        self.trans.is_transaction_active().await
+    }
+
+    async fn set(&self, record: DbRecord) -> Result<(), StorageError> {
+        if self.is_transaction_active().await {
+            self.trans.set(&record).await;
+            return Ok(());
+        }
+
+        let (key, value) = Self::encode(&record)?;
+        self.store.set(key, value).await
+    }
+
+    async fn batch_set(&self, records: Vec<DbRecord>) -> Result<(), StorageError> {
+        // we're in a transaction, set the items in the transaction
+        if self.is_transaction_active().await {
+            for record in records.into_iter() {
+                self.trans.set(&record).await;
+            }
+            return Ok(());
+        }
+
+        let items = records
+            .iter()
+            .map(Self::encode)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.store.batch_set(items).await
+    }
+
+    async fn get<St: Storable>(&self, id: &St::StorageKey) -> Result<DbRecord, StorageError> {
+        if self.is_transaction_active().await {
+            if let Some(result) = self.trans.get::<St>(id).await {
+                // there's a transacted item, return that one since it's "more up to date"
+                return Ok(result);
+            }
+        }
+        self.get_direct::<St>(id).await
+    }
+
+    async fn get_direct<St: Storable>(
+        &self,
+        id: &St::StorageKey,
+    ) -> Result<DbRecord, StorageError> {
+        let mut key = St::get_full_binary_key_id(id);
+        if St::data_type() == StorageType::ValueState {
+            let ValueStateKey(username, epoch) =
+                ValueState::key_from_full_binary(&key).map_err(StorageError::Other)?;
+            key = Self::value_state_key(&username, epoch);
+        }
+        match self.store.get(&key).await? {
+            Some(value) => Self::decode(&value),
+            None => Err(StorageError::NotFound(format!(
+                "{:?} {:?}",
+                St::data_type(),
+                id
+            ))),
+        }
+    }
+
+    async fn flush_cache(&self) {
+        // no-op
+    }
+
+    async fn batch_get<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> Result<Vec<DbRecord>, StorageError> {
+        let mut records = Vec::new();
+        for id in ids.iter() {
+            match self.get::<St>(id).await {
+                Ok(record) => records.push(record),
+                // swallow not found errors, as the other storage layers do
+                Err(StorageError::NotFound(_)) => {}
+                Err(other) => return Err(other),
+            }
+        }
+        Ok(records)
+    }
+
+    async fn tombstone_value_states(&self, keys: &[ValueStateKey]) -> Result<(), StorageError> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        let data = self.batch_get::<ValueState>(keys).await?;
+        let mut new_data = vec![];
+        for record in data {
+            if let DbRecord::ValueState(value_state) = record {
+                debug!(
+                    "Tombstoning 0x{}",
+                    hex::encode(value_state.username.to_vec())
+                );
+
+                new_data.push(DbRecord::ValueState(ValueState {
+                    plaintext_val: crate::AkdValue(crate::TOMBSTONE.to_vec()),
+                    ..value_state
+                }));
+            }
+        }
+
+        if !new_data.is_empty() {
+            debug!("Tombstoning {} entries", new_data.len());
+            self.batch_set(new_data).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn get_user_data(&self, username: &AkdLabel) -> Result<KeyData, StorageError> {
+        let mut states = Vec::new();
+        for (_, value) in self.store.scan_prefix(&Self::user_prefix(username)).await? {
+            if let DbRecord::ValueState(state) = Self::decode(&value)? {
+                states.push(state);
+            }
+        }
+        if states.is_empty() {
+            return Err(StorageError::NotFound(format!("ValueState {:?}", username)));
+        }
+        // The keys end with the big-endian epoch, but sort anyway in case a store
+        // doesn't return a scan in key order
+        states.sort_by_key(|a| a.epoch);
+        Ok(KeyData { states })
+    }
+
+    async fn get_user_state(
+        &self,
+        username: &AkdLabel,
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<ValueState, StorageError> {
+        // ordered from the smallest to the largest epoch
+        let states = self.get_user_data(username).await?.states;
+        let found = match flag {
+            ValueStateRetrievalFlag::SpecificVersion(version) => {
+                states.into_iter().find(|state| state.version == version)
+            }
+            ValueStateRetrievalFlag::SpecificEpoch(epoch) => {
+                states.into_iter().find(|state| state.epoch == epoch)
+            }
+            ValueStateRetrievalFlag::LeqEpoch(epoch) => {
+                states.into_iter().rev().find(|state| state.epoch <= epoch)
+            }
+            ValueStateRetrievalFlag::MaxEpoch => states.last().cloned(),
+            ValueStateRetrievalFlag::MinEpoch => states.first().cloned(),
+        };
+        found.ok_or_else(|| StorageError::NotFound(format!("ValueState {:?}", username)))
+    }
+
+    async fn get_user_state_versions(
+        &self,
+        keys: &[AkdLabel],
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<HashMap<AkdLabel, (u64, AkdValue)>, StorageError> {
+        let mut map = HashMap::new();
+        for username in keys.iter() {
+            if let Ok(result) = self.get_user_state(username, flag).await {
+                map.insert(
+                    AkdLabel(result.username.to_vec()),
+                    (result.version, AkdValue(result.plaintext_val.to_vec())),
+                );
+            }
+        }
+        Ok(map)
+    }
+}
@@ -9,11 +9,12 @@
 //! an in-memory implementation which contains some caching implementations for
 //! benchmarking
 
+use crate::append_only_zks::{Azks, DEFAULT_AZKS_KEY};
 use crate::errors::StorageError;
 use crate::storage::transaction::Transaction;
 use crate::storage::types::{
-    AkdLabel, AkdValue, DbRecord, KeyData, StorageType, ValueState, ValueStateKey,
-    ValueStateRetrievalFlag,
+    AkdLabel, AkdValue, DbRecord, EpochCommitMarker, KeyData, StorageType, ValueState,
+    ValueStateKey, ValueStateRetrievalFlag,
 };
 use crate::storage::{Storable, Storage, StorageUtil};
 use async_trait::async_trait;
@@ -47,6 +48,40 @@ impl AsyncInMemoryDatabase {
             trans: Transaction::new(),
         }
     }
+
+    /// Removes every record from the database. The maps keep their capacity, so a caller
+    /// which fills the database with a similar amount of data again (e.g. an auditor
+    /// verifying one epoch after another) avoids reallocating its storage.
+    pub async fn clear(&self) {
+        self.db.write().await.clear();
+        self.user_info.write().await.clear();
+    }
+
+    fn write_records(
+        db: &mut HashMap<Vec<u8>, DbRecord>,
+        user_info: &mut UserStates,
+        records: Vec<DbRecord>,
+    ) {
+        for record in records.into_iter() {
+            if let DbRecord::ValueState(value_state) = &record {
+                let username = value_state.username.to_vec();
+                match user_info.get(&username) {
+                    Some(old_states) => {
+                        let mut new_states = old_states.clone();
+                        new_states.insert(value_state.epoch, value_state.clone());
+                        user_info.insert(username, new_states);
+                    }
+                    None => {
+                        let mut new_map = HashMap::new();
+                        new_map.insert(value_state.epoch, value_state.clone());
+                        user_info.insert(username, new_map);
+                    }
+                }
+            } else {
+                db.insert(record.get_full_binary_id(), record);
+            }
+        }
+    }
 }
 
 impl Default for AsyncInMemoryDatabase {
@@ -94,6 +129,41 @@ impl Storage for AsyncInMemoryDatabase {
         self.trans.rollback_transaction().await
     }
 
+    async fn commit_epoch(&self, expected_epoch: u64) -> Result<(), StorageError> {
+        let mut ops = self.trans.commit_transaction().await?;
+        let new_epoch = expected_epoch + 1;
+        let records_root_hash = ops
+            .iter()
+            .any(|op| matches!(op, DbRecord::EpochRootHash(root) if root.epoch == new_epoch));
+        // the operations are in priority order, so the marker is written last
+        ops.push(DbRecord::EpochCommitMarker(EpochCommitMarker {
+            epoch: new_epoch,
+        }));
+        // hold the write locks from the epoch check through to the writes, so no other
+        // writer can advance the epoch in between
+        let mut u_guard = self.user_info.write().await;
+        let mut guard = self.db.write().await;
+
+        let azks_key = Azks::get_full_binary_key_id(&DEFAULT_AZKS_KEY);
+        if let Some(DbRecord::Azks(azks)) = guard.get(&azks_key) {
+            let actual = azks.get_latest_epoch();
+            if actual != expected_epoch {
+                // the transaction has already been taken, so dropping its operations
+                // here rolls it back
+                return Err(StorageError::EpochRaceLost {
+                    expected: expected_epoch,
+                    actual,
+                });
+            }
+        }
+        if !records_root_hash {
+            return Err(StorageError::RootHashNotRecorded(new_epoch));
+        }
+
+        Self::write_records(&mut guard, &mut u_guard, ops);
+        Ok(())
+    }
+
     async fn is_transaction_active(&self) -> bool {
         self.trans.is_transaction_active().await
     }
@@ -128,28 +198,18 @@ impl Storage for AsyncInMemoryDatabase {
     }
 
     async fn batch_set(&self, records: Vec<DbRecord>) -> Result<(), StorageError> {
+        // we're in a transaction, set the items in the transaction
+        if self.is_transaction_active().await {
+            for record in records.into_iter() {
+                self.trans.set(&record).await;
+            }
+            return Ok(());
+        }
+
         let mut u_guard = self.user_info.write().await;
         let mut guard = self.db.write().await;
 
-        for record in records.into_iter() {
-            if let DbRecord::ValueState(value_state) = &record {
-                let username = value_state.username.to_vec();
-                match u_guard.get(&username) {
-                    Some(old_states) => {
-                        let mut new_states = old_states.clone();
-                        new_states.insert(value_state.epoch, value_state.clone());
-                        u_guard.insert(username, new_states);
-                    }
-                    None => {
-                        let mut new_map = HashMap::new();
-                        new_map.insert(value_state.epoch, value_state.clone());
-                        u_guard.insert(username, new_map);
-                    }
-                }
-            } else {
-                guard.insert(record.get_full_binary_id(), record);
-            }
-        }
+        Self::write_records(&mut guard, &mut u_guard, records);
         Ok(())
     }
 
@@ -353,6 +413,10 @@ impl StorageUtil for AsyncInMemoryDatabase {
                 DbRecord::Azks(_) => St::data_type() == StorageType::Azks,
                 DbRecord::TreeNode(_) => St::data_type() == StorageType::TreeNode,
                 DbRecord::ValueState(_) => St::data_type() == StorageType::ValueState,
+                DbRecord::EpochTimestamp(_) => St::data_type() == StorageType::EpochTimestamp,
+                DbRecord::EpochCommitMarker(_) => St::data_type() == StorageType::EpochCommitMarker,
+                DbRecord::EpochRootHash(_) => St::data_type() == StorageType::EpochRootHash,
+                DbRecord::LeafMetadata(_) => St::data_type() == StorageType::LeafMetadata,
             })
             .collect();
 
@@ -558,6 +622,14 @@ impl Storage for AsyncInMemoryDbWithCache {
     }
 
     async fn batch_set(&self, records: Vec<DbRecord>) -> Result<(), StorageError> {
+        // we're in a transaction, set the items in the transaction
+        if self.is_transaction_active().await {
+            for record in records.into_iter() {
+                self.trans.set(&record).await;
+            }
+            return Ok(());
+        }
+
         let mut u_guard = self.user_info.write().await;
         let mut stats = self.stats.write().await;
         let mut guard = self.cache.write().await;
@@ -789,6 +861,10 @@ impl StorageUtil for AsyncInMemoryDbWithCache {
                 DbRecord::Azks(_) => St::data_type() == StorageType::Azks,
                 DbRecord::TreeNode(_) => St::data_type() == StorageType::TreeNode,
                 DbRecord::ValueState(_) => St::data_type() == StorageType::ValueState,
+                DbRecord::EpochTimestamp(_) => St::data_type() == StorageType::EpochTimestamp,
+                DbRecord::EpochCommitMarker(_) => St::data_type() == StorageType::EpochCommitMarker,
+                DbRecord::EpochRootHash(_) => St::data_type() == StorageType::EpochRootHash,
+                DbRecord::LeafMetadata(_) => St::data_type() == StorageType::LeafMetadata,
             })
             .collect();
 
@@ -7,8 +7,10 @@
 
 //! Storage module for a auditable key directory
 
+use crate::append_only_zks::{Azks, DEFAULT_AZKS_KEY};
 use crate::errors::StorageError;
-use crate::storage::types::{DbRecord, StorageType};
+use crate::node_label::NodeLabel;
+use crate::storage::types::{DbRecord, EpochCommitMarker, EpochRootHash, StorageType};
 
 use async_trait::async_trait;
 #[cfg(feature = "serde_serialization")]
@@ -16,8 +18,15 @@ use serde::{de::DeserializeOwned, Serialize};
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::marker::Send;
+#[cfg(feature = "tokio_runtime")]
+use std::time::Duration;
 
+pub mod counting;
+#[cfg(feature = "serde_serialization")]
+pub mod key_value;
 pub mod timed_cache;
+#[cfg(feature = "tokio_runtime")]
+pub mod timeout;
 pub mod transaction;
 pub mod types;
 
@@ -79,6 +88,30 @@ pub trait Storable: Clone + Sync {
     fn key_from_full_binary(bin: &[u8]) -> Result<Self::Key, String>;
 }
 
+/// How a commit checks that the epoch it wrote can be read back from storage, see
+/// [Storage::verify_epoch_visible]. Which variants exist depends on the enabled features, so
+/// matches on it need a wildcard arm.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReadAfterWritePolicy {
+    /// Don't read anything back, for storage which is strongly consistent
+    #[default]
+    Skip,
+    /// Read the epoch back once, straight after the commit
+    Immediate,
+    /// Read the epoch back up to `max_attempts` times, sleeping between attempts for
+    /// `initial_backoff` and then twice as long as the previous sleep each time, for storage
+    /// which is eventually consistent. Sleeping needs a tokio runtime, so this is only
+    /// available with the _tokio_runtime_ feature
+    #[cfg(feature = "tokio_runtime")]
+    RetryWithBackoff {
+        /// The number of reads to make before giving up
+        max_attempts: u32,
+        /// How long to wait after the first read which didn't find the epoch
+        initial_backoff: Duration,
+    },
+}
+
 /// Storage layer with support for asynchronous work and batched operations
 #[async_trait]
 pub trait Storage: Clone {
@@ -97,6 +130,121 @@ pub trait Storage: Clone {
     /// Retrieve a flag determining if there is a transaction active
     async fn is_transaction_active(&self) -> bool;
 
+    /// Commit a transaction which advances the directory to a new epoch, but only if the
+    /// stored azks is still at `expected_epoch`. If another writer has moved the epoch on
+    /// in the meantime, the transaction is rolled back and [StorageError::EpochRaceLost]
+    /// is returned.
+    ///
+    /// The transaction must hold the [EpochRootHash] of the new epoch (`expected_epoch + 1`),
+    /// so that every committed epoch has its root hash recorded for audits to be served from.
+    /// If it doesn't, the transaction is rolled back and [StorageError::RootHashNotRecorded]
+    /// is returned.
+    ///
+    /// An [EpochCommitMarker] for the new epoch is committed along with the transaction. The
+    /// marker relies on the storage layer applying the records of a transaction atomically
+    /// (e.g. in a single SQL transaction), so that it's present exactly when all of the
+    /// epoch's writes are, and an azks at an epoch without its marker means the commit didn't
+    /// complete. Within the transaction the marker has the highest priority, but a storage
+    /// layer may write the records in any order.
+    ///
+    /// The default implementation checks the stored epoch and then commits, which leaves
+    /// a window between the two. Storage layers which can should override this with a
+    /// single conditional write.
+    async fn commit_epoch(&self, expected_epoch: u64) -> Result<(), StorageError> {
+        let actual = match self.get_direct::<Azks>(&DEFAULT_AZKS_KEY).await {
+            Ok(DbRecord::Azks(azks)) => azks.get_latest_epoch(),
+            // no azks has been committed yet, so nothing could have moved it on
+            Err(StorageError::NotFound(_)) => expected_epoch,
+            Ok(_) => {
+                return Err(StorageError::Other(
+                    "Retrieved a record of the wrong type for the azks".to_string(),
+                ))
+            }
+            Err(other) => return Err(other),
+        };
+        if actual != expected_epoch {
+            self.rollback_transaction().await?;
+            return Err(StorageError::EpochRaceLost {
+                expected: expected_epoch,
+                actual,
+            });
+        }
+        if self.is_transaction_active().await {
+            match self.get::<EpochRootHash>(&(expected_epoch + 1)).await {
+                Ok(DbRecord::EpochRootHash(_)) => {}
+                Ok(_) | Err(StorageError::NotFound(_)) => {
+                    self.rollback_transaction().await?;
+                    return Err(StorageError::RootHashNotRecorded(expected_epoch + 1));
+                }
+                Err(other) => return Err(other),
+            }
+            let marker = EpochCommitMarker {
+                epoch: expected_epoch + 1,
+            };
+            self.set(DbRecord::EpochCommitMarker(marker)).await?;
+        }
+        self.commit_transaction().await
+    }
+
+    /// Commits the transaction as [Storage::commit_epoch] does, then checks that the new
+    /// epoch can be read back according to `policy`, see [Storage::verify_epoch_visible].
+    async fn commit_epoch_with_policy(
+        &self,
+        expected_epoch: u64,
+        policy: ReadAfterWritePolicy,
+    ) -> Result<(), StorageError> {
+        self.commit_epoch(expected_epoch).await?;
+        self.verify_epoch_visible(expected_epoch + 1, policy).await
+    }
+
+    /// Checks that a committed `epoch` can be read back, bypassing any caching, according to
+    /// `policy`. Since the [EpochCommitMarker] of an epoch is committed atomically with the
+    /// other records of its commit, the epoch is visible once its marker and an azks at (or
+    /// past) the epoch can both be read. On an eventually consistent store a read straight after
+    /// the write may not see it yet, in which case a policy which retries with a backoff
+    /// gives the write time to become visible.
+    ///
+    /// Returns [StorageError::WriteNotVisible] if the epoch still can't be read after the
+    /// attempts allowed by the policy.
+    async fn verify_epoch_visible(
+        &self,
+        epoch: u64,
+        policy: ReadAfterWritePolicy,
+    ) -> Result<(), StorageError> {
+        let max_attempts = match policy {
+            ReadAfterWritePolicy::Skip => return Ok(()),
+            ReadAfterWritePolicy::Immediate => 1,
+            #[cfg(feature = "tokio_runtime")]
+            ReadAfterWritePolicy::RetryWithBackoff { max_attempts, .. } => max_attempts.max(1),
+        };
+        let mut key = format!("epoch commit marker {}", epoch);
+        for attempt in 1..=max_attempts {
+            key = match self.get_direct::<EpochCommitMarker>(&epoch).await {
+                Ok(_) => match self.get_direct::<Azks>(&DEFAULT_AZKS_KEY).await {
+                    Ok(DbRecord::Azks(azks)) if azks.get_latest_epoch() >= epoch => return Ok(()),
+                    Ok(_) | Err(StorageError::NotFound(_)) => format!("azks at epoch {}", epoch),
+                    Err(other) => return Err(other),
+                },
+                Err(StorageError::NotFound(_)) => format!("epoch commit marker {}", epoch),
+                Err(other) => return Err(other),
+            };
+            #[cfg(feature = "tokio_runtime")]
+            if let ReadAfterWritePolicy::RetryWithBackoff {
+                initial_backoff, ..
+            } = policy
+            {
+                if attempt < max_attempts {
+                    let backoff = initial_backoff.saturating_mul(2u32.saturating_pow(attempt - 1));
+                    tokio::time::sleep(backoff).await;
+                }
+            }
+        }
+        Err(StorageError::WriteNotVisible {
+            key,
+            attempts: max_attempts,
+        })
+    }
+
     /// Set a record in the data layer
     async fn set(&self, record: DbRecord) -> Result<(), StorageError>;
 
@@ -113,6 +261,14 @@ pub trait Storage: Clone {
     /// Flush the caching of objects (if present)
     async fn flush_cache(&self);
 
+    /// Move the caching of objects (if present) on to the given epoch, keeping the cached tree
+    /// nodes which are unchanged since the previous one. The changed labels are those given by
+    /// [crate::append_only_zks::Azks::get_changed_labels_since]. By default this flushes the
+    /// cache, as a cache which doesn't track the epoch of its entries can't tell what to keep.
+    async fn advance_cache_epoch(&self, _epoch: u64, _changed: &[NodeLabel]) {
+        self.flush_cache().await
+    }
+
     /// Convert the given value state's into tombstones, replacing the plaintext value with
     /// the tombstone key array
     async fn tombstone_value_states(
@@ -126,6 +282,25 @@ pub trait Storage: Clone {
         ids: &[St::StorageKey],
     ) -> Result<Vec<DbRecord>, StorageError>;
 
+    /// Retrieve a batch of records by id from the data layer, ignoring any caching or
+    /// transaction pending. As with [Storage::batch_get], ids which aren't found are left out
+    /// of the result. By default this reads the records one at a time with
+    /// [Storage::get_direct], storage layers which can read them in one go should override it.
+    async fn batch_get_direct<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> Result<Vec<DbRecord>, StorageError> {
+        let mut records = Vec::with_capacity(ids.len());
+        for id in ids.iter() {
+            match self.get_direct::<St>(id).await {
+                Ok(record) => records.push(record),
+                Err(StorageError::NotFound(_)) => {}
+                Err(other) => return Err(other),
+            }
+        }
+        Ok(records)
+    }
+
     /* User data searching */
 
     /// Retrieve the user data for a given user
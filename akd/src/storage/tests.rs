@@ -10,12 +10,12 @@
 use crate::errors::StorageError;
 use crate::node_label::byte_arr_from_u64;
 use crate::storage::types::*;
-use crate::storage::Storage;
+use crate::storage::{ReadAfterWritePolicy, Storage};
 use crate::tree_node::*;
 use crate::NodeLabel;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
-use tokio::time::{Duration, Instant};
+use std::time::{Duration, Instant};
 
 type Azks = crate::append_only_zks::Azks;
 type TreeNode = crate::tree_node::TreeNode;
@@ -43,6 +43,19 @@ mod memory_storage_tests {
     }
 }
 
+#[cfg(test)]
+mod key_value_storage_tests {
+    use crate::storage::key_value::{InMemoryKeyValueStore, KeyValueStorage};
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_key_value_storage_over_in_memory_store() {
+        let db = KeyValueStorage::new(InMemoryKeyValueStore::new());
+        crate::storage::tests::run_test_cases_for_storage_impl(&db).await;
+    }
+}
+
 // *** Run the test cases for a given data-layer impl *** //
 /// Run the storage-layer test suite for a given storage implementation.
 /// This is public because it can be used by other implemented storage layers
@@ -52,6 +65,7 @@ pub async fn run_test_cases_for_storage_impl<S: Storage + Sync + Send>(db: &S) {
     test_get_and_set_item(db).await;
     test_user_data(db).await;
     test_transactions(db).await;
+    test_commit_epoch(db).await;
     test_batch_get_items(db).await;
     test_tombstoning_data(db).await.unwrap();
 }
@@ -380,6 +394,112 @@ async fn test_transactions<S: Storage + Sync + Send>(storage: &S) {
     }
 }
 
+async fn test_commit_epoch<S: Storage + Sync + Send>(storage: &S) {
+    let azks_at = |latest_epoch| Azks {
+        latest_epoch,
+        num_nodes: 10,
+    };
+    // the records a publish commits for an epoch
+    let epoch_records = |epoch| {
+        vec![
+            DbRecord::Azks(azks_at(epoch)),
+            DbRecord::EpochRootHash(EpochRootHash {
+                epoch,
+                root_hash: [epoch as u8; 32],
+            }),
+        ]
+    };
+    async fn stored_epoch<S: Storage + Sync + Send>(storage: &S) -> u64 {
+        match storage
+            .get_direct::<Azks>(&crate::append_only_zks::DEFAULT_AZKS_KEY)
+            .await
+        {
+            Ok(DbRecord::Azks(azks)) => azks.latest_epoch,
+            other => panic!("Failed to retrieve the azks: {:?}", other),
+        }
+    }
+    assert_eq!(Ok(()), storage.set(DbRecord::Azks(azks_at(34))).await);
+
+    // a writer which read an older epoch loses the race, and its transaction is dropped
+    assert!(storage.begin_transaction().await);
+    assert_eq!(Ok(()), storage.batch_set(epoch_records(35)).await);
+    assert_eq!(
+        Err(StorageError::EpochRaceLost {
+            expected: 33,
+            actual: 34
+        }),
+        storage.commit_epoch(33).await
+    );
+    assert!(!storage.is_transaction_active().await);
+    assert_eq!(34, stored_epoch(storage).await);
+
+    // a writer which read the current epoch advances it
+    assert!(storage.begin_transaction().await);
+    assert_eq!(Ok(()), storage.batch_set(epoch_records(35)).await);
+    assert_eq!(Ok(()), storage.commit_epoch(34).await);
+    assert_eq!(35, stored_epoch(storage).await);
+
+    // only the commit which advanced the epoch wrote a commit marker
+    assert!(storage.get_direct::<EpochCommitMarker>(&35).await.is_ok());
+    assert!(matches!(
+        storage.get_direct::<EpochCommitMarker>(&34).await,
+        Err(StorageError::NotFound(_))
+    ));
+
+    // a commit which doesn't record the new epoch's root hash is dropped
+    assert!(storage.begin_transaction().await);
+    assert_eq!(Ok(()), storage.set(DbRecord::Azks(azks_at(36))).await);
+    assert_eq!(
+        Err(StorageError::RootHashNotRecorded(36)),
+        storage.commit_epoch(35).await
+    );
+    assert!(!storage.is_transaction_active().await);
+    assert_eq!(35, stored_epoch(storage).await);
+
+    // a committed epoch reads back under every policy, while one which was never committed
+    // is reported as not visible once the policy's attempts run out
+    assert!(storage.begin_transaction().await);
+    assert_eq!(Ok(()), storage.batch_set(epoch_records(36)).await);
+    assert_eq!(
+        Ok(()),
+        storage
+            .commit_epoch_with_policy(35, ReadAfterWritePolicy::Immediate)
+            .await
+    );
+    assert_eq!(
+        Ok(()),
+        storage
+            .verify_epoch_visible(37, ReadAfterWritePolicy::Skip)
+            .await
+    );
+    assert_eq!(
+        Err(StorageError::WriteNotVisible {
+            key: "epoch commit marker 37".to_string(),
+            attempts: 1
+        }),
+        storage
+            .verify_epoch_visible(37, ReadAfterWritePolicy::Immediate)
+            .await
+    );
+
+    // retrying with a backoff needs a runtime to sleep on
+    #[cfg(feature = "tokio_runtime")]
+    {
+        let retry = ReadAfterWritePolicy::RetryWithBackoff {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+        };
+        assert_eq!(Ok(()), storage.verify_epoch_visible(36, retry).await);
+        assert_eq!(
+            Err(StorageError::WriteNotVisible {
+                key: "epoch commit marker 37".to_string(),
+                attempts: 3
+            }),
+            storage.verify_epoch_visible(37, retry).await
+        );
+    }
+}
+
 async fn test_user_data<S: Storage + Sync + Send>(storage: &S) {
     let rand_user = thread_rng()
         .sample_iter(&Alphanumeric)
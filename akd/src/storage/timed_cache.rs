@@ -7,8 +7,10 @@
 
 //! This module implements a basic async timed cache
 
+use crate::node_label::NodeLabel;
 use crate::storage::DbRecord;
 use crate::storage::Storable;
+use crate::tree_node::{NodeKey, TreeNodeWithPreviousValue};
 use log::{debug, error, info, trace, warn};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
@@ -21,6 +23,8 @@ const CACHE_CLEAN_FREQUENCY_MS: u64 = 15000;
 
 struct CachedItem {
     expiration: Instant,
+    // the cache's epoch when the item was loaded. Items from another epoch are misses
+    epoch: u64,
     data: DbRecord,
 }
 
@@ -33,6 +37,7 @@ pub struct TimedCache {
     can_clean: Arc<tokio::sync::RwLock<bool>>,
     item_lifetime: Duration,
     hit_count: Arc<tokio::sync::RwLock<u64>>,
+    epoch: Arc<tokio::sync::RwLock<u64>>,
 }
 
 impl TimedCache {
@@ -66,6 +71,7 @@ impl Clone for TimedCache {
             can_clean: self.can_clean.clone(),
             item_lifetime: self.item_lifetime,
             hit_count: self.hit_count.clone(),
+            epoch: self.epoch.clone(),
         }
     }
 }
@@ -120,6 +126,7 @@ impl TimedCache {
             can_clean: Arc::new(tokio::sync::RwLock::new(true)),
             item_lifetime: lifetime,
             hit_count: Arc::new(tokio::sync::RwLock::new(0)),
+            epoch: Arc::new(tokio::sync::RwLock::new(0)),
         }
     }
 
@@ -148,10 +155,15 @@ impl TimedCache {
             return record;
         }
 
+        let epoch = *self.epoch.read().await;
         let guard = self.map.read().await;
         let ptr: &HashMap<_, _> = &*guard;
         debug!("END cache retrieve");
         if let Some(result) = ptr.get(&full_key) {
+            if result.epoch != epoch {
+                // loaded for another epoch and not known to be unchanged since
+                return None;
+            }
             *(self.hit_count.write().await) += 1;
 
             let ignore_clean = !*self.can_clean.read().await;
@@ -177,10 +189,12 @@ impl TimedCache {
             let mut guard = self.azks.write().await;
             *guard = Some(DbRecord::Azks(azks_ref.clone()));
         } else {
+            let epoch = *self.epoch.read().await;
             let mut guard = self.map.write().await;
             // overwrite any existing items since a flush is requested
             let item = CachedItem {
                 expiration: Instant::now() + self.item_lifetime,
+                epoch,
                 data: record.clone(),
             };
             (*guard).insert(key, item);
@@ -193,6 +207,7 @@ impl TimedCache {
         self.clean().await;
 
         debug!("BEGIN cache put batch");
+        let epoch = *self.epoch.read().await;
         let mut guard = self.map.write().await;
         for record in records.iter() {
             if let DbRecord::Azks(azks_ref) = &record {
@@ -202,6 +217,7 @@ impl TimedCache {
                 let key = record.get_full_binary_id();
                 let item = CachedItem {
                     expiration: Instant::now() + self.item_lifetime,
+                    epoch,
                     data: record.clone(),
                 };
                 (*guard).insert(key, item);
@@ -220,6 +236,32 @@ impl TimedCache {
         debug!("END cache flush");
     }
 
+    /// Move the cache on to the given epoch. Tree nodes cached for the current epoch which
+    /// aren't among the changed labels are carried over to the new one, while every other
+    /// item is left to miss until it's reloaded. The AZKS is dropped so that it's reloaded
+    /// for the new epoch too.
+    pub async fn advance_epoch(&self, epoch: u64, changed: &[NodeLabel]) {
+        debug!("BEGIN cache advance epoch");
+        let changed_keys = changed
+            .iter()
+            .map(|label| TreeNodeWithPreviousValue::get_full_binary_key_id(&NodeKey(*label)))
+            .collect::<HashSet<_>>();
+        let mut epoch_guard = self.epoch.write().await;
+        let mut guard = self.map.write().await;
+        for (key, item) in guard.iter_mut() {
+            if item.epoch == *epoch_guard
+                && matches!(item.data, DbRecord::TreeNode(_))
+                && !changed_keys.contains(key)
+            {
+                item.epoch = epoch;
+            }
+        }
+        *epoch_guard = epoch;
+        let mut azks_guard = self.azks.write().await;
+        *azks_guard = None;
+        debug!("END cache advance epoch");
+    }
+
     /// Disable cache-cleaning (i.e. during a transaction)
     pub async fn disable_clean(&self) {
         debug!("Disabling MySQL object cache cleaning");
@@ -0,0 +1,205 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under both the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree and the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree.
+
+//! A storage wrapper which bounds the time each storage operation may take
+
+use crate::errors::StorageError;
+use crate::node_label::NodeLabel;
+use crate::storage::types::{
+    AkdLabel, AkdValue, DbRecord, KeyData, ValueState, ValueStateKey, ValueStateRetrievalFlag,
+};
+use crate::storage::{Storable, Storage};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Wraps a [Storage] implementation, failing any operation on it which doesn't complete
+/// within the configured timeout with [StorageError::Timeout]. With no timeout configured,
+/// every call is passed through unchanged.
+///
+/// Operations which can't fail (such as checking whether a transaction is active) aren't
+/// bounded, and neither are commits: a commit which timed out would be dropped part way
+/// through writing, leaving the caller with an error for a commit which may already be
+/// durable, so commits are always waited for. Likewise a write made outside of a transaction
+/// which times out may or may not have been applied. When combined with a wrapper which
+/// retries failed operations, the timeout should sit inside of the retries (i.e. wrap the
+/// backend in this, and that in the retrying wrapper), so that each attempt is bounded
+/// individually and a timed out attempt is retried.
+#[derive(Clone)]
+pub struct TimeoutStorage<S: Storage> {
+    storage: S,
+    timeout: Option<Duration>,
+}
+
+impl<S: Storage + Sync + Send> TimeoutStorage<S> {
+    /// Wraps the given storage, bounding each operation by timeout if one is given
+    pub fn new(storage: S, timeout: Option<Duration>) -> Self {
+        Self { storage, timeout }
+    }
+
+    async fn bounded<T, F>(&self, operation: &str, future: F) -> Result<T, StorageError>
+    where
+        F: Future<Output = Result<T, StorageError>> + Send,
+    {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return future.await,
+        };
+        let start = Instant::now();
+        match tokio::time::timeout(timeout, future).await {
+            Ok(result) => result,
+            Err(_) => Err(StorageError::Timeout {
+                operation: operation.to_string(),
+                elapsed: start.elapsed(),
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Storage + Sync + Send> Storage for TimeoutStorage<S> {
+    async fn log_metrics(&self, level: log::Level) {
+        self.storage.log_metrics(level).await
+    }
+
+    async fn begin_transaction(&self) -> bool {
+        self.storage.begin_transaction().await
+    }
+
+    async fn commit_transaction(&self) -> Result<(), StorageError> {
+        self.storage.commit_transaction().await
+    }
+
+    async fn rollback_transaction(&self) -> Result<(), StorageError> {
+        self.bounded("rollback_transaction", self.storage.rollback_transaction())
+            .await
+    }
+
+    async fn is_transaction_active(&self) -> bool {
+        self.storage.is_transaction_active().await
+    }
+
+    async fn commit_epoch(&self, expected_epoch: u64) -> Result<(), StorageError> {
+        self.storage.commit_epoch(expected_epoch).await
+    }
+
+    async fn set(&self, record: DbRecord) -> Result<(), StorageError> {
+        self.bounded("set", self.storage.set(record)).await
+    }
+
+    async fn batch_set(&self, records: Vec<DbRecord>) -> Result<(), StorageError> {
+        self.bounded("batch_set", self.storage.batch_set(records))
+            .await
+    }
+
+    async fn get<St: Storable>(&self, id: &St::StorageKey) -> Result<DbRecord, StorageError> {
+        self.bounded("get", self.storage.get::<St>(id)).await
+    }
+
+    async fn get_direct<St: Storable>(
+        &self,
+        id: &St::StorageKey,
+    ) -> Result<DbRecord, StorageError> {
+        self.bounded("get_direct", self.storage.get_direct::<St>(id))
+            .await
+    }
+
+    async fn flush_cache(&self) {
+        self.storage.flush_cache().await
+    }
+
+    async fn advance_cache_epoch(&self, epoch: u64, changed: &[NodeLabel]) {
+        self.storage.advance_cache_epoch(epoch, changed).await
+    }
+
+    async fn tombstone_value_states(&self, keys: &[ValueStateKey]) -> Result<(), StorageError> {
+        self.bounded(
+            "tombstone_value_states",
+            self.storage.tombstone_value_states(keys),
+        )
+        .await
+    }
+
+    async fn batch_get<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> Result<Vec<DbRecord>, StorageError> {
+        self.bounded("batch_get", self.storage.batch_get::<St>(ids))
+            .await
+    }
+
+    async fn batch_get_direct<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> Result<Vec<DbRecord>, StorageError> {
+        self.bounded("batch_get_direct", self.storage.batch_get_direct::<St>(ids))
+            .await
+    }
+
+    async fn get_user_data(&self, username: &AkdLabel) -> Result<KeyData, StorageError> {
+        self.bounded("get_user_data", self.storage.get_user_data(username))
+            .await
+    }
+
+    async fn get_user_state(
+        &self,
+        username: &AkdLabel,
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<ValueState, StorageError> {
+        self.bounded(
+            "get_user_state",
+            self.storage.get_user_state(username, flag),
+        )
+        .await
+    }
+
+    async fn get_user_state_versions(
+        &self,
+        usernames: &[AkdLabel],
+        flag: ValueStateRetrievalFlag,
+    ) -> Result<HashMap<AkdLabel, (u64, AkdValue)>, StorageError> {
+        self.bounded(
+            "get_user_state_versions",
+            self.storage.get_user_state_versions(usernames, flag),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::AsyncInMemoryDatabase;
+    use serial_test::serial;
+
+    #[tokio::test]
+    async fn test_operation_times_out() {
+        let storage = TimeoutStorage::new(
+            AsyncInMemoryDatabase::new(),
+            Some(Duration::from_millis(10)),
+        );
+        let result = storage
+            .bounded("get", std::future::pending::<Result<(), StorageError>>())
+            .await;
+        match result {
+            Err(StorageError::Timeout { operation, elapsed }) => {
+                assert_eq!("get", operation);
+                assert!(elapsed >= Duration::from_millis(10));
+            }
+            other => panic!("Expected a timeout, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_no_timeout_passes_through() {
+        let storage = TimeoutStorage::new(AsyncInMemoryDatabase::new(), None);
+        crate::storage::tests::run_test_cases_for_storage_impl(&storage).await;
+    }
+}
@@ -6,10 +6,11 @@
 // of this source tree.
 
 //! Various storage and representation related types
+use crate::errors::AzksError;
 #[cfg(feature = "serde_serialization")]
 use crate::serialization::{bytes_deserialize_hex, bytes_serialize_hex};
 use crate::storage::Storable;
-use crate::tree_node::{NodeType, TreeNode, TreeNodeWithPreviousValue};
+use crate::tree_node::{NodeKey, NodeType, TreeNode, TreeNodeWithPreviousValue};
 use crate::{Azks, NodeLabel};
 use std::convert::TryInto;
 
@@ -24,6 +25,14 @@ pub enum StorageType {
     /// Better to keep ValueState = 4 as is?
     /// ValueState
     ValueState = 4,
+    /// EpochTimestamp
+    EpochTimestamp = 5,
+    /// EpochCommitMarker
+    EpochCommitMarker = 6,
+    /// EpochRootHash
+    EpochRootHash = 7,
+    /// LeafMetadata
+    LeafMetadata = 8,
 }
 
 /// The keys for this key-value store
@@ -170,6 +179,236 @@ impl ValueState {
     }
 }
 
+/// The wall-clock time at which an epoch was finalized. These records are only
+/// written when the directory is configured to do so, see
+/// [`crate::directory::Directory::with_epoch_timestamps`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct EpochTimestamp {
+    /// The epoch which was finalized
+    pub epoch: u64,
+    /// The time at which the epoch was finalized, in milliseconds since the unix epoch
+    pub timestamp_millis: u64,
+}
+
+impl crate::storage::Storable for EpochTimestamp {
+    type StorageKey = u64;
+
+    fn data_type() -> StorageType {
+        StorageType::EpochTimestamp
+    }
+
+    fn get_id(&self) -> u64 {
+        self.epoch
+    }
+
+    fn get_full_binary_key_id(key: &u64) -> Vec<u8> {
+        let mut result = vec![StorageType::EpochTimestamp as u8];
+        result.extend_from_slice(&key.to_le_bytes());
+
+        result
+    }
+
+    fn key_from_full_binary(bin: &[u8]) -> Result<u64, String> {
+        if bin.len() < 9 {
+            return Err("Not enough bytes to form a proper key".to_string());
+        }
+
+        if bin[0] != StorageType::EpochTimestamp as u8 {
+            return Err("Not an epoch timestamp key".to_string());
+        }
+
+        let epoch_bytes: [u8; 8] = bin[1..=8].try_into().expect("Slice with incorrect length");
+        Ok(u64::from_le_bytes(epoch_bytes))
+    }
+}
+
+/// Records that every write of an epoch has been committed to storage. The marker is written
+/// in the same storage transaction as the epoch's other records (see
+/// [crate::storage::Storage::commit_epoch]), e.g. a single SQL transaction for MySQL, so it's
+/// present exactly when all of them, including the azks which advances to the epoch, are.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct EpochCommitMarker {
+    /// The epoch which was committed
+    pub epoch: u64,
+}
+
+impl crate::storage::Storable for EpochCommitMarker {
+    type StorageKey = u64;
+
+    fn data_type() -> StorageType {
+        StorageType::EpochCommitMarker
+    }
+
+    fn get_id(&self) -> u64 {
+        self.epoch
+    }
+
+    fn get_full_binary_key_id(key: &u64) -> Vec<u8> {
+        let mut result = vec![StorageType::EpochCommitMarker as u8];
+        result.extend_from_slice(&key.to_le_bytes());
+
+        result
+    }
+
+    fn key_from_full_binary(bin: &[u8]) -> Result<u64, String> {
+        if bin.len() < 9 {
+            return Err("Not enough bytes to form a proper key".to_string());
+        }
+
+        if bin[0] != StorageType::EpochCommitMarker as u8 {
+            return Err("Not an epoch commit marker key".to_string());
+        }
+
+        let epoch_bytes: [u8; 8] = bin[1..=8].try_into().expect("Slice with incorrect length");
+        Ok(u64::from_le_bytes(epoch_bytes))
+    }
+}
+
+/// The root hash of the tree as of an epoch, written when the epoch is published. The tree
+/// itself only keeps the latest and the previous state of each node, so this is what lets
+/// the root hash of an older epoch be read back, e.g. to verify a history proof.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct EpochRootHash {
+    /// The epoch which was published
+    pub epoch: u64,
+    /// The root hash of the tree as of the epoch
+    pub root_hash: [u8; 32],
+}
+
+impl crate::storage::Storable for EpochRootHash {
+    type StorageKey = u64;
+
+    fn data_type() -> StorageType {
+        StorageType::EpochRootHash
+    }
+
+    fn get_id(&self) -> u64 {
+        self.epoch
+    }
+
+    fn get_full_binary_key_id(key: &u64) -> Vec<u8> {
+        let mut result = vec![StorageType::EpochRootHash as u8];
+        result.extend_from_slice(&key.to_le_bytes());
+
+        result
+    }
+
+    fn key_from_full_binary(bin: &[u8]) -> Result<u64, String> {
+        if bin.len() < 9 {
+            return Err("Not enough bytes to form a proper key".to_string());
+        }
+
+        if bin[0] != StorageType::EpochRootHash as u8 {
+            return Err("Not an epoch root hash key".to_string());
+        }
+
+        let epoch_bytes: [u8; 8] = bin[1..=8].try_into().expect("Slice with incorrect length");
+        Ok(u64::from_le_bytes(epoch_bytes))
+    }
+}
+
+/// Application data kept alongside a leaf of the tree, e.g. when or where its value came from.
+/// This is stored out of the tree: it isn't hashed into the leaf or anywhere else, so it can
+/// be changed at any time without a new epoch and it is NOT cryptographically committed to.
+/// It takes no part in any proof, and clients mustn't trust it as if it did.
+///
+/// The metadata is keyed by the leaf's label alone, not by the azks the leaf belongs to, so
+/// trees which share a storage layer also share the metadata of any labels they have in common.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde_serialization",
+    derive(serde::Deserialize, serde::Serialize)
+)]
+pub struct LeafMetadata {
+    /// The label of the leaf
+    pub label: NodeLabel,
+    /// The metadata, at most [LeafMetadata::MAX_DATA_LEN] bytes
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(serialize_with = "bytes_serialize_hex")
+    )]
+    #[cfg_attr(
+        feature = "serde_serialization",
+        serde(deserialize_with = "bytes_deserialize_hex")
+    )]
+    data: Vec<u8>,
+}
+
+impl LeafMetadata {
+    /// The most bytes of metadata a leaf can have, which is what the MySQL storage layer's
+    /// column holds
+    pub const MAX_DATA_LEN: usize = 2000;
+
+    /// Creates the metadata of the leaf with the given label. Fails with
+    /// [AzksError::LeafMetadataTooLong] if there are more than [LeafMetadata::MAX_DATA_LEN]
+    /// bytes of it.
+    pub fn new(label: NodeLabel, data: Vec<u8>) -> Result<Self, AzksError> {
+        if data.len() > Self::MAX_DATA_LEN {
+            return Err(AzksError::LeafMetadataTooLong {
+                label,
+                len: data.len(),
+            });
+        }
+        Ok(LeafMetadata { label, data })
+    }
+
+    /// The metadata
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl crate::storage::Storable for LeafMetadata {
+    type StorageKey = NodeKey;
+
+    fn data_type() -> StorageType {
+        StorageType::LeafMetadata
+    }
+
+    fn get_id(&self) -> NodeKey {
+        NodeKey(self.label)
+    }
+
+    fn get_full_binary_key_id(key: &NodeKey) -> Vec<u8> {
+        let mut result = vec![StorageType::LeafMetadata as u8];
+        result.extend_from_slice(&key.0.label_len.to_le_bytes());
+        result.extend_from_slice(&key.0.label_val);
+        result
+    }
+
+    fn key_from_full_binary(bin: &[u8]) -> Result<NodeKey, String> {
+        if bin.len() < 37 {
+            return Err("Not enough bytes to form a proper key".to_string());
+        }
+
+        if bin[0] != StorageType::LeafMetadata as u8 {
+            return Err("Not a leaf metadata key".to_string());
+        }
+
+        let len_bytes: [u8; 4] = bin[1..=4]
+            .try_into()
+            .map_err(|_| "Slice with incorrect length".to_string())?;
+        let val_bytes: [u8; 32] = bin[5..=36]
+            .try_into()
+            .map_err(|_| "Slice with incorrect length".to_string())?;
+        let len = u32::from_le_bytes(len_bytes);
+
+        Ok(NodeKey(NodeLabel::new(val_bytes, len)))
+    }
+}
+
 /// Data associated with a given key. That is all the states at the various epochs
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(
@@ -214,6 +453,14 @@ pub enum DbRecord {
     TreeNode(TreeNodeWithPreviousValue),
     /// The state of the value for a particular key.
     ValueState(ValueState),
+    /// The time at which an epoch was finalized
+    EpochTimestamp(EpochTimestamp),
+    /// The marker that every write of an epoch was committed
+    EpochCommitMarker(EpochCommitMarker),
+    /// The root hash of the tree as of an epoch
+    EpochRootHash(EpochRootHash),
+    /// Metadata kept alongside a leaf, out of the tree
+    LeafMetadata(LeafMetadata),
 }
 
 impl Clone for DbRecord {
@@ -222,6 +469,10 @@ impl Clone for DbRecord {
             DbRecord::Azks(azks) => DbRecord::Azks(azks.clone()),
             DbRecord::TreeNode(node) => DbRecord::TreeNode(node.clone()),
             DbRecord::ValueState(state) => DbRecord::ValueState(state.clone()),
+            DbRecord::EpochTimestamp(timestamp) => DbRecord::EpochTimestamp(timestamp.clone()),
+            DbRecord::EpochCommitMarker(marker) => DbRecord::EpochCommitMarker(marker.clone()),
+            DbRecord::EpochRootHash(root) => DbRecord::EpochRootHash(root.clone()),
+            DbRecord::LeafMetadata(metadata) => DbRecord::LeafMetadata(metadata.clone()),
         }
     }
 }
@@ -234,6 +485,10 @@ impl DbRecord {
             DbRecord::Azks(azks) => azks.get_full_binary_id(),
             DbRecord::TreeNode(node) => node.get_full_binary_id(),
             DbRecord::ValueState(state) => state.get_full_binary_id(),
+            DbRecord::EpochTimestamp(timestamp) => timestamp.get_full_binary_id(),
+            DbRecord::EpochCommitMarker(marker) => marker.get_full_binary_id(),
+            DbRecord::EpochRootHash(root) => root.get_full_binary_id(),
+            DbRecord::LeafMetadata(metadata) => metadata.get_full_binary_id(),
         }
     }
 
@@ -241,9 +496,11 @@ impl DbRecord {
     /// A smaller value indicates higher priority in being written first.
     /// An Azks record should always be updated last, so that any concurrent storage readers will
     /// not see an increase in the current epoch until every other record for the new epoch has
-    /// been written to storage.
+    /// been written to storage. The only exception is an epoch commit marker, which is written
+    /// after the Azks, as it records that every write of the epoch has completed.
     pub(crate) fn transaction_priority(&self) -> u8 {
         match &self {
+            DbRecord::EpochCommitMarker(_) => 3,
             DbRecord::Azks(_) => 2,
             _ => 1,
         }
@@ -318,6 +575,32 @@ impl DbRecord {
         }
     }
 
+    /// Build an epoch timestamp from the properties
+    pub fn build_epoch_timestamp(epoch: u64, timestamp_millis: u64) -> EpochTimestamp {
+        EpochTimestamp {
+            epoch,
+            timestamp_millis,
+        }
+    }
+
+    /// Build an epoch commit marker from the properties
+    pub fn build_epoch_commit_marker(epoch: u64) -> EpochCommitMarker {
+        EpochCommitMarker { epoch }
+    }
+
+    /// Build an epoch root hash from the properties
+    pub fn build_epoch_root_hash(epoch: u64, root_hash: [u8; 32]) -> EpochRootHash {
+        EpochRootHash { epoch, root_hash }
+    }
+
+    /// Build leaf metadata from the properties
+    pub fn build_leaf_metadata(label_val: [u8; 32], label_len: u32, data: Vec<u8>) -> LeafMetadata {
+        LeafMetadata {
+            label: NodeLabel::new(label_val, label_len),
+            data,
+        }
+    }
+
     /// Build a user state from the properties
     pub fn build_user_state(
         username: Vec<u8>,
@@ -9,20 +9,41 @@
 //! Contains the tests for the high-level API (directory, auditor, client)
 
 use crate::{
-    auditor::audit_verify,
+    append_only_zks::{Azks, InsertOptions, InsertPolicy},
+    auditor::{
+        audit_verify, verify_append_only_segment, verify_append_only_segment_in, verify_bundle,
+        verify_bundle_with_algorithm, verify_compact, verify_epoch_inclusion, AuditBundle,
+        AuditHasher, AuditSession, EpochRootMmr,
+    },
     client::{key_history_verify, lookup_verify},
     directory::{get_key_history_hashes, Directory},
     ecvrf::{HardCodedAkdVRF, VRFKeyStorage},
-    errors::AkdError,
+    errors::{AkdError, AuditorError, AzksError, DirectoryError, TreeNodeError},
+    proof_structs::ProofDiff,
     storage::{
         memory::AsyncInMemoryDatabase,
-        types::{AkdLabel, AkdValue, DbRecord},
+        types::{
+            AkdLabel, AkdValue, DbRecord, EpochRootHash, EpochTimestamp, ValueStateRetrievalFlag,
+        },
         Storage,
     },
+    tree_node::merge_epoch,
+    Node, NodeLabel,
+};
+#[cfg(feature = "serde_serialization")]
+use crate::{
+    errors::StorageError,
+    storage::{
+        key_value::{AsyncKeyValueStore, InMemoryKeyValueStore, KeyValueStorage},
+        types::StorageType,
+        ReadAfterWritePolicy,
+    },
 };
+use futures::{StreamExt, TryStreamExt};
+use std::task::Poll;
 use winter_crypto::{
     hashers::{Blake3_256, Sha3_256},
-    Digest,
+    Digest, Hasher,
 };
 use winter_math::fields::f128::BaseElement;
 type Blake3 = Blake3_256<BaseElement>;
@@ -99,6 +120,46 @@ async fn test_simple_lookup() -> Result<(), AkdError> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_constant_time_lookup() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+    akd.publish::<Blake3>(vec![
+        (
+            AkdLabel::from_utf8_str("hello"),
+            AkdValue::from_utf8_str("world"),
+        ),
+        (
+            AkdLabel::from_utf8_str("hello2"),
+            AkdValue::from_utf8_str("world2"),
+        ),
+    ])
+    .await?;
+
+    // Padding the traversal shouldn't change the proof itself
+    let constant_time_akd = akd.clone().with_constant_time_proofs(true);
+    let lookup_proof = constant_time_akd
+        .lookup::<Blake3>(AkdLabel::from_utf8_str("hello"))
+        .await?;
+    assert_eq!(
+        akd.lookup::<Blake3>(AkdLabel::from_utf8_str("hello"))
+            .await?,
+        lookup_proof
+    );
+
+    let current_azks = akd.retrieve_current_azks().await?;
+    let root_hash = akd.get_root_hash::<Blake3>(&current_azks).await?;
+    let vrf_pk = akd.get_public_key().await?;
+    lookup_verify::<Blake3>(
+        &vrf_pk,
+        root_hash,
+        AkdLabel::from_utf8_str("hello"),
+        lookup_proof,
+    )?;
+    Ok(())
+}
+
 // This test also covers #144: That key history doesn't fail on very small trees,
 // i.e. trees with a potentially empty child for the root node.
 // Other that it is just a simple check to see that a valid key history proof passes.
@@ -463,6 +524,841 @@ async fn test_simple_audit() -> Result<(), AkdError> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_audit_verify_errors() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+
+    let root_hashes = publish_epochs(&akd, 3).await?;
+
+    // The start hash doesn't match the proof
+    let audit_proof = akd.audit::<Blake3>(2, 3).await?;
+    let result = audit_verify::<Blake3>(vec![root_hashes[0], root_hashes[2]], audit_proof).await;
+    assert!(matches!(
+        result,
+        Err(AkdError::AuditErr(AuditorError::StartHashMismatch(3, _, _)))
+    ));
+
+    // The end hash doesn't match the proof
+    let audit_proof = akd.audit::<Blake3>(1, 2).await?;
+    let result = audit_verify::<Blake3>(vec![root_hashes[0], root_hashes[2]], audit_proof).await;
+    assert!(matches!(
+        result,
+        Err(AkdError::AuditErr(AuditorError::EndHashMismatch(2, _, _)))
+    ));
+
+    // The proof skips over an epoch
+    let mut audit_proof = akd.audit::<Blake3>(1, 3).await?;
+    audit_proof.epochs[1] += 1;
+    let result = audit_verify::<Blake3>(root_hashes.clone(), audit_proof).await;
+    assert!(matches!(
+        result,
+        Err(AkdError::AuditErr(AuditorError::EpochGap(1, 3)))
+    ));
+
+    // The proof has a segment starting at the last possible epoch
+    let mut audit_proof = akd.audit::<Blake3>(1, 2).await?;
+    audit_proof.epochs[0] = u64::MAX;
+    let result = audit_verify::<Blake3>(root_hashes[0..2].to_vec(), audit_proof).await;
+    assert!(matches!(
+        result,
+        Err(AkdError::AuditErr(AuditorError::MalformedProof(_)))
+    ));
+
+    // The proof inserts the same leaf twice
+    let mut audit_proof = akd.audit::<Blake3>(1, 2).await?;
+    let duplicate = audit_proof.proofs[0].inserted[0];
+    audit_proof.proofs[0].inserted.push(duplicate);
+    let result = audit_verify::<Blake3>(root_hashes[0..2].to_vec(), audit_proof).await;
+    match result {
+        Err(AkdError::AuditErr(AuditorError::DuplicateInsertedLeaf(label))) => {
+            assert_eq!(duplicate.label, label);
+        }
+        other => panic!("Expected a duplicate inserted leaf, got {:?}", other),
+    }
+
+    // The proof overwrites a node which it also claims is unchanged, with the end hash of
+    // the tree in which the overwrite took effect
+    let mut audit_proof = akd.audit::<Blake3>(1, 2).await?;
+    let overwritten = Node::<Blake3> {
+        label: audit_proof.proofs[0].unchanged_nodes[0].label,
+        hash: Blake3::hash(b"overwritten"),
+    };
+    audit_proof.proofs[0].inserted.push(overwritten);
+    let forged_db = AsyncInMemoryDatabase::new();
+    let mut forged = Azks::new::<_, Blake3>(&forged_db).await?;
+    let inserted = audit_proof.proofs[0]
+        .inserted
+        .iter()
+        .map(|node| Node::<Blake3> {
+            label: node.label,
+            hash: merge_epoch::<Blake3>(node.hash, 2),
+        })
+        .collect();
+    for (epoch, nodes) in vec![audit_proof.proofs[0].unchanged_nodes.clone(), inserted]
+        .into_iter()
+        .enumerate()
+    {
+        forged.latest_epoch = epoch as u64;
+        forged
+            .batch_insert_leaves_helper::<_, Blake3>(
+                &forged_db,
+                nodes,
+                InsertOptions {
+                    append_only_exclude_usage: true,
+                    policy: InsertPolicy::UpdateExisting,
+                    ..InsertOptions::default()
+                },
+            )
+            .await?;
+    }
+    let forged_hash = forged.get_root_hash::<_, Blake3>(&forged_db).await?;
+    let result = audit_verify::<Blake3>(vec![root_hashes[0], forged_hash], audit_proof).await;
+    match result {
+        Err(AkdError::TreeNode(TreeNodeError::LabelAlreadyExists(label))) => {
+            assert_eq!(overwritten.label, label);
+        }
+        other => panic!("Expected an existing label to be rejected, got {:?}", other),
+    }
+
+    // The proof doesn't have as many epochs as hashes provided
+    let audit_proof = akd.audit::<Blake3>(1, 2).await?;
+    let result = audit_verify::<Blake3>(root_hashes, audit_proof).await;
+    assert!(matches!(
+        result,
+        Err(AkdError::AuditErr(AuditorError::MalformedProof(_)))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chained_append_only_segments() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+
+    let root_hashes = publish_epochs(&akd, 4).await?;
+
+    // Only the first start hash is supplied, each later one is carried over from the
+    // previous segment
+    let audit_proof = akd.audit::<Blake3>(1, 4).await?;
+    let mut start_hash = root_hashes[0];
+    for (i, proof) in audit_proof.proofs.iter().enumerate() {
+        let epoch = audit_proof.epochs[i] + 1;
+        start_hash = verify_append_only_segment::<Blake3>(proof, start_hash, epoch).await?;
+        assert_eq!(root_hashes[epoch as usize - 1], start_hash);
+    }
+
+    // The segments can also all be verified in one reused database
+    let audit_db = AsyncInMemoryDatabase::new();
+    let mut start_hash = root_hashes[0];
+    for (i, proof) in audit_proof.proofs.iter().enumerate() {
+        let epoch = audit_proof.epochs[i] + 1;
+        start_hash =
+            verify_append_only_segment_in::<Blake3>(&audit_db, proof, start_hash, epoch).await?;
+        assert_eq!(root_hashes[epoch as usize - 1], start_hash);
+    }
+
+    // A segment doesn't chain on from the wrong start hash
+    let result =
+        verify_append_only_segment::<Blake3>(&audit_proof.proofs[1], root_hashes[0], 3).await;
+    assert!(matches!(
+        result,
+        Err(AkdError::AuditErr(AuditorError::StartHashMismatch(3, _, _)))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_audit_session() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+
+    let root_hashes = publish_epochs(&akd, 6).await?;
+
+    // Two segments per step, so the five segments take three steps
+    let mut session =
+        AuditSession::<Blake3>::new(root_hashes.clone(), akd.audit::<Blake3>(1, 6).await?)?;
+    assert_eq!((0, 5), session.progress());
+    assert!(session.step(0).await.is_pending());
+    assert!(session.step(2).await.is_pending());
+    assert_eq!((2, 5), session.progress());
+    assert!(session.step(2).await.is_pending());
+    assert!(matches!(session.step(2).await, Poll::Ready(Ok(()))));
+    assert_eq!((5, 5), session.progress());
+    assert!(matches!(session.step(2).await, Poll::Ready(Ok(()))));
+
+    // A resumed session only verifies the remaining segments
+    let mut session =
+        AuditSession::<Blake3>::resume(root_hashes.clone(), akd.audit::<Blake3>(1, 6).await?, 3)?;
+    assert!(matches!(
+        session.step(usize::MAX).await,
+        Poll::Ready(Ok(()))
+    ));
+    assert!(AuditSession::<Blake3>::resume(
+        root_hashes.clone(),
+        akd.audit::<Blake3>(1, 6).await?,
+        6
+    )
+    .is_err());
+
+    // A bad segment fails its step, and isn't counted as verified
+    let mut bad_hashes = root_hashes.clone();
+    bad_hashes[4] = root_hashes[0];
+    let mut session = AuditSession::<Blake3>::new(bad_hashes, akd.audit::<Blake3>(1, 6).await?)?;
+    assert!(session.step(3).await.is_pending());
+    assert!(matches!(
+        session.step(3).await,
+        Poll::Ready(Err(AkdError::AuditErr(AuditorError::EndHashMismatch(
+            5,
+            _,
+            _
+        ))))
+    ));
+    assert_eq!((3, 5), session.progress());
+
+    // The proof and hashes must line up
+    assert!(AuditSession::<Blake3>::new(
+        root_hashes[1..].to_vec(),
+        akd.audit::<Blake3>(1, 6).await?
+    )
+    .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_epoch_inclusion_proof() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+
+    let root_hashes = publish_epochs(&akd, 7).await?;
+    for i in 1..=7 {
+        let mmr = EpochRootMmr::<Blake3>::from_storage(&db, 1, i).await?;
+
+        // Every epoch so far is provable against the MMR root, whatever its shape
+        let mmr_root = mmr.root();
+        for epoch in 1..=i {
+            let proof = mmr.get_epoch_inclusion_proof(epoch)?;
+            verify_epoch_inclusion::<Blake3>(mmr_root, root_hashes[epoch as usize - 1], &proof)?;
+        }
+    }
+    let mut mmr = EpochRootMmr::<Blake3>::from_storage(&db, 1, 7).await?;
+    assert_eq!(7, mmr.len());
+    assert_eq!(3, mmr.peaks().len());
+
+    // Another epoch's root hash, or a proof against an older root, doesn't verify
+    let proof = mmr.get_epoch_inclusion_proof(3)?;
+    assert!(verify_epoch_inclusion::<Blake3>(mmr.root(), root_hashes[3], &proof).is_err());
+    let mut old_mmr = EpochRootMmr::<Blake3>::from_storage(&db, 1, 6).await?;
+    assert!(verify_epoch_inclusion::<Blake3>(old_mmr.root(), root_hashes[2], &proof).is_err());
+    let mut moved = proof.clone();
+    moved.epoch = 4;
+    assert!(verify_epoch_inclusion::<Blake3>(mmr.root(), root_hashes[2], &moved).is_err());
+
+    // Epochs can only be appended in order, and proven once appended
+    assert!(matches!(
+        mmr.append(9, root_hashes[0]),
+        Err(AkdError::AuditErr(AuditorError::EpochGap(7, 9)))
+    ));
+    assert!(matches!(
+        mmr.get_epoch_inclusion_proof(8),
+        Err(AkdError::AuditErr(AuditorError::EpochNotInMmr(8)))
+    ));
+
+    // An MMR built earlier is caught up with the epochs published since
+    old_mmr.extend_from_storage(&db, 7).await?;
+    assert_eq!(mmr, old_mmr);
+    assert!(matches!(
+        mmr.extend_from_storage(&db, 8).await,
+        Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(_, 8)))
+    ));
+
+    // An MMR from genesis covers the root hash of the empty tree at epoch 0 as well
+    let genesis_mmr = EpochRootMmr::<Blake3>::from_storage(&db, 0, 7).await?;
+    assert_eq!(8, genesis_mmr.len());
+    verify_epoch_inclusion::<Blake3>(
+        genesis_mmr.root(),
+        root_hashes[6],
+        &genesis_mmr.get_epoch_inclusion_proof(7)?,
+    )?;
+
+    // The root of a single epoch's MMR is its one peak bagged onto the range it covers, with
+    // the start epoch and number of epochs as 8 little-endian bytes each
+    let single_mmr = EpochRootMmr::<Blake3>::from_storage(&db, 3, 3).await?;
+    let range = Blake3::hash(&[3u64.to_le_bytes(), 1u64.to_le_bytes()].concat());
+    assert_eq!(
+        Blake3::merge(&[range, merge_epoch::<Blake3>(root_hashes[2], 3)]),
+        single_mmr.root()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_compact_audit_proof() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+
+    let mut root_hashes = vec![];
+
+    // A large first epoch followed by several small ones, so consecutive
+    // epochs share most of their unchanged subtrees
+    let initial = (0..50)
+        .map(|i| {
+            (
+                AkdLabel::from_utf8_str(&format!("user{}", i)),
+                AkdValue::from_utf8_str(&format!("value{}", i)),
+            )
+        })
+        .collect::<Vec<_>>();
+    root_hashes.push(akd.publish::<Blake3>(initial).await?.1);
+    root_hashes.extend(publish_epochs(&akd, 4).await?);
+
+    let audit_proof = akd.audit::<Blake3>(1, 5).await?;
+    let compact_proof = audit_proof.compact();
+
+    // The compact form should be smaller than the full proof
+    let full_node_count: usize = audit_proof
+        .proofs
+        .iter()
+        .map(|proof| proof.inserted.len() + proof.unchanged_nodes.len())
+        .sum();
+    assert!(compact_proof.nodes.len() < full_node_count);
+
+    // And expand back into exactly the original proof
+    assert_eq!(audit_proof, compact_proof.expand()?);
+
+    verify_compact::<Blake3>(root_hashes.clone(), compact_proof.clone()).await?;
+
+    // A compact proof referencing a node which doesn't exist should fail to verify
+    let mut invalid_proof = compact_proof;
+    invalid_proof.proofs[0]
+        .unchanged_nodes
+        .push(invalid_proof.nodes.len() as u64);
+    assert!(verify_compact::<Blake3>(root_hashes, invalid_proof)
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_audit_proof_diff() -> Result<(), AkdError> {
+    let vrf = HardCodedAkdVRF {};
+    let db = AsyncInMemoryDatabase::new();
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+    let other_db = AsyncInMemoryDatabase::new();
+    let other_akd = Directory::<_, _>::new::<Blake3>(&other_db, &vrf, false).await?;
+
+    // Both directories agree on the first two epochs, then diverge on the value
+    // published in the third
+    for (i, other_value) in ["value0", "value1", "other_value2"].iter().enumerate() {
+        let label = AkdLabel::from_utf8_str(&format!("user{}", i));
+        akd.publish::<Blake3>(vec![(
+            label.clone(),
+            AkdValue::from_utf8_str(&format!("value{}", i)),
+        )])
+        .await?;
+        other_akd
+            .publish::<Blake3>(vec![(label, AkdValue::from_utf8_str(other_value))])
+            .await?;
+    }
+
+    let proof = akd.audit::<Blake3>(1, 3).await?;
+    let other_proof = other_akd.audit::<Blake3>(1, 3).await?;
+    assert!(proof.diff(&proof).is_empty());
+
+    // Only the inserted leaf of the last segment differs
+    let diffs = proof.diff(&other_proof);
+    assert_eq!(1, diffs.len());
+    match &diffs[0] {
+        ProofDiff::Inserted {
+            segment,
+            ours,
+            theirs,
+            ..
+        } => {
+            assert_eq!(1, *segment);
+            assert!(ours.is_some() && theirs.is_some());
+            assert_ne!(ours, theirs);
+        }
+        other => panic!("Unexpected difference {:?}", other),
+    }
+
+    // A shorter proof is reported as missing the later segments
+    let shorter_proof = akd.audit::<Blake3>(1, 2).await?;
+    let diffs = proof.diff(&shorter_proof);
+    assert!(diffs.contains(&ProofDiff::Epoch {
+        segment: 1,
+        ours: Some(2),
+        theirs: None,
+    }));
+    assert!(diffs.iter().all(|diff| match diff {
+        ProofDiff::Epoch { segment, .. }
+        | ProofDiff::UnchangedNode { segment, .. }
+        | ProofDiff::Inserted { segment, .. } => *segment == 1,
+    }));
+
+    // A repeated leaf is reported for each of its occurrences, even when both proofs repeat it
+    let mut repeated_proof = akd.audit::<Blake3>(1, 3).await?;
+    let repeated = repeated_proof.proofs[1].inserted[0];
+    repeated_proof.proofs[1].inserted.push(repeated);
+    let diffs = proof.diff(&repeated_proof);
+    assert_eq!(2, diffs.len());
+    assert!(diffs.iter().all(|diff| matches!(
+        diff,
+        ProofDiff::Inserted { segment: 1, label, .. } if *label == repeated.label
+    )));
+    assert_eq!(2, repeated_proof.diff(&repeated_proof).len());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_verify_audit_bundle() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+
+    let root_hashes = publish_epochs(&akd, 3).await?;
+
+    let bundle = AuditBundle::<Blake3> {
+        hasher: AuditHasher::Blake3,
+        hashes: root_hashes,
+        proof: akd.audit::<Blake3>(1, 3).await?,
+    };
+    let bundle_bytes = bundle.to_bytes();
+
+    // The bundle should round-trip and verify without any other context
+    assert_eq!(bundle, AuditBundle::<Blake3>::from_bytes(&bundle_bytes)?);
+    verify_bundle(&bundle_bytes).await?;
+
+    // Claiming the wrong hash function should fail verification
+    let mut wrong_hasher = bundle_bytes.clone();
+    wrong_hasher[1] = AuditHasher::Sha3 as u8;
+    assert!(verify_bundle(&wrong_hasher).await.is_err());
+
+    // Decoding checks the recorded hash function against the one it decodes with
+    assert!(matches!(
+        AuditBundle::<Blake3>::from_bytes(&wrong_hasher),
+        Err(AkdError::AuditErr(AuditorError::AlgorithmMismatch {
+            expected: AuditHasher::Blake3,
+            got: AuditHasher::Sha3
+        }))
+    ));
+
+    // A verifier expecting a given hash function rejects a bundle made under another one
+    verify_bundle_with_algorithm(&bundle_bytes, AuditHasher::Blake3).await?;
+    assert!(matches!(
+        verify_bundle_with_algorithm(&wrong_hasher, AuditHasher::Blake3).await,
+        Err(AkdError::AuditErr(AuditorError::AlgorithmMismatch {
+            expected: AuditHasher::Blake3,
+            got: AuditHasher::Sha3
+        }))
+    ));
+    assert!(matches!(
+        verify_bundle_with_algorithm(&bundle_bytes, AuditHasher::Sha3).await,
+        Err(AkdError::AuditErr(AuditorError::AlgorithmMismatch {
+            expected: AuditHasher::Sha3,
+            got: AuditHasher::Blake3
+        }))
+    ));
+
+    // As should an unknown hash function or a truncated bundle
+    let mut unknown_hasher = bundle_bytes.clone();
+    unknown_hasher[1] = 0;
+    assert!(matches!(
+        verify_bundle(&unknown_hasher).await,
+        Err(AkdError::AuditErr(AuditorError::MalformedProof(_)))
+    ));
+    assert!(matches!(
+        verify_bundle(&bundle_bytes[..bundle_bytes.len() - 1]).await,
+        Err(AkdError::AuditErr(AuditorError::MalformedProof(_)))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_publish_with_epoch_timestamps() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false)
+        .await?
+        .with_epoch_timestamps(true);
+
+    for i in 0..3 {
+        akd.publish::<Blake3>(vec![(
+            AkdLabel::from_utf8_str(&format!("hello{}", i)),
+            AkdValue::from_utf8_str(&format!("world{}", i)),
+        )])
+        .await?;
+    }
+
+    let azks = akd.retrieve_current_azks().await?;
+    let mut timestamps = vec![];
+    for epoch in 1..=3 {
+        match db.get::<EpochTimestamp>(&epoch).await? {
+            DbRecord::EpochTimestamp(timestamp) => timestamps.push(timestamp.timestamp_millis),
+            _ => panic!("Epoch timestamp not found in storage"),
+        }
+    }
+
+    // Before the first epoch was published
+    assert_eq!(None, azks.epoch_at_or_before(&db, timestamps[0] - 1).await?);
+    // At the time the latest epoch was finalized
+    assert_eq!(Some(3), azks.epoch_at_or_before(&db, timestamps[2]).await?);
+    // After the latest epoch
+    assert_eq!(Some(3), azks.epoch_at_or_before(&db, u64::MAX).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_root_hashes_of_old_epochs() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+
+    let mut published = vec![];
+    for i in 0..3 {
+        let epoch_hash = akd
+            .publish::<Blake3>(vec![(
+                AkdLabel::from_utf8_str("hello"),
+                AkdValue::from_utf8_str(&format!("world{}", i)),
+            )])
+            .await?;
+        published.push(epoch_hash.1);
+    }
+
+    // The root has been overwritten since the first epoch, but its root hash was recorded
+    let azks = akd.retrieve_current_azks().await?;
+    for (epoch, root_hash) in (1..=3).zip(published.iter()) {
+        assert_eq!(
+            *root_hash,
+            akd.get_root_hash_at_epoch::<Blake3>(&azks, epoch).await?
+        );
+    }
+
+    let history_proof = akd
+        .key_history::<Blake3>(&AkdLabel::from_utf8_str("hello"))
+        .await?;
+    let (root_hashes, previous_root_hashes) = get_key_history_hashes(&akd, &history_proof).await?;
+    assert_eq!(vec![published[2], published[1], published[0]], root_hashes);
+    assert_eq!(
+        vec![Some(published[1]), Some(published[0]), None],
+        previous_root_hashes
+    );
+    Ok(())
+}
+
+// A key-value store which never returns an epoch commit marker, as if the commit of an
+// epoch hadn't become visible to readers yet
+#[cfg(feature = "serde_serialization")]
+#[derive(Clone, Default)]
+struct MarkerHidingStore(InMemoryKeyValueStore);
+
+#[cfg(feature = "serde_serialization")]
+#[async_trait::async_trait]
+impl AsyncKeyValueStore for MarkerHidingStore {
+    async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        if key.first() == Some(&(StorageType::EpochCommitMarker as u8)) {
+            return Ok(None);
+        }
+        self.0.get(key).await
+    }
+
+    async fn set(&self, key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError> {
+        self.0.set(key, value).await
+    }
+
+    async fn batch_set(&self, items: Vec<(Vec<u8>, Vec<u8>)>) -> Result<(), StorageError> {
+        self.0.batch_set(items).await
+    }
+
+    async fn scan_prefix(&self, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, StorageError> {
+        self.0.scan_prefix(prefix).await
+    }
+}
+
+// A key-value store whose backend can't be connected to
+#[cfg(feature = "serde_serialization")]
+#[derive(Clone, Default)]
+struct UnreachableStore;
+
+#[cfg(feature = "serde_serialization")]
+#[async_trait::async_trait]
+impl AsyncKeyValueStore for UnreachableStore {
+    async fn get(&self, _key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
+        Err(StorageError::Connection("Connection refused".to_string()))
+    }
+
+    async fn set(&self, _key: Vec<u8>, _value: Vec<u8>) -> Result<(), StorageError> {
+        Err(StorageError::Connection("Connection refused".to_string()))
+    }
+
+    async fn batch_set(&self, _items: Vec<(Vec<u8>, Vec<u8>)>) -> Result<(), StorageError> {
+        Err(StorageError::Connection("Connection refused".to_string()))
+    }
+
+    async fn scan_prefix(&self, _prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, StorageError> {
+        Err(StorageError::Connection("Connection refused".to_string()))
+    }
+}
+
+#[cfg(feature = "serde_serialization")]
+#[tokio::test]
+async fn test_health_check_unreachable_storage() -> Result<(), AkdError> {
+    let db = KeyValueStorage::new(UnreachableStore);
+    let report = Azks::health_check::<_, Blake3>(&db).await?;
+    assert!(!report.storage_reachable);
+    assert_eq!(None, report.latest_epoch);
+    assert_eq!(None, report.root_hash);
+    Ok(())
+}
+
+#[cfg(feature = "serde_serialization")]
+#[tokio::test]
+async fn test_publish_committed_not_readable() -> Result<(), AkdError> {
+    let db = KeyValueStorage::new(MarkerHidingStore::default());
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false)
+        .await?
+        .with_read_after_write_policy(ReadAfterWritePolicy::Immediate);
+
+    let result = akd
+        .publish::<Blake3>(vec![(
+            AkdLabel::from_utf8_str("hello"),
+            AkdValue::from_utf8_str("world"),
+        )])
+        .await;
+    assert!(matches!(
+        result,
+        Err(AkdError::Directory(DirectoryError::CommittedNotReadable {
+            epoch: 1,
+            error: StorageError::WriteNotVisible { .. },
+        }))
+    ));
+    // The epoch stays committed rather than being rolled back
+    assert_eq!(1, akd.retrieve_current_azks().await?.get_latest_epoch());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_publish_ignores_stale_root_hash() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+    let expected_db = AsyncInMemoryDatabase::new();
+    let expected_akd = Directory::<_, _>::new::<Blake3>(&expected_db, &vrf, false).await?;
+
+    // A partial commit of epoch 1 left its root hash behind without the epoch itself
+    db.set(DbRecord::EpochRootHash(EpochRootHash {
+        epoch: 1,
+        root_hash: [7u8; 32],
+    }))
+    .await?;
+
+    let updates = vec![(
+        AkdLabel::from_utf8_str("hello"),
+        AkdValue::from_utf8_str("world"),
+    )];
+    let expected = expected_akd.publish::<Blake3>(updates.clone()).await?;
+    let published = akd.publish::<Blake3>(updates).await?;
+    assert_eq!(expected, published);
+
+    // and the record was replaced with the published root hash
+    assert_eq!(
+        DbRecord::EpochRootHash(EpochRootHash {
+            epoch: 1,
+            root_hash: crate::serialization::from_digest::<Blake3>(published.1),
+        }),
+        db.get::<EpochRootHash>(&1).await?
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stream_root_hashes() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+
+    let mut published = vec![];
+    for i in 0..3 {
+        let epoch_hash = akd
+            .publish::<Blake3>(vec![(
+                AkdLabel::from_utf8_str(&format!("hello{}", i)),
+                AkdValue::from_utf8_str(&format!("world{}", i)),
+            )])
+            .await?;
+        published.push((epoch_hash.0, epoch_hash.1));
+    }
+
+    let azks = akd.retrieve_current_azks().await?;
+    let streamed = azks
+        .stream_root_hashes::<_, Blake3>(&db, 1, 3)
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(published, streamed);
+
+    // The stream ends with an error at the first epoch past the latest one
+    let streamed = azks
+        .stream_root_hashes::<_, Blake3>(&db, 2, 5)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(3, streamed.len());
+    assert_eq!(Ok(published[1]), streamed[0]);
+    assert_eq!(Ok(published[2]), streamed[1]);
+    assert_eq!(
+        Err(AkdError::TreeNode(TreeNodeError::NonexistentAtEpoch(
+            NodeLabel::root(),
+            4
+        ))),
+        streamed[2]
+    );
+
+    // A tree built outside of a directory has no recorded root hashes
+    let tree_db = AsyncInMemoryDatabase::new();
+    let mut tree = Azks::new::<_, Blake3>(&tree_db).await?;
+    tree.batch_insert_leaves::<_, Blake3>(
+        &tree_db,
+        vec![Node::<Blake3> {
+            label: NodeLabel::new([1u8; 32], 256),
+            hash: Blake3::hash(b"leaf"),
+        }],
+    )
+    .await?;
+    let streamed = tree
+        .stream_root_hashes::<_, Blake3>(&tree_db, 1, 1)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(
+        vec![Err(AkdError::AzksErr(AzksError::RootHashNotRecorded(1)))],
+        streamed
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_backfill_root_hashes() -> Result<(), AkdError> {
+    // A new directory records the root hash of its empty tree at epoch 0
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+    let azks = akd.retrieve_current_azks().await?;
+    let streamed = azks
+        .stream_root_hashes::<_, Blake3>(&db, 0, 0)
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(
+        vec![(0, akd.get_root_hash::<Blake3>(&azks).await?)],
+        streamed
+    );
+    assert!(azks
+        .backfill_root_hashes::<_, Blake3>(&db)
+        .await?
+        .is_empty());
+
+    // A tree whose epochs were written without recording their root hashes
+    let db = AsyncInMemoryDatabase::new();
+    let mut azks = Azks::new::<_, Blake3>(&db).await?;
+    let mut root_hashes = vec![];
+    for i in 0..3u8 {
+        azks.batch_insert_leaves::<_, Blake3>(
+            &db,
+            vec![Node::<Blake3> {
+                label: NodeLabel::new([i; 32], 256),
+                hash: Blake3::hash(&[i]),
+            }],
+        )
+        .await?;
+        root_hashes.push((
+            azks.get_latest_epoch(),
+            azks.get_root_hash::<_, Blake3>(&db).await?,
+        ));
+    }
+    db.set(DbRecord::Azks(azks.clone())).await?;
+
+    // Only the epochs still held in the root node's states can be recovered
+    assert_eq!(
+        vec![0, 1],
+        azks.backfill_root_hashes::<_, Blake3>(&db).await?
+    );
+    let streamed = azks
+        .stream_root_hashes::<_, Blake3>(&db, 2, 3)
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(root_hashes[1..].to_vec(), streamed);
+    assert_eq!(
+        vec![0, 1],
+        azks.backfill_root_hashes::<_, Blake3>(&db).await?
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_epoch_commit_markers() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+    assert!(Azks::is_epoch_fully_committed(&db, 0).await?);
+    assert!(!Azks::is_epoch_fully_committed(&db, 1).await?);
+
+    akd.publish::<Blake3>(vec![(
+        AkdLabel::from_utf8_str("hello"),
+        AkdValue::from_utf8_str("world"),
+    )])
+    .await?;
+    assert!(Azks::is_epoch_fully_committed(&db, 1).await?);
+    assert!(!Azks::is_epoch_fully_committed(&db, 2).await?);
+
+    // An epoch whose writes were interrupted before its marker is reported as incomplete
+    db.set(DbRecord::Azks(DbRecord::build_azks(2, 0))).await?;
+    assert!(!Azks::is_epoch_fully_committed(&db, 2).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_value_state_consistency() -> Result<(), AkdError> {
+    let db = AsyncInMemoryDatabase::new();
+    let vrf = HardCodedAkdVRF {};
+    let akd = Directory::<_, _>::new::<Blake3>(&db, &vrf, false).await?;
+    let uname = AkdLabel::from_utf8_str("hello");
+    for value in ["world", "world2"] {
+        akd.publish::<Blake3>(vec![(uname.clone(), AkdValue::from_utf8_str(value))])
+            .await?;
+    }
+    akd.verify_value_state_consistency::<Blake3>(&uname, 1)
+        .await?;
+    akd.verify_value_state_consistency::<Blake3>(&uname, 2)
+        .await?;
+
+    // A value state which was overwritten without updating the tree no longer matches its leaf
+    let mut value_state = db
+        .get_user_state(&uname, ValueStateRetrievalFlag::SpecificEpoch(2))
+        .await?;
+    value_state.plaintext_val = AkdValue::from_utf8_str("tampered");
+    db.set(DbRecord::ValueState(value_state)).await?;
+    assert!(matches!(
+        akd.verify_value_state_consistency::<Blake3>(&uname, 2)
+            .await,
+        Err(AkdError::Directory(DirectoryError::InconsistentValueState(
+            _
+        )))
+    ));
+    akd.verify_value_state_consistency::<Blake3>(&uname, 1)
+        .await?;
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_read_during_publish() -> Result<(), AkdError> {
     let db = AsyncInMemoryDatabase::new();
@@ -998,6 +1894,25 @@ async fn test_simple_lookup_for_small_tree_sha256() -> Result<(), AkdError> {
 =========== Test Helpers ===========
 */
 
+// Publishes one new label in each of the given number of epochs, returning the root hash
+// of each epoch in order
+async fn publish_epochs<T: Storage + Sync + Send, V: VRFKeyStorage>(
+    akd: &Directory<T, V>,
+    num_epochs: u64,
+) -> Result<Vec<<Blake3 as Hasher>::Digest>, AkdError> {
+    let mut root_hashes = vec![];
+    for i in 0..num_epochs {
+        let epoch_hash = akd
+            .publish::<Blake3>(vec![(
+                AkdLabel::from_utf8_str(&format!("hello{}", i)),
+                AkdValue::from_utf8_str(&format!("world{}", i)),
+            )])
+            .await?;
+        root_hashes.push(epoch_hash.1);
+    }
+    Ok(root_hashes)
+}
+
 async fn async_poll_helper_proof<T: Storage + Sync + Send, V: VRFKeyStorage>(
     reader: &Directory<T, V>,
     value: AkdValue,
@@ -6,20 +6,25 @@
 // of this source tree.
 
 //! The implementation of a node for a history patricia tree
+//!
+//! Nodes are read back from storage which may be corrupted, so nothing in here may panic on
+//! malformed data: every failure is returned as an error instead.
+#![deny(clippy::unwrap_used, clippy::expect_used)]
 
 use crate::errors::{AkdError, StorageError, TreeNodeError};
 #[cfg(feature = "serde_serialization")]
 use crate::serialization::{bytes_deserialize_hex, bytes_serialize_hex};
-use crate::serialization::{from_digest, to_digest};
+use crate::serialization::{from_digest, to_digest, HexDigest};
 use crate::storage::types::{DbRecord, StorageType};
 use crate::storage::{Storable, Storage};
-use crate::{node_label::*, Direction, EMPTY_LABEL};
+use crate::{node_label::*, Direction, ARITY, EMPTY_LABEL};
 use async_recursion::async_recursion;
 use log::debug;
 use std::cmp::min;
 use std::convert::TryInto;
 use std::marker::{Send, Sync};
 use winter_crypto::Hasher;
+use winter_utils::Serializable;
 
 /// There are three types of nodes: root, leaf and interior.
 /// This enum is used to mark nodes using the node_type variable
@@ -55,6 +60,88 @@ impl NodeType {
 
 pub(crate) type InsertionNode<'a> = (Direction, &'a mut TreeNode);
 
+// The value of a leaf, the hash of a node and the hash of a label are all plain digests,
+// and passing one where another is expected still yields a plausible looking (but wrong)
+// root hash. These wrappers keep them apart where node hashes are computed.
+macro_rules! digest_newtype {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[repr(transparent)]
+        pub struct $name<H: Hasher>(pub H::Digest);
+
+        impl<H: Hasher> $name<H> {
+            /// Wraps a digest
+            pub fn new(digest: H::Digest) -> Self {
+                Self(digest)
+            }
+
+            /// Unwraps the digest
+            pub fn into_digest(self) -> H::Digest {
+                self.0
+            }
+        }
+
+        // Manual implementations, see: https://github.com/rust-lang/rust/issues/41481
+        impl<H: Hasher> Clone for $name<H> {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl<H: Hasher> Copy for $name<H> {}
+
+        impl<H: Hasher> PartialEq for $name<H> {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0
+            }
+        }
+
+        impl<H: Hasher> Eq for $name<H> {}
+
+        impl<H: Hasher> std::fmt::Debug for $name<H> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}({})", stringify!($name), HexDigest::<H>(self.0))
+            }
+        }
+    };
+}
+
+digest_newtype!(
+    /// The digest of a leaf's value, before the epoch of the leaf is bound into it
+    ValueDigest
+);
+
+digest_newtype!(
+    /// The hash of a node, which for a leaf has its epoch bound in
+    NodeHash
+);
+
+digest_newtype!(
+    /// The hash of a node's label
+    LabelHash
+);
+
+impl<H: Hasher> ValueDigest<H> {
+    /// The hash of a leaf holding this value, last updated in the given epoch
+    pub fn bind_epoch(self, epoch: u64) -> NodeHash<H> {
+        NodeHash(merge_epoch::<H>(self.0, epoch))
+    }
+}
+
+impl<H: Hasher> NodeHash<H> {
+    /// What a node with this hash and the given label contributes to its parent's hash
+    pub fn with_label(self, label: LabelHash<H>) -> H::Digest {
+        H::merge(&[self.0, label.0])
+    }
+}
+
+impl<H: Hasher> LabelHash<H> {
+    /// Hashes the given label
+    pub fn of(label: NodeLabel) -> Self {
+        Self(hash_label::<H>(label))
+    }
+}
+
 /// Represents a `TreeNode` with its current state and potential future state.
 /// Depending on the `epoch` which the Directory believes is the "most current"
 /// version, we may need to load a slightly older version of the tree node. This is because
@@ -110,8 +197,12 @@ impl Storable for TreeNodeWithPreviousValue {
             return Err("Not a tree node key".to_string());
         }
 
-        let len_bytes: [u8; 4] = bin[1..=4].try_into().expect("Slice with incorrect length");
-        let val_bytes: [u8; 32] = bin[5..=36].try_into().expect("Slice with incorrect length");
+        let len_bytes: [u8; 4] = bin[1..=4]
+            .try_into()
+            .map_err(|_| "Slice with incorrect length".to_string())?;
+        let val_bytes: [u8; 32] = bin[5..=36]
+            .try_into()
+            .map_err(|_| "Slice with incorrect length".to_string())?;
         let len = u32::from_le_bytes(len_bytes);
 
         Ok(NodeKey(NodeLabel::new(val_bytes, len)))
@@ -238,6 +329,22 @@ pub struct TreeNode {
     pub hash: [u8; 32],
 }
 
+/// A description of one of the child slots of a [TreeNode] at some epoch,
+/// for tools which inspect the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChildInfo {
+    /// The direction of the child from its parent
+    pub direction: usize,
+    /// Whether the slot is empty, in which case the label and hash are placeholders
+    pub dummy: bool,
+    /// The label of the child, or [EMPTY_LABEL] for a dummy child
+    pub label: NodeLabel,
+    /// The hex encoded hash which the child contributes to its parent
+    pub hash_hex: String,
+    /// The epoch in which the child was last updated, none for a dummy child
+    pub last_epoch: Option<u64>,
+}
+
 impl TreeNode {
     // Storage operations
     pub(crate) async fn write_to_storage<S: Storage + Send + Sync>(
@@ -292,6 +399,29 @@ impl TreeNode {
         .await
     }
 
+    /// Like [TreeNode::batch_get_from_storage], but bypasses any caching in the storage layer
+    pub(crate) async fn batch_get_direct_from_storage<S: Storage + Send + Sync>(
+        storage: &S,
+        keys: &[NodeKey],
+        target_epoch: u64,
+    ) -> Result<Vec<TreeNode>, StorageError> {
+        let mut nodes = Vec::with_capacity(keys.len());
+        for record in storage
+            .batch_get_direct::<TreeNodeWithPreviousValue>(keys)
+            .await?
+        {
+            match record {
+                DbRecord::TreeNode(node) => nodes.push(node.determine_node_to_get(target_epoch)?),
+                _ => {
+                    return Err(StorageError::NotFound(
+                        "Batch retrieve returned types <> TreeNodeWithPreviousValue".to_string(),
+                    ))
+                }
+            }
+        }
+        Ok(nodes)
+    }
+
     pub(crate) async fn batch_get_from_storage<S: Storage + Send + Sync>(
         storage: &S,
         keys: &[NodeKey],
@@ -407,6 +537,14 @@ impl TreeNode {
         hashing: bool,
         exclude_ep: Option<bool>,
     ) -> Result<(), AkdError> {
+        // A leaf can't sit at or above an interior node, since the interior node's label
+        // would then be both the longest common prefix and the label of the leaf
+        if self.node_type != NodeType::Leaf && new_leaf.label.is_prefix_of(&self.label) {
+            return Err(AkdError::TreeNode(
+                TreeNodeError::LabelCollidesWithInterior(new_leaf.label),
+            ));
+        }
+
         let (lcs_label, dir_leaf, dir_self) = self
             .label
             .get_longest_common_prefix_and_dirs(new_leaf.label);
@@ -427,31 +565,24 @@ impl TreeNode {
             }
         }
 
-        // if a node is the longest common prefix of itself and the leaf, dir_self will be None
-        match dir_self {
-            Some(_) => {
-                // This is the case where the calling node and the leaf have a longest common prefix
-                // not equal to the label of the calling node.
-                // This means that the current node needs to be pushed down one level (away from root)
-                // in the tree and replaced with a new node whose label is equal to the longest common prefix.
-                return self
-                    .insert_single_leaf_helper_base_case_handler::<S, H>(
-                        storage, new_leaf, epoch, num_nodes, hashing, exclude_ep, lcs_label,
-                        dir_leaf, dir_self,
-                    )
-                    .await;
-            }
-            // Case where the current node is equal to the lcs
-            // Recurse!
-            None => {
-                // This is the case where the calling node is the longest common prefix of itself
-                // and the inserted leaf, so we just need to modify the tree structure further down the tree.
-                return self
-                    .insert_single_leaf_helper_recursive_case_handler::<S, H>(
-                        storage, new_leaf, epoch, num_nodes, hashing, exclude_ep, dir_leaf,
-                    )
-                    .await;
-            }
+        // if a node is a prefix of the leaf, it is the longest common prefix of itself and the leaf
+        if self.label.is_prefix_of(&new_leaf.label) {
+            // This is the case where the calling node is the longest common prefix of itself
+            // and the inserted leaf, so we just need to modify the tree structure further down the tree.
+            self.insert_single_leaf_helper_recursive_case_handler::<S, H>(
+                storage, new_leaf, epoch, num_nodes, hashing, exclude_ep, dir_leaf,
+            )
+            .await
+        } else {
+            // This is the case where the calling node and the leaf have a longest common prefix
+            // not equal to the label of the calling node.
+            // This means that the current node needs to be pushed down one level (away from root)
+            // in the tree and replaced with a new node whose label is equal to the longest common prefix.
+            self.insert_single_leaf_helper_base_case_handler::<S, H>(
+                storage, new_leaf, epoch, num_nodes, hashing, exclude_ep, lcs_label, dir_leaf,
+                dir_self,
+            )
+            .await
         }
     }
 
@@ -617,10 +748,15 @@ impl TreeNode {
                 debug!("END insert single leaf (dir_self = None)");
                 Ok(())
             }
-            None => Err(AkdError::TreeNode(TreeNodeError::NoChildAtEpoch(
-                epoch,
-                dir_leaf.unwrap(),
-            ))),
+            None => match dir_leaf {
+                Some(dir) => Err(AkdError::TreeNode(TreeNodeError::NoChildAtEpoch(
+                    epoch, dir,
+                ))),
+                None => Err(AkdError::TreeNode(TreeNodeError::NoDirection(
+                    self.label,
+                    Some(new_leaf.label),
+                ))),
+            },
         }
     }
 
@@ -643,15 +779,9 @@ impl TreeNode {
             // For non-leaf nodes, the hash is updated by merging the hashes of the node's children.
             // It is assumed that the children already updated their hashes.
             _ => {
-                // Get children states.
-                let left_child_state = self.get_child_state(storage, Some(0), epoch).await?;
-                let right_child_state = self.get_child_state(storage, Some(1), epoch).await?;
-
-                // Get merged hashes for the children.
-                let child_hashes = H::merge(&[
-                    optional_child_state_label_hash::<H>(&left_child_state, exclude_ep_val)?,
-                    optional_child_state_label_hash::<H>(&right_child_state, exclude_ep_val)?,
-                ]);
+                let child_hashes = self
+                    .compute_children_hash::<_, H>(storage, epoch, exclude_ep_val)
+                    .await?;
                 // Store the hash
                 self.hash = from_digest::<H>(child_hashes);
             }
@@ -663,6 +793,22 @@ impl TreeNode {
         Ok(())
     }
 
+    /// Computes the hash of a non-leaf node from the states of its children at epoch,
+    /// without modifying the node.
+    pub(crate) async fn compute_children_hash<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        epoch: u64,
+        exclude_ep_val: bool,
+    ) -> Result<H::Digest, AkdError> {
+        // Get children states.
+        let left_child_state = self.get_child_state(storage, Some(0), epoch).await?;
+        let right_child_state = self.get_child_state(storage, Some(1), epoch).await?;
+
+        // Get merged hashes for the children.
+        hash_children::<H>(&[left_child_state, right_child_state], exclude_ep_val)
+    }
+
     /// Inserts a child into this node, adding the state to the state at this epoch,
     /// without updating its own hash.
     pub(crate) async fn set_child<S: Storage + Sync + Send>(
@@ -741,6 +887,17 @@ impl TreeNode {
         matches!(self.node_type, NodeType::Leaf)
     }
 
+    /// Returns the label of this node's parent. The root is its own parent, so walking up the
+    /// tree stops at the root; this checks that the node labelled as the root is the root,
+    /// and that no other node is its own parent, so that such a walk can't loop forever.
+    pub(crate) fn get_parent_label(&self) -> Result<NodeLabel, TreeNodeError> {
+        let has_root_label = self.label == NodeLabel::root();
+        if has_root_label != self.is_root() || (!self.is_root() && self.parent == self.label) {
+            return Err(TreeNodeError::SelfParentCycle(self.label));
+        }
+        Ok(self.parent)
+    }
+
     ///// getrs for child nodes ////
 
     /// Loads (from storage) the left or right child of a node using given direction
@@ -761,7 +918,20 @@ impl TreeNode {
                         Self::get_from_storage(storage, &child_key, current_epoch).await;
                     match get_result {
                         Ok(node) => Ok(Some(node)),
-                        Err(StorageError::NotFound(_)) => Ok(None),
+                        Err(StorageError::NotFound(_)) => {
+                            // The child may just not have existed yet at the epoch, but if
+                            // there's no record of it at all, the record this node refers to
+                            // has been lost
+                            match storage.get::<TreeNodeWithPreviousValue>(&child_key).await {
+                                Err(StorageError::NotFound(_)) => {
+                                    Err(AkdError::TreeNode(TreeNodeError::StateRecordMissing {
+                                        label: child_label,
+                                        epoch: current_epoch,
+                                    }))
+                                }
+                                _ => Ok(None),
+                            }
+                        }
                         _ => Err(AkdError::Storage(StorageError::NotFound(format!(
                             "TreeNode {:?}",
                             child_key
@@ -774,6 +944,30 @@ impl TreeNode {
         }
     }
 
+    /// Describes each of the [ARITY] child slots of this node as of epoch,
+    /// see [ChildInfo].
+    pub async fn children_at_epoch<S: Storage + Sync + Send, H: Hasher>(
+        &self,
+        storage: &S,
+        epoch: u64,
+    ) -> Result<Vec<ChildInfo>, AkdError> {
+        let mut children = Vec::with_capacity(ARITY);
+        for direction in 0..ARITY {
+            let child = self
+                .get_child_state(storage, Direction::Some(direction), epoch)
+                .await?;
+            let hash = optional_child_state_hash::<H>(&child)?;
+            children.push(ChildInfo {
+                direction,
+                dummy: child.is_none(),
+                label: optional_child_state_to_label(&child),
+                hash_hex: HexDigest::<H>(hash).to_string(),
+                last_epoch: child.map(|child| child.last_epoch),
+            });
+        }
+        Ok(children)
+    }
+
     pub(crate) fn get_child(&self, direction: Direction) -> Result<Option<NodeLabel>, AkdError> {
         match direction {
             Direction::None => Err(AkdError::TreeNode(TreeNodeError::NoDirection(
@@ -813,6 +1007,46 @@ pub(crate) fn hash_u8_with_label<H: Hasher>(
     Ok(H::merge(&[to_digest::<H>(digest)?, hash_label::<H>(label)]))
 }
 
+/// Computes the hash of an interior node from the states of its children, without a node or
+/// storage, in exactly the way the tree computes it. This is the reference for the hash format:
+///
+/// 1. Each child, in direction order (left, then right), contributes `H(hash || H(label))`,
+///    where `hash` is the child's node hash, which for a leaf is its value digest with the
+///    epoch it was last updated in bound in by [merge_epoch]. A missing child contributes
+///    the empty node hash, merged with the hash of the empty label, in the same way.
+/// 2. The node's hash, as held in [TreeNode::hash], is the merge of the children's
+///    contributions, `H(left || right)`. The node's own label isn't part of it: it's merged
+///    in by its parent, as in step 1.
+/// 3. The root has no parent, so if `is_root` is set, its label is merged in once more to
+///    give the root hash which clients verify proofs against, `H(node_hash || H(label))`.
+///
+/// The label is only used for the root.
+pub fn compute_node_hash<H: Hasher>(
+    children: &[Option<TreeNode>; ARITY],
+    label: NodeLabel,
+    is_root: bool,
+) -> Result<H::Digest, AkdError> {
+    let node_hash = hash_children::<H>(children, false)?;
+    if is_root {
+        Ok(NodeHash::<H>::new(node_hash).with_label(LabelHash::of(label)))
+    } else {
+        Ok(node_hash)
+    }
+}
+
+/// Merges the contributions of the children, in direction order, into the hash of their
+/// parent. Leaves' epochs are not bound into their hashes if `exclude_ep_val` is set.
+fn hash_children<H: Hasher>(
+    children: &[Option<TreeNode>; ARITY],
+    exclude_ep_val: bool,
+) -> Result<H::Digest, AkdError> {
+    let [left, right] = children;
+    Ok(H::merge(&[
+        optional_child_state_label_hash::<H>(left, exclude_ep_val)?,
+        optional_child_state_label_hash::<H>(right, exclude_ep_val)?,
+    ]))
+}
+
 pub(crate) fn optional_child_state_to_label(input: &Option<TreeNode>) -> NodeLabel {
     match input {
         Some(child_state) => child_state.label,
@@ -826,16 +1060,15 @@ pub(crate) fn optional_child_state_label_hash<H: Hasher>(
 ) -> Result<H::Digest, AkdError> {
     match input {
         Some(child_state) => {
-            let mut hash = to_digest::<H>(&child_state.hash)?;
-            if child_state.is_leaf() && !exclude_ep_val {
-                hash = H::merge_with_int(hash, child_state.last_epoch);
-            }
-            Ok(H::merge(&[hash, hash_label::<H>(child_state.label)]))
+            let hash = if child_state.is_leaf() && !exclude_ep_val {
+                leaf_node_hash::<H>(child_state)?
+            } else {
+                NodeHash::new(to_digest::<H>(&child_state.hash)?)
+            };
+            Ok(hash.with_label(LabelHash::of(child_state.label)))
         }
-        None => Ok(H::merge(&[
-            crate::utils::empty_node_hash::<H>(),
-            hash_label::<H>(EMPTY_LABEL),
-        ])),
+        None => Ok(NodeHash::<H>::new(crate::utils::dummy_child_hash::<H>())
+            .with_label(LabelHash::of(EMPTY_LABEL))),
     }
 }
 
@@ -845,22 +1078,55 @@ pub(crate) fn optional_child_state_hash<H: Hasher>(
     match input {
         Some(child_state) => {
             if child_state.is_leaf() {
-                Ok(H::merge_with_int(
-                    to_digest::<H>(&child_state.hash)?,
-                    child_state.last_epoch,
-                ))
+                Ok(leaf_node_hash::<H>(child_state)?.into_digest())
             } else {
                 to_digest::<H>(&child_state.hash)
             }
         }
-        None => Ok(crate::utils::empty_node_hash::<H>()),
+        None => Ok(crate::utils::dummy_child_hash::<H>()),
     }
 }
 
+/// A leaf stores the digest of its value, so its node hash is only formed by binding in the
+/// epoch it was last updated in
+fn leaf_node_hash<H: Hasher>(leaf: &TreeNode) -> Result<NodeHash<H>, AkdError> {
+    let value = ValueDigest::<H>::new(to_digest::<H>(&leaf.hash)?);
+    Ok(value.bind_epoch(leaf.last_epoch))
+}
+
+/// Binds an epoch into a hash, as is done for the value of a leaf with the epoch in which
+/// it was last updated. The epoch is encoded as exactly 8 little-endian bytes, appended to the
+/// serialized bytes of the hash (as many as the hasher's digest has, with no padding) before
+/// hashing them together:
+///
+/// `merge_epoch(hash, epoch) = H(hash || le_bytes_u64(epoch))`
+///
+/// This is byte for byte what [Hasher::merge_with_int] computes for each of the hashers of
+/// `winter_crypto`, so it's fixed by the leaf hashes already committed to in stored trees and
+/// published root hashes. It's spelled out here rather than left to the hasher so that it is
+/// part of the proof format: an implementation verifying proofs produced by this crate, in
+/// any language, must bind epochs in exactly this way.
+pub fn merge_epoch<H: Hasher>(hash: H::Digest, epoch: u64) -> H::Digest {
+    let mut data = hash.to_bytes();
+    data.extend_from_slice(&epoch.to_le_bytes());
+    H::hash(&data)
+}
+
+/// The exact bytes of the storage key under which the node with the given label is kept, for
+/// tools which read or write the storage directly. The key is the [StorageType::TreeNode] byte,
+/// followed by the label's length as 4 little-endian bytes and the 32 bytes of its value.
+///
+/// A node's latest and previous states are kept together in one
+/// [TreeNodeWithPreviousValue] record, so there's a single key per node rather than one per
+/// epoch.
+pub fn node_storage_key(label: NodeLabel) -> Vec<u8> {
+    TreeNodeWithPreviousValue::get_full_binary_key_id(&NodeKey(label))
+}
+
 /// Retrieve an empty root node
 pub fn get_empty_root<H: Hasher>(ep: Option<u64>, least_descendant_ep: Option<u64>) -> TreeNode {
     // Empty root hash is the same as empty node hash
-    let empty_root_hash = from_digest::<H>(crate::utils::empty_node_hash_no_label::<H>());
+    let empty_root_hash = from_digest::<H>(crate::utils::empty_node_hash::<H>());
     let mut node = TreeNode::new(
         NodeLabel::root(),
         NodeLabel::root(),
@@ -902,17 +1168,23 @@ pub fn get_leaf_node<H: Hasher>(
 }
 
 #[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used)]
 mod tests {
     use super::*;
     use crate::{
         node_label::{byte_arr_from_u64, hash_label, NodeLabel},
+        storage::StorageUtil,
         EMPTY_VALUE,
     };
     use std::convert::TryInto;
-    use winter_crypto::{hashers::Blake3_256, Hasher};
+    use winter_crypto::{
+        hashers::{Blake3_192, Blake3_256},
+        Hasher,
+    };
     use winter_math::fields::f128::BaseElement;
 
     type Blake3 = Blake3_256<BaseElement>;
+    type ShortBlake3 = Blake3_192<BaseElement>;
     type InMemoryDb = crate::storage::memory::AsyncInMemoryDatabase;
 
     #[tokio::test]
@@ -1008,7 +1280,219 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_label_collides_with_interior() -> Result<(), AkdError> {
+        let db = InMemoryDb::new();
+        let mut root = get_empty_root::<Blake3>(Option::Some(0u64), Option::Some(0u64));
+        root.write_to_storage(&db).await?;
+        let mut num_nodes = 1;
+
+        // Leaves 0b00 and 0b01 share the interior node 0b0
+        for (i, bits) in [0b00u64, 0b01u64].iter().enumerate() {
+            let leaf = get_leaf_node::<Blake3>(
+                NodeLabel::new(byte_arr_from_u64(bits << 62), 2u32),
+                &Blake3::hash(&[i as u8]),
+                NodeLabel::root(),
+                1,
+            );
+            root.insert_single_leaf_and_hash::<_, Blake3>(&db, leaf, 1, &mut num_nodes, None)
+                .await?;
+        }
+        let interior_label = NodeLabel::new(byte_arr_from_u64(0u64), 1u32);
+        let interior = TreeNode::get_from_storage(&db, &NodeKey(interior_label), 1).await?;
+        assert_eq!(NodeType::Interior, interior.node_type);
+
+        // A leaf labelled exactly like the interior node can't be placed
+        let colliding =
+            get_leaf_node::<Blake3>(interior_label, &Blake3::hash(&[2u8]), NodeLabel::root(), 2);
+        let result = root
+            .insert_single_leaf_and_hash::<_, Blake3>(&db, colliding, 2, &mut num_nodes, None)
+            .await;
+        assert_eq!(
+            Err(AkdError::TreeNode(
+                TreeNodeError::LabelCollidesWithInterior(interior_label)
+            )),
+            result
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_parent_label() {
+        let root = get_empty_root::<Blake3>(Some(0), Some(0));
+        assert_eq!(Ok(NodeLabel::root()), root.get_parent_label());
+
+        let label = NodeLabel::new(byte_arr_from_u64(0b1u64 << 63), 1u32);
+        let value = Blake3::hash(&EMPTY_VALUE);
+        let leaf = get_leaf_node::<Blake3>(label, &value, NodeLabel::root(), 1);
+        assert_eq!(Ok(NodeLabel::root()), leaf.get_parent_label());
+
+        // Only the root may be its own parent
+        let self_parent = get_leaf_node::<Blake3>(label, &value, label, 1);
+        assert_eq!(
+            Err(TreeNodeError::SelfParentCycle(label)),
+            self_parent.get_parent_label()
+        );
+
+        // and the node labelled as the root must be the root
+        let root_leaf = get_leaf_node::<Blake3>(NodeLabel::root(), &value, NodeLabel::root(), 1);
+        assert_eq!(
+            Err(TreeNodeError::SelfParentCycle(NodeLabel::root())),
+            root_leaf.get_parent_label()
+        );
+    }
+
+    #[test]
+    fn test_typed_digests() -> Result<(), AkdError> {
+        let label = NodeLabel::new(byte_arr_from_u64(0b1u64 << 63), 1u32);
+        let value = Blake3::hash(&EMPTY_VALUE);
+        let leaf = get_leaf_node::<Blake3>(label, &value, NodeLabel::root(), 3);
+
+        // The contribution of a leaf to its parent binds its epoch into its value, then its label
+        let expected =
+            Blake3::merge(&[merge_epoch::<Blake3>(value, 3), hash_label::<Blake3>(label)]);
+        let contribution = ValueDigest::<Blake3>::new(value)
+            .bind_epoch(3)
+            .with_label(LabelHash::of(label));
+        assert_eq!(expected, contribution);
+        assert_eq!(
+            expected,
+            optional_child_state_label_hash::<Blake3>(&Some(leaf), false)?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_children_at_epoch() -> Result<(), AkdError> {
+        let db = InMemoryDb::new();
+        let mut root = get_empty_root::<Blake3>(Option::Some(0u64), Option::Some(0u64));
+        root.write_to_storage(&db).await?;
+        let mut num_nodes = 1;
+
+        // Only the right child of the root exists
+        let leaf = get_leaf_node::<Blake3>(
+            NodeLabel::new(byte_arr_from_u64(0b1u64 << 63), 1u32),
+            &Blake3::hash(&[1u8]),
+            NodeLabel::root(),
+            1,
+        );
+        root.insert_single_leaf_and_hash::<_, Blake3>(&db, leaf.clone(), 1, &mut num_nodes, None)
+            .await?;
+
+        let empty_hash = crate::utils::dummy_child_hash::<Blake3>();
+        let leaf_hash = merge_epoch::<Blake3>(Blake3::hash(&[1u8]), 1);
+
+        let children = root.children_at_epoch::<_, Blake3>(&db, 1).await?;
+        assert_eq!(ARITY, children.len());
+        assert_eq!(
+            ChildInfo {
+                direction: 0,
+                dummy: true,
+                label: EMPTY_LABEL,
+                hash_hex: hex::encode(from_digest::<Blake3>(empty_hash)),
+                last_epoch: None,
+            },
+            children[0]
+        );
+        assert_eq!(
+            ChildInfo {
+                direction: 1,
+                dummy: false,
+                label: leaf.label,
+                hash_hex: hex::encode(from_digest::<Blake3>(leaf_hash)),
+                last_epoch: Some(1),
+            },
+            children[1]
+        );
+
+        Ok(())
+    }
+
     // insert_single_leaf tests
+    #[test]
+    fn test_node_storage_key() {
+        let label = NodeLabel::new(byte_arr_from_u64(0b101u64 << 61), 3u32);
+        let key = node_storage_key(label);
+
+        assert_eq!(37, key.len());
+        assert_eq!(StorageType::TreeNode as u8, key[0]);
+        assert_eq!(&3u32.to_le_bytes(), &key[1..5]);
+        assert_eq!(&label.label_val, &key[5..]);
+        assert_eq!(
+            NodeKey(label),
+            TreeNodeWithPreviousValue::key_from_full_binary(&key).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_empty_hashes() {
+        let empty_node_hash = Blake3::hash(&EMPTY_VALUE);
+        assert_eq!(empty_node_hash, crate::empty_node_hash::<Blake3>());
+        assert_eq!(
+            Blake3::merge(&[empty_node_hash, hash_label::<Blake3>(EMPTY_LABEL)]),
+            crate::dummy_child_hash::<Blake3>()
+        );
+
+        // The empty root's hash is the empty node hash, and a missing child hashes as a dummy
+        let root = get_empty_root::<Blake3>(Some(0), Some(0));
+        assert_eq!(empty_node_hash, to_digest::<Blake3>(&root.hash).unwrap());
+        assert_eq!(
+            crate::dummy_child_hash::<Blake3>(),
+            optional_child_state_hash::<Blake3>(&None).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_child_state_record_missing() -> Result<(), AkdError> {
+        let db = InMemoryDb::new();
+        let mut root = get_empty_root::<Blake3>(Option::Some(0u64), Option::Some(0u64));
+        root.write_to_storage(&db).await?;
+        let mut num_nodes = 1;
+
+        let leaf_0 = get_leaf_node::<Blake3>(
+            NodeLabel::new(byte_arr_from_u64(0b0u64), 1u32),
+            &Blake3::hash(&EMPTY_VALUE),
+            NodeLabel::root(),
+            1,
+        );
+        root.insert_single_leaf_and_hash::<_, Blake3>(&db, leaf_0.clone(), 1, &mut num_nodes, None)
+            .await?;
+        let leaf_1 = get_leaf_node::<Blake3>(
+            NodeLabel::new(byte_arr_from_u64(0b1u64 << 63), 1u32),
+            &Blake3::hash(&[1u8]),
+            NodeLabel::root(),
+            2,
+        );
+        root.insert_single_leaf_and_hash::<_, Blake3>(&db, leaf_1, 2, &mut num_nodes, None)
+            .await?;
+        let root = TreeNode::get_from_storage(&db, &NodeKey(NodeLabel::root()), 2).await?;
+
+        // A child which was only inserted after the epoch just isn't there yet
+        assert_eq!(None, root.get_child_state(&db, Some(1), 1).await?);
+        assert!(root.get_child_state(&db, Some(1), 2).await?.is_some());
+
+        // But a child whose record has been lost is an error
+        let damaged_db = InMemoryDb::new();
+        let records = db
+            .batch_get_all_direct()
+            .await?
+            .into_iter()
+            .filter(
+                |record| !matches!(record, DbRecord::TreeNode(node) if node.label == leaf_0.label),
+            )
+            .collect();
+        damaged_db.batch_set(records).await?;
+        assert_eq!(
+            Err(AkdError::TreeNode(TreeNodeError::StateRecordMissing {
+                label: leaf_0.label,
+                epoch: 2,
+            })),
+            root.get_child_state(&damaged_db, Some(0), 2).await
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_insert_single_leaf_root() -> Result<(), AkdError> {
         let db = InMemoryDb::new();
@@ -1045,12 +1529,12 @@ mod tests {
 
         // Calculate expected root hash.
         let leaf_0_hash = Blake3::merge(&[
-            Blake3::merge_with_int(Blake3::hash(&EMPTY_VALUE), 0),
+            merge_epoch::<Blake3>(Blake3::hash(&EMPTY_VALUE), 0),
             hash_label::<Blake3>(leaf_0.label),
         ]);
 
         let leaf_1_hash = Blake3::merge(&[
-            Blake3::merge_with_int(Blake3::hash(&[1u8]), 0),
+            merge_epoch::<Blake3>(Blake3::hash(&[1u8]), 0),
             hash_label::<Blake3>(leaf_1.label),
         ]);
 
@@ -1071,6 +1555,17 @@ mod tests {
 
         assert_eq!(root_digest, expected, "Root hash not equal to expected");
 
+        // The pure hash of the root from its children agrees with the stored tree
+        let children = [Some(leaf_0.clone()), Some(leaf_1.clone())];
+        assert_eq!(
+            expected,
+            compute_node_hash::<Blake3>(&children, root.label, true)?
+        );
+        assert_eq!(
+            leaves_hash,
+            compute_node_hash::<Blake3>(&children, root.label, false)?
+        );
+
         Ok(())
     }
 
@@ -1100,17 +1595,17 @@ mod tests {
         );
 
         let leaf_0_hash = Blake3::merge(&[
-            Blake3::merge_with_int(Blake3::hash(&EMPTY_VALUE), 1),
+            merge_epoch::<Blake3>(Blake3::hash(&EMPTY_VALUE), 1),
             hash_label::<Blake3>(leaf_0.label),
         ]);
 
         let leaf_1_hash = Blake3::merge(&[
-            Blake3::merge_with_int(Blake3::hash(&[0b1u8]), 2),
+            merge_epoch::<Blake3>(Blake3::hash(&[0b1u8]), 2),
             hash_label::<Blake3>(leaf_1.label),
         ]);
 
         let leaf_2_hash = Blake3::merge(&[
-            Blake3::merge_with_int(Blake3::hash(&[1u8, 1u8]), 3),
+            merge_epoch::<Blake3>(Blake3::hash(&[1u8, 1u8]), 3),
             hash_label::<Blake3>(leaf_2.label),
         ]);
 
@@ -1183,21 +1678,21 @@ mod tests {
         );
 
         let leaf_0_hash = Blake3::merge(&[
-            Blake3::merge_with_int(Blake3::hash(&EMPTY_VALUE), 1),
+            merge_epoch::<Blake3>(Blake3::hash(&EMPTY_VALUE), 1),
             hash_label::<Blake3>(leaf_0.label),
         ]);
 
         let leaf_1_hash = Blake3::merge(&[
-            Blake3::merge_with_int(Blake3::hash(&[1u8]), 2),
+            merge_epoch::<Blake3>(Blake3::hash(&[1u8]), 2),
             hash_label::<Blake3>(leaf_1.label),
         ]);
         let leaf_2_hash = Blake3::merge(&[
-            Blake3::merge_with_int(Blake3::hash(&[1u8, 1u8]), 3),
+            merge_epoch::<Blake3>(Blake3::hash(&[1u8, 1u8]), 3),
             hash_label::<Blake3>(leaf_2.label),
         ]);
 
         let leaf_3_hash = Blake3::merge(&[
-            Blake3::merge_with_int(Blake3::hash(&[0u8, 1u8]), 4),
+            merge_epoch::<Blake3>(Blake3::hash(&[0u8, 1u8]), 4),
             hash_label::<Blake3>(leaf_3.label),
         ]);
 
@@ -1262,7 +1757,7 @@ mod tests {
                 7 - i,
             );
             leaf_hashes.push(Blake3::merge(&[
-                Blake3::merge_with_int(Blake3::hash(&leaf_u64.to_be_bytes()), 8 - i),
+                merge_epoch::<Blake3>(Blake3::hash(&leaf_u64.to_be_bytes()), 8 - i),
                 hash_label::<Blake3>(new_leaf.label),
             ]));
             leaves.push(new_leaf);
@@ -1322,4 +1817,27 @@ mod tests {
         assert!(root_digest == expected, "Root hash not equal to expected");
         Ok(())
     }
+
+    #[test]
+    fn test_merge_epoch_encoding() {
+        // The epoch is appended as 8 little-endian bytes, as leaves have always been hashed
+        let value = Blake3::hash(&EMPTY_VALUE);
+        let epoch = 0x0102_0304_0506_0708u64;
+        let mut data = from_digest::<Blake3>(value).to_vec();
+        data.extend_from_slice(&[8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(Blake3::hash(&data), merge_epoch::<Blake3>(value, epoch));
+
+        // A fixed vector, so any change to the encoding is caught
+        assert_eq!(
+            "2e1103d39f9ce30d677c0c5b96a16560db379dbb81963619ad7213aa7c69e95f",
+            hex::encode(from_digest::<Blake3>(merge_epoch::<Blake3>(value, epoch)))
+        );
+
+        // Only the digest's own bytes are hashed, so a shorter digest isn't padded
+        let value = ShortBlake3::hash(&EMPTY_VALUE);
+        assert_eq!(
+            ShortBlake3::merge_with_int(value, epoch),
+            merge_epoch::<ShortBlake3>(value, epoch)
+        );
+    }
 }
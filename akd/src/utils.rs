@@ -10,7 +10,7 @@
 // 3. If so, add child label to batch set
 
 use crate::{
-    node_label::{hash_label, NodeLabel},
+    node_label::{ancestor_prefixes, hash_label, NodeLabel},
     storage::types::AkdValue,
     EMPTY_LABEL, EMPTY_VALUE,
 };
@@ -21,9 +21,7 @@ use winter_crypto::{Digest, Hasher};
 pub(crate) fn build_prefixes_set(labels: &[NodeLabel]) -> HashSet<NodeLabel> {
     let mut prefixes_set = HashSet::new();
     for label in labels {
-        for len in 0..(label.get_len() + 1) {
-            prefixes_set.insert(label.get_prefix(len));
-        }
+        prefixes_set.extend(ancestor_prefixes(*label));
     }
     prefixes_set
 }
@@ -46,11 +44,18 @@ pub(crate) fn build_lookup_prefixes_set(labels: &[NodeLabel]) -> HashSet<NodeLab
     lookup_prefixes_set
 }
 
-pub(crate) fn empty_node_hash<H: Hasher>() -> H::Digest {
-    H::merge(&[H::hash(&EMPTY_VALUE), hash_label::<H>(EMPTY_LABEL)])
+/// The hash value of a missing child, `H::merge(&[empty_node_hash(), hash_label(EMPTY_LABEL)])`.
+/// Wherever a node has no child in a direction, this stands in for the child's hash value,
+/// e.g. when hashing the node's children or as the sibling of a layer of a proof, and it's
+/// merged with the hash of [EMPTY_LABEL] like any other child's value is with its label's.
+pub fn dummy_child_hash<H: Hasher>() -> H::Digest {
+    H::merge(&[empty_node_hash::<H>(), hash_label::<H>(EMPTY_LABEL)])
 }
 
-pub(crate) fn empty_node_hash_no_label<H: Hasher>() -> H::Digest {
+/// The hash value of an empty node, `H::hash(&EMPTY_VALUE)`. This is the hash value of the
+/// root of an empty tree, which has no children to hash, so the root hash of an empty tree
+/// is this merged with the hash of the root's label.
+pub fn empty_node_hash<H: Hasher>() -> H::Digest {
     H::hash(&EMPTY_VALUE)
 }
 
@@ -80,7 +80,6 @@ pub(crate) fn merge(items: &[[u8; DIGEST_BYTES]]) -> PublicDigest {
 }
 
 /// Take a hash and merge it with an integer and hash the resulting bytes
-#[cfg(feature = "vrf")]
 pub(crate) fn merge_with_int(digest: PublicDigest, value: u64) -> PublicDigest {
     let mut data = [0; DIGEST_BYTES + 8];
     data[..DIGEST_BYTES].copy_from_slice(&digest);
@@ -90,6 +89,12 @@ pub(crate) fn merge_with_int(digest: PublicDigest, value: u64) -> PublicDigest {
     hash(&data)
 }
 
+/// Binds an epoch into a hash, encoding the epoch as exactly 8 little-endian bytes.
+/// This must match `akd::tree_node::merge_epoch`
+pub(crate) fn merge_epoch(digest: PublicDigest, epoch: u64) -> PublicDigest {
+    merge_with_int(digest, epoch)
+}
+
 /// Hashes all the children of a node, as well as their labels
 pub(crate) fn build_and_hash_layer(
     hashes: Vec<PublicDigest>,
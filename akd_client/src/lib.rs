@@ -105,6 +105,10 @@ pub(crate) mod utils;
 /// The arity of the tree. Should EXACTLY match the ARITY within
 /// the AKD crate (i.e. akd::ARITY)
 pub(crate) const ARITY: usize = 2;
+
+/// The length of a full label in bits, and so the deepest a leaf of the tree can be. Should
+/// EXACTLY match the LEAF_LEN within the AKD crate (i.e. akd::LEAF_LEN)
+pub(crate) const LEAF_LEN: u32 = 256;
 #[cfg(test)]
 mod tests;
 
@@ -189,6 +189,24 @@ where
 // Test cases
 // ===================================
 
+#[test]
+fn test_merge_epoch_matches_akd() {
+    let value = crate::hash::hash(&crate::types::EMPTY_VALUE);
+    let epoch = 0x0102_0304_0506_0708u64;
+    let expected = akd::tree_node::merge_epoch::<Hash>(Hash::hash(&akd::EMPTY_VALUE), epoch);
+    assert_eq!(
+        to_digest::<Hash>(expected),
+        crate::hash::merge_epoch(value, epoch)
+    );
+
+    // The same fixed vector as akd's, so any change to the encoding is caught on both sides
+    #[cfg(feature = "blake3")]
+    assert_eq!(
+        "2e1103d39f9ce30d677c0c5b96a16560db379dbb81963619ad7213aa7c69e95f",
+        hex::encode(crate::hash::merge_epoch(value, epoch))
+    );
+}
+
 #[tokio::test]
 async fn test_simple_lookup() -> Result<(), AkdError> {
     let db = InMemoryDb::new();
@@ -16,10 +16,22 @@ use core::convert::TryFrom;
 
 use crate::hash::*;
 use crate::types::*;
-use crate::{verify_error, VerificationError, VerificationErrorType, ARITY};
+use crate::{verify_error, VerificationError, VerificationErrorType, ARITY, LEAF_LEN};
 
-/// Verify the membership proof
+/// Verify the membership proof. A proof with more layers than a label has bits can't come from
+/// the tree, so it's rejected before any of it is hashed.
 fn verify_membership(root_hash: Digest, proof: &MembershipProof) -> Result<(), VerificationError> {
+    if proof.layer_proofs.len() > LEAF_LEN as usize {
+        return Err(verify_error!(
+            MembershipProof,
+            (),
+            format!(
+                "Membership proof has {} layers, more than the maximum of {}",
+                proof.layer_proofs.len(),
+                LEAF_LEN
+            )
+        ));
+    }
     if proof.label.label_len == 0 {
         let final_hash = merge(&[proof.hash_val, proof.label.hash()]);
         if final_hash == root_hash {
@@ -116,7 +128,7 @@ fn verify_nonmembership(
 
 fn hash_leaf_with_value(value: &crate::AkdValue, epoch: u64, proof: &[u8]) -> Digest {
     let single_hash = crate::utils::generate_commitment_from_proof_client(value, proof);
-    merge_with_int(single_hash, epoch)
+    merge_epoch(single_hash, epoch)
 }
 
 /// This function is called to verify that a given NodeLabel is indeed
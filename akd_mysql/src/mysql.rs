@@ -10,7 +10,8 @@
 use crate::mysql_storables::MySqlStorable;
 use akd::errors::StorageError;
 use akd::storage::types::{
-    AkdLabel, AkdValue, DbRecord, KeyData, StorageType, ValueState, ValueStateRetrievalFlag,
+    AkdLabel, AkdValue, DbRecord, EpochCommitMarker, KeyData, StorageType, ValueState,
+    ValueStateRetrievalFlag,
 };
 use akd::storage::{Storable, Storage};
 use akd::tree_node::TreeNodeWithPreviousValue;
@@ -35,6 +36,10 @@ use akd::storage::timed_cache::*;
 const TABLE_AZKS: &str = crate::mysql_storables::TABLE_AZKS;
 const TABLE_HISTORY_TREE_NODES: &str = crate::mysql_storables::TABLE_HISTORY_TREE_NODES;
 const TABLE_USER: &str = crate::mysql_storables::TABLE_USER;
+const TABLE_EPOCH_TIMESTAMPS: &str = crate::mysql_storables::TABLE_EPOCH_TIMESTAMPS;
+const TABLE_EPOCH_COMMIT_MARKERS: &str = crate::mysql_storables::TABLE_EPOCH_COMMIT_MARKERS;
+const TABLE_EPOCH_ROOT_HASHES: &str = crate::mysql_storables::TABLE_EPOCH_ROOT_HASHES;
+const TABLE_LEAF_METADATA: &str = crate::mysql_storables::TABLE_LEAF_METADATA;
 const TEMP_IDS_TABLE: &str = crate::mysql_storables::TEMP_IDS_TABLE;
 
 const MAXIMUM_SQL_TIER_CONNECTION_TIMEOUT_SECS: u64 = 300;
@@ -366,6 +371,33 @@ impl<'a> AsyncMySqlDatabase {
             + " PRIMARY KEY(`username`, `epoch`))";
         tx.query_drop(command).await?;
 
+        // Epoch timestamps table
+        let command = "CREATE TABLE IF NOT EXISTS `".to_owned()
+            + TABLE_EPOCH_TIMESTAMPS
+            + "` (`epoch` BIGINT UNSIGNED NOT NULL, `timestamp_millis` BIGINT UNSIGNED NOT NULL,"
+            + " PRIMARY KEY(`epoch`))";
+        tx.query_drop(command).await?;
+
+        // Epoch commit markers table
+        let command = "CREATE TABLE IF NOT EXISTS `".to_owned()
+            + TABLE_EPOCH_COMMIT_MARKERS
+            + "` (`epoch` BIGINT UNSIGNED NOT NULL, PRIMARY KEY(`epoch`))";
+        tx.query_drop(command).await?;
+
+        // Epoch root hashes table
+        let command = "CREATE TABLE IF NOT EXISTS `".to_owned()
+            + TABLE_EPOCH_ROOT_HASHES
+            + "` (`epoch` BIGINT UNSIGNED NOT NULL, `root_hash` VARBINARY(32) NOT NULL,"
+            + " PRIMARY KEY(`epoch`))";
+        tx.query_drop(command).await?;
+
+        // Leaf metadata table
+        let command = "CREATE TABLE IF NOT EXISTS `".to_owned()
+            + TABLE_LEAF_METADATA
+            + "` (`label_len` INT UNSIGNED NOT NULL, `label_val` VARBINARY(32) NOT NULL,"
+            + " `data` VARBINARY(2000), PRIMARY KEY(`label_len`, `label_val`))";
+        tx.query_drop(command).await?;
+
         // if we got here, we're good to commit. Transaction's will auto-rollback when memory freed if commit wasn't done.
         tx.commit().await?;
         Ok(())
@@ -385,6 +417,18 @@ impl<'a> AsyncMySqlDatabase {
         let command = "DELETE FROM `".to_owned() + TABLE_HISTORY_TREE_NODES + "`";
         tx.query_drop(command).await?;
 
+        let command = "DELETE FROM `".to_owned() + TABLE_EPOCH_TIMESTAMPS + "`";
+        tx.query_drop(command).await?;
+
+        let command = "DELETE FROM `".to_owned() + TABLE_EPOCH_COMMIT_MARKERS + "`";
+        tx.query_drop(command).await?;
+
+        let command = "DELETE FROM `".to_owned() + TABLE_EPOCH_ROOT_HASHES + "`";
+        tx.query_drop(command).await?;
+
+        let command = "DELETE FROM `".to_owned() + TABLE_LEAF_METADATA + "`";
+        tx.query_drop(command).await?;
+
         tx.commit().await?;
 
         Ok(())
@@ -404,6 +448,18 @@ impl<'a> AsyncMySqlDatabase {
         let command = "DROP TABLE IF EXISTS `".to_owned() + TABLE_HISTORY_TREE_NODES + "`";
         tx.query_drop(command).await?;
 
+        let command = "DROP TABLE IF EXISTS `".to_owned() + TABLE_EPOCH_TIMESTAMPS + "`";
+        tx.query_drop(command).await?;
+
+        let command = "DROP TABLE IF EXISTS `".to_owned() + TABLE_EPOCH_COMMIT_MARKERS + "`";
+        tx.query_drop(command).await?;
+
+        let command = "DROP TABLE IF EXISTS `".to_owned() + TABLE_EPOCH_ROOT_HASHES + "`";
+        tx.query_drop(command).await?;
+
+        let command = "DROP TABLE IF EXISTS `".to_owned() + TABLE_LEAF_METADATA + "`";
+        tx.query_drop(command).await?;
+
         tx.commit().await?;
 
         Ok(())
@@ -491,6 +547,18 @@ impl<'a> AsyncMySqlDatabase {
                 DbRecord::ValueState(_) => {
                     DbRecord::set_batch_statement::<akd::storage::types::ValueState>(i)
                 }
+                DbRecord::EpochTimestamp(_) => {
+                    DbRecord::set_batch_statement::<akd::storage::types::EpochTimestamp>(i)
+                }
+                DbRecord::EpochCommitMarker(_) => {
+                    DbRecord::set_batch_statement::<akd::storage::types::EpochCommitMarker>(i)
+                }
+                DbRecord::EpochRootHash(_) => {
+                    DbRecord::set_batch_statement::<akd::storage::types::EpochRootHash>(i)
+                }
+                DbRecord::LeafMetadata(_) => {
+                    DbRecord::set_batch_statement::<akd::storage::types::LeafMetadata>(i)
+                }
             }
         };
 
@@ -528,6 +596,111 @@ impl<'a> AsyncMySqlDatabase {
         Ok(trans)
     }
 
+    /// Writes the records in a single MySQL transaction, grouped into batches by type. With
+    /// an `expected_epoch`, the azks row is first read and locked within the same transaction,
+    /// and nothing is written unless the stored epoch is the expected one: the epoch found is
+    /// returned instead.
+    async fn write_records(
+        &self,
+        records: Vec<DbRecord>,
+        expected_epoch: Option<u64>,
+    ) -> core::result::Result<Option<u64>, MySqlError> {
+        // generate batches by type
+        let mut groups = std::collections::HashMap::new();
+        for record in records {
+            match &record {
+                DbRecord::Azks(_) => groups
+                    .entry(StorageType::Azks)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+                DbRecord::TreeNode(_) => groups
+                    .entry(StorageType::TreeNode)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+                DbRecord::ValueState(_) => groups
+                    .entry(StorageType::ValueState)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+                DbRecord::EpochTimestamp(_) => groups
+                    .entry(StorageType::EpochTimestamp)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+                DbRecord::EpochCommitMarker(_) => groups
+                    .entry(StorageType::EpochCommitMarker)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+                DbRecord::EpochRootHash(_) => groups
+                    .entry(StorageType::EpochRootHash)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+                DbRecord::LeafMetadata(_) => groups
+                    .entry(StorageType::LeafMetadata)
+                    .or_insert_with(Vec::new)
+                    .push(record),
+            }
+        }
+        // now execute each type'd batch in batch operations
+        let mut conn = self.get_connection().await?;
+        let mut tx = conn.start_transaction(TxOpts::default()).await?;
+        if let Some(expected_epoch) = expected_epoch {
+            // read the epoch with the azks row locked until the transaction ends, so no
+            // other writer can advance it between the check and the writes
+            let statement = format!(
+                "SELECT `epoch` FROM `{}` WHERE `key` = :key FOR UPDATE",
+                TABLE_AZKS
+            );
+            let out = tx
+                .exec_first::<u64, _, _>(statement, params! { "key" => 1u8 })
+                .await;
+            // no azks has been committed yet, so nothing could have moved it on
+            let actual = self.check_for_infra_error(out)?.unwrap_or(expected_epoch);
+            if actual != expected_epoch {
+                // dropping the transaction without committing it rolls it back
+                return Ok(Some(actual));
+            }
+        }
+        // go through each group which is narrowed to a single type
+        // applying the changes on the transaction
+        tx.query_drop("SET autocommit=0").await?;
+        tx.query_drop("SET unique_checks=0").await?;
+        tx.query_drop("SET foreign_key_checks=0").await?;
+
+        for (_key, mut value) in groups.into_iter() {
+            if !value.is_empty() {
+                // Sort the records to match db-layer sorting which will help with insert performance
+                value.sort_by(|a, b| match &a {
+                    DbRecord::TreeNode(node) => {
+                        if let DbRecord::TreeNode(node2) = &b {
+                            node.label.cmp(&node2.label)
+                        } else {
+                            Ordering::Equal
+                        }
+                    }
+                    DbRecord::ValueState(state) => {
+                        if let DbRecord::ValueState(state2) = &b {
+                            match state.username.0.cmp(&state2.username.0) {
+                                Ordering::Equal => state.epoch.cmp(&state2.epoch),
+                                other => other,
+                            }
+                        } else {
+                            Ordering::Equal
+                        }
+                    }
+                    _ => Ordering::Equal,
+                });
+                // execute the multi-batch insert statement(s)
+                tx = self.internal_batch_set(value, tx).await?;
+            }
+        }
+
+        tx.query_drop("SET autocommit=1").await?;
+        tx.query_drop("SET unique_checks=1").await?;
+        tx.query_drop("SET foreign_key_checks=1").await?;
+
+        tx.commit().await?;
+        Ok(None)
+    }
+
     /// Create the test database
     #[allow(dead_code)]
     pub async fn create_test_db<T: Into<String>>(
@@ -550,6 +723,107 @@ impl<'a> AsyncMySqlDatabase {
         Ok(())
     }
 
+    /// Retrieves the records with the given ids from the database with a single batched
+    /// query, bypassing the transaction log and the cache
+    async fn batch_get_from_db<St: Storable>(
+        &self,
+        key_set_vec: Vec<St::StorageKey>,
+    ) -> core::result::Result<Vec<DbRecord>, MySqlError> {
+        let tic = Instant::now();
+
+        debug!("BEGIN MySQL get batch");
+        let mut conn = self.get_connection().await?;
+
+        let results = if let Some(create_table_cmd) = DbRecord::get_batch_create_temp_table::<St>()
+        {
+            // Create the temp table of ids
+            let out = conn.query_drop(create_table_cmd).await;
+            self.check_for_infra_error(out)?;
+
+            // Fill temp table with the requested ids
+            let mut tx = conn.start_transaction(TxOpts::default()).await?;
+            tx.query_drop("SET autocommit=0").await?;
+            tx.query_drop("SET unique_checks=0").await?;
+            tx.query_drop("SET foreign_key_checks=0").await?;
+
+            let mut fallout: Option<Vec<_>> = None;
+            let mut params = vec![];
+            for batch in key_set_vec.chunks(self.tunable_insert_depth) {
+                if batch.len() < self.tunable_insert_depth {
+                    fallout = Some(batch.to_vec());
+                } else if let Some(p) = DbRecord::get_multi_row_specific_params::<St>(batch) {
+                    params.push(p);
+                } else {
+                    return Err(MySqlError::Other(
+                        "Unable to generate type-specific MySQL parameters".into(),
+                    ));
+                }
+            }
+
+            // insert the batches of size = MYSQL_EXTENDED_INSERT_DEPTH
+            if !params.is_empty() {
+                let fill_statement =
+                    DbRecord::get_batch_fill_temp_table::<St>(Some(self.tunable_insert_depth));
+                let out = tx.exec_batch(fill_statement, params).await;
+                self.check_for_infra_error(out)?;
+                // We would need the statement for it. (Possibly) No need for close here.
+                // See https://docs.rs/mysql_async/0.28.1/mysql_async/struct.Opts.html#caveats.
+                // tx.close().await?;
+            }
+
+            // insert the remainder as a final statement
+            if let Some(remainder) = fallout {
+                let remainder_stmt =
+                    DbRecord::get_batch_fill_temp_table::<St>(Some(remainder.len()));
+                let params_batch = DbRecord::get_multi_row_specific_params::<St>(&remainder);
+                if let Some(pb) = params_batch {
+                    let out = tx.exec_drop(remainder_stmt, pb).await;
+                    self.check_for_infra_error(out)?;
+                } else {
+                    return Err(MySqlError::Other(
+                        "Unable to generate type-specific MySQL parameters".into(),
+                    ));
+                }
+            }
+
+            tx.query_drop("SET autocommit=1").await?;
+            tx.query_drop("SET unique_checks=1").await?;
+            tx.query_drop("SET foreign_key_checks=1").await?;
+            tx.commit().await?;
+
+            // Query the records which intersect (INNER JOIN) with the temp table of ids
+            let query = DbRecord::get_batch_statement::<St>();
+            let out = conn.query_iter(query).await;
+            let result = self.check_for_infra_error(out)?;
+
+            let out = result
+                .reduce_and_drop(vec![], |mut acc, mut row| {
+                    if let Ok(result) = DbRecord::from_row::<St>(&mut row) {
+                        acc.push(result);
+                    }
+                    acc
+                })
+                .await?;
+
+            // drop the temp table of ids
+            let t_out = conn
+                .query_drop(format!("DROP TEMPORARY TABLE `{}`", TEMP_IDS_TABLE))
+                .await;
+            self.check_for_infra_error(t_out)?;
+
+            out
+        } else {
+            // no results (i.e. AZKS table doesn't support "get by batch ids")
+            vec![]
+        };
+
+        debug!("END MySQL get batch");
+        let toc = Instant::now() - tic;
+        *(self.time_read.write().await) += toc;
+
+        Ok(results)
+    }
+
     async fn record_call_stats(&self, call_type: char, caller_name: String, data_type: String) {
         let mut stats;
         if call_type == 'r' {
@@ -743,6 +1017,48 @@ impl Storage for AsyncMySqlDatabase {
         self.batch_set(ops).await
     }
 
+    /// Commit a transaction which advances the directory to a new epoch. The stored epoch
+    /// is checked and the transaction's records written in a single MySQL transaction, with
+    /// the azks row locked from the check until the commit, so no other writer can advance
+    /// the epoch in between.
+    async fn commit_epoch(&self, expected_epoch: u64) -> core::result::Result<(), StorageError> {
+        if let Some(cache) = &self.cache {
+            cache.enable_clean().await;
+        }
+
+        // this retrieves all the trans operations, and "de-activates" the transaction flag
+        let mut ops = self.trans.commit_transaction().await?;
+        // dropping the operations rolls the transaction back
+        let new_epoch = expected_epoch + 1;
+        if !ops
+            .iter()
+            .any(|op| matches!(op, DbRecord::EpochRootHash(root) if root.epoch == new_epoch))
+        {
+            return Err(StorageError::RootHashNotRecorded(new_epoch));
+        }
+        ops.push(DbRecord::EpochCommitMarker(EpochCommitMarker {
+            epoch: new_epoch,
+        }));
+
+        match self.write_records(ops.clone(), Some(expected_epoch)).await {
+            Ok(Some(actual)) => Err(StorageError::EpochRaceLost {
+                expected: expected_epoch,
+                actual,
+            }),
+            Ok(None) => {
+                // only cache the records once they're known to have been written
+                if let Some(cache) = &self.cache {
+                    let _ = cache.batch_put(&ops).await;
+                }
+                Ok(())
+            }
+            Err(error) => {
+                error!("MySQL error {}", error);
+                Err(StorageError::Other(format!("MySQL Error {}", error)))
+            }
+        }
+    }
+
     /// Rollback a transaction
     async fn rollback_transaction(&self) -> core::result::Result<(), StorageError> {
         // The transaction is being reverted and therefore we can re-enable
@@ -798,70 +1114,7 @@ impl Storage for AsyncMySqlDatabase {
             let _ = cache.batch_put(&records).await;
         }
 
-        // generate batches by type
-        let mut groups = std::collections::HashMap::new();
-        for record in records {
-            match &record {
-                DbRecord::Azks(_) => groups
-                    .entry(StorageType::Azks)
-                    .or_insert_with(Vec::new)
-                    .push(record),
-                DbRecord::TreeNode(_) => groups
-                    .entry(StorageType::TreeNode)
-                    .or_insert_with(Vec::new)
-                    .push(record),
-                DbRecord::ValueState(_) => groups
-                    .entry(StorageType::ValueState)
-                    .or_insert_with(Vec::new)
-                    .push(record),
-            }
-        }
-        // now execute each type'd batch in batch operations
-        let result = async {
-            let mut conn = self.get_connection().await?;
-            let mut tx = conn.start_transaction(TxOpts::default()).await?;
-            // go through each group which is narrowed to a single type
-            // applying the changes on the transaction
-            tx.query_drop("SET autocommit=0").await?;
-            tx.query_drop("SET unique_checks=0").await?;
-            tx.query_drop("SET foreign_key_checks=0").await?;
-
-            for (_key, mut value) in groups.into_iter() {
-                if !value.is_empty() {
-                    // Sort the records to match db-layer sorting which will help with insert performance
-                    value.sort_by(|a, b| match &a {
-                        DbRecord::TreeNode(node) => {
-                            if let DbRecord::TreeNode(node2) = &b {
-                                node.label.cmp(&node2.label)
-                            } else {
-                                Ordering::Equal
-                            }
-                        }
-                        DbRecord::ValueState(state) => {
-                            if let DbRecord::ValueState(state2) = &b {
-                                match state.username.0.cmp(&state2.username.0) {
-                                    Ordering::Equal => state.epoch.cmp(&state2.epoch),
-                                    other => other,
-                                }
-                            } else {
-                                Ordering::Equal
-                            }
-                        }
-                        _ => Ordering::Equal,
-                    });
-                    // execute the multi-batch insert statement(s)
-                    tx = self.internal_batch_set(value, tx).await?;
-                }
-            }
-
-            tx.query_drop("SET autocommit=1").await?;
-            tx.query_drop("SET unique_checks=1").await?;
-            tx.query_drop("SET foreign_key_checks=1").await?;
-
-            tx.commit().await?;
-            Ok::<(), MySqlError>(())
-        };
-        match result.await {
+        match self.write_records(records, None).await {
             Ok(_) => Ok(()),
             Err(error) => {
                 error!("MySQL error {}", error);
@@ -956,6 +1209,35 @@ impl Storage for AsyncMySqlDatabase {
         }
     }
 
+    async fn batch_get_direct<St: Storable>(
+        &self,
+        ids: &[St::StorageKey],
+    ) -> core::result::Result<Vec<DbRecord>, StorageError> {
+        if ids.is_empty() {
+            // nothing to retrieve, save the cycles
+            return Ok(vec![]);
+        }
+        *(self.num_reads.write().await) += 1;
+        self.record_call_stats(
+            'r',
+            "batch_get_direct".to_string(),
+            format!("{:?}", St::data_type()),
+        )
+        .await;
+
+        let key_set: HashSet<St::StorageKey> = ids.iter().cloned().collect();
+        match self
+            .batch_get_from_db::<St>(key_set.into_iter().collect())
+            .await
+        {
+            Ok(records) => Ok(records),
+            Err(error) => {
+                error!("MySQL error {}", error);
+                Err(StorageError::Other(format!("MySQL Error {}", error)))
+            }
+        }
+    }
+
     /// Flush the caching of objects (if present)
     async fn flush_cache(&self) {
         if let Some(cache) = &self.cache {
@@ -963,6 +1245,13 @@ impl Storage for AsyncMySqlDatabase {
         }
     }
 
+    /// Move the caching of objects (if present) on to the given epoch
+    async fn advance_cache_epoch(&self, epoch: u64, changed: &[NodeLabel]) {
+        if let Some(cache) = &self.cache {
+            cache.advance_epoch(epoch, changed).await;
+        }
+    }
+
     /// Retrieve a batch of records by id
     async fn batch_get<St: Storable>(
         &self,
@@ -1003,103 +1292,9 @@ impl Storage for AsyncMySqlDatabase {
         if !key_set.is_empty() {
             // these are items to be retrieved from the backing database (not in pending transaction or in the object cache)
             let result = async {
-                let tic = Instant::now();
-
-                let key_set_vec: Vec<_> = key_set.into_iter().collect();
-
-                debug!("BEGIN MySQL get batch");
-                let mut conn = self.get_connection().await?;
-
-                let results = if let Some(create_table_cmd) =
-                    DbRecord::get_batch_create_temp_table::<St>()
-                {
-                    // Create the temp table of ids
-                    let out = conn.query_drop(create_table_cmd).await;
-                    self.check_for_infra_error(out)?;
-
-                    // Fill temp table with the requested ids
-                    let mut tx = conn.start_transaction(TxOpts::default()).await?;
-                    tx.query_drop("SET autocommit=0").await?;
-                    tx.query_drop("SET unique_checks=0").await?;
-                    tx.query_drop("SET foreign_key_checks=0").await?;
-
-                    let mut fallout: Option<Vec<_>> = None;
-                    let mut params = vec![];
-                    for batch in key_set_vec.chunks(self.tunable_insert_depth) {
-                        if batch.len() < self.tunable_insert_depth {
-                            fallout = Some(batch.to_vec());
-                        } else if let Some(p) = DbRecord::get_multi_row_specific_params::<St>(batch)
-                        {
-                            params.push(p);
-                        } else {
-                            return Err(MySqlError::Other(
-                                "Unable to generate type-specific MySQL parameters".into(),
-                            ));
-                        }
-                    }
-
-                    // insert the batches of size = MYSQL_EXTENDED_INSERT_DEPTH
-                    if !params.is_empty() {
-                        let fill_statement = DbRecord::get_batch_fill_temp_table::<St>(Some(
-                            self.tunable_insert_depth,
-                        ));
-                        let out = tx.exec_batch(fill_statement, params).await;
-                        self.check_for_infra_error(out)?;
-                        // We would need the statement for it. (Possibly) No need for close here.
-                        // See https://docs.rs/mysql_async/0.28.1/mysql_async/struct.Opts.html#caveats.
-                        // tx.close().await?;
-                    }
-
-                    // insert the remainder as a final statement
-                    if let Some(remainder) = fallout {
-                        let remainder_stmt =
-                            DbRecord::get_batch_fill_temp_table::<St>(Some(remainder.len()));
-                        let params_batch =
-                            DbRecord::get_multi_row_specific_params::<St>(&remainder);
-                        if let Some(pb) = params_batch {
-                            let out = tx.exec_drop(remainder_stmt, pb).await;
-                            self.check_for_infra_error(out)?;
-                        } else {
-                            return Err(MySqlError::Other(
-                                "Unable to generate type-specific MySQL parameters".into(),
-                            ));
-                        }
-                    }
-
-                    tx.query_drop("SET autocommit=1").await?;
-                    tx.query_drop("SET unique_checks=1").await?;
-                    tx.query_drop("SET foreign_key_checks=1").await?;
-                    tx.commit().await?;
-
-                    // Query the records which intersect (INNER JOIN) with the temp table of ids
-                    let query = DbRecord::get_batch_statement::<St>();
-                    let out = conn.query_iter(query).await;
-                    let result = self.check_for_infra_error(out)?;
-
-                    let out = result
-                        .reduce_and_drop(vec![], |mut acc, mut row| {
-                            if let Ok(result) = DbRecord::from_row::<St>(&mut row) {
-                                acc.push(result);
-                            }
-                            acc
-                        })
-                        .await?;
-
-                    // drop the temp table of ids
-                    let t_out = conn
-                        .query_drop(format!("DROP TEMPORARY TABLE `{}`", TEMP_IDS_TABLE))
-                        .await;
-                    self.check_for_infra_error(t_out)?;
-
-                    out
-                } else {
-                    // no results (i.e. AZKS table doesn't support "get by batch ids")
-                    vec![]
-                };
-
-                debug!("END MySQL get batch");
-                let toc = Instant::now() - tic;
-                *(self.time_read.write().await) += toc;
+                let results = self
+                    .batch_get_from_db::<St>(key_set.into_iter().collect())
+                    .await?;
 
                 if let Some(cache) = &self.cache {
                     // insert retrieved records into the cache for faster future access
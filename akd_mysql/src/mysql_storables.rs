@@ -21,6 +21,10 @@ type MySqlError = mysql_async::Error;
 pub(crate) const TABLE_AZKS: &str = "azks";
 pub(crate) const TABLE_HISTORY_TREE_NODES: &str = "history";
 pub(crate) const TABLE_USER: &str = "users";
+pub(crate) const TABLE_EPOCH_TIMESTAMPS: &str = "epoch_timestamps";
+pub(crate) const TABLE_EPOCH_COMMIT_MARKERS: &str = "epoch_commit_markers";
+pub(crate) const TABLE_EPOCH_ROOT_HASHES: &str = "epoch_root_hashes";
+pub(crate) const TABLE_LEAF_METADATA: &str = "leaf_metadata";
 pub(crate) const TEMP_IDS_TABLE: &str = "temp_ids_table";
 
 const SELECT_AZKS_DATA: &str = "`epoch`, `num_nodes`";
@@ -28,6 +32,10 @@ const SELECT_HISTORY_TREE_NODE_DATA: &str =
     "`label_len`, `label_val`, `last_epoch`, `least_descendant_ep`, `parent_label_len`, `parent_label_val`, `node_type`, `left_child_len`, `left_child_label_val`, `right_child_len`, `right_child_label_val`, `hash`, `p_last_epoch`, `p_least_descendant_ep`, `p_parent_label_len`, `p_parent_label_val`, `p_node_type`, `p_left_child_len`, `p_left_child_label_val`, `p_right_child_len`, `p_right_child_label_val`, `p_hash`";
 const SELECT_USER_DATA: &str =
     "`username`, `epoch`, `version`, `node_label_val`, `node_label_len`, `data`";
+const SELECT_EPOCH_TIMESTAMP_DATA: &str = "`epoch`, `timestamp_millis`";
+const SELECT_EPOCH_COMMIT_MARKER_DATA: &str = "`epoch`";
+const SELECT_EPOCH_ROOT_HASH_DATA: &str = "`epoch`, `root_hash`";
+const SELECT_LEAF_METADATA_DATA: &str = "`label_len`, `label_val`, `data`";
 
 pub(crate) trait MySqlStorable {
     fn set_statement(&self) -> String;
@@ -65,6 +73,10 @@ impl MySqlStorable for DbRecord {
             DbRecord::Azks(_) => format!("INSERT INTO `{}` (`key`, {}) VALUES (:key, :epoch, :num_nodes) ON DUPLICATE KEY UPDATE `epoch` = :epoch, `num_nodes` = :num_nodes", TABLE_AZKS, SELECT_AZKS_DATA),
             DbRecord::TreeNode(_) => format!("INSERT INTO `{}` ({}) VALUES (:label_len, :label_val, :last_epoch, :least_descendant_ep, :parent_label_len, :parent_label_val, :node_type, :left_child_len, :left_child_label_val, :right_child_len, :right_child_label_val, :hash, :p_last_epoch, :p_least_descendant_ep, :p_parent_label_len, :p_parent_label_val, :p_node_type, :p_left_child_len, :p_left_child_label_val, :p_right_child_len, :p_right_child_label_val, :p_hash) ON DUPLICATE KEY UPDATE `label_len` = :label_len, `label_val` = :label_val, `last_epoch` = :last_epoch, `least_descendant_ep` = :least_descendant_ep, `parent_label_len` = :parent_label_len, `parent_label_val` = :parent_label_val, `node_type` = :node_type, `left_child_len` = :left_child_len, `left_child_label_val` = :left_child_label_val, `right_child_len` = :right_child_len, `right_child_label_val` = :right_child_label_val, `hash` = :hash, `p_last_epoch` = :p_last_epoch, `p_least_descendant_ep` = :p_least_descendant_ep, `p_parent_label_len` = :p_parent_label_len, `p_parent_label_val` = :p_parent_label_val, `p_node_type` = :p_node_type, `p_left_child_len` = :p_left_child_len, `p_left_child_label_val` = :p_left_child_label_val, `p_right_child_len` = :p_right_child_len, `p_right_child_label_val` = :p_right_child_label_val, `p_hash` = :p_hash", TABLE_HISTORY_TREE_NODES, SELECT_HISTORY_TREE_NODE_DATA),
             DbRecord::ValueState(_) => format!("INSERT INTO `{}` ({}) VALUES (:username, :epoch, :version, :node_label_val, :node_label_len, :data)", TABLE_USER, SELECT_USER_DATA),
+            DbRecord::EpochTimestamp(_) => format!("INSERT INTO `{}` ({}) VALUES (:epoch, :timestamp_millis) ON DUPLICATE KEY UPDATE `timestamp_millis` = :timestamp_millis", TABLE_EPOCH_TIMESTAMPS, SELECT_EPOCH_TIMESTAMP_DATA),
+            DbRecord::EpochCommitMarker(_) => format!("INSERT INTO `{}` ({}) VALUES (:epoch) ON DUPLICATE KEY UPDATE `epoch` = :epoch", TABLE_EPOCH_COMMIT_MARKERS, SELECT_EPOCH_COMMIT_MARKER_DATA),
+            DbRecord::EpochRootHash(_) => format!("INSERT INTO `{}` ({}) VALUES (:epoch, :root_hash) ON DUPLICATE KEY UPDATE `root_hash` = :root_hash", TABLE_EPOCH_ROOT_HASHES, SELECT_EPOCH_ROOT_HASH_DATA),
+            DbRecord::LeafMetadata(_) => format!("INSERT INTO `{}` ({}) VALUES (:label_len, :label_val, :data) ON DUPLICATE KEY UPDATE `data` = :data", TABLE_LEAF_METADATA, SELECT_LEAF_METADATA_DATA),
         }
     }
 
@@ -102,6 +114,16 @@ impl MySqlStorable for DbRecord {
             DbRecord::ValueState(state) => Some(
                 params! { "username" => state.get_id().0, "epoch" => state.epoch, "version" => state.version, "node_label_len" => state.label.label_len, "node_label_val" => state.label.label_val, "data" => state.plaintext_val.0.clone() },
             ),
+            DbRecord::EpochTimestamp(timestamp) => Some(
+                params! { "epoch" => timestamp.epoch, "timestamp_millis" => timestamp.timestamp_millis },
+            ),
+            DbRecord::EpochCommitMarker(marker) => Some(params! { "epoch" => marker.epoch }),
+            DbRecord::EpochRootHash(root) => {
+                Some(params! { "epoch" => root.epoch, "root_hash" => root.root_hash })
+            }
+            DbRecord::LeafMetadata(metadata) => Some(
+                params! { "label_len" => metadata.label.label_len, "label_val" => metadata.label.label_val, "data" => metadata.data().to_vec() },
+            ),
         }
     }
 
@@ -121,6 +143,18 @@ impl MySqlStorable for DbRecord {
                         parts, i, i, i, i, i, i
                     );
                 }
+                StorageType::EpochTimestamp => {
+                    parts = format!("{}(:epoch{}, :timestamp_millis{})", parts, i, i);
+                }
+                StorageType::EpochCommitMarker => {
+                    parts = format!("{}(:epoch{})", parts, i);
+                }
+                StorageType::EpochRootHash => {
+                    parts = format!("{}(:epoch{}, :root_hash{})", parts, i, i);
+                }
+                StorageType::LeafMetadata => {
+                    parts = format!("{}(:label_len{}, :label_val{}, :data{})", parts, i, i, i);
+                }
                 _ => {
                     // azks
                 }
@@ -135,6 +169,10 @@ impl MySqlStorable for DbRecord {
             StorageType::Azks => format!("INSERT INTO `{}` (`key`, {}) VALUES (:key, :epoch, :num_nodes) as new ON DUPLICATE KEY UPDATE `epoch` = new.epoch, `num_nodes` = new.num_nodes", TABLE_AZKS, SELECT_AZKS_DATA),
             StorageType::TreeNode => format!("INSERT INTO `{}` ({}) VALUES {} as new ON DUPLICATE KEY UPDATE `label_len` = new.label_len, `label_val` = new.label_val, `least_descendant_ep` = new.least_descendant_ep, `last_epoch` = new.last_epoch, `parent_label_len` = new.parent_label_len, `parent_label_val` = new.parent_label_val, `node_type` = new.node_type, `left_child_len` = new.left_child_len, `left_child_label_val` = new.left_child_label_val, `right_child_len` = new.right_child_len, `right_child_label_val` = new.right_child_label_val, `hash` = new.hash, `p_last_epoch` = new.p_last_epoch, `p_least_descendant_ep` = new.p_least_descendant_ep, `p_parent_label_len` = new.p_parent_label_len, `p_parent_label_val` = new.p_parent_label_val, `p_node_type` = new.p_node_type, `p_left_child_len` = new.p_left_child_len, `p_left_child_label_val` = new.p_left_child_label_val, `p_right_child_len` = new.p_right_child_len, `p_right_child_label_val` = new.p_right_child_label_val, `p_hash` = new.p_hash", TABLE_HISTORY_TREE_NODES, SELECT_HISTORY_TREE_NODE_DATA, parts),
             StorageType::ValueState => format!("INSERT INTO `{}` ({}) VALUES {} as new ON DUPLICATE KEY UPDATE `data` = new.data, `node_label_val` = new.node_label_val, `node_label_len` = new.node_label_len, `version` = new.version", TABLE_USER, SELECT_USER_DATA, parts),
+            StorageType::EpochTimestamp => format!("INSERT INTO `{}` ({}) VALUES {} as new ON DUPLICATE KEY UPDATE `timestamp_millis` = new.timestamp_millis", TABLE_EPOCH_TIMESTAMPS, SELECT_EPOCH_TIMESTAMP_DATA, parts),
+            StorageType::EpochCommitMarker => format!("INSERT INTO `{}` ({}) VALUES {} as new ON DUPLICATE KEY UPDATE `epoch` = new.epoch", TABLE_EPOCH_COMMIT_MARKERS, SELECT_EPOCH_COMMIT_MARKER_DATA, parts),
+            StorageType::EpochRootHash => format!("INSERT INTO `{}` ({}) VALUES {} as new ON DUPLICATE KEY UPDATE `root_hash` = new.root_hash", TABLE_EPOCH_ROOT_HASHES, SELECT_EPOCH_ROOT_HASH_DATA, parts),
+            StorageType::LeafMetadata => format!("INSERT INTO `{}` ({}) VALUES {} as new ON DUPLICATE KEY UPDATE `data` = new.data", TABLE_LEAF_METADATA, SELECT_LEAF_METADATA_DATA, parts),
         }
     }
 
@@ -271,6 +309,34 @@ impl MySqlStorable for DbRecord {
                         Value::from(state.plaintext_val.0.clone()),
                     ),
                 ]),
+                DbRecord::EpochTimestamp(timestamp) => Ok(vec![
+                    (format!("epoch{}", idx), Value::from(timestamp.epoch)),
+                    (
+                        format!("timestamp_millis{}", idx),
+                        Value::from(timestamp.timestamp_millis),
+                    ),
+                ]),
+                DbRecord::EpochCommitMarker(marker) => {
+                    Ok(vec![(format!("epoch{}", idx), Value::from(marker.epoch))])
+                }
+                DbRecord::EpochRootHash(root) => Ok(vec![
+                    (format!("epoch{}", idx), Value::from(root.epoch)),
+                    (format!("root_hash{}", idx), Value::from(root.root_hash)),
+                ]),
+                DbRecord::LeafMetadata(metadata) => Ok(vec![
+                    (
+                        format!("label_len{}", idx),
+                        Value::from(metadata.label.label_len),
+                    ),
+                    (
+                        format!("label_val{}", idx),
+                        Value::from(metadata.label.label_val),
+                    ),
+                    (
+                        format!("data{}", idx),
+                        Value::from(metadata.data().to_vec()),
+                    ),
+                ]),
             })
             .into_iter()
             .collect::<Result<Vec<_>>>()?
@@ -288,13 +354,29 @@ impl MySqlStorable for DbRecord {
                 SELECT_HISTORY_TREE_NODE_DATA, TABLE_HISTORY_TREE_NODES
             ),
             StorageType::ValueState => format!("SELECT {} FROM `{}`", SELECT_USER_DATA, TABLE_USER),
+            StorageType::EpochTimestamp => format!(
+                "SELECT {} FROM `{}`",
+                SELECT_EPOCH_TIMESTAMP_DATA, TABLE_EPOCH_TIMESTAMPS
+            ),
+            StorageType::EpochCommitMarker => format!(
+                "SELECT {} FROM `{}`",
+                SELECT_EPOCH_COMMIT_MARKER_DATA, TABLE_EPOCH_COMMIT_MARKERS
+            ),
+            StorageType::EpochRootHash => format!(
+                "SELECT {} FROM `{}`",
+                SELECT_EPOCH_ROOT_HASH_DATA, TABLE_EPOCH_ROOT_HASHES
+            ),
+            StorageType::LeafMetadata => format!(
+                "SELECT {} FROM `{}`",
+                SELECT_LEAF_METADATA_DATA, TABLE_LEAF_METADATA
+            ),
         }
     }
 
     fn get_batch_create_temp_table<St: Storable>() -> Option<String> {
         match St::data_type() {
             StorageType::Azks => None,
-            StorageType::TreeNode => {
+            StorageType::TreeNode | StorageType::LeafMetadata => {
                 Some(
                     format!(
                         "CREATE TEMPORARY TABLE `{}`(`label_len` INT UNSIGNED NOT NULL, `label_val` VARBINARY(32) NOT NULL, PRIMARY KEY(`label_len`, `label_val`))",
@@ -310,13 +392,23 @@ impl MySqlStorable for DbRecord {
                     )
                 )
             },
+            StorageType::EpochTimestamp
+            | StorageType::EpochCommitMarker
+            | StorageType::EpochRootHash => {
+                Some(
+                    format!(
+                        "CREATE TEMPORARY TABLE `{}`(`epoch` BIGINT UNSIGNED NOT NULL, PRIMARY KEY(`epoch`))",
+                        TEMP_IDS_TABLE
+                    )
+                )
+            },
         }
     }
 
     fn get_batch_fill_temp_table<St: Storable>(num_items: Option<usize>) -> String {
         let mut statement = match St::data_type() {
             StorageType::Azks => "".to_string(),
-            StorageType::TreeNode => {
+            StorageType::TreeNode | StorageType::LeafMetadata => {
                 format!(
                     "INSERT INTO `{}` (`label_len`, `label_val`) VALUES ",
                     TEMP_IDS_TABLE
@@ -328,17 +420,27 @@ impl MySqlStorable for DbRecord {
                     TEMP_IDS_TABLE
                 )
             }
+            StorageType::EpochTimestamp
+            | StorageType::EpochCommitMarker
+            | StorageType::EpochRootHash => {
+                format!("INSERT INTO `{}` (`epoch`) VALUES ", TEMP_IDS_TABLE)
+            }
         };
         if let Some(item_count) = num_items {
             for i in 0..item_count {
                 let append = match St::data_type() {
                     StorageType::Azks => String::from(""),
-                    StorageType::TreeNode => {
+                    StorageType::TreeNode | StorageType::LeafMetadata => {
                         format!("(:label_len{}, :label_val{})", i, i)
                     }
                     StorageType::ValueState => {
                         format!("(:username{}, :epoch{})", i, i)
                     }
+                    StorageType::EpochTimestamp
+                    | StorageType::EpochCommitMarker
+                    | StorageType::EpochRootHash => {
+                        format!("(:epoch{})", i)
+                    }
                 };
                 statement = format!("{}{}", statement, append);
 
@@ -350,8 +452,11 @@ impl MySqlStorable for DbRecord {
         } else {
             statement += match St::data_type() {
                 StorageType::Azks => "",
-                StorageType::TreeNode => "(:label_len, :label_val)",
+                StorageType::TreeNode | StorageType::LeafMetadata => "(:label_len, :label_val)",
                 StorageType::ValueState => "(:username, :epoch)",
+                StorageType::EpochTimestamp
+                | StorageType::EpochCommitMarker
+                | StorageType::EpochRootHash => "(:epoch)",
             };
         }
         statement
@@ -376,6 +481,33 @@ impl MySqlStorable for DbRecord {
                     TEMP_IDS_TABLE
                 )
             }
+            StorageType::EpochTimestamp => {
+                format!(
+                    "SELECT a.`epoch`, a.`timestamp_millis` FROM `{}` a INNER JOIN {} ids ON ids.`epoch` = a.`epoch`",
+                    TABLE_EPOCH_TIMESTAMPS,
+                    TEMP_IDS_TABLE
+                )
+            }
+            StorageType::EpochCommitMarker => {
+                format!(
+                    "SELECT a.`epoch` FROM `{}` a INNER JOIN {} ids ON ids.`epoch` = a.`epoch`",
+                    TABLE_EPOCH_COMMIT_MARKERS, TEMP_IDS_TABLE
+                )
+            }
+            StorageType::EpochRootHash => {
+                format!(
+                    "SELECT a.`epoch`, a.`root_hash` FROM `{}` a INNER JOIN {} ids ON ids.`epoch` = a.`epoch`",
+                    TABLE_EPOCH_ROOT_HASHES,
+                    TEMP_IDS_TABLE
+                )
+            }
+            StorageType::LeafMetadata => {
+                format!(
+                    "SELECT a.`label_len`, a.`label_val`, a.`data` FROM `{}` a INNER JOIN {} ids ON ids.`label_len` = a.`label_len` AND ids.`label_val` = a.`label_val`",
+                    TABLE_LEAF_METADATA,
+                    TEMP_IDS_TABLE
+                )
+            }
         }
     }
 
@@ -392,6 +524,22 @@ impl MySqlStorable for DbRecord {
                 "SELECT {} FROM `{}` WHERE `username` = :username AND `epoch` = :epoch",
                 SELECT_USER_DATA, TABLE_USER
             ),
+            StorageType::EpochTimestamp => format!(
+                "SELECT {} FROM `{}` WHERE `epoch` = :epoch",
+                SELECT_EPOCH_TIMESTAMP_DATA, TABLE_EPOCH_TIMESTAMPS
+            ),
+            StorageType::EpochCommitMarker => format!(
+                "SELECT {} FROM `{}` WHERE `epoch` = :epoch",
+                SELECT_EPOCH_COMMIT_MARKER_DATA, TABLE_EPOCH_COMMIT_MARKERS
+            ),
+            StorageType::EpochRootHash => format!(
+                "SELECT {} FROM `{}` WHERE `epoch` = :epoch",
+                SELECT_EPOCH_ROOT_HASH_DATA, TABLE_EPOCH_ROOT_HASHES
+            ),
+            StorageType::LeafMetadata => format!(
+                "SELECT {} FROM `{}` WHERE `label_len` = :label_len AND `label_val` = :label_val",
+                SELECT_LEAF_METADATA_DATA, TABLE_LEAF_METADATA
+            ),
         }
     }
 
@@ -420,6 +568,49 @@ impl MySqlStorable for DbRecord {
                     None
                 }
             }
+            StorageType::EpochTimestamp => {
+                let bin = St::get_full_binary_key_id(key);
+                if let Ok(epoch) = akd::storage::types::EpochTimestamp::key_from_full_binary(&bin) {
+                    Some(params! {
+                        "epoch" => epoch
+                    })
+                } else {
+                    None
+                }
+            }
+            StorageType::EpochCommitMarker => {
+                let bin = St::get_full_binary_key_id(key);
+                if let Ok(epoch) =
+                    akd::storage::types::EpochCommitMarker::key_from_full_binary(&bin)
+                {
+                    Some(params! {
+                        "epoch" => epoch
+                    })
+                } else {
+                    None
+                }
+            }
+            StorageType::EpochRootHash => {
+                let bin = St::get_full_binary_key_id(key);
+                if let Ok(epoch) = akd::storage::types::EpochRootHash::key_from_full_binary(&bin) {
+                    Some(params! {
+                        "epoch" => epoch
+                    })
+                } else {
+                    None
+                }
+            }
+            StorageType::LeafMetadata => {
+                let bin = St::get_full_binary_key_id(key);
+                if let Ok(back) = akd::storage::types::LeafMetadata::key_from_full_binary(&bin) {
+                    Some(params! {
+                        "label_len" => back.0.label_len,
+                        "label_val" => back.0.label_val,
+                    })
+                } else {
+                    None
+                }
+            }
         }
     }
 
@@ -468,6 +659,72 @@ impl MySqlStorable for DbRecord {
                     .collect::<Vec<_>>();
                 Some(mysql_async::Params::from(pvec))
             }
+            StorageType::EpochTimestamp => {
+                let pvec = keys
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, key)| {
+                        let bin = St::get_full_binary_key_id(key);
+                        // Since these are constructed from a safe key, they should never fail
+                        // so we'll leave the unwrap to simplify
+                        let epoch = akd::storage::types::EpochTimestamp::key_from_full_binary(&bin)
+                            .unwrap();
+                        (format!("epoch{}", idx), Value::from(epoch))
+                    })
+                    .collect::<Vec<_>>();
+                Some(mysql_async::Params::from(pvec))
+            }
+            StorageType::EpochCommitMarker => {
+                let pvec = keys
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, key)| {
+                        let bin = St::get_full_binary_key_id(key);
+                        // Since these are constructed from a safe key, they should never fail
+                        // so we'll leave the unwrap to simplify
+                        let epoch =
+                            akd::storage::types::EpochCommitMarker::key_from_full_binary(&bin)
+                                .unwrap();
+                        (format!("epoch{}", idx), Value::from(epoch))
+                    })
+                    .collect::<Vec<_>>();
+                Some(mysql_async::Params::from(pvec))
+            }
+            StorageType::EpochRootHash => {
+                let pvec = keys
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, key)| {
+                        let bin = St::get_full_binary_key_id(key);
+                        // Since these are constructed from a safe key, they should never fail
+                        // so we'll leave the unwrap to simplify
+                        let epoch =
+                            akd::storage::types::EpochRootHash::key_from_full_binary(&bin).unwrap();
+                        (format!("epoch{}", idx), Value::from(epoch))
+                    })
+                    .collect::<Vec<_>>();
+                Some(mysql_async::Params::from(pvec))
+            }
+            StorageType::LeafMetadata => {
+                let pvec = keys
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, key)| {
+                        let bin = St::get_full_binary_key_id(key);
+                        // Since these are constructed from a safe key, they should never fail
+                        // so we'll leave the unwrap to simplify
+                        let back: NodeKey =
+                            akd::storage::types::LeafMetadata::key_from_full_binary(&bin).unwrap();
+                        vec![
+                            (format!("label_len{}", idx), Value::from(back.0.label_len)),
+                            (format!("label_val{}", idx), Value::from(back.0.label_val)),
+                        ]
+                    })
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>();
+                Some(mysql_async::Params::from(pvec))
+            }
         }
     }
 
@@ -645,6 +902,47 @@ impl MySqlStorable for DbRecord {
                     return Ok(DbRecord::ValueState(state));
                 }
             }
+            StorageType::EpochTimestamp => {
+                // `epoch`, `timestamp_millis`
+                if let (Some(Ok(epoch)), Some(Ok(timestamp_millis))) =
+                    (row.take_opt(0), row.take_opt(1))
+                {
+                    let timestamp = DbRecord::build_epoch_timestamp(epoch, timestamp_millis);
+                    return Ok(DbRecord::EpochTimestamp(timestamp));
+                }
+            }
+            StorageType::EpochCommitMarker => {
+                // `epoch`
+                if let Some(Ok(epoch)) = row.take_opt(0) {
+                    let marker = DbRecord::build_epoch_commit_marker(epoch);
+                    return Ok(DbRecord::EpochCommitMarker(marker));
+                }
+            }
+            StorageType::EpochRootHash => {
+                // `epoch`, `root_hash`
+                if let (Some(Ok(epoch)), Some(Ok(root_hash))) = (row.take_opt(0), row.take_opt(1)) {
+                    let root_hash_vec: Vec<u8> = root_hash;
+                    let root = DbRecord::build_epoch_root_hash(
+                        epoch,
+                        root_hash_vec.try_into().map_err(|_| cast_err())?,
+                    );
+                    return Ok(DbRecord::EpochRootHash(root));
+                }
+            }
+            StorageType::LeafMetadata => {
+                // `label_len`, `label_val`, `data`
+                if let (Some(Ok(label_len)), Some(Ok(label_val)), Some(Ok(data))) =
+                    (row.take_opt(0), row.take_opt(1), row.take_opt(2))
+                {
+                    let label_val_vec: Vec<u8> = label_val;
+                    let metadata = DbRecord::build_leaf_metadata(
+                        label_val_vec.try_into().map_err(|_| cast_err())?,
+                        label_len,
+                        data,
+                    );
+                    return Ok(DbRecord::LeafMetadata(metadata));
+                }
+            }
         }
         // fallback
         let err = MySqlError::Driver(mysql_async::DriverError::FromRow { row: row.clone() });
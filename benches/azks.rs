@@ -8,7 +8,8 @@
 #[macro_use]
 extern crate criterion;
 
-use akd::{append_only_zks::Azks, node_state::Node, node_state::NodeLabel};
+use akd::auditor::{verify_append_only_segment, verify_append_only_segment_in};
+use akd::{append_only_zks::Azks, Node, NodeLabel};
 use criterion::Criterion;
 use rand::{prelude::ThreadRng, thread_rng, RngCore};
 use std::time::Instant;
@@ -28,15 +29,17 @@ fn single_insertion(c: &mut Criterion) {
     let db = InMemoryDb::new();
 
     let mut azks1 = runtime.block_on(Azks::new::<_, Blake3>(&db)).unwrap();
+    let mut insertion_set = vec![];
     for _ in 0..num_nodes {
         let label = NodeLabel::random(&mut rng);
         let mut input = [0u8; 32];
         rng.fill_bytes(&mut input);
         let hash = Blake3::hash(&input);
-        runtime
-            .block_on(azks1.insert_leaf::<_, Blake3>(&db, Node::<Blake3> { hash, label }, 1))
-            .unwrap();
+        insertion_set.push(Node::<Blake3> { hash, label });
     }
+    runtime
+        .block_on(azks1.batch_insert_leaves::<_, Blake3>(&db, insertion_set))
+        .unwrap();
 
     c.bench_function("single insertion into tree with 1000 nodes", move |b| {
         b.iter(|| {
@@ -47,11 +50,133 @@ fn single_insertion(c: &mut Criterion) {
 
             let _start = Instant::now();
             runtime
-                .block_on(azks1.insert_leaf::<_, Blake3>(&db, Node::<Blake3> { hash, label }, 2))
+                .block_on(
+                    azks1.batch_insert_leaves::<_, Blake3>(
+                        &db,
+                        vec![Node::<Blake3> { hash, label }],
+                    ),
+                )
+                .unwrap();
+        })
+    });
+}
+
+fn constant_time_proofs(c: &mut Criterion) {
+    let num_nodes = 1000;
+
+    let mut rng: ThreadRng = thread_rng();
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let db = InMemoryDb::new();
+
+    let mut azks = runtime.block_on(Azks::new::<_, Blake3>(&db)).unwrap();
+    let mut insertion_set = vec![];
+    for _ in 0..num_nodes {
+        let label = NodeLabel::random(&mut rng);
+        let mut input = [0u8; 32];
+        rng.fill_bytes(&mut input);
+        let hash = Blake3::hash(&input);
+        insertion_set.push(Node::<Blake3> { hash, label });
+    }
+    runtime
+        .block_on(azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set))
+        .unwrap();
+
+    let mut group = c.benchmark_group("non-membership proof in tree with 1000 nodes");
+    group.bench_function("default", |b| {
+        b.iter(|| {
+            let label = NodeLabel::random(&mut rng);
+            runtime
+                .block_on(azks.get_non_membership_proof::<_, Blake3>(&db, label))
                 .unwrap();
         })
     });
+    group.bench_function("constant time", |b| {
+        b.iter(|| {
+            let label = NodeLabel::random(&mut rng);
+            runtime
+                .block_on(azks.get_constant_time_non_membership_proof::<_, Blake3>(&db, label))
+                .unwrap();
+        })
+    });
+    group.finish();
+}
+
+fn audit_segments(c: &mut Criterion) {
+    let num_epochs = 50;
+    let num_nodes_per_epoch = 20;
+
+    let mut rng: ThreadRng = thread_rng();
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let db = InMemoryDb::new();
+
+    let mut azks = runtime.block_on(Azks::new::<_, Blake3>(&db)).unwrap();
+    let mut root_hashes = vec![runtime
+        .block_on(azks.get_root_hash::<_, Blake3>(&db))
+        .unwrap()];
+    for _ in 0..num_epochs {
+        let mut insertion_set = vec![];
+        for _ in 0..num_nodes_per_epoch {
+            let label = NodeLabel::random(&mut rng);
+            let mut input = [0u8; 32];
+            rng.fill_bytes(&mut input);
+            let hash = Blake3::hash(&input);
+            insertion_set.push(Node::<Blake3> { hash, label });
+        }
+        runtime
+            .block_on(azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set))
+            .unwrap();
+        root_hashes.push(
+            runtime
+                .block_on(azks.get_root_hash::<_, Blake3>(&db))
+                .unwrap(),
+        );
+    }
+    let proof = runtime
+        .block_on(azks.get_append_only_proof::<_, Blake3>(&db, 0, num_epochs))
+        .unwrap();
+
+    let mut group = c.benchmark_group("audit of 50 epochs");
+    group.bench_function("new database per epoch", |b| {
+        b.iter(|| {
+            for (i, single_proof) in proof.proofs.iter().enumerate() {
+                let epoch = proof.epochs[i] + 1;
+                runtime
+                    .block_on(verify_append_only_segment::<Blake3>(
+                        single_proof,
+                        root_hashes[i],
+                        epoch,
+                    ))
+                    .unwrap();
+            }
+        })
+    });
+    group.bench_function("reused database", |b| {
+        let audit_db = InMemoryDb::new();
+        b.iter(|| {
+            for (i, single_proof) in proof.proofs.iter().enumerate() {
+                let epoch = proof.epochs[i] + 1;
+                runtime
+                    .block_on(verify_append_only_segment_in::<Blake3>(
+                        &audit_db,
+                        single_proof,
+                        root_hashes[i],
+                        epoch,
+                    ))
+                    .unwrap();
+            }
+        })
+    });
+    group.finish();
 }
 
-criterion_group!(azks_benches, single_insertion);
+criterion_group!(
+    azks_benches,
+    single_insertion,
+    constant_time_proofs,
+    audit_segments
+);
 criterion_main!(azks_benches);
@@ -0,0 +1,63 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under both the MIT license found in the
+// LICENSE-MIT file in the root directory of this source tree and the Apache
+// License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+// of this source tree.
+
+//! The initial load of a large tree takes minutes per sample, so this isn't run by a plain
+//! `cargo bench`. Run it with `cargo bench --features bench --bench bulk_load`.
+
+#[macro_use]
+extern crate criterion;
+
+use akd::{append_only_zks::Azks, Node, NodeLabel};
+use criterion::Criterion;
+use rand::{prelude::ThreadRng, thread_rng, RngCore};
+use winter_crypto::{hashers::Blake3_256, Hasher};
+use winter_math::fields::f128::BaseElement;
+
+type Blake3 = Blake3_256<BaseElement>;
+type InMemoryDb = akd::storage::memory::AsyncInMemoryDatabase;
+
+fn bulk_load(c: &mut Criterion) {
+    let num_nodes = 1_000_000;
+
+    let mut rng: ThreadRng = thread_rng();
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let mut insertion_set = vec![];
+    for _ in 0..num_nodes {
+        let label = NodeLabel::random(&mut rng);
+        let mut input = [0u8; 32];
+        rng.fill_bytes(&mut input);
+        let hash = Blake3::hash(&input);
+        insertion_set.push(Node::<Blake3> { hash, label });
+    }
+
+    let mut group = c.benchmark_group("initial load of 1000000 leaves");
+    group.sample_size(10);
+    group.bench_function("batch insertion", |b| {
+        b.iter(|| {
+            let db = InMemoryDb::new();
+            let mut azks = runtime.block_on(Azks::new::<_, Blake3>(&db)).unwrap();
+            runtime
+                .block_on(azks.batch_insert_leaves::<_, Blake3>(&db, insertion_set.clone()))
+                .unwrap();
+        })
+    });
+    group.bench_function("bulk load", |b| {
+        b.iter(|| {
+            let db = InMemoryDb::new();
+            let mut azks = runtime.block_on(Azks::new::<_, Blake3>(&db)).unwrap();
+            runtime
+                .block_on(azks.bulk_load::<_, Blake3>(&db, insertion_set.clone()))
+                .unwrap();
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(bulk_load_benches, bulk_load);
+criterion_main!(bulk_load_benches);